@@ -82,6 +82,36 @@ cfg_task! {
         }
     }
 
+    pub fn ax_set_current_affinity(cpu_mask: usize) -> crate::AxResult {
+        if axtask::set_affinity(axtask::current().as_task_ref(), cpu_mask) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_affinity: no valid CPUs in the mask"
+            )
+        }
+    }
+
+    pub fn ax_current_affinity() -> usize {
+        axtask::current().cpu_mask()
+    }
+
+    pub fn ax_set_task_affinity(task: &AxTaskHandle, cpu_mask: usize) -> crate::AxResult {
+        if axtask::set_affinity(&task.inner, cpu_mask) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_task_affinity: no valid CPUs in the mask"
+            )
+        }
+    }
+
+    pub fn ax_task_affinity(task: &AxTaskHandle) -> usize {
+        axtask::get_affinity(&task.inner)
+    }
+
     pub fn ax_wait_queue_wait(
         wq: &AxWaitQueueHandle,
         until_condition: impl Fn() -> bool,
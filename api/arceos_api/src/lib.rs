@@ -119,6 +119,19 @@ pub mod task {
         pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32>;
         /// Sets the priority of the current task.
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
+        /// Sets the CPU affinity of the current task, i.e. the CPUs that it
+        /// is allowed to run on, bit `i` of `cpu_mask` for CPU `i`.
+        ///
+        /// The current task is migrated immediately if it is not allowed to
+        /// run on the current CPU anymore.
+        pub fn ax_set_current_affinity(cpu_mask: usize) -> crate::AxResult;
+        /// Returns the CPU affinity of the current task.
+        pub fn ax_current_affinity() -> usize;
+        /// Sets the CPU affinity of the given task, see
+        /// [`ax_set_current_affinity`].
+        pub fn ax_set_task_affinity(task: &AxTaskHandle, cpu_mask: usize) -> crate::AxResult;
+        /// Returns the CPU affinity of the given task.
+        pub fn ax_task_affinity(task: &AxTaskHandle) -> usize;
 
         /// Blocks the current task and put it into the wait queue, until the
         /// given condition becomes true, or the the given duration has elapsed
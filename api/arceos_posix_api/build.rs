@@ -1,16 +1,20 @@
 fn main() {
     use std::io::Write;
 
-    fn gen_pthread_mutex(out_file: &str) -> std::io::Result<()> {
-        // TODO: generate size and initial content automatically.
-        let (mutex_size, mutex_init) = if cfg!(feature = "multitask") {
+    fn gen_pthread_types(out_file: &str) -> std::io::Result<()> {
+        // Sizes in `long`s, which are checked against the Rust types by
+        // `const_assert_eq!`. The Rust types are `#[repr(C)]` and valid when
+        // zero-filled, as they are constructed on first use in that case (see
+        // `StaticInit` in `src/imp/pthread`), so the initializers are all zeros
+        // whatever the layout of `axsync` types is.
+        let (mutex_size, cond_size, rwlock_size) = if cfg!(feature = "multitask") {
             if cfg!(feature = "smp") {
-                (6, "{0, 8, 0, 0, 0, 0}") // core::mem::transmute::<_, [usize; 6]>(axsync::Mutex::new(()))
+                (10, 7, 13)
             } else {
-                (5, "{8, 0, 0, 0, 0}") // core::mem::transmute::<_, [usize; 5]>(axsync::Mutex::new(()))
+                (8, 6, 11)
             }
         } else {
-            (1, "{0}")
+            (1, 1, 1)
         };
 
         let mut output = Vec::new();
@@ -25,7 +29,19 @@ typedef struct {{
     long __l[{mutex_size}];
 }} pthread_mutex_t;
 
-#define PTHREAD_MUTEX_INITIALIZER {{ .__l = {mutex_init}}}
+#define PTHREAD_MUTEX_INITIALIZER {{ .__l = {{0}} }}
+
+typedef struct {{
+    long __l[{cond_size}];
+}} pthread_cond_t;
+
+#define PTHREAD_COND_INITIALIZER {{ .__l = {{0}} }}
+
+typedef struct {{
+    long __l[{rwlock_size}];
+}} pthread_rwlock_t;
+
+#define PTHREAD_RWLOCK_INITIALIZER {{ .__l = {{0}} }}
 "#
         )?;
         std::fs::write(out_file, output)?;
@@ -41,18 +57,29 @@ typedef struct {{
             "ssize_t",
             "off_t",
             "mode_t",
+            "uid_t",
+            "gid_t",
             "sock.*",
             "fd_set",
             "timeval",
             "pthread_t",
+            "cpu_set_t",
             "pthread_attr_t",
             "pthread_mutex_t",
             "pthread_mutexattr_t",
+            "pthread_cond_t",
+            "pthread_condattr_t",
+            "pthread_rwlock_t",
+            "pthread_rwlockattr_t",
+            "pthread_key_t",
+            "pthread_once_t",
             "epoll_event",
             "iovec",
             "clockid_t",
             "rlimit",
             "aibuf",
+            "flock",
+            "dirent",
         ];
         let allow_vars = [
             "O_.*",
@@ -67,6 +94,14 @@ typedef struct {{
             "RLIMIT_.*",
             "EAI_.*",
             "MAXADDRS",
+            "AT_.*",
+            "UTIME_.*",
+            "LOCK_.*",
+            "PROT_.*",
+            "MAP_.*",
+            "MS_.*",
+            "WNOHANG",
+            "PTHREAD_.*",
         ];
 
         #[derive(Debug)]
@@ -74,7 +109,7 @@ typedef struct {{
 
         impl bindgen::callbacks::ParseCallbacks for MyCallbacks {
             fn include_file(&self, fname: &str) {
-                if !fname.contains("ax_pthread_mutex.h") {
+                if !fname.contains("ax_pthread_types.h") {
                     println!("cargo:rerun-if-changed={}", fname);
                 }
             }
@@ -101,6 +136,6 @@ typedef struct {{
             .expect("Couldn't write bindings!");
     }
 
-    gen_pthread_mutex("../../ulib/axlibc/include/ax_pthread_mutex.h").unwrap();
+    gen_pthread_types("../../ulib/axlibc/include/ax_pthread_types.h").unwrap();
     gen_c_to_rust_bindings("ctypes.h", "src/ctypes_gen.rs");
 }
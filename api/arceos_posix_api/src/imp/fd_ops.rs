@@ -20,37 +20,97 @@ pub trait FileLike: Send + Sync {
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
 }
 
-lazy_static::lazy_static! {
-    static ref FD_TABLE: RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> = {
+/// A table of file descriptors, the kernel and each process have their own.
+pub struct FdTable(FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>);
+
+impl FdTable {
+    /// Creates a table with only the standard input, output and error.
+    fn with_stdio() -> Self {
         let mut fd_table = FlattenObjects::new();
         fd_table.add_at(0, Arc::new(stdin()) as _).unwrap(); // stdin
         fd_table.add_at(1, Arc::new(stdout()) as _).unwrap(); // stdout
         fd_table.add_at(2, Arc::new(stdout()) as _).unwrap(); // stderr
-        RwLock::new(fd_table)
-    };
+        Self(fd_table)
+    }
+
+    /// Closes all file descriptors, as if by `close` from the current task.
+    #[cfg(feature = "process")]
+    pub fn close_all(&mut self) {
+        for fd in 0..AX_FILE_LIMIT {
+            if let Some(f) = self.0.remove(fd) {
+                unlock_on_close(f);
+            }
+        }
+    }
+}
+
+/// The copied file descriptors refer to the same open files.
+impl Clone for FdTable {
+    fn clone(&self) -> Self {
+        let mut fd_table = FlattenObjects::new();
+        for fd in 0..AX_FILE_LIMIT {
+            if let Some(f) = self.0.get(fd) {
+                fd_table.add_at(fd, f.clone());
+            }
+        }
+        Self(fd_table)
+    }
+}
+
+impl Drop for FdTable {
+    fn drop(&mut self) {
+        // `FlattenObjects` does not drop the objects in it
+        for fd in 0..AX_FILE_LIMIT {
+            self.0.remove(fd);
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref KERNEL_FD_TABLE: Arc<RwLock<FdTable>> =
+        Arc::new(RwLock::new(FdTable::with_stdio()));
+}
+
+/// Returns the file descriptor table of the current process, or the kernel's
+/// for kernel tasks.
+pub fn fd_table() -> Arc<RwLock<FdTable>> {
+    #[cfg(feature = "process")]
+    if let Some(fd_table) = super::process::current_fd_table() {
+        return fd_table;
+    }
+    KERNEL_FD_TABLE.clone()
 }
 
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
-    FD_TABLE
+    fd_table()
         .read()
+        .0
         .get(fd as usize)
         .cloned()
         .ok_or(LinuxError::EBADF)
 }
 
 pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).ok_or(LinuxError::EMFILE)? as c_int)
+    Ok(fd_table().write().0.add(f).ok_or(LinuxError::EMFILE)? as c_int)
 }
 
 pub fn close_file_like(fd: c_int) -> LinuxResult {
-    let f = FD_TABLE
+    let f = fd_table()
         .write()
+        .0
         .remove(fd as usize)
         .ok_or(LinuxError::EBADF)?;
-    drop(f);
+    unlock_on_close(f);
     Ok(())
 }
 
+fn unlock_on_close(f: Arc<dyn FileLike>) {
+    #[cfg(feature = "fs")]
+    super::fs::unlock_on_close(f);
+    #[cfg(not(feature = "fs"))]
+    drop(f);
+}
+
 /// Close a file by `fd`.
 pub fn sys_close(fd: c_int) -> c_int {
     debug!("sys_close <= {}", fd);
@@ -91,8 +151,9 @@ pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
         }
 
         let f = get_file_like(old_fd)?;
-        FD_TABLE
+        fd_table()
             .write()
+            .0
             .add_at(new_fd as usize, f)
             .ok_or(LinuxError::EMFILE)?;
 
@@ -119,6 +180,13 @@ pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
                 get_file_like(fd)?.set_nonblocking(arg & (ctypes::O_NONBLOCK as usize) > 0)?;
                 Ok(0)
             }
+            #[cfg(feature = "fs")]
+            ctypes::F_GETLK
+            | ctypes::F_SETLK
+            | ctypes::F_SETLKW
+            | ctypes::F_OFD_GETLK
+            | ctypes::F_OFD_SETLK
+            | ctypes::F_OFD_SETLKW => unsafe { super::fs::fcntl_lock(fd, cmd as u32, arg as _) },
             _ => {
                 warn!("unsupported fcntl parameters: cmd {}", cmd);
                 Ok(0)
@@ -1,8 +1,10 @@
 use alloc::sync::Arc;
-use core::ffi::{c_char, c_int};
+use core::ffi::{c_char, c_int, c_long};
+use core::ptr::addr_of_mut;
+use core::time::Duration;
 
-use axerrno::{LinuxError, LinuxResult};
-use axfs::fops::OpenOptions;
+use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
+use axfs::fops::{DirEntry, FileLocks, LockOwner, LockType, OpenOptions, RangeLock};
 use axio::{PollState, SeekFrom};
 use axsync::Mutex;
 
@@ -10,12 +12,15 @@ use super::fd_ops::{get_file_like, FileLike};
 use crate::{ctypes, utils::char_ptr_to_str};
 
 pub struct File {
-    inner: Mutex<axfs::fops::File>,
+    pub(super) inner: Mutex<axfs::fops::File>,
+    /// The locks of the file, which can be operated without locking `inner`.
+    locks: FileLocks,
 }
 
 impl File {
     fn new(inner: axfs::fops::File) -> Self {
         Self {
+            locks: inner.locks().clone(),
             inner: Mutex::new(inner),
         }
     }
@@ -24,39 +29,69 @@ impl File {
         super::fd_ops::add_file_like(Arc::new(self))
     }
 
-    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+    pub(super) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         let f = super::fd_ops::get_file_like(fd)?;
         f.into_any()
             .downcast::<Self>()
             .map_err(|_| LinuxError::EINVAL)
     }
+
+    /// Convert the range of `struct flock` to `start..end`, where `end` is
+    /// `u64::MAX` if the range extends to the end of the file.
+    fn flock_range(&self, flock: &ctypes::flock) -> LinuxResult<(u64, u64)> {
+        let base = match flock.l_whence {
+            0 => 0,
+            1 => self.inner.lock().seek(SeekFrom::Current(0))? as i64,
+            2 => self.inner.lock().get_attr()?.size() as i64,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let start = base
+            .checked_add(flock.l_start)
+            .ok_or(LinuxError::EOVERFLOW)?;
+        let end = start
+            .checked_add(flock.l_len)
+            .ok_or(LinuxError::EOVERFLOW)?;
+        let (start, end) = match flock.l_len {
+            0 => (start, None),
+            len if len > 0 => (start, Some(end)),
+            _ => (end, Some(start)), // negative length, locking bytes before
+        };
+        if start < 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok((start as u64, end.map_or(u64::MAX, |end| end as u64)))
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        // release them before `inner` is dropped to notify the waiters
+        self.locks.unlock_all(self.locks.owner());
+        notify_lock_waiters();
+    }
 }
 
 impl FileLike for File {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        #[cfg(feature = "mmap")]
+        {
+            super::mmap::read_file(self, buf)
+        }
+        #[cfg(not(feature = "mmap"))]
         Ok(self.inner.lock().read(buf)?)
     }
 
     fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        #[cfg(feature = "mmap")]
+        {
+            super::mmap::write_file(self, buf)
+        }
+        #[cfg(not(feature = "mmap"))]
         Ok(self.inner.lock().write(buf)?)
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
-        let metadata = self.inner.lock().get_attr()?;
-        let ty = metadata.file_type() as u8;
-        let perm = metadata.perm().bits() as u32;
-        let st_mode = ((ty as u32) << 12) | perm;
-        Ok(ctypes::stat {
-            st_ino: 1,
-            st_nlink: 1,
-            st_mode,
-            st_uid: 1000,
-            st_gid: 1000,
-            st_size: metadata.size() as _,
-            st_blocks: metadata.blocks() as _,
-            st_blksize: 512,
-            ..Default::default()
-        })
+        Ok(file_attr_to_stat(&self.inner.lock().get_attr()?))
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
@@ -75,8 +110,101 @@ impl FileLike for File {
     }
 }
 
+/// A directory opened by `open` or `openat`, which can be used as the
+/// `dirfd` of the `*at` functions.
+pub struct Directory {
+    inner: axfs::fops::Directory,
+    /// The index of the next entry to read by [`sys_getdents64`].
+    pos: Mutex<usize>,
+}
+
+impl Directory {
+    fn new(inner: axfs::fops::Directory) -> Self {
+        Self {
+            inner,
+            pos: Mutex::new(0),
+        }
+    }
+
+    fn add_to_fd_table(self) -> LinuxResult<c_int> {
+        super::fd_ops::add_file_like(Arc::new(self))
+    }
+
+    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+        let f = super::fd_ops::get_file_like(fd)?;
+        f.into_any()
+            .downcast::<Self>()
+            .map_err(|_| LinuxError::ENOTDIR)
+    }
+}
+
+impl FileLike for Directory {
+    fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EISDIR)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EBADF)
+    }
+
+    fn stat(&self) -> LinuxResult<ctypes::stat> {
+        Ok(file_attr_to_stat(&self.inner.get_attr()?))
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn core::any::Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: true,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+        Ok(())
+    }
+}
+
+/// Run `f` with the directory that `path` is relative to, which is indicated
+/// by `dirfd`, or `None` for `AT_FDCWD` (the current directory) and absolute
+/// paths.
+fn with_dirfd<R>(
+    dirfd: c_int,
+    path: &str,
+    f: impl FnOnce(Option<&axfs::fops::Directory>) -> LinuxResult<R>,
+) -> LinuxResult<R> {
+    if dirfd == ctypes::AT_FDCWD || path.starts_with('/') {
+        f(None)
+    } else {
+        f(Some(&Directory::from_fd(dirfd)?.inner))
+    }
+}
+
+/// Convert [`FileAttr`](axfs::fops::FileAttr) to [`ctypes::stat`].
+fn file_attr_to_stat(metadata: &axfs::fops::FileAttr) -> ctypes::stat {
+    let ty = metadata.file_type() as u8;
+    let perm = metadata.perm().bits() as u32;
+    let st_mode = ((ty as u32) << 12) | perm;
+    ctypes::stat {
+        st_ino: metadata.ino(),
+        st_nlink: metadata.nlink() as _,
+        st_mode,
+        st_uid: metadata.uid(),
+        st_gid: metadata.gid(),
+        st_size: metadata.size() as _,
+        st_blocks: metadata.blocks() as _,
+        st_blksize: 512,
+        st_atim: metadata.atime().into(),
+        st_mtim: metadata.mtime().into(),
+        st_ctim: metadata.ctime().into(),
+        ..Default::default()
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
-fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
+fn flags_to_options(flags: c_int, mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
     let mut options = OpenOptions::new();
     match flags & 0b11 {
@@ -99,9 +227,49 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     if flags & ctypes::O_EXEC != 0 {
         options.create_new(true);
     }
+    options.mode(mode);
     options
 }
 
+/// Convert the permission bits of `mode` to [`FilePerm`](axfs::fops::FilePerm).
+fn mode_to_perm(mode: ctypes::mode_t) -> axfs::fops::FilePerm {
+    axfs::fops::FilePerm::from_bits_truncate((mode & 0o777) as u16)
+}
+
+/// Convert the user ID or group ID to `None` if it is `-1`, which means
+/// unchanged.
+fn id_to_option(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+/// Open a file or a directory at `path` relative to `dir` (or the current
+/// directory if `None`), and insert it into the file descriptor table.
+fn open_at(
+    dir: Option<&axfs::fops::Directory>,
+    path: &str,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> LinuxResult<c_int> {
+    let options = flags_to_options(flags, mode);
+    let open_dir = || match dir {
+        Some(dir) => dir.open_dir_at(path, &options),
+        None => axfs::fops::Directory::open_dir(path, &options),
+    };
+    if flags as u32 & ctypes::O_DIRECTORY != 0 {
+        return Directory::new(open_dir()?).add_to_fd_table();
+    }
+    let file = match dir {
+        Some(dir) => dir.open_file_at(path, &options)?,
+        None => axfs::fops::File::open(path, &options)?,
+    };
+    if file.get_attr()?.is_dir() {
+        // directories can be opened for reading without `O_DIRECTORY`
+        drop(file);
+        return Directory::new(open_dir()?).add_to_fd_table();
+    }
+    File::new(file).add_to_fd_table()
+}
+
 /// Open a file by `filename` and insert it into the file descriptor table.
 ///
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
@@ -109,10 +277,28 @@ fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
 pub fn sys_open(filename: *const c_char, flags: c_int, mode: ctypes::mode_t) -> c_int {
     let filename = char_ptr_to_str(filename);
     debug!("sys_open <= {:?} {:#o} {:#o}", filename, flags, mode);
-    syscall_body!(sys_open, {
-        let options = flags_to_options(flags, mode);
-        let file = axfs::fops::File::open(filename?, &options)?;
-        File::new(file).add_to_fd_table()
+    syscall_body!(sys_open, open_at(None, filename?, flags, mode))
+}
+
+/// Open a file by `filename` relative to the directory `dirfd` like
+/// [`sys_open`]. `filename` is relative to the current directory if `dirfd` is
+/// `AT_FDCWD`.
+///
+/// Return its index in the file table (`fd`).
+pub fn sys_openat(
+    dirfd: c_int,
+    filename: *const c_char,
+    flags: c_int,
+    mode: ctypes::mode_t,
+) -> c_int {
+    let filename = char_ptr_to_str(filename);
+    debug!(
+        "sys_openat <= {} {:?} {:#o} {:#o}",
+        dirfd, filename, flags, mode
+    );
+    syscall_body!(sys_openat, {
+        let filename = filename?;
+        with_dirfd(dirfd, filename, |dir| open_at(dir, filename, flags, mode))
     })
 }
 
@@ -128,11 +314,45 @@ pub fn sys_lseek(fd: c_int, offset: ctypes::off_t, whence: c_int) -> ctypes::off
             2 => SeekFrom::End(offset as _),
             _ => return Err(LinuxError::EINVAL),
         };
+        if let Ok(dir) = Directory::from_fd(fd) {
+            // the offset of a directory is the index of the next entry
+            return match pos {
+                SeekFrom::Start(idx) => {
+                    *dir.pos.lock() = idx as usize;
+                    Ok(idx as _)
+                }
+                _ => Err(LinuxError::EINVAL),
+            };
+        }
         let off = File::from_fd(fd)?.inner.lock().seek(pos)?;
         Ok(off)
     })
 }
 
+/// Synchronize the data and metadata of the file indicated by `fd` to the
+/// disk.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fsync(fd: c_int) -> c_int {
+    debug!("sys_fsync <= {}", fd);
+    syscall_body!(sys_fsync, {
+        File::from_fd(fd)?.inner.lock().sync_all()?;
+        Ok(0)
+    })
+}
+
+/// Synchronize the data of the file indicated by `fd` to the disk, like
+/// [`sys_fsync`] (the metadata is always synchronized as well).
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fdatasync(fd: c_int) -> c_int {
+    debug!("sys_fdatasync <= {}", fd);
+    syscall_body!(sys_fdatasync, {
+        File::from_fd(fd)?.inner.lock().sync_all()?;
+        Ok(0)
+    })
+}
+
 /// Get the file metadata by `path` and write into `buf`.
 ///
 /// Return 0 if success.
@@ -143,11 +363,8 @@ pub unsafe fn sys_stat(path: *const c_char, buf: *mut ctypes::stat) -> c_int {
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        let mut options = OpenOptions::new();
-        options.read(true);
-        let file = axfs::fops::File::open(path?, &options)?;
-        let st = File::new(file).stat()?;
-        unsafe { *buf = st };
+        let metadata = axfs::api::metadata(path?)?;
+        unsafe { *buf = file_attr_to_stat(metadata.raw_metadata()) };
         Ok(0)
     })
 }
@@ -177,7 +394,311 @@ pub unsafe fn sys_lstat(path: *const c_char, buf: *mut ctypes::stat) -> ctypes::
         if buf.is_null() {
             return Err(LinuxError::EFAULT);
         }
-        unsafe { *buf = Default::default() }; // TODO
+        let metadata = axfs::api::symlink_metadata(path?)?;
+        unsafe { *buf = file_attr_to_stat(metadata.raw_metadata()) };
+        Ok(0)
+    })
+}
+
+/// Get the metadata of the file `path` relative to the directory `dirfd` and
+/// write into `buf`.
+///
+/// `flags` can contain `AT_SYMLINK_NOFOLLOW` to not follow symbolic links, and
+/// `AT_EMPTY_PATH` to get the metadata of `dirfd` itself if `path` is empty.
+///
+/// Return 0 if success.
+pub unsafe fn sys_fstatat(
+    dirfd: c_int,
+    path: *const c_char,
+    buf: *mut ctypes::stat,
+    flags: c_int,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!(
+        "sys_fstatat <= {} {:?} {:#x} {:#x}",
+        dirfd, path, buf as usize, flags
+    );
+    syscall_body!(sys_fstatat, {
+        let path = path?;
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let flags = flags as u32;
+        if flags & !(ctypes::AT_SYMLINK_NOFOLLOW | ctypes::AT_EMPTY_PATH) != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let follow = flags & ctypes::AT_SYMLINK_NOFOLLOW == 0;
+        let attr = if path.is_empty() && flags & ctypes::AT_EMPTY_PATH != 0 {
+            if dirfd != ctypes::AT_FDCWD {
+                unsafe { *buf = get_file_like(dirfd)?.stat()? };
+                return Ok(0);
+            }
+            *axfs::api::metadata(".")?.raw_metadata()
+        } else {
+            with_dirfd(dirfd, path, |dir| match dir {
+                Some(dir) => Ok(dir.get_attr_at(path, follow)?),
+                None if follow => Ok(*axfs::api::metadata(path)?.raw_metadata()),
+                None => Ok(*axfs::api::symlink_metadata(path)?.raw_metadata()),
+            })?
+        };
+        unsafe { *buf = file_attr_to_stat(&attr) };
+        Ok(0)
+    })
+}
+
+/// Create a directory `path` relative to the directory `dirfd`, with the
+/// permission bits of `mode` masked by the umask.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mkdirat(dirfd: c_int, path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_mkdirat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_mkdirat <= {} {:?} {:#o}", dirfd, path, mode);
+        with_dirfd(dirfd, path, |dir| match dir {
+            Some(dir) => Ok(dir.create_dir_with_mode(path, mode)?),
+            None => Ok(axfs::api::DirBuilder::new().mode(mode).create(path)?),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Remove the file `path` relative to the directory `dirfd`, or the empty
+/// directory if `flags` is `AT_REMOVEDIR`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_unlinkat(dirfd: c_int, path: *const c_char, flags: c_int) -> c_int {
+    syscall_body!(sys_unlinkat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_unlinkat <= {} {:?} {:#x}", dirfd, path, flags);
+        let remove_dir = match flags as u32 {
+            0 => false,
+            ctypes::AT_REMOVEDIR => true,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        with_dirfd(dirfd, path, |dir| match (dir, remove_dir) {
+            (Some(dir), true) => Ok(dir.remove_dir(path)?),
+            (Some(dir), false) => Ok(dir.remove_file(path)?),
+            (None, true) => Ok(axfs::api::remove_dir(path)?),
+            (None, false) => Ok(axfs::api::remove_file(path)?),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Read the entries of the directory indicated by `fd` into `dirp` as
+/// `struct dirent` (i.e., `struct linux_dirent64`) records, at most `count`
+/// bytes.
+///
+/// Return the number of bytes read, 0 at the end of the directory.
+pub unsafe fn sys_getdents64(
+    fd: c_int,
+    dirp: *mut core::ffi::c_void,
+    count: usize,
+) -> ctypes::ssize_t {
+    debug!("sys_getdents64 <= {} {:#x} {}", fd, dirp as usize, count);
+    syscall_body!(sys_getdents64, {
+        if dirp.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dir = Directory::from_fd(fd)?;
+        let mut pos = dir.pos.lock();
+        let mut entry = [DirEntry::default()];
+        let mut written = 0;
+        while dir.inner.read_dir_at(*pos, &mut entry)? > 0 {
+            let name = entry[0].name_as_bytes();
+            let ino = match entry[0].ino() {
+                // unknown to the filesystem, take the one reported by `stat`
+                0 => core::str::from_utf8(name)
+                    .ok()
+                    .and_then(|name| dir.inner.get_attr_at(name, false).ok())
+                    .map_or(1, |attr| attr.ino()),
+                ino => ino,
+            };
+            let name_offset = core::mem::offset_of!(ctypes::dirent, d_name);
+            let reclen = (name_offset + name.len() + 1).next_multiple_of(8);
+            if written + reclen > count {
+                if written == 0 {
+                    return Err(LinuxError::EINVAL); // buffer too small
+                }
+                break;
+            }
+            // the record may be smaller than `struct dirent`, write the fields
+            unsafe {
+                let rec = dirp.cast::<u8>().add(written).cast::<ctypes::dirent>();
+                addr_of_mut!((*rec).d_ino).write_unaligned(ino);
+                addr_of_mut!((*rec).d_off).write_unaligned((*pos + 1) as _);
+                addr_of_mut!((*rec).d_reclen).write_unaligned(reclen as _);
+                addr_of_mut!((*rec).d_type).write(entry[0].entry_type() as _);
+                let d_name = addr_of_mut!((*rec).d_name).cast::<u8>();
+                d_name.copy_from_nonoverlapping(name.as_ptr(), name.len());
+                d_name.add(name.len()).write(0);
+            }
+            written += reclen;
+            *pos += 1;
+        }
+        Ok(written)
+    })
+}
+
+/// Change the permission bits of the file `path` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chmod(path: *const c_char, mode: ctypes::mode_t) -> c_int {
+    syscall_body!(sys_chmod, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_chmod <= {:?} {:#o}", path, mode);
+        axfs::api::set_permissions(path, mode_to_perm(mode))?;
+        Ok(0)
+    })
+}
+
+/// Change the permission bits of the file indicated by `fd` to `mode`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchmod(fd: c_int, mode: ctypes::mode_t) -> c_int {
+    debug!("sys_fchmod <= {} {:#o}", fd, mode);
+    syscall_body!(sys_fchmod, {
+        File::from_fd(fd)?
+            .inner
+            .lock()
+            .set_perm(mode_to_perm(mode))?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file `path`, an ID of `-1` means
+/// unchanged.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_chown(path: *const c_char, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    syscall_body!(sys_chown, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_chown <= {:?} {} {}", path, owner, group);
+        axfs::api::chown(path, id_to_option(owner), id_to_option(group))?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file `path` like [`sys_chown`], but
+/// does not follow symbolic links.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_lchown(path: *const c_char, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    syscall_body!(sys_lchown, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_lchown <= {:?} {} {}", path, owner, group);
+        axfs::api::lchown(path, id_to_option(owner), id_to_option(group))?;
+        Ok(0)
+    })
+}
+
+/// Change the owner and the group of the file indicated by `fd`, an ID of
+/// `-1` means unchanged.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_fchown(fd: c_int, owner: ctypes::uid_t, group: ctypes::gid_t) -> c_int {
+    debug!("sys_fchown <= {} {} {}", fd, owner, group);
+    syscall_body!(sys_fchown, {
+        File::from_fd(fd)?
+            .inner
+            .lock()
+            .set_owner(id_to_option(owner), id_to_option(group))?;
+        Ok(0)
+    })
+}
+
+/// Set the file mode creation mask of the current task to `mask`.
+///
+/// Return the previous mask, it never fails.
+pub fn sys_umask(mask: ctypes::mode_t) -> ctypes::mode_t {
+    debug!("sys_umask <= {:#o}", mask);
+    axfs::api::umask(mask) as _
+}
+
+/// The timestamps to set by [`sys_utimensat`] and [`sys_futimens`].
+enum FileTimes {
+    /// Set both the access and modification times to the current time.
+    Now,
+    /// Set the access and modification times, `None` means unchanged.
+    Set(Option<Duration>, Option<Duration>),
+}
+
+/// Parse the `times` argument of [`sys_utimensat`] and [`sys_futimens`],
+/// which may contain the special values `UTIME_NOW` and `UTIME_OMIT`.
+unsafe fn parse_file_times(times: *const ctypes::timespec) -> LinuxResult<FileTimes> {
+    const UTIME_NOW: c_long = ctypes::UTIME_NOW as _;
+    const UTIME_OMIT: c_long = ctypes::UTIME_OMIT as _;
+
+    if times.is_null() {
+        return Ok(FileTimes::Now);
+    }
+    let times = core::slice::from_raw_parts(times, 2);
+    if times.iter().all(|ts| ts.tv_nsec == UTIME_NOW) {
+        return Ok(FileTimes::Now);
+    }
+    let now = axhal::time::current_time();
+    let parse = |ts: &ctypes::timespec| match ts.tv_nsec {
+        UTIME_NOW => Ok(Some(now)),
+        UTIME_OMIT => Ok(None),
+        0..=999_999_999 if ts.tv_sec >= 0 => Ok(Some(Duration::from(*ts))),
+        _ => Err(LinuxError::EINVAL),
+    };
+    Ok(FileTimes::Set(parse(&times[0])?, parse(&times[1])?))
+}
+
+/// Change the access and modification times of the file `path` to `times[0]`
+/// and `times[1]`, or to the current time if `times` is null.
+///
+/// `path` is relative to the directory `dirfd`. `flags` can contain
+/// `AT_SYMLINK_NOFOLLOW` to change the times of a symbolic link itself.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_utimensat(
+    dirfd: c_int,
+    path: *const c_char,
+    times: *const ctypes::timespec,
+    flags: c_int,
+) -> c_int {
+    syscall_body!(sys_utimensat, {
+        let path = char_ptr_to_str(path)?;
+        debug!("sys_utimensat <= {} {:?} {:#x}", dirfd, path, flags);
+        let flags = flags as u32;
+        if flags & !ctypes::AT_SYMLINK_NOFOLLOW != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let follow = flags & ctypes::AT_SYMLINK_NOFOLLOW == 0;
+        let times = parse_file_times(times)?;
+        with_dirfd(dirfd, path, |dir| match (times, dir) {
+            (FileTimes::Set(None, None), _) => Ok(()),
+            (FileTimes::Now, Some(dir)) => Ok(dir.touch_at(path, follow)?),
+            (FileTimes::Now, None) if follow => Ok(axfs::api::touch(path)?),
+            (FileTimes::Now, None) => Ok(axfs::api::ltouch(path)?),
+            (FileTimes::Set(atime, mtime), Some(dir)) => {
+                Ok(dir.set_times_at(path, atime, mtime, follow)?)
+            }
+            (FileTimes::Set(atime, mtime), None) if follow => {
+                Ok(axfs::api::set_times(path, atime, mtime)?)
+            }
+            (FileTimes::Set(atime, mtime), None) => Ok(axfs::api::lset_times(path, atime, mtime)?),
+        })?;
+        Ok(0)
+    })
+}
+
+/// Change the access and modification times of the file indicated by `fd`
+/// like [`sys_utimensat`].
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub unsafe fn sys_futimens(fd: c_int, times: *const ctypes::timespec) -> c_int {
+    debug!("sys_futimens <= {}", fd);
+    syscall_body!(sys_futimens, {
+        let file = File::from_fd(fd)?;
+        let file = file.inner.lock();
+        match parse_file_times(times)? {
+            FileTimes::Now => file.touch()?,
+            FileTimes::Set(None, None) => {}
+            FileTimes::Set(atime, mtime) => file.set_times(atime, mtime)?,
+        }
         Ok(0)
     })
 }
@@ -215,3 +736,301 @@ pub fn sys_rename(old: *const c_char, new: *const c_char) -> c_int {
         Ok(0)
     })
 }
+
+/// Rename `old` relative to the directory `olddirfd` to `new` relative to the
+/// directory `newdirfd`, like [`sys_rename`].
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_renameat(
+    olddirfd: c_int,
+    old: *const c_char,
+    newdirfd: c_int,
+    new: *const c_char,
+) -> c_int {
+    syscall_body!(sys_renameat, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!(
+            "sys_renameat <= old: {} {:?}, new: {} {:?}",
+            olddirfd, old_path, newdirfd, new_path
+        );
+        with_dirfd(olddirfd, old_path, |old_dir| {
+            with_dirfd(newdirfd, new_path, |new_dir| {
+                Ok(axfs::fops::Directory::rename_at(
+                    old_dir, old_path, new_dir, new_path,
+                )?)
+            })
+        })?;
+        Ok(0)
+    })
+}
+
+/// Create a symbolic link `linkpath` which contains the string `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_symlink(target: *const c_char, linkpath: *const c_char) -> c_int {
+    syscall_body!(sys_symlink, {
+        let target = char_ptr_to_str(target)?;
+        let linkpath = char_ptr_to_str(linkpath)?;
+        debug!(
+            "sys_symlink <= target: {:?}, linkpath: {:?}",
+            target, linkpath
+        );
+        axfs::api::symlink(target, linkpath)?;
+        Ok(0)
+    })
+}
+
+/// Read the target of the symbolic link `path` into `buf`.
+///
+/// Return the number of bytes placed in `buf`, the content is truncated if
+/// `buf` is too small, and no null byte is appended.
+pub unsafe fn sys_readlink(
+    path: *const c_char,
+    buf: *mut c_char,
+    bufsiz: usize,
+) -> ctypes::ssize_t {
+    let path = char_ptr_to_str(path);
+    debug!("sys_readlink <= {:?} {:#x} {}", path, buf as usize, bufsiz);
+    syscall_body!(sys_readlink, {
+        if buf.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let target = axfs::api::read_link(path?)?;
+        let target = target.as_bytes();
+        let len = target.len().min(bufsiz);
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len) };
+        dst.copy_from_slice(&target[..len]);
+        Ok(len)
+    })
+}
+
+/// Create a new hard link `new` to the existing file `old`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_link(old: *const c_char, new: *const c_char) -> c_int {
+    syscall_body!(sys_link, {
+        let old_path = char_ptr_to_str(old)?;
+        let new_path = char_ptr_to_str(new)?;
+        debug!("sys_link <= old: {:?}, new: {:?}", old_path, new_path);
+        axfs::api::hard_link(old_path, new_path)?;
+        Ok(0)
+    })
+}
+
+/// Mount the filesystem of type `fstype` from `source` on the directory `target`.
+///
+/// Only in-memory filesystems (`ramfs` or `tmpfs`) are supported, `flags` and
+/// `data` are ignored.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_mount(
+    source: *const c_char,
+    target: *const c_char,
+    fstype: *const c_char,
+    flags: core::ffi::c_ulong,
+    _data: *const core::ffi::c_void,
+) -> c_int {
+    syscall_body!(sys_mount, {
+        let source = char_ptr_to_str(source)?;
+        let target = char_ptr_to_str(target)?;
+        let fstype = char_ptr_to_str(fstype)?;
+        debug!(
+            "sys_mount <= source: {:?}, target: {:?}, fstype: {:?}, flags: {:#x}",
+            source, target, fstype, flags
+        );
+        let fs = axfs::api::create_filesystem(fstype, source)?;
+        axfs::api::mount(target, fs)?;
+        Ok(0)
+    })
+}
+
+/// Unmount the filesystem mounted on the directory `target`.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_umount(target: *const c_char) -> c_int {
+    syscall_body!(sys_umount, {
+        let target = char_ptr_to_str(target)?;
+        debug!("sys_umount <= target: {:?}", target);
+        axfs::api::umount(target)?;
+        Ok(0)
+    })
+}
+
+/// The tasks waiting for file locks, notified whenever locks are released.
+#[cfg(feature = "multitask")]
+static FILE_LOCK_WAIT_QUEUE: axtask::WaitQueue = axtask::WaitQueue::new();
+
+/// Wake up the tasks waiting for file locks.
+fn notify_lock_waiters() {
+    #[cfg(feature = "multitask")]
+    FILE_LOCK_WAIT_QUEUE.notify_all(true);
+}
+
+/// Try to place a lock by `try_lock`. If it conflicts with other locks and
+/// `wait` is true, block until it is placed.
+///
+/// Without `multitask`, no other task can release the locks, so it returns
+/// `EDEADLK` instead of blocking forever.
+fn lock_or_wait(wait: bool, try_lock: impl Fn() -> AxResult) -> LinuxResult {
+    if !wait {
+        return Ok(try_lock()?);
+    }
+    #[cfg(feature = "multitask")]
+    {
+        // the condition is checked with the wait queue locked, `try_lock` does
+        // not block
+        let res = core::cell::Cell::new(Ok(()));
+        FILE_LOCK_WAIT_QUEUE.wait_until(|| {
+            let r = try_lock();
+            res.set(r);
+            r != Err(AxError::WouldBlock)
+        });
+        Ok(res.get()?)
+    }
+    #[cfg(not(feature = "multitask"))]
+    match try_lock() {
+        Err(AxError::WouldBlock) => Err(LinuxError::EDEADLK),
+        r => Ok(r?),
+    }
+}
+
+/// The owner of the POSIX record locks placed by the current task, which is
+/// its process.
+///
+/// All kernel tasks are in the same process (the unikernel itself), so like
+/// the threads of a process, they share the record locks and are not
+/// protected from each other by them.
+fn current_lock_owner() -> LockOwner {
+    #[cfg(feature = "process")]
+    let pid = super::process::current_pid();
+    #[cfg(not(feature = "process"))]
+    let pid = 0; // the kernel
+    LockOwner::Process(pid)
+}
+
+/// Release the record locks of the current process on the file `f`, as
+/// closing any file descriptor of a file releases them.
+pub(super) fn unlock_on_close(f: Arc<dyn FileLike>) {
+    if let Ok(file) = f.into_any().downcast::<File>() {
+        file.locks.unlock_all(current_lock_owner());
+        notify_lock_waiters();
+    }
+}
+
+/// Handle the record lock commands of `fcntl` (`F_GETLK`, `F_SETLK`,
+/// `F_SETLKW` and their `F_OFD_*` variants), `arg` points to a `struct flock`.
+///
+/// The locks of `F_OFD_*` commands are owned by the open file, others are
+/// owned by the current process.
+pub(super) unsafe fn fcntl_lock(
+    fd: c_int,
+    cmd: u32,
+    arg: *mut ctypes::flock,
+) -> LinuxResult<c_int> {
+    if arg.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    let flock = unsafe { &mut *arg };
+    let file = File::from_fd(fd)?;
+    let owner = match cmd {
+        ctypes::F_OFD_GETLK | ctypes::F_OFD_SETLK | ctypes::F_OFD_SETLKW => {
+            if flock.l_pid != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            file.locks.owner()
+        }
+        _ => current_lock_owner(),
+    };
+    let ty = match flock.l_type as u32 {
+        ctypes::F_RDLCK => Some(LockType::Shared),
+        ctypes::F_WRLCK => Some(LockType::Exclusive),
+        ctypes::F_UNLCK => None,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    let (start, end) = file.flock_range(flock)?;
+
+    match cmd {
+        ctypes::F_GETLK | ctypes::F_OFD_GETLK => {
+            let ty = ty.ok_or(LinuxError::EINVAL)?;
+            let lock = RangeLock {
+                ty,
+                start,
+                end,
+                owner,
+            };
+            match file.locks.test_range(&lock) {
+                Some(conflict) => {
+                    flock.l_type = match conflict.ty {
+                        LockType::Shared => ctypes::F_RDLCK,
+                        LockType::Exclusive => ctypes::F_WRLCK,
+                    } as _;
+                    flock.l_whence = 0;
+                    flock.l_start = conflict.start as _;
+                    flock.l_len = match conflict.end {
+                        u64::MAX => 0,
+                        end => (end - conflict.start) as _,
+                    };
+                    flock.l_pid = match conflict.owner {
+                        LockOwner::Process(pid) => pid as _,
+                        LockOwner::File(_) => -1,
+                    };
+                }
+                None => flock.l_type = ctypes::F_UNLCK as _,
+            }
+        }
+        _ => match ty {
+            Some(ty) => {
+                let wait = matches!(cmd, ctypes::F_SETLKW | ctypes::F_OFD_SETLKW);
+                let lock = RangeLock {
+                    ty,
+                    start,
+                    end,
+                    owner,
+                };
+                lock_or_wait(wait, || file.locks.lock_range(lock)).map_err(|e| match e {
+                    LinuxError::EACCES => LinuxError::EBADF, // not opened for the lock type
+                    e => e,
+                })?;
+                // the lock may replace a stronger one
+                notify_lock_waiters();
+            }
+            None => {
+                file.locks.unlock_range(owner, start, end);
+                notify_lock_waiters();
+            }
+        },
+    }
+    Ok(0)
+}
+
+/// Apply or remove an advisory lock on the whole file indicated by `fd`.
+///
+/// `operation` is one of `LOCK_SH`, `LOCK_EX` and `LOCK_UN`, optionally with
+/// `LOCK_NB` to fail with `EWOULDBLOCK` instead of blocking. The lock is owned
+/// by the open file, and released when all its file descriptors are closed.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+pub fn sys_flock(fd: c_int, operation: c_int) -> c_int {
+    debug!("sys_flock <= {} {:#x}", fd, operation);
+    syscall_body!(sys_flock, {
+        let file = File::from_fd(fd)?;
+        let locks = &file.locks;
+        let operation = operation as u32;
+        let ty = match operation & !ctypes::LOCK_NB {
+            ctypes::LOCK_SH => LockType::Shared,
+            ctypes::LOCK_EX => LockType::Exclusive,
+            ctypes::LOCK_UN => {
+                locks.unlock_whole(locks.owner());
+                notify_lock_waiters();
+                return Ok(0);
+            }
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let wait = operation & ctypes::LOCK_NB == 0;
+        lock_or_wait(wait, || locks.lock_whole(ty, locks.owner()))?;
+        // the lock may replace a stronger one
+        notify_lock_waiters();
+        Ok(0)
+    })
+}
@@ -0,0 +1,552 @@
+//! Memory mappings created by [`sys_mmap`], which are placed in the dynamic
+//! mapping region of the kernel address space.
+//!
+//! Anonymous mappings are filled with zeros, and file mappings are loaded from
+//! the file. Changes to shared file mappings are written back to the file on
+//! [`sys_msync`] and [`sys_munmap`], and the data written to the file by
+//! `write` is also copied to its shared mappings.
+//!
+//! The pages of a mapping are populated on the first access, by the page fault
+//! handler registered to [`axmm`].
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::ffi::{c_int, c_void};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axmm::MappingFlags;
+use axsync::Mutex;
+use spinlock::SpinNoIrq;
+
+use crate::ctypes;
+
+#[cfg(feature = "fs")]
+use {super::fs::File, alloc::string::String, alloc::sync::Arc, alloc::vec, axio::SeekFrom};
+
+/// What the pages of a mapping are loaded from.
+#[derive(Clone)]
+enum Backing {
+    /// Zero-filled pages.
+    Anonymous,
+    /// A file, starting at `offset`.
+    #[cfg(feature = "fs")]
+    File {
+        file: Arc<File>,
+        /// The absolute path of the file, to find the mappings of the file
+        /// without locking it.
+        path: String,
+        offset: u64,
+    },
+}
+
+/// A mapping created by [`sys_mmap`].
+#[derive(Clone)]
+struct Mapping {
+    /// Unique among the mappings created by [`sys_mmap`], and kept by the
+    /// parts of a split mapping, whose pages are still loaded the same way.
+    id: usize,
+    start: usize,
+    /// Size of the mapping in bytes, aligned to pages.
+    size: usize,
+    flags: MappingFlags,
+    shared: bool,
+    backing: Backing,
+}
+
+/// All the mappings, by their start addresses.
+///
+/// It's locked by the page fault handler, so it's only held briefly: never
+/// across file operations, or while dropping the mappings, which may close
+/// their files. The files of the mappings may be locked before it.
+static MAPPINGS: SpinNoIrq<BTreeMap<usize, Mapping>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Serializes the changes to [`MAPPINGS`] and the write-backs, so that the
+/// pages being written back are not unmapped meanwhile. Page faults don't
+/// take it.
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+impl Mapping {
+    const fn end(&self) -> usize {
+        self.start + self.size
+    }
+
+    /// Splits the mapping at `addr`, returns the part after it.
+    fn split_off(&mut self, addr: usize) -> Self {
+        let backing = match &self.backing {
+            Backing::Anonymous => Backing::Anonymous,
+            #[cfg(feature = "fs")]
+            Backing::File { file, path, offset } => Backing::File {
+                file: file.clone(),
+                path: path.clone(),
+                offset: offset + (addr - self.start) as u64,
+            },
+        };
+        let tail = Self {
+            id: self.id,
+            start: addr,
+            size: self.end() - addr,
+            flags: self.flags,
+            shared: self.shared,
+            backing,
+        };
+        self.size = addr - self.start;
+        tail
+    }
+
+    /// Maps the page at `vaddr` to a new frame filled with its contents.
+    ///
+    /// `self` is a copy of the mapping taken without [`MAPPINGS`] locked, so
+    /// it returns `false` if the page no longer belongs to the mapping.
+    fn populate(&self, vaddr: usize) -> LinuxResult<bool> {
+        let paddr = axmm::alloc_frame(false)?;
+        let frame = unsafe { axmm::frame_slice_mut(paddr) };
+        match &self.backing {
+            Backing::Anonymous => {
+                frame.fill(0);
+                self.map_frame(vaddr, paddr)
+            }
+            #[cfg(feature = "fs")]
+            Backing::File { file, offset, .. } => {
+                // the file stays locked until the page is mapped, otherwise the
+                // data written to it meanwhile would be missed (see `write_file`)
+                let file = file.inner.lock();
+                match read_full_at(&file, offset + (vaddr - self.start) as u64, frame) {
+                    Ok(len) => {
+                        // the part after the end of the file is filled with zeros
+                        frame[len..].fill(0);
+                        self.map_frame(vaddr, paddr)
+                    }
+                    Err(e) => {
+                        axmm::dealloc_frame(paddr);
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Maps the page at `vaddr` to the loaded frame, if the page still belongs
+    /// to the mapping and is not populated yet. Otherwise the frame is freed.
+    ///
+    /// Returns whether the page still belongs to the mapping.
+    fn map_frame(&self, vaddr: usize, paddr: PhysAddr) -> LinuxResult<bool> {
+        let mappings = MAPPINGS.lock();
+        let current = mappings
+            .range(..=vaddr)
+            .next_back()
+            .is_some_and(|(_, m)| m.id == self.id && vaddr < m.end());
+        let res = if !current {
+            Ok(false)
+        } else if axmm::query_page(vaddr.into()).is_ok() {
+            Ok(true) // populated by another CPU
+        } else {
+            match axmm::map_page(vaddr.into(), paddr, self.flags) {
+                Ok(()) => return Ok(true),
+                Err(e) => Err(e.into()),
+            }
+        };
+        drop(mappings);
+        axmm::dealloc_frame(paddr);
+        res
+    }
+
+    /// Writes the populated pages in `[start, end)` back to the file, if it's
+    /// a writable shared file mapping.
+    fn write_back(&self, start: usize, end: usize) -> LinuxResult {
+        #[cfg(feature = "fs")]
+        if let (true, Backing::File { file, offset, .. }) = (
+            self.shared && self.flags.contains(MappingFlags::WRITE),
+            &self.backing,
+        ) {
+            let file = file.inner.lock();
+            let file_size = file.get_attr()?.size();
+            for vaddr in (start..end).step_by(PAGE_SIZE_4K) {
+                let pos = offset + (vaddr - self.start) as u64;
+                if pos >= file_size {
+                    break; // the file is not extended
+                }
+                if let Ok((paddr, _)) = axmm::query_page(vaddr.into()) {
+                    let len = PAGE_SIZE_4K.min((file_size - pos) as usize);
+                    let frame = unsafe { axmm::frame_slice_mut(paddr) };
+                    write_all_at(&file, pos, &frame[..len])?;
+                }
+            }
+            file.flush()?;
+        }
+        #[cfg(not(feature = "fs"))]
+        let _ = (start, end);
+        Ok(())
+    }
+
+    /// Unmaps the populated pages in `[start, end)` and frees their frames.
+    fn unpopulate(&self, start: usize, end: usize) {
+        for vaddr in (start..end).step_by(PAGE_SIZE_4K) {
+            if let Ok(paddr) = axmm::unmap_page(vaddr.into()) {
+                axmm::dealloc_frame(paddr);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+fn read_full_at(file: &axfs::fops::File, mut offset: u64, buf: &mut [u8]) -> LinuxResult<usize> {
+    let mut read_len = 0;
+    while read_len < buf.len() {
+        match file.read_at(offset, &mut buf[read_len..])? {
+            0 => break,
+            len => {
+                read_len += len;
+                offset += len as u64;
+            }
+        }
+    }
+    Ok(read_len)
+}
+
+#[cfg(feature = "fs")]
+fn write_all_at(file: &axfs::fops::File, mut offset: u64, mut buf: &[u8]) -> LinuxResult {
+    while !buf.is_empty() {
+        match file.write_at(offset, buf)? {
+            0 => return Err(LinuxError::EIO),
+            len => {
+                buf = &buf[len..];
+                offset += len as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether `buf` may be in a mapping.
+///
+/// Accessing such a buffer may cause page faults, which lock the mapped file,
+/// so it must not be accessed with the file locked.
+#[cfg(feature = "fs")]
+fn may_be_mapped(buf: &[u8]) -> bool {
+    let start = buf.as_ptr() as usize;
+    axmm::is_vmap_addr(start.into()) || axmm::is_vmap_addr((start + buf.len()).into())
+}
+
+/// Reads the file at its cursor to `buf`.
+#[cfg(feature = "fs")]
+pub(super) fn read_file(file: &File, buf: &mut [u8]) -> LinuxResult<usize> {
+    if may_be_mapped(buf) {
+        let mut bounce = vec![0; buf.len()];
+        let len = file.inner.lock().read(&mut bounce)?;
+        buf[..len].copy_from_slice(&bounce[..len]);
+        Ok(len)
+    } else {
+        Ok(file.inner.lock().read(buf)?)
+    }
+}
+
+/// Writes `buf` to the file at its cursor, and copies the written data to the
+/// populated pages of the shared mappings of the file.
+#[cfg(feature = "fs")]
+pub(super) fn write_file(file: &File, buf: &[u8]) -> LinuxResult<usize> {
+    let bounce;
+    let buf = if may_be_mapped(buf) {
+        bounce = buf.to_vec();
+        &bounce
+    } else {
+        buf
+    };
+    let mut inner = file.inner.lock();
+    let len = inner.write(buf)?;
+    let end = inner.seek(SeekFrom::Current(0))?;
+    let start = end - len as u64;
+    // the pages of the file can't be populated until the file is unlocked
+    let mappings = MAPPINGS.lock();
+    for m in mappings.values().filter(|m| m.shared) {
+        let Backing::File { path, offset, .. } = &m.backing else {
+            continue;
+        };
+        if path != inner.path() || *offset >= end || offset + m.size as u64 <= start {
+            continue;
+        }
+        // copy `[from, to)` of the file to the mapping
+        let (from, to) = (start.max(*offset), end.min(offset + m.size as u64));
+        let mut pos = from;
+        while pos < to {
+            let vaddr = m.start + (pos - offset) as usize;
+            let page_off = vaddr % PAGE_SIZE_4K;
+            let chunk = (PAGE_SIZE_4K - page_off).min((to - pos) as usize);
+            if let Ok((paddr, _)) = axmm::query_page((vaddr - page_off).into()) {
+                let frame = unsafe { axmm::frame_slice_mut(paddr) };
+                let src = (pos - start) as usize;
+                frame[page_off..page_off + chunk].copy_from_slice(&buf[src..src + chunk]);
+            }
+            pos += chunk as u64;
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(feature = "fs")]
+fn file_backing(fd: c_int, offset: u64, flags: MappingFlags, shared: bool) -> LinuxResult<Backing> {
+    let file = File::from_fd(fd).map_err(|e| match e {
+        LinuxError::EINVAL => LinuxError::ENODEV, // not a regular file
+        e => e,
+    })?;
+    let inner = file.inner.lock();
+    if !inner.is_readable()
+        || (shared && flags.contains(MappingFlags::WRITE) && !inner.is_writable())
+    {
+        return Err(LinuxError::EACCES);
+    }
+    let path = inner.path().into();
+    drop(inner);
+    Ok(Backing::File { file, path, offset })
+}
+
+#[cfg(not(feature = "fs"))]
+fn file_backing(
+    _fd: c_int,
+    _offset: u64,
+    _flags: MappingFlags,
+    _shared: bool,
+) -> LinuxResult<Backing> {
+    Err(LinuxError::ENODEV) // file mappings need the `fs` feature
+}
+
+/// Populates the page of the mapping at `vaddr` on page faults.
+fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+    let vaddr = vaddr.align_down_4k().as_usize();
+    loop {
+        let mappings = MAPPINGS.lock();
+        let m = match mappings.range(..=vaddr).next_back() {
+            Some((_, m)) if vaddr < m.end() && m.flags.contains(access_flags) => m.clone(),
+            _ => return false,
+        };
+        drop(mappings);
+        match m.populate(vaddr) {
+            Ok(true) => return true,
+            Ok(false) => continue, // the mapping has been changed, try again
+            Err(e) => {
+                warn!("failed to populate page {:#x}: {:?}", vaddr, e);
+                return false;
+            }
+        }
+    }
+}
+
+/// Returns the start addresses of the mappings that overlap `[start, end)`.
+fn overlapping(mappings: &BTreeMap<usize, Mapping>, start: usize, end: usize) -> Vec<usize> {
+    let mut keys = mappings
+        .range(..end)
+        .rev()
+        .take_while(|(_, m)| m.end() > start)
+        .map(|(&key, _)| key)
+        .collect::<Vec<_>>();
+    keys.reverse();
+    keys
+}
+
+/// Returns whether `[start, end)` is fully covered by the mappings.
+fn is_covered(mappings: &BTreeMap<usize, Mapping>, start: usize, end: usize) -> bool {
+    let mut pos = start;
+    for key in overlapping(mappings, start, end) {
+        if key > pos {
+            return false;
+        }
+        pos = mappings[&key].end();
+    }
+    pos >= end
+}
+
+/// Returns copies of the mappings that overlap `[start, end)`, which can be
+/// used without [`MAPPINGS`] locked.
+fn snapshot(start: usize, end: usize) -> Vec<Mapping> {
+    let mappings = MAPPINGS.lock();
+    overlapping(&mappings, start, end)
+        .into_iter()
+        .map(|key| mappings[&key].clone())
+        .collect()
+}
+
+/// Removes the mappings in `[start, end)`, which may split the mappings that
+/// cross the boundaries. The address space is released if `release` is true.
+///
+/// [`UPDATE_LOCK`] must be held.
+fn unmap_range(start: usize, end: usize, release: bool) {
+    // written back before they are removed, as the data written to the files
+    // is no longer copied to them afterwards (see `write_file`)
+    for m in snapshot(start, end) {
+        if let Err(e) = m.write_back(start.max(m.start), end.min(m.end())) {
+            warn!("failed to write back mapping {:#x}: {:?}", m.start, e);
+        }
+    }
+    let mut mappings = MAPPINGS.lock();
+    let mut removed = Vec::new();
+    for key in overlapping(&mappings, start, end) {
+        let mut m = mappings.remove(&key).unwrap();
+        if m.start < start {
+            let tail = m.split_off(start);
+            mappings.insert(m.start, m);
+            m = tail;
+        }
+        if m.end() > end {
+            let tail = m.split_off(end);
+            mappings.insert(tail.start, tail);
+        }
+        removed.push(m);
+    }
+    drop(mappings);
+    for m in removed {
+        m.unpopulate(m.start, m.end());
+        if release {
+            axmm::dealloc_vmap_pages(m.start.into(), m.size / PAGE_SIZE_4K);
+        }
+    }
+}
+
+/// Returns the length aligned up to pages, or `None` if it's zero or too
+/// large.
+fn page_aligned_len(len: usize) -> Option<usize> {
+    match len.checked_next_multiple_of(PAGE_SIZE_4K) {
+        Some(0) | None => None,
+        len => len,
+    }
+}
+
+fn prot_to_flags(prot: u32) -> LinuxResult<MappingFlags> {
+    if prot & !(ctypes::PROT_READ | ctypes::PROT_WRITE | ctypes::PROT_EXEC) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let mut flags = MappingFlags::empty();
+    if prot & ctypes::PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::READ | MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::READ | MappingFlags::EXECUTE;
+    }
+    Ok(flags)
+}
+
+/// Creates a new mapping of `len` bytes, returns its start address.
+///
+/// The mapping is anonymous if `MAP_ANONYMOUS` is set in `flags`, otherwise it
+/// maps the file `fd` from `offset`. With `MAP_FIXED`, the mapping is placed
+/// at `addr`, replacing the existing mappings there, which must cover the
+/// whole range. Otherwise, `addr` is ignored.
+pub fn sys_mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    offset: ctypes::off_t,
+) -> *mut c_void {
+    debug!(
+        "sys_mmap <= {:#x} {:#x} {:#x} {:#x} {} {}",
+        addr as usize, len, prot, flags, fd, offset
+    );
+    syscall_body!(sys_mmap, {
+        let flags = flags as u32;
+        let shared = match flags & ctypes::MAP_TYPE {
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE => true,
+            ctypes::MAP_PRIVATE => false,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let size = page_aligned_len(len).ok_or(LinuxError::EINVAL)?;
+        if offset < 0 || offset as usize % PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let map_flags = prot_to_flags(prot as u32)?;
+        let backing = if flags & ctypes::MAP_ANONYMOUS != 0 {
+            Backing::Anonymous
+        } else {
+            file_backing(fd, offset as u64, map_flags, shared)?
+        };
+
+        static HANDLER_REGISTERED: spin::Once = spin::Once::new();
+        HANDLER_REGISTERED.call_once(|| {
+            assert!(axmm::register_page_fault_handler(handle_page_fault));
+        });
+
+        let _update = UPDATE_LOCK.lock();
+        let start = if flags & ctypes::MAP_FIXED != 0 {
+            let start = addr as usize;
+            let end = start.checked_add(size).ok_or(LinuxError::EINVAL)?;
+            if start % PAGE_SIZE_4K != 0 {
+                return Err(LinuxError::EINVAL);
+            } else if !is_covered(&MAPPINGS.lock(), start, end) {
+                return Err(LinuxError::ENOMEM);
+            }
+            unmap_range(start, end, false);
+            start
+        } else {
+            axmm::alloc_vmap_pages(size / PAGE_SIZE_4K)?.as_usize()
+        };
+
+        MAPPINGS.lock().insert(
+            start,
+            Mapping {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                start,
+                size,
+                flags: map_flags,
+                shared,
+                backing,
+            },
+        );
+        Ok(start)
+    })
+}
+
+/// Removes the mappings in the range of `len` bytes from `addr`.
+///
+/// Changes to the shared file mappings in the range are written back. It's
+/// not an error if there are no mappings in the range.
+pub fn sys_munmap(addr: *mut c_void, len: usize) -> c_int {
+    debug!("sys_munmap <= {:#x} {:#x}", addr as usize, len);
+    syscall_body!(sys_munmap, {
+        let start = addr as usize;
+        let size = page_aligned_len(len).ok_or(LinuxError::EINVAL)?;
+        let end = start.checked_add(size).ok_or(LinuxError::EINVAL)?;
+        if start % PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let _update = UPDATE_LOCK.lock();
+        unmap_range(start, end, true);
+        Ok(0)
+    })
+}
+
+/// Writes the changes to the shared file mappings in the range of `len` bytes
+/// from `addr` back to the files.
+///
+/// The range must be fully mapped. The data is always written synchronously,
+/// so `MS_ASYNC` and `MS_SYNC` are the same.
+pub fn sys_msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int {
+    debug!("sys_msync <= {:#x} {:#x} {:#x}", addr as usize, len, flags);
+    syscall_body!(sys_msync, {
+        let flags = flags as u32;
+        let start = addr as usize;
+        if start % PAGE_SIZE_4K != 0
+            || flags & !(ctypes::MS_ASYNC | ctypes::MS_SYNC | ctypes::MS_INVALIDATE) != 0
+            || flags & (ctypes::MS_ASYNC | ctypes::MS_SYNC) == ctypes::MS_ASYNC | ctypes::MS_SYNC
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let Some(size) = page_aligned_len(len) else {
+            return Ok(0);
+        };
+        let end = start.checked_add(size).ok_or(LinuxError::ENOMEM)?;
+
+        let _update = UPDATE_LOCK.lock();
+        if !is_covered(&MAPPINGS.lock(), start, end) {
+            return Err(LinuxError::ENOMEM);
+        }
+        for m in snapshot(start, end) {
+            m.write_back(start.max(m.start), end.min(m.end()))?;
+        }
+        Ok(0)
+    })
+}
@@ -12,9 +12,13 @@ pub mod fd_ops;
 pub mod fs;
 #[cfg(any(feature = "select", feature = "epoll"))]
 pub mod io_mpx;
+#[cfg(feature = "mmap")]
+pub mod mmap;
 #[cfg(feature = "net")]
 pub mod net;
 #[cfg(feature = "pipe")]
 pub mod pipe;
+#[cfg(feature = "process")]
+pub mod process;
 #[cfg(feature = "multitask")]
 pub mod pthread;
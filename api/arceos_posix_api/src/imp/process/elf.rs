@@ -0,0 +1,309 @@
+//! Loading ELF64 executables into user address spaces.
+
+use alloc::{string::String, vec::Vec};
+use core::mem::size_of;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{VirtAddr, PAGE_SIZE_4K};
+use axmm::{AddrSpace, MappingFlags};
+use memory_addr::{align_down_4k, align_up_4k};
+
+const ELF_MAGIC: [u8; 4] = *b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+#[cfg(target_arch = "x86_64")]
+const EM_CURRENT: u16 = 62; // EM_X86_64
+#[cfg(target_arch = "riscv64")]
+const EM_CURRENT: u16 = 243; // EM_RISCV
+#[cfg(target_arch = "aarch64")]
+const EM_CURRENT: u16 = 183; // EM_AARCH64
+
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_PHDR: u32 = 6;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+// Types of the auxiliary vector entries.
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_BASE: usize = 7;
+const AT_ENTRY: usize = 9;
+const AT_UID: usize = 11;
+const AT_EUID: usize = 12;
+const AT_GID: usize = 13;
+const AT_EGID: usize = 14;
+const AT_SECURE: usize = 23;
+const AT_RANDOM: usize = 25;
+
+/// Where position-independent executables are loaded.
+const PIE_BASE: usize = 0x1000_0000;
+
+/// Size of the user stack, whose pages are allocated on the first access.
+const USER_STACK_SIZE: usize = 0x80_0000; // 8M
+
+/// Maximum total size of the arguments and environment strings.
+const MAX_ARG_STRLEN: usize = USER_STACK_SIZE / 4;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+/// A program loaded into an address space, ready to run.
+pub struct LoadedProgram {
+    /// The entry point.
+    pub entry: usize,
+    /// The initial user stack pointer, which points to `argc`.
+    pub ustack_top: VirtAddr,
+    /// The end of the loaded segments, where the heap starts.
+    pub heap_bottom: usize,
+}
+
+/// Reads a `T` at `offset` of `data`, fails if it's out of bounds.
+fn read_pod<T: Copy>(data: &[u8], offset: usize) -> LinuxResult<T> {
+    let end = offset
+        .checked_add(size_of::<T>())
+        .ok_or(LinuxError::ENOEXEC)?;
+    if end > data.len() {
+        return Err(LinuxError::ENOEXEC);
+    }
+    Ok(unsafe { (data.as_ptr().add(offset) as *const T).read_unaligned() })
+}
+
+fn parse_header(data: &[u8]) -> LinuxResult<(Elf64Ehdr, Vec<Elf64Phdr>)> {
+    let ehdr: Elf64Ehdr = read_pod(data, 0)?;
+    if ehdr.e_ident[..4] != ELF_MAGIC
+        || ehdr.e_ident[4] != ELFCLASS64
+        || ehdr.e_ident[5] != ELFDATA2LSB
+        || ehdr.e_machine != EM_CURRENT
+        || !matches!(ehdr.e_type, ET_EXEC | ET_DYN)
+        || ehdr.e_phentsize as usize != size_of::<Elf64Phdr>()
+    {
+        return Err(LinuxError::ENOEXEC);
+    }
+    let phdrs = (0..ehdr.e_phnum as usize)
+        .map(|i| {
+            read_pod(
+                data,
+                (ehdr.e_phoff as usize).saturating_add(i * size_of::<Elf64Phdr>()),
+            )
+        })
+        .collect::<LinuxResult<Vec<Elf64Phdr>>>()?;
+    Ok((ehdr, phdrs))
+}
+
+const fn segment_flags(p_flags: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if p_flags & PF_R != 0 {
+        flags = flags.union(MappingFlags::READ);
+    }
+    if p_flags & PF_W != 0 {
+        flags = flags.union(MappingFlags::WRITE);
+    }
+    if p_flags & PF_X != 0 {
+        flags = flags.union(MappingFlags::EXECUTE);
+    }
+    flags
+}
+
+/// Loads the ELF executable `data` into `aspace`, and sets up the user stack
+/// with the arguments `args`, the environment `envs` and the auxiliary vector.
+///
+/// Only statically linked executables are supported, which may be
+/// position-independent.
+pub fn load(
+    aspace: &mut AddrSpace,
+    data: &[u8],
+    args: &[String],
+    envs: &[String],
+) -> LinuxResult<LoadedProgram> {
+    let (ehdr, phdrs) = parse_header(data)?;
+    if phdrs.iter().any(|ph| ph.p_type == PT_INTERP) {
+        warn!("dynamically linked executables are not supported");
+        return Err(LinuxError::ENOEXEC);
+    }
+    let bias = if ehdr.e_type == ET_DYN { PIE_BASE } else { 0 };
+
+    // Segments are sorted by their addresses. A page shared by two segments
+    // is mapped once, with the flags of the first one.
+    let mut mapped_end = 0;
+    let mut segment_end = 0;
+    let mut phdr_addr = None;
+    for ph in phdrs.iter() {
+        let vaddr = (ph.p_vaddr as usize)
+            .checked_add(bias)
+            .ok_or(LinuxError::ENOEXEC)?;
+        let (offset, filesz, memsz) = (
+            ph.p_offset as usize,
+            ph.p_filesz as usize,
+            ph.p_memsz as usize,
+        );
+        match ph.p_type {
+            PT_PHDR => phdr_addr = Some(vaddr),
+            PT_LOAD => {
+                let file_end = offset.checked_add(filesz).ok_or(LinuxError::ENOEXEC)?;
+                let mem_end = vaddr.checked_add(memsz).ok_or(LinuxError::ENOEXEC)?;
+                if filesz > memsz || file_end > data.len() || vaddr < segment_end {
+                    return Err(LinuxError::ENOEXEC);
+                }
+                let start = align_down_4k(vaddr).max(mapped_end);
+                let end = align_up_4k(mem_end);
+                if start < end {
+                    aspace.map_alloc(
+                        start.into(),
+                        end - start,
+                        segment_flags(ph.p_flags),
+                        false,
+                    )?;
+                    mapped_end = end;
+                }
+                segment_end = mem_end;
+                aspace.write(vaddr.into(), &data[offset..file_end])?;
+                let phoff = ehdr.e_phoff as usize;
+                if phdr_addr.is_none() && (offset..file_end).contains(&phoff) {
+                    phdr_addr = Some(vaddr + phoff - offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    if mapped_end == 0 {
+        return Err(LinuxError::ENOEXEC);
+    }
+    let entry = (ehdr.e_entry as usize).wrapping_add(bias);
+
+    let ustack_top = aspace.end().as_usize();
+    aspace.map_alloc(
+        (ustack_top - USER_STACK_SIZE).into(),
+        USER_STACK_SIZE,
+        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
+        false,
+    )?;
+    let auxv = [
+        (AT_PHDR, phdr_addr.unwrap_or(0)),
+        (AT_PHENT, ehdr.e_phentsize as usize),
+        (AT_PHNUM, ehdr.e_phnum as usize),
+        (AT_PAGESZ, PAGE_SIZE_4K),
+        (AT_BASE, 0),
+        (AT_ENTRY, entry),
+        (AT_UID, axtask::current().uid() as usize),
+        (AT_EUID, axtask::current().uid() as usize),
+        (AT_GID, axtask::current().gid() as usize),
+        (AT_EGID, axtask::current().gid() as usize),
+        (AT_SECURE, 0),
+    ];
+    let sp = init_stack(aspace, ustack_top, args, envs, &auxv)?;
+
+    Ok(LoadedProgram {
+        entry,
+        ustack_top: sp.into(),
+        heap_bottom: mapped_end,
+    })
+}
+
+/// Writes the initial stack below `top`, returns the stack pointer.
+///
+/// From the stack pointer upwards, the stack holds `argc`, the `argv` array,
+/// the `envp` array, the auxiliary vector, then the strings they point to.
+fn init_stack(
+    aspace: &mut AddrSpace,
+    top: usize,
+    args: &[String],
+    envs: &[String],
+    auxv: &[(usize, usize)],
+) -> LinuxResult<usize> {
+    let str_len: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    if str_len > MAX_ARG_STRLEN {
+        return Err(LinuxError::E2BIG);
+    }
+
+    let mut sp = top;
+    let mut push_str = |aspace: &mut AddrSpace, s: &str| -> LinuxResult<usize> {
+        sp -= s.len() + 1;
+        aspace.write(sp.into(), s.as_bytes())?;
+        aspace.write((sp + s.len()).into(), &[0])?;
+        Ok(sp)
+    };
+    let arg_ptrs = args
+        .iter()
+        .map(|s| push_str(aspace, s))
+        .collect::<LinuxResult<Vec<_>>>()?;
+    let env_ptrs = envs
+        .iter()
+        .map(|s| push_str(aspace, s))
+        .collect::<LinuxResult<Vec<_>>>()?;
+
+    // `AT_RANDOM` is required by the C libraries, fall back to the timer if
+    // there is no hardware random number generator.
+    let mut random = [0u8; 16];
+    if let (Some(lo), Some(hi)) = (axhal::arch::hw_random(), axhal::arch::hw_random()) {
+        random[..8].copy_from_slice(&lo.to_ne_bytes());
+        random[8..].copy_from_slice(&hi.to_ne_bytes());
+    } else {
+        warn!("No hardware random number generator, AT_RANDOM is predictable");
+        let ticks = axhal::time::current_ticks().to_ne_bytes();
+        random[..8].copy_from_slice(&ticks);
+        random[8..].copy_from_slice(&ticks.map(|b| b.rotate_left(4)));
+    }
+    sp -= random.len();
+    aspace.write(sp.into(), &random)?;
+    let random_ptr = sp;
+
+    let mut words = Vec::with_capacity(args.len() + envs.len() + auxv.len() * 2 + 5);
+    words.push(args.len());
+    words.extend_from_slice(&arg_ptrs);
+    words.push(0);
+    words.extend_from_slice(&env_ptrs);
+    words.push(0);
+    for &(ty, val) in auxv.iter().chain(&[(AT_RANDOM, random_ptr), (AT_NULL, 0)]) {
+        words.push(ty);
+        words.push(val);
+    }
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            words.as_ptr() as *const u8,
+            words.len() * size_of::<usize>(),
+        )
+    };
+    // the stack pointer must be 16-byte aligned on all architectures
+    sp = (sp - bytes.len()) & !0xf;
+    aspace.write(sp.into(), bytes)?;
+    Ok(sp)
+}
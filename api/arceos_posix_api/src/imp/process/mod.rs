@@ -0,0 +1,382 @@
+//! User processes, which run ELF executables in their own address spaces in
+//! user mode.
+//!
+//! A process is created by [`sys_posix_spawn`] or `fork` and runs in a single
+//! task, whose ID is also the process ID. The system calls from user space are
+//! dispatched to the `sys_*` functions of this crate, after the user pointers
+//! are checked. Each process has its own file descriptor table, which is
+//! copied from its parent (or the kernel) when it's created, and closed when
+//! it exits.
+//!
+//! The processes spawned by kernel tasks are the children of the kernel, so
+//! are the orphans whose parents have exited. An exited process is kept as a
+//! zombie until it is waited by [`sys_waitpid`].
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::cell::Cell;
+use core::ffi::{c_char, c_int, c_void};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::{TrapFrame, UspaceContext};
+use axmm::{AddrSpace, MappingFlags};
+use axsync::Mutex;
+use axtask::{AxTaskRef, TaskState};
+use memory_addr::align_up_4k;
+use spin::RwLock;
+use spinlock::SpinNoIrq;
+
+use super::fd_ops::{fd_table, FdTable};
+use crate::ctypes;
+use crate::utils::char_ptr_to_str;
+
+mod elf;
+mod syscall;
+
+/// Maximum size of the heap managed by `brk`.
+const MAX_HEAP_SIZE: usize = 0x1000_0000; // 256M
+
+/// The process ID of the kernel, as the parent of processes.
+const KERNEL_PID: u64 = 0;
+
+#[derive(Clone, Copy)]
+struct Heap {
+    /// The start of the heap, after the loaded segments.
+    bottom: usize,
+    /// The current program break.
+    top: usize,
+}
+
+/// The states of a process, besides its address space.
+struct Process {
+    pid: u64,
+    /// The ID of the parent process, or [`KERNEL_PID`].
+    ppid: AtomicU64,
+    task: AxTaskRef,
+    heap: Mutex<Heap>,
+    fd_table: Arc<RwLock<FdTable>>,
+}
+
+/// All the processes that have not been waited, by their IDs.
+static PROCESSES: Mutex<BTreeMap<u64, Arc<Process>>> = Mutex::new(BTreeMap::new());
+
+impl Heap {
+    const fn new(bottom: usize) -> Self {
+        Self {
+            bottom,
+            top: bottom,
+        }
+    }
+}
+
+impl Process {
+    /// Where the memory mappings of the process are placed, above the heap.
+    fn mmap_base(&self) -> usize {
+        self.heap.lock().bottom + MAX_HEAP_SIZE
+    }
+
+    /// Sets the program break to `addr`, returns the new program break.
+    ///
+    /// The program break is unchanged if `addr` is out of the heap or the
+    /// memory is exhausted, in which case the current one is returned.
+    fn brk(&self, aspace: &mut AddrSpace, addr: usize) -> usize {
+        let mut heap = self.heap.lock();
+        if addr < heap.bottom || addr > heap.bottom + MAX_HEAP_SIZE {
+            return heap.top;
+        }
+        let (old_end, new_end) = (align_up_4k(heap.top), align_up_4k(addr));
+        if new_end > old_end {
+            let flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+            if aspace
+                .map_alloc(old_end.into(), new_end - old_end, flags, false)
+                .is_err()
+            {
+                return heap.top;
+            }
+        } else if new_end < old_end {
+            aspace.unmap(new_end.into(), old_end - new_end).ok();
+        }
+        heap.top = addr;
+        addr
+    }
+
+    fn has_exited(&self) -> bool {
+        self.task.state() == TaskState::Exited
+    }
+
+    /// Returns the ID of the parent process, which is [`KERNEL_PID`] once the
+    /// parent has exited.
+    fn parent_pid(&self, processes: &BTreeMap<u64, Arc<Process>>) -> u64 {
+        let ppid = self.ppid.load(Ordering::Acquire);
+        match processes.get(&ppid) {
+            Some(parent) if !parent.has_exited() => ppid,
+            _ => KERNEL_PID,
+        }
+    }
+}
+
+/// Returns the ID of the current process, or [`KERNEL_PID`] for kernel tasks.
+pub(super) fn current_pid() -> u64 {
+    let curr = axtask::current();
+    if curr.aspace().is_some() {
+        curr.id().as_u64()
+    } else {
+        KERNEL_PID
+    }
+}
+
+/// Returns the file descriptor table of the current process, or `None` for
+/// kernel tasks.
+pub(super) fn current_fd_table() -> Option<Arc<RwLock<FdTable>>> {
+    let curr = axtask::current();
+    curr.aspace()?;
+    let processes = PROCESSES.lock();
+    Some(processes.get(&curr.id().as_u64())?.fd_table.clone())
+}
+
+/// Returns the current process and its address space.
+fn current() -> LinuxResult<(Arc<Process>, Arc<SpinNoIrq<AddrSpace>>)> {
+    let curr = axtask::current();
+    let aspace = curr.aspace().ok_or(LinuxError::ESRCH)?.clone();
+    let process = PROCESSES
+        .lock()
+        .get(&curr.id().as_u64())
+        .ok_or(LinuxError::ESRCH)?
+        .clone();
+    Ok((process, aspace))
+}
+
+/// Exits the current process with `exit_code`.
+///
+/// The process becomes a zombie, and its children are adopted by the kernel.
+/// Its file descriptors are closed.
+fn exit_current(exit_code: c_int) -> ! {
+    if let Ok((process, _)) = current() {
+        process.fd_table.write().close_all();
+    }
+    crate::sys_exit(exit_code)
+}
+
+/// Loads the executable at `path` into a new address space.
+fn load_program(
+    path: &str,
+    args: &[String],
+    envs: &[String],
+) -> LinuxResult<(AddrSpace, elf::LoadedProgram)> {
+    let meta = axfs::api::metadata(path)?;
+    if !meta.is_file() || meta.permissions().mode() & 0o111 == 0 {
+        return Err(LinuxError::EACCES);
+    }
+    let data = axfs::api::read(path)?;
+    let mut aspace = AddrSpace::new_empty()?;
+    let prog = elf::load(&mut aspace, &data, args, envs)?;
+    Ok((aspace, prog))
+}
+
+/// Enters user space with `ctx` from the current task, on its kernel stack.
+fn enter_uspace(ctx: UspaceContext) -> ! {
+    let kstack_top = axtask::current().kernel_stack_top().unwrap();
+    unsafe { ctx.enter_uspace(kstack_top) }
+}
+
+/// Spawns a task running `entry` in `aspace`, and registers it as a child of
+/// the current process, returns the process ID.
+///
+/// The new process gets a copy of the file descriptors of the current process.
+fn new_process<F>(entry: F, name: String, aspace: AddrSpace, heap: Heap) -> c_int
+where
+    F: FnOnce() + Send + 'static,
+{
+    let ppid = current_pid();
+    let fd_table = fd_table().read().clone();
+    // keep the lock until the process is registered, as its system calls
+    // may start before `spawn_user` returns
+    let mut processes = PROCESSES.lock();
+    let task = axtask::spawn_user(entry, name, Arc::new(SpinNoIrq::new(aspace)));
+    let pid = task.id().as_u64();
+    let process = Arc::new(Process {
+        pid,
+        ppid: AtomicU64::new(ppid),
+        task,
+        heap: Mutex::new(heap),
+        fd_table: Arc::new(RwLock::new(fd_table)),
+    });
+    processes.insert(pid, process);
+    pid as c_int
+}
+
+/// Spawns a process running the executable at `path`, returns its ID.
+fn spawn(path: &str, args: Vec<String>, envs: Vec<String>) -> LinuxResult<c_int> {
+    let (aspace, prog) = load_program(path, &args, &envs)?;
+    let name = args.into_iter().next().unwrap_or_else(|| path.into());
+    let entry = move || enter_uspace(UspaceContext::new(prog.entry, prog.ustack_top, 0));
+    Ok(new_process(
+        entry,
+        name,
+        aspace,
+        Heap::new(prog.heap_bottom),
+    ))
+}
+
+/// Creates a child of the current process, which is in the system call with
+/// the trap frame `tf`, returns the ID of the child.
+///
+/// The child gets a copy-on-write copy of the address space, and returns 0
+/// from the system call.
+fn fork(tf: &TrapFrame) -> LinuxResult<c_int> {
+    let (process, aspace) = current()?;
+    let aspace = aspace.lock().clone_cow()?;
+    let heap = *process.heap.lock();
+
+    let mut ctx = UspaceContext::from(tf);
+    ctx.set_retval(0);
+    // the user thread pointer is in the trap frame only on RISC-V
+    #[cfg(not(target_arch = "riscv64"))]
+    let tp = axhal::arch::read_thread_pointer();
+    let entry = move || {
+        #[cfg(not(target_arch = "riscv64"))]
+        unsafe {
+            axhal::arch::write_thread_pointer(tp)
+        };
+        enter_uspace(ctx)
+    };
+    Ok(new_process(entry, process.task.name().into(), aspace, heap))
+}
+
+/// Replaces the program of the current process with the executable at
+/// `path`, returns the user context to start it.
+///
+/// The address space is unchanged on failure.
+fn exec(path: &str, args: &[String], envs: &[String]) -> LinuxResult<UspaceContext> {
+    let (process, aspace) = current()?;
+    let (new_aspace, prog) = load_program(path, args, envs)?;
+    let mut aspace = aspace.lock();
+    let old_aspace = core::mem::replace(&mut *aspace, new_aspace);
+    unsafe { aspace.activate() };
+    drop(aspace);
+    drop(old_aspace);
+    *process.heap.lock() = Heap::new(prog.heap_bottom);
+    Ok(UspaceContext::new(prog.entry, prog.ustack_top, 0))
+}
+
+/// Waits for a child of the current process to exit, and reaps it.
+///
+/// Returns the ID and the exit code of the child, or `None` if `nohang` is
+/// set and no matching child has exited.
+fn wait(pid: c_int, nohang: bool) -> LinuxResult<Option<(c_int, c_int)>> {
+    let ppid = current_pid();
+    // process groups are not supported, all children are in the same group
+    let target = (pid > 0).then_some(pid as u64);
+    let children = || {
+        let processes = PROCESSES.lock();
+        processes
+            .values()
+            .filter(|p| target.unwrap_or(p.pid) == p.pid)
+            .filter(|p| p.parent_pid(&processes) == ppid)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    let child = loop {
+        let children = children();
+        if children.is_empty() {
+            return Err(LinuxError::ECHILD);
+        }
+        if let Some(child) = children.iter().find(|p| p.has_exited()) {
+            break child.clone();
+        }
+        if nohang {
+            return Ok(None);
+        }
+        // Wake up on the exit of any task, as the orphans adopted by the
+        // current process or new children may exit as well.
+        let first_check = Cell::new(true);
+        axtask::wait_any_exit_until(|| {
+            children.iter().any(|p| p.has_exited()) || !first_check.replace(false)
+        });
+    };
+    let exit_code = child.task.join().unwrap_or(0);
+    PROCESSES.lock().remove(&child.pid);
+    Ok(Some((child.pid as c_int, exit_code)))
+}
+
+/// Collects the strings of the null-terminated array `strs`.
+unsafe fn str_array(strs: *const *mut c_char) -> LinuxResult<Vec<String>> {
+    let mut res = Vec::new();
+    if strs.is_null() {
+        return Ok(res);
+    }
+    let mut p = strs;
+    while !(*p).is_null() {
+        res.push(char_ptr_to_str(*p)?.into());
+        p = p.add(1);
+    }
+    Ok(res)
+}
+
+/// Spawns a process running the ELF executable at `path`, with the arguments
+/// `argv` and the environment `envp`, and stores its process ID in `pid`.
+///
+/// Only statically linked executables are supported. The file actions and
+/// attributes are not supported, so `file_actions` and `attrp` must be null.
+///
+/// Return 0 if success.
+pub unsafe fn sys_posix_spawn(
+    pid: *mut c_int,
+    path: *const c_char,
+    file_actions: *const c_void,
+    attrp: *const c_void,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    let path = char_ptr_to_str(path);
+    debug!("sys_posix_spawn <= {:?}", path);
+    syscall_body!(sys_posix_spawn, {
+        if !file_actions.is_null() || !attrp.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let new_pid = spawn(path?, str_array(argv)?, str_array(envp)?)?;
+        if !pid.is_null() {
+            *pid = new_pid;
+        }
+        Ok(0)
+    })
+}
+
+/// Waits for the child process `pid` to exit, and stores its exit status in
+/// `wstatus`.
+///
+/// Any child is waited if `pid` is not positive, as process groups are not
+/// supported. The children of kernel tasks are the processes spawned by them,
+/// and the orphans. Only the `WNOHANG` option is supported.
+///
+/// Return the ID of the exited child, or 0 if `WNOHANG` is set and no child
+/// has exited.
+pub unsafe fn sys_waitpid(pid: c_int, wstatus: *mut c_int, options: c_int) -> c_int {
+    debug!("sys_waitpid <= {} {:#x}", pid, options);
+    syscall_body!(sys_waitpid, {
+        let nohang = options as u32 & ctypes::WNOHANG != 0;
+        match wait(pid, nohang)? {
+            Some((pid, exit_code)) => {
+                if !wstatus.is_null() {
+                    *wstatus = (exit_code & 0xff) << 8; // exited normally
+                }
+                Ok(pid)
+            }
+            None => Ok(0),
+        }
+    })
+}
+
+/// Get the parent process ID.
+///
+/// Return 0 if the parent is the kernel.
+pub fn sys_getppid() -> c_int {
+    syscall_body!(sys_getppid, {
+        let processes = PROCESSES.lock();
+        let ppid = processes
+            .get(&current_pid())
+            .map_or(KERNEL_PID, |p| p.parent_pid(&processes));
+        Ok(ppid as c_int)
+    })
+}
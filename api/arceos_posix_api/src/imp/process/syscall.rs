@@ -0,0 +1,579 @@
+//! Dispatching system calls from user space to the `sys_*` functions.
+//!
+//! The system call numbers and arguments follow the Linux ABI of each
+//! architecture. Pointers passed by user programs are checked against the
+//! address space of the process, and their pages are populated before the
+//! `sys_*` functions access them directly.
+
+use alloc::{string::String, vec::Vec};
+use core::ffi::{c_char, c_int};
+use core::mem::size_of;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::arch::TrapFrame;
+use axhal::mem::PAGE_SIZE_4K;
+use axhal::trap::{ProcessHandler, SyscallHandler};
+use axmm::{AddrSpace, MappingFlags};
+use memory_addr::align_down_4k;
+use spinlock::SpinNoIrq;
+
+use crate::ctypes;
+#[cfg(feature = "net")]
+use crate::imp::net::*;
+#[cfg(feature = "pipe")]
+use crate::imp::pipe::sys_pipe;
+use crate::imp::{fd_ops::*, fs::*, io::*, resources::*, task::*, time::*};
+use crate::utils::char_ptr_to_str;
+
+#[cfg(target_arch = "x86_64")]
+#[allow(dead_code)]
+mod nr {
+    pub const READ: usize = 0;
+    pub const WRITE: usize = 1;
+    pub const OPEN: usize = 2;
+    pub const CLOSE: usize = 3;
+    pub const STAT: usize = 4;
+    pub const FSTAT: usize = 5;
+    pub const LSTAT: usize = 6;
+    pub const LSEEK: usize = 8;
+    pub const MMAP: usize = 9;
+    pub const MUNMAP: usize = 11;
+    pub const BRK: usize = 12;
+    pub const RT_SIGACTION: usize = 13;
+    pub const RT_SIGPROCMASK: usize = 14;
+    pub const IOCTL: usize = 16;
+    pub const WRITEV: usize = 20;
+    pub const PIPE: usize = 22;
+    pub const SCHED_YIELD: usize = 24;
+    pub const DUP: usize = 32;
+    pub const DUP2: usize = 33;
+    pub const NANOSLEEP: usize = 35;
+    pub const GETPID: usize = 39;
+    pub const SOCKET: usize = 41;
+    pub const CONNECT: usize = 42;
+    pub const ACCEPT: usize = 43;
+    pub const SENDTO: usize = 44;
+    pub const RECVFROM: usize = 45;
+    pub const SHUTDOWN: usize = 48;
+    pub const BIND: usize = 49;
+    pub const LISTEN: usize = 50;
+    pub const CLONE: usize = 56;
+    pub const FORK: usize = 57;
+    pub const VFORK: usize = 58;
+    pub const EXECVE: usize = 59;
+    pub const EXIT: usize = 60;
+    pub const WAIT4: usize = 61;
+    pub const FCNTL: usize = 72;
+    pub const FSYNC: usize = 74;
+    pub const FDATASYNC: usize = 75;
+    pub const GETCWD: usize = 79;
+    pub const GETRLIMIT: usize = 97;
+    pub const GETUID: usize = 102;
+    pub const GETGID: usize = 104;
+    pub const SETUID: usize = 105;
+    pub const SETGID: usize = 106;
+    pub const GETEUID: usize = 107;
+    pub const GETEGID: usize = 108;
+    pub const GETPPID: usize = 110;
+    pub const ARCH_PRCTL: usize = 158;
+    pub const SETRLIMIT: usize = 160;
+    pub const GETTID: usize = 186;
+    pub const SCHED_SETAFFINITY: usize = 203;
+    pub const SCHED_GETAFFINITY: usize = 204;
+    pub const GETDENTS64: usize = 217;
+    pub const SET_TID_ADDRESS: usize = 218;
+    pub const CLOCK_GETTIME: usize = 228;
+    pub const EXIT_GROUP: usize = 231;
+    pub const OPENAT: usize = 257;
+    pub const MKDIRAT: usize = 258;
+    pub const NEWFSTATAT: usize = 262;
+    pub const UNLINKAT: usize = 263;
+    pub const DUP3: usize = 292;
+    pub const PIPE2: usize = 293;
+}
+
+/// The generic system call numbers shared by RISC-V and AArch64.
+#[cfg(not(target_arch = "x86_64"))]
+#[allow(dead_code)]
+mod nr {
+    pub const GETCWD: usize = 17;
+    pub const DUP: usize = 23;
+    pub const DUP3: usize = 24;
+    pub const FCNTL: usize = 25;
+    pub const IOCTL: usize = 29;
+    pub const MKDIRAT: usize = 34;
+    pub const UNLINKAT: usize = 35;
+    pub const OPENAT: usize = 56;
+    pub const CLOSE: usize = 57;
+    pub const PIPE2: usize = 59;
+    pub const GETDENTS64: usize = 61;
+    pub const LSEEK: usize = 62;
+    pub const READ: usize = 63;
+    pub const WRITE: usize = 64;
+    pub const WRITEV: usize = 66;
+    pub const NEWFSTATAT: usize = 79;
+    pub const FSTAT: usize = 80;
+    pub const FSYNC: usize = 82;
+    pub const FDATASYNC: usize = 83;
+    pub const EXIT: usize = 93;
+    pub const EXIT_GROUP: usize = 94;
+    pub const SET_TID_ADDRESS: usize = 96;
+    pub const NANOSLEEP: usize = 101;
+    pub const CLOCK_GETTIME: usize = 113;
+    pub const SCHED_SETAFFINITY: usize = 122;
+    pub const SCHED_GETAFFINITY: usize = 123;
+    pub const SCHED_YIELD: usize = 124;
+    pub const RT_SIGACTION: usize = 134;
+    pub const RT_SIGPROCMASK: usize = 135;
+    pub const SETGID: usize = 144;
+    pub const SETUID: usize = 146;
+    pub const GETRLIMIT: usize = 163;
+    pub const SETRLIMIT: usize = 164;
+    pub const GETPID: usize = 172;
+    pub const GETPPID: usize = 173;
+    pub const GETUID: usize = 174;
+    pub const GETEUID: usize = 175;
+    pub const GETGID: usize = 176;
+    pub const GETEGID: usize = 177;
+    pub const GETTID: usize = 178;
+    pub const SOCKET: usize = 198;
+    pub const BIND: usize = 200;
+    pub const LISTEN: usize = 201;
+    pub const ACCEPT: usize = 202;
+    pub const CONNECT: usize = 203;
+    pub const SENDTO: usize = 206;
+    pub const RECVFROM: usize = 207;
+    pub const SHUTDOWN: usize = 210;
+    pub const BRK: usize = 214;
+    pub const MUNMAP: usize = 215;
+    pub const CLONE: usize = 220;
+    pub const EXECVE: usize = 221;
+    pub const MMAP: usize = 222;
+    pub const WAIT4: usize = 260;
+}
+
+const PATH_MAX: usize = 4096;
+/// Maximum length of each argument or environment string of `execve`.
+const MAX_ARG_STRLEN: usize = 0x2_0000; // 128K
+/// Maximum number of the arguments or environment strings of `execve`.
+const MAX_ARG_STRINGS: usize = 0x1_0000;
+
+/// The signal sent to the parent when a child exits, the only `clone` flag
+/// supported.
+const SIGCHLD: usize = 17;
+
+#[cfg(target_arch = "x86_64")]
+const ARCH_SET_FS: usize = 0x1002;
+#[cfg(target_arch = "x86_64")]
+const ARCH_GET_FS: usize = 0x1003;
+
+/// Checks that the user memory of `len` bytes at `ptr` permits the access of
+/// `access`, and populates it so that the kernel can access it directly.
+fn user_ptr<T>(
+    aspace: &SpinNoIrq<AddrSpace>,
+    ptr: usize,
+    len: usize,
+    access: MappingFlags,
+) -> LinuxResult<*mut T> {
+    aspace
+        .lock()
+        .populate(ptr.into(), len, access | MappingFlags::USER)?;
+    Ok(ptr as *mut T)
+}
+
+/// Like [`user_ptr`], but a null pointer is allowed.
+fn user_ptr_or_null<T>(
+    aspace: &SpinNoIrq<AddrSpace>,
+    ptr: usize,
+    len: usize,
+    access: MappingFlags,
+) -> LinuxResult<*mut T> {
+    if ptr == 0 {
+        Ok(core::ptr::null_mut())
+    } else {
+        user_ptr(aspace, ptr, len, access)
+    }
+}
+
+/// Checks and populates the null-terminated user string at `ptr`.
+fn user_str(aspace: &SpinNoIrq<AddrSpace>, ptr: usize) -> LinuxResult<*const c_char> {
+    user_str_max(aspace, ptr, PATH_MAX)
+}
+
+/// Like [`user_str`], but the string can be up to `max_len` bytes long.
+fn user_str_max(
+    aspace: &SpinNoIrq<AddrSpace>,
+    ptr: usize,
+    max_len: usize,
+) -> LinuxResult<*const c_char> {
+    let mut start = ptr;
+    loop {
+        let end = align_down_4k(start) + PAGE_SIZE_4K;
+        aspace.lock().populate(
+            start.into(),
+            end - start,
+            MappingFlags::READ | MappingFlags::USER,
+        )?;
+        let bytes = unsafe { core::slice::from_raw_parts(start as *const u8, end - start) };
+        if bytes.contains(&0) {
+            return Ok(ptr as _);
+        } else if end - ptr >= max_len {
+            return Err(LinuxError::ENAMETOOLONG);
+        }
+        start = end;
+    }
+}
+
+/// Copies the strings of the null-terminated user array at `ptr`, which may
+/// be null, for `execve`.
+fn user_str_array(aspace: &SpinNoIrq<AddrSpace>, ptr: usize) -> LinuxResult<Vec<String>> {
+    let mut res = Vec::new();
+    if ptr == 0 {
+        return Ok(res);
+    }
+    loop {
+        if res.len() >= MAX_ARG_STRINGS {
+            return Err(LinuxError::E2BIG);
+        }
+        let item = ptr + res.len() * size_of::<usize>();
+        let s =
+            unsafe { *user_ptr::<usize>(aspace, item, size_of::<usize>(), MappingFlags::READ)? };
+        if s == 0 {
+            return Ok(res);
+        }
+        let s = user_str_max(aspace, s, MAX_ARG_STRLEN).map_err(|e| match e {
+            LinuxError::ENAMETOOLONG => LinuxError::E2BIG,
+            e => e,
+        })?;
+        res.push(char_ptr_to_str(s)?.into());
+    }
+}
+
+const fn prot_to_flags(prot: u32) -> MappingFlags {
+    let mut flags = MappingFlags::USER;
+    if prot & ctypes::PROT_READ != 0 {
+        flags = flags.union(MappingFlags::READ);
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags = flags.union(MappingFlags::WRITE);
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags = flags.union(MappingFlags::EXECUTE);
+    }
+    flags
+}
+
+/// Creates an anonymous mapping in the address space of the current process.
+///
+/// File mappings are not supported.
+fn mmap(addr: usize, len: usize, prot: u32, flags: u32) -> LinuxResult<isize> {
+    if flags & ctypes::MAP_ANONYMOUS == 0 {
+        return Err(LinuxError::ENODEV);
+    }
+    if len == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let size = len
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(LinuxError::ENOMEM)?;
+    let (process, aspace) = super::current()?;
+    let mut aspace = aspace.lock();
+    let start = if flags & ctypes::MAP_FIXED != 0 {
+        if addr % PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        } else if !aspace.contains_range(addr.into(), size) {
+            return Err(LinuxError::ENOMEM);
+        }
+        aspace.unmap(addr.into(), size)?;
+        addr.into()
+    } else {
+        let hint = if addr == 0 { process.mmap_base() } else { addr };
+        aspace
+            .find_free_area(hint.into(), size)
+            .ok_or(LinuxError::ENOMEM)?
+    };
+    aspace.map_alloc(start, size, prot_to_flags(prot), false)?;
+    Ok(start.as_usize() as isize)
+}
+
+fn dispatch(tf: &TrapFrame, syscall_num: usize) -> LinuxResult<isize> {
+    let (a0, a1, a2, a3) = (tf.arg0(), tf.arg1(), tf.arg2(), tf.arg3());
+    const R: MappingFlags = MappingFlags::READ;
+    const W: MappingFlags = MappingFlags::WRITE;
+
+    let curr = axtask::current();
+    let aspace = curr.aspace().ok_or(LinuxError::ENOSYS)?;
+
+    let ret = match syscall_num {
+        nr::READ => sys_read(a0 as _, user_ptr(aspace, a1, a2, W)?, a2),
+        nr::WRITE => sys_write(a0 as _, user_ptr(aspace, a1, a2, R)?, a2),
+        nr::WRITEV => {
+            let iocnt = a2 as c_int;
+            if !(0..=1024).contains(&iocnt) {
+                return Err(LinuxError::EINVAL);
+            }
+            let iov: *const ctypes::iovec =
+                user_ptr(aspace, a1, iocnt as usize * size_of::<ctypes::iovec>(), R)?;
+            for i in 0..iocnt as usize {
+                let iov = unsafe { &*iov.add(i) };
+                user_ptr::<u8>(aspace, iov.iov_base as usize, iov.iov_len, R)?;
+            }
+            unsafe { sys_writev(a0 as _, iov, iocnt) }
+        }
+        #[cfg(target_arch = "x86_64")]
+        nr::OPEN => sys_open(user_str(aspace, a0)?, a1 as _, a2 as _) as _,
+        nr::OPENAT => sys_openat(a0 as _, user_str(aspace, a1)?, a2 as _, a3 as _) as _,
+        nr::CLOSE => sys_close(a0 as _) as _,
+        nr::LSEEK => sys_lseek(a0 as _, a1 as _, a2 as _) as _,
+        #[cfg(target_arch = "x86_64")]
+        nr::STAT => unsafe {
+            sys_stat(
+                user_str(aspace, a0)?,
+                user_ptr(aspace, a1, size_of::<ctypes::stat>(), W)?,
+            ) as _
+        },
+        #[cfg(target_arch = "x86_64")]
+        nr::LSTAT => unsafe {
+            sys_lstat(
+                user_str(aspace, a0)?,
+                user_ptr(aspace, a1, size_of::<ctypes::stat>(), W)?,
+            ) as _
+        },
+        nr::FSTAT => unsafe {
+            sys_fstat(a0 as _, user_ptr(aspace, a1, size_of::<ctypes::stat>(), W)?) as _
+        },
+        nr::NEWFSTATAT => unsafe {
+            let buf = user_ptr(aspace, a2, size_of::<ctypes::stat>(), W)?;
+            sys_fstatat(a0 as _, user_str(aspace, a1)?, buf, a3 as _) as _
+        },
+        nr::FSYNC => sys_fsync(a0 as _) as _,
+        nr::FDATASYNC => sys_fdatasync(a0 as _) as _,
+        nr::GETDENTS64 => unsafe { sys_getdents64(a0 as _, user_ptr(aspace, a1, a2, W)?, a2) },
+        nr::MKDIRAT => sys_mkdirat(a0 as _, user_str(aspace, a1)?, a2 as _) as _,
+        nr::UNLINKAT => sys_unlinkat(a0 as _, user_str(aspace, a1)?, a2 as _) as _,
+        nr::GETCWD => {
+            let buf: *mut c_char = user_ptr(aspace, a0, a1, W)?;
+            let ret = sys_getcwd(buf, a1) as isize;
+            if ret < 0 {
+                ret
+            } else {
+                // the length of the path, including the terminating null byte
+                unsafe { core::ffi::CStr::from_ptr(buf) }.to_bytes().len() as isize + 1
+            }
+        }
+        nr::FCNTL => {
+            match a1 as u32 {
+                ctypes::F_GETLK
+                | ctypes::F_SETLK
+                | ctypes::F_SETLKW
+                | ctypes::F_OFD_GETLK
+                | ctypes::F_OFD_SETLK
+                | ctypes::F_OFD_SETLKW => {
+                    user_ptr::<ctypes::flock>(aspace, a2, size_of::<ctypes::flock>(), R | W)?;
+                }
+                _ => {}
+            }
+            sys_fcntl(a0 as _, a1 as _, a2) as _
+        }
+        nr::DUP => sys_dup(a0 as _) as _,
+        #[cfg(target_arch = "x86_64")]
+        nr::DUP2 => sys_dup2(a0 as _, a1 as _) as _,
+        nr::DUP3 => {
+            if a0 == a1 || a2 != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            sys_dup2(a0 as _, a1 as _) as _
+        }
+        #[cfg(all(feature = "pipe", target_arch = "x86_64"))]
+        nr::PIPE => {
+            let fds: *mut c_int = user_ptr(aspace, a0, 2 * size_of::<c_int>(), W)?;
+            sys_pipe(unsafe { core::slice::from_raw_parts_mut(fds, 2) }) as _
+        }
+        #[cfg(feature = "pipe")]
+        nr::PIPE2 => {
+            if a1 != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            let fds: *mut c_int = user_ptr(aspace, a0, 2 * size_of::<c_int>(), W)?;
+            sys_pipe(unsafe { core::slice::from_raw_parts_mut(fds, 2) }) as _
+        }
+        // no terminals yet
+        nr::IOCTL => return Err(LinuxError::ENOTTY),
+
+        nr::MMAP => mmap(a0, a1, a2 as _, a3 as _)?,
+        nr::MUNMAP => {
+            if a0 % PAGE_SIZE_4K != 0 || a1 == 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            let size = a1
+                .checked_next_multiple_of(PAGE_SIZE_4K)
+                .ok_or(LinuxError::EINVAL)?;
+            aspace.lock().unmap(a0.into(), size)?;
+            0
+        }
+        nr::BRK => {
+            let (process, _) = super::current()?;
+            let brk = process.brk(&mut aspace.lock(), a0);
+            brk as isize
+        }
+
+        nr::EXIT | nr::EXIT_GROUP => super::exit_current(a0 as _),
+        #[cfg(target_arch = "x86_64")]
+        nr::FORK | nr::VFORK => super::fork(tf)? as _,
+        nr::CLONE => {
+            // only the `fork`-like usage without a new stack is supported
+            if a0 != SIGCHLD || a1 != 0 {
+                warn!("unsupported clone flags: {:#x}", a0);
+                return Err(LinuxError::EINVAL);
+            }
+            super::fork(tf)? as _
+        }
+        nr::EXECVE => {
+            let ctx = {
+                let path = char_ptr_to_str(user_str(aspace, a0)?)?;
+                let args = user_str_array(aspace, a1)?;
+                let envs = user_str_array(aspace, a2)?;
+                super::exec(path, &args, &envs)?
+            };
+            super::enter_uspace(ctx)
+        }
+        nr::WAIT4 => unsafe {
+            // the resource usage is not collected
+            let wstatus = user_ptr_or_null(aspace, a1, size_of::<c_int>(), W)?;
+            super::sys_waitpid(a0 as _, wstatus, a2 as _) as _
+        },
+        nr::GETPPID => super::sys_getppid() as _,
+        nr::GETPID | nr::GETTID | nr::SET_TID_ADDRESS => sys_getpid() as _,
+        nr::GETUID | nr::GETEUID => sys_getuid() as _,
+        nr::GETGID | nr::GETEGID => sys_getgid() as _,
+        nr::SETUID => sys_setuid(a0 as _) as _,
+        nr::SETGID => sys_setgid(a0 as _) as _,
+        nr::SCHED_YIELD => sys_sched_yield() as _,
+        nr::SCHED_SETAFFINITY => unsafe {
+            sys_sched_setaffinity(a0 as _, a1, user_ptr(aspace, a2, a1, R)?) as _
+        },
+        nr::SCHED_GETAFFINITY => unsafe {
+            // returns the size of the CPU set written, as the raw syscall
+            match sys_sched_getaffinity(a0 as _, a1, user_ptr(aspace, a2, a1, W)?) {
+                0 => a1 as _,
+                err => err as _,
+            }
+        },
+        nr::NANOSLEEP => unsafe {
+            let req = user_ptr(aspace, a0, size_of::<ctypes::timespec>(), R)?;
+            let rem = user_ptr_or_null(aspace, a1, size_of::<ctypes::timespec>(), W)?;
+            sys_nanosleep(req, rem) as _
+        },
+        nr::CLOCK_GETTIME => unsafe {
+            sys_clock_gettime(
+                a0 as _,
+                user_ptr(aspace, a1, size_of::<ctypes::timespec>(), W)?,
+            ) as _
+        },
+        nr::GETRLIMIT => unsafe {
+            sys_getrlimit(
+                a0 as _,
+                user_ptr(aspace, a1, size_of::<ctypes::rlimit>(), W)?,
+            ) as _
+        },
+        nr::SETRLIMIT => unsafe {
+            sys_setrlimit(
+                a0 as _,
+                user_ptr(aspace, a1, size_of::<ctypes::rlimit>(), R)?,
+            ) as _
+        },
+        // signals are not supported, the handlers and masks are ignored
+        nr::RT_SIGACTION | nr::RT_SIGPROCMASK => 0,
+        #[cfg(target_arch = "x86_64")]
+        nr::ARCH_PRCTL => match a0 {
+            ARCH_SET_FS => {
+                unsafe { axhal::arch::write_thread_pointer(a1) };
+                0
+            }
+            ARCH_GET_FS => {
+                let addr: *mut usize = user_ptr(aspace, a1, size_of::<usize>(), W)?;
+                unsafe { addr.write(axhal::arch::read_thread_pointer()) };
+                0
+            }
+            _ => return Err(LinuxError::EINVAL),
+        },
+
+        #[cfg(feature = "net")]
+        nr::SOCKET => sys_socket(a0 as _, a1 as _, a2 as _) as _,
+        #[cfg(feature = "net")]
+        nr::BIND => sys_bind(a0 as _, user_ptr(aspace, a1, a2, R)?, a2 as _) as _,
+        #[cfg(feature = "net")]
+        nr::CONNECT => sys_connect(a0 as _, user_ptr(aspace, a1, a2, R)?, a2 as _) as _,
+        #[cfg(feature = "net")]
+        nr::LISTEN => sys_listen(a0 as _, a1 as _) as _,
+        #[cfg(feature = "net")]
+        nr::ACCEPT => unsafe {
+            let len_ptr = user_ptr_or_null::<ctypes::socklen_t>(
+                aspace,
+                a2,
+                size_of::<ctypes::socklen_t>(),
+                R | W,
+            )?;
+            let len = if len_ptr.is_null() {
+                0
+            } else {
+                *len_ptr as usize
+            };
+            let addr = user_ptr_or_null(aspace, a1, len, W)?;
+            sys_accept(a0 as _, addr, len_ptr) as _
+        },
+        #[cfg(feature = "net")]
+        nr::SENDTO => {
+            let (flags, addr, addrlen) = (a3 as c_int, tf.arg4(), tf.arg5());
+            let buf = user_ptr(aspace, a1, a2, R)?;
+            let addr = user_ptr_or_null(aspace, addr, addrlen, R)?;
+            sys_sendto(a0 as _, buf, a2, flags, addr, addrlen as _) as _
+        }
+        #[cfg(feature = "net")]
+        nr::RECVFROM => unsafe {
+            let (flags, addr, addrlen) = (a3 as c_int, tf.arg4(), tf.arg5());
+            let buf = user_ptr(aspace, a1, a2, W)?;
+            let len_ptr = user_ptr_or_null::<ctypes::socklen_t>(
+                aspace,
+                addrlen,
+                size_of::<ctypes::socklen_t>(),
+                R | W,
+            )?;
+            let len = if len_ptr.is_null() {
+                0
+            } else {
+                *len_ptr as usize
+            };
+            let addr = user_ptr_or_null(aspace, addr, len, W)?;
+            sys_recvfrom(a0 as _, buf, a2, flags, addr, len_ptr) as _
+        },
+        #[cfg(feature = "net")]
+        nr::SHUTDOWN => sys_shutdown(a0 as _, a1 as _) as _,
+
+        _ => {
+            warn!("unsupported syscall: {}", syscall_num);
+            return Err(LinuxError::ENOSYS);
+        }
+    };
+    Ok(ret)
+}
+
+struct SyscallHandlerImpl;
+
+#[crate_interface::impl_interface]
+impl SyscallHandler for SyscallHandlerImpl {
+    fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+        trace!("syscall {} <= {:#x?}", syscall_num, tf);
+        dispatch(tf, syscall_num).unwrap_or_else(|e| -e.code() as isize)
+    }
+}
+
+struct ProcessHandlerImpl;
+
+#[crate_interface::impl_interface]
+impl ProcessHandler for ProcessHandlerImpl {
+    fn kill_current(signo: i32) -> ! {
+        // the same as the exit status of a shell command killed by `signo`
+        super::exit_current(128 + signo)
+    }
+}
@@ -0,0 +1,144 @@
+#[cfg(feature = "irq")]
+use crate::utils::check_null_ptr;
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+#[cfg(feature = "irq")]
+use axerrno::LinuxError;
+use axerrno::LinuxResult;
+use axsync::Condvar;
+
+use core::ffi::c_int;
+use core::mem::{size_of, ManuallyDrop};
+#[cfg(feature = "irq")]
+use core::time::Duration;
+
+use super::mutex::PthreadMutex;
+use super::StaticInit;
+
+static_assertions::const_assert_eq!(
+    size_of::<PthreadCond>(),
+    size_of::<ctypes::pthread_cond_t>()
+);
+
+#[repr(C)]
+pub struct PthreadCond(StaticInit<Condvar>);
+
+impl PthreadCond {
+    const fn new() -> Self {
+        Self(StaticInit::new(Condvar::new()))
+    }
+
+    fn inner(&self) -> &Condvar {
+        self.0.get_or_init(Condvar::new)
+    }
+
+    fn wait(&self, mutex: &PthreadMutex) -> LinuxResult {
+        let guard = unsafe { mutex.inner().make_guard_unchecked() };
+        let _guard = ManuallyDrop::new(self.inner().wait(guard));
+        Ok(())
+    }
+
+    #[cfg(feature = "irq")]
+    fn wait_until(&self, mutex: &PthreadMutex, deadline: Duration) -> LinuxResult {
+        let dur = deadline.saturating_sub(axhal::time::current_time());
+        let guard = unsafe { mutex.inner().make_guard_unchecked() };
+        let (guard, res) = self.inner().wait_timeout(guard, dur);
+        let _guard = ManuallyDrop::new(guard);
+        if res.timed_out() {
+            Err(LinuxError::ETIMEDOUT)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Initialize a condition variable.
+///
+/// The clock of `attr` is ignored, as all clocks are the monotonic time
+/// since boot.
+pub fn sys_pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    _attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    debug!("sys_pthread_cond_init <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_init, {
+        check_null_mut_ptr(cond)?;
+        unsafe {
+            cond.cast::<PthreadCond>().write(PthreadCond::new());
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a condition variable.
+pub fn sys_pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_destroy <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_destroy, {
+        check_null_mut_ptr(cond)?;
+        Ok(0)
+    })
+}
+
+/// Unlock the mutex and wait on the condition variable, the mutex is locked
+/// again before returning.
+pub fn sys_pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_wait <= {:#x}, {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_wait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>()).wait(&*mutex.cast::<PthreadMutex>())?;
+        }
+        Ok(0)
+    })
+}
+
+/// Like `sys_pthread_cond_wait`, but returns `ETIMEDOUT` if the condition
+/// variable is not signaled before the absolute time `abstime`.
+#[cfg(feature = "irq")]
+pub unsafe fn sys_pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_timedwait <= {:#x}, {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_timedwait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        check_null_ptr(abstime)?;
+        unsafe {
+            (*cond.cast::<PthreadCond>())
+                .wait_until(&*mutex.cast::<PthreadMutex>(), (*abstime).into())?;
+        }
+        Ok(0)
+    })
+}
+
+/// Wake up one thread waiting on the condition variable.
+pub fn sys_pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_signal <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_signal, {
+        check_null_mut_ptr(cond)?;
+        unsafe { (*cond.cast::<PthreadCond>()).inner().notify_one() };
+        Ok(0)
+    })
+}
+
+/// Wake up all threads waiting on the condition variable.
+pub fn sys_pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_broadcast <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_broadcast, {
+        check_null_mut_ptr(cond)?;
+        unsafe { (*cond.cast::<PthreadCond>()).inner().notify_all() };
+        Ok(0)
+    })
+}
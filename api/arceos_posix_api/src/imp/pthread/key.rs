@@ -0,0 +1,109 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::LinuxError;
+use spin::Mutex;
+
+use core::ffi::{c_int, c_void};
+
+use super::{Pthread, TID_TO_PTHREAD};
+
+type Destructor = unsafe extern "C" fn(*mut c_void);
+
+const KEYS_MAX: usize = ctypes::PTHREAD_KEYS_MAX as usize;
+
+/// The destructors of the allocated keys, `None` if the key is not allocated.
+static KEYS: Mutex<[Option<Option<Destructor>>; KEYS_MAX]> = Mutex::new([None; KEYS_MAX]);
+
+fn is_allocated(key: ctypes::pthread_key_t) -> bool {
+    KEYS.lock()
+        .get(key as usize)
+        .is_some_and(|slot| slot.is_some())
+}
+
+/// Calls the destructors of the non-null values of the exiting thread, for
+/// at most `PTHREAD_DESTRUCTOR_ITERATIONS` rounds as the destructors may set
+/// new values.
+pub(super) fn run_destructors(thread: &Pthread) {
+    for _ in 0..ctypes::PTHREAD_DESTRUCTOR_ITERATIONS {
+        // The values are set to null before calling the destructors
+        let values = core::mem::take(&mut *thread.specific.lock());
+        let mut called = false;
+        for (key, value) in values {
+            if value.is_null() {
+                continue;
+            }
+            let destructor = KEYS.lock()[key as usize];
+            if let Some(Some(destructor)) = destructor {
+                unsafe { destructor(value) };
+                called = true;
+            }
+        }
+        if !called {
+            break;
+        }
+    }
+}
+
+/// Create a thread-specific data key, and store it in `key`.
+///
+/// `destructor` is called with the value of the key when a thread exits, if
+/// both of them are not null.
+pub unsafe fn sys_pthread_key_create(
+    key: *mut ctypes::pthread_key_t,
+    destructor: Option<Destructor>,
+) -> c_int {
+    debug!("sys_pthread_key_create <= {:#x}", key as usize);
+    syscall_body!(sys_pthread_key_create, {
+        check_null_mut_ptr(key)?;
+        let mut keys = KEYS.lock();
+        let idx = keys
+            .iter()
+            .position(|slot| slot.is_none())
+            .ok_or(LinuxError::EAGAIN)?;
+        keys[idx] = Some(destructor);
+        unsafe { key.write(idx as _) };
+        Ok(0)
+    })
+}
+
+/// Delete a thread-specific data key.
+///
+/// The values of the key are cleared without calling the destructor.
+pub fn sys_pthread_key_delete(key: ctypes::pthread_key_t) -> c_int {
+    debug!("sys_pthread_key_delete <= {}", key);
+    syscall_body!(sys_pthread_key_delete, {
+        let mut keys = KEYS.lock();
+        match keys.get_mut(key as usize) {
+            Some(slot) if slot.is_some() => *slot = None,
+            _ => return Err(LinuxError::EINVAL),
+        }
+        // Don't leak the old values to the next key with the same number
+        for ptr in TID_TO_PTHREAD.read().values() {
+            let thread = unsafe { &*(ptr.0 as *const Pthread) };
+            thread.specific.lock().remove(&key);
+        }
+        Ok(0)
+    })
+}
+
+/// Returns the value of the key for the current thread, or null if it's not
+/// set.
+pub fn sys_pthread_getspecific(key: ctypes::pthread_key_t) -> *mut c_void {
+    debug!("sys_pthread_getspecific <= {}", key);
+    Pthread::current()
+        .and_then(|thread| thread.specific.lock().get(&key).copied())
+        .unwrap_or(core::ptr::null_mut())
+}
+
+/// Set the value of the key for the current thread.
+pub fn sys_pthread_setspecific(key: ctypes::pthread_key_t, value: *const c_void) -> c_int {
+    debug!("sys_pthread_setspecific <= {}, {:#x}", key, value as usize);
+    syscall_body!(sys_pthread_setspecific, {
+        if !is_allocated(key) {
+            return Err(LinuxError::EINVAL);
+        }
+        let thread = Pthread::current().ok_or(LinuxError::ESRCH)?;
+        thread.specific.lock().insert(key, value as *mut c_void);
+        Ok(0)
+    })
+}
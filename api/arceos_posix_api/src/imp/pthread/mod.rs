@@ -1,72 +1,146 @@
-use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use alloc::{boxed::Box, collections::BTreeMap};
 use core::cell::UnsafeCell;
 use core::ffi::{c_int, c_void};
+use core::mem::{size_of, MaybeUninit};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, Ordering};
 
 use axerrno::{LinuxError, LinuxResult};
-use axtask::AxTaskRef;
-use spin::RwLock;
+use axtask::{AxTaskRef, WaitQueue};
+use spin::{Mutex, RwLock};
 
 use crate::ctypes;
 
+pub mod cond;
+pub mod key;
 pub mod mutex;
+pub mod rwlock;
+
+/// The value returned to the joiner of a canceled thread.
+const PTHREAD_CANCELED: *mut c_void = -1isize as _;
+
+/// The thread can be joined, and its resources are freed by the joiner.
+const JOINABLE: u8 = 0;
+/// The thread is detached, and its resources are freed when it exits.
+const DETACHED: u8 = 1;
+/// The thread has exited and is waiting to be joined or detached.
+const EXITED: u8 = 2;
 
 lazy_static::lazy_static! {
     static ref TID_TO_PTHREAD: RwLock<BTreeMap<u64, ForceSendSync<ctypes::pthread_t>>> = {
         let mut map = BTreeMap::new();
         let main_task = axtask::current();
         let main_tid = main_task.id().as_u64();
-        let main_thread = Pthread {
-            inner: main_task.as_task_ref().clone(),
-            retval: Arc::new(Packet {
-                result: UnsafeCell::new(core::ptr::null_mut()),
-            }),
-        };
+        let main_thread = Pthread::new(main_task.as_task_ref().clone(), JOINABLE);
         let ptr = Box::into_raw(Box::new(main_thread)) as *mut c_void;
         map.insert(main_tid, ForceSendSync(ptr));
         RwLock::new(map)
     };
 }
 
-struct Packet<T> {
-    result: UnsafeCell<T>,
+/// A synchronization object that can be statically initialized with zeros by
+/// `PTHREAD_*_INITIALIZER`, and is constructed on its first use in that case.
+///
+/// It's `#[repr(C)]` with the state first, so the initializers don't depend on
+/// the layout of `T`.
+#[repr(C)]
+struct StaticInit<T> {
+    /// One of `UNINIT`, `INITIALIZING` and `READY`.
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
 }
 
-unsafe impl<T> Send for Packet<T> {}
-unsafe impl<T> Sync for Packet<T> {}
+impl<T> StaticInit<T> {
+    const UNINIT: u8 = 0;
+    const INITIALIZING: u8 = 1;
+    const READY: u8 = 2;
+
+    const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU8::new(Self::READY),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+
+    /// Returns the value, constructing it by `init` if it's zero-initialized.
+    fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        loop {
+            match self.state.compare_exchange(
+                Self::UNINIT,
+                Self::INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    unsafe { (*self.value.get()).write(init()) };
+                    self.state.store(Self::READY, Ordering::Release);
+                    break;
+                }
+                Err(Self::READY) => break,
+                Err(_) => core::hint::spin_loop(),
+            }
+        }
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+}
 
 pub struct Pthread {
     inner: AxTaskRef,
-    retval: Arc<Packet<*mut c_void>>,
+    retval: UnsafeCell<*mut c_void>,
+    /// One of `JOINABLE`, `DETACHED` and `EXITED`.
+    state: AtomicU8,
+    cancel_disabled: AtomicBool,
+    cancel_pending: AtomicBool,
+    /// Values of the thread-specific data keys, see [`key`].
+    specific: Mutex<BTreeMap<ctypes::pthread_key_t, *mut c_void>>,
 }
 
 impl Pthread {
+    fn new(inner: AxTaskRef, state: u8) -> Self {
+        Self {
+            inner,
+            retval: UnsafeCell::new(core::ptr::null_mut()),
+            state: AtomicU8::new(state),
+            cancel_disabled: AtomicBool::new(false),
+            cancel_pending: AtomicBool::new(false),
+            specific: Mutex::new(BTreeMap::new()),
+        }
+    }
+
     fn create(
-        _attr: *const ctypes::pthread_attr_t,
+        attr: *const ctypes::pthread_attr_t,
         start_routine: extern "C" fn(arg: *mut c_void) -> *mut c_void,
         arg: *mut c_void,
     ) -> LinuxResult<ctypes::pthread_t> {
-        let arg_wrapper = ForceSendSync(arg);
-
-        let my_packet: Arc<Packet<*mut c_void>> = Arc::new(Packet {
-            result: UnsafeCell::new(core::ptr::null_mut()),
-        });
-        let their_packet = my_packet.clone();
+        let (stack_size, detached) = if attr.is_null() {
+            (axconfig::TASK_STACK_SIZE, false)
+        } else {
+            // Same layout as `_a_stacksize` and `_a_detach` in `pthread.h`
+            let attr = unsafe { &(*attr).__u };
+            let stack_size = unsafe { attr.__s[0] } as usize;
+            let detach = unsafe { attr.__i[3 * size_of::<usize>() / size_of::<c_int>()] };
+            if stack_size == 0 {
+                (axconfig::TASK_STACK_SIZE, detach != 0)
+            } else {
+                (stack_size, detach != 0)
+            }
+        };
 
+        let arg_wrapper = ForceSendSync(arg);
         let main = move || {
             let arg = arg_wrapper;
             let ret = start_routine(arg.0);
-            unsafe { *their_packet.result.get() = ret };
-            drop(their_packet);
+            Self::exit_current(ret);
         };
 
-        let task_inner = axtask::spawn(main);
+        // Hold the lock until the thread is inserted, as it looks itself up
+        // in the map when it exits.
+        let mut map = TID_TO_PTHREAD.write();
+        let task_inner = axtask::spawn_raw(main, "".into(), stack_size);
         let tid = task_inner.id().as_u64();
-        let thread = Pthread {
-            inner: task_inner,
-            retval: my_packet,
-        };
+        let state = if detached { DETACHED } else { JOINABLE };
+        let thread = Pthread::new(task_inner, state);
         let ptr = Box::into_raw(Box::new(thread)) as *mut c_void;
-        TID_TO_PTHREAD.write().insert(tid, ForceSendSync(ptr));
+        map.insert(tid, ForceSendSync(ptr));
         Ok(ptr)
     }
 
@@ -82,25 +156,87 @@ impl Pthread {
         unsafe { core::ptr::NonNull::new(Self::current_ptr()).map(|ptr| ptr.as_ref()) }
     }
 
+    /// Removes the thread from the map and frees it.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must not be used after it's freed.
+    unsafe fn free(ptr: *mut Pthread) {
+        let tid = unsafe { (*ptr).inner.id().as_u64() };
+        TID_TO_PTHREAD.write().remove(&tid);
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+
     fn exit_current(retval: *mut c_void) -> ! {
         let thread = Self::current().expect("fail to get current thread");
-        unsafe { *thread.retval.result.get() = retval };
+        key::run_destructors(thread);
+        unsafe { *thread.retval.get() = retval };
+        if thread
+            .state
+            .compare_exchange(JOINABLE, EXITED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // Detached, no one will join it
+            unsafe { Self::free(thread as *const Pthread as *mut Pthread) };
+        }
         axtask::exit(0);
     }
 
+    /// Exits the current thread if it has a pending cancellation request.
+    fn test_cancel() {
+        if let Some(thread) = Self::current() {
+            if !thread.cancel_disabled.load(Ordering::Acquire)
+                && thread.cancel_pending.load(Ordering::Acquire)
+            {
+                Self::exit_current(PTHREAD_CANCELED);
+            }
+        }
+    }
+
+    /// Calls `f` on the thread `ptr`, or returns `ESRCH` if it's not a thread
+    /// or has been freed.
+    fn with_thread<R>(ptr: ctypes::pthread_t, f: impl FnOnce(&Pthread) -> R) -> LinuxResult<R> {
+        let map = TID_TO_PTHREAD.read();
+        if !map.values().any(|p| core::ptr::eq(p.0, ptr)) {
+            return Err(LinuxError::ESRCH);
+        }
+        // It can't be freed while the map is locked
+        Ok(f(unsafe { &*(ptr as *const Pthread) }))
+    }
+
     fn join(ptr: ctypes::pthread_t) -> LinuxResult<*mut c_void> {
         if core::ptr::eq(ptr, Self::current_ptr() as _) {
             return Err(LinuxError::EDEADLK);
         }
+        Self::with_thread(ptr, |_| ())?;
+        Self::test_cancel();
 
-        let thread = unsafe { Box::from_raw(ptr as *mut Pthread) };
+        let thread = unsafe { &*(ptr as *const Pthread) };
+        if thread.state.load(Ordering::Acquire) == DETACHED {
+            return Err(LinuxError::EINVAL);
+        }
         thread.inner.join();
-        let tid = thread.inner.id().as_u64();
-        let retval = unsafe { *thread.retval.result.get() };
-        TID_TO_PTHREAD.write().remove(&tid);
-        drop(thread);
+        let retval = unsafe { *thread.retval.get() };
+        unsafe { Self::free(ptr as *mut Pthread) };
         Ok(retval)
     }
+
+    fn detach(ptr: ctypes::pthread_t) -> LinuxResult {
+        Self::with_thread(ptr, |_| ())?;
+        let thread = unsafe { &*(ptr as *const Pthread) };
+        match thread
+            .state
+            .compare_exchange(JOINABLE, DETACHED, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Ok(()),
+            // Already exited, free it now as no one will join it
+            Err(EXITED) => {
+                unsafe { Self::free(ptr as *mut Pthread) };
+                Ok(())
+            }
+            Err(_) => Err(LinuxError::EINVAL),
+        }
+    }
 }
 
 /// Returns the `pthread` struct of current thread.
@@ -111,7 +247,8 @@ pub fn sys_pthread_self() -> ctypes::pthread_t {
 /// Create a new thread with the given entry point and argument.
 ///
 /// If successful, it stores the pointer to the newly created `struct __pthread`
-/// in `res` and returns 0.
+/// in `res` and returns 0. The stack size and the detach state are taken from
+/// `attr` if it's not null.
 pub unsafe fn sys_pthread_create(
     res: *mut ctypes::pthread_t,
     attr: *const ctypes::pthread_attr_t,
@@ -147,6 +284,145 @@ pub unsafe fn sys_pthread_join(thread: ctypes::pthread_t, retval: *mut *mut c_vo
     })
 }
 
+/// Detach the given thread, so that its resources are freed when it exits
+/// instead of being joined.
+pub unsafe fn sys_pthread_detach(thread: ctypes::pthread_t) -> c_int {
+    debug!("sys_pthread_detach <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_detach, {
+        Pthread::detach(thread)?;
+        Ok(0)
+    })
+}
+
+/// Request the given thread to be canceled.
+///
+/// Only deferred cancellation is supported: the thread exits with
+/// `PTHREAD_CANCELED` when it reaches a cancellation point, which are
+/// `pthread_testcancel` and `pthread_join`. A blocked thread is not woken up
+/// by the request.
+///
+/// `pthread_cond_wait` is not a cancellation point, as there are no cleanup
+/// handlers to release the mutex it returns with.
+pub unsafe fn sys_pthread_cancel(thread: ctypes::pthread_t) -> c_int {
+    debug!("sys_pthread_cancel <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_cancel, {
+        Pthread::with_thread(thread, |t| {
+            t.cancel_pending.store(true, Ordering::Release);
+        })?;
+        Ok(0)
+    })
+}
+
+/// Set the cancelability state of the current thread, and store the old
+/// state in `oldstate` if it's not null.
+pub unsafe fn sys_pthread_setcancelstate(state: c_int, oldstate: *mut c_int) -> c_int {
+    debug!("sys_pthread_setcancelstate <= {}", state);
+    syscall_body!(sys_pthread_setcancelstate, {
+        let disabled = match state as u32 {
+            ctypes::PTHREAD_CANCEL_ENABLE => false,
+            ctypes::PTHREAD_CANCEL_DISABLE => true,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let thread = Pthread::current().ok_or(LinuxError::ESRCH)?;
+        let old = thread.cancel_disabled.swap(disabled, Ordering::AcqRel);
+        if !oldstate.is_null() {
+            let old = if old {
+                ctypes::PTHREAD_CANCEL_DISABLE
+            } else {
+                ctypes::PTHREAD_CANCEL_ENABLE
+            };
+            unsafe { oldstate.write(old as _) };
+        }
+        Ok(0)
+    })
+}
+
+/// Set the cancelability type of the current thread, and store the old type
+/// in `oldtype` if it's not null.
+///
+/// Asynchronous cancellation is accepted but behaves as deferred.
+pub unsafe fn sys_pthread_setcanceltype(ty: c_int, oldtype: *mut c_int) -> c_int {
+    debug!("sys_pthread_setcanceltype <= {}", ty);
+    syscall_body!(sys_pthread_setcanceltype, {
+        match ty as u32 {
+            ctypes::PTHREAD_CANCEL_DEFERRED | ctypes::PTHREAD_CANCEL_ASYNCHRONOUS => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
+        if !oldtype.is_null() {
+            unsafe { oldtype.write(ctypes::PTHREAD_CANCEL_DEFERRED as _) };
+        }
+        Ok(0)
+    })
+}
+
+/// Exits the current thread if a cancellation is requested.
+pub fn sys_pthread_testcancel() {
+    debug!("sys_pthread_testcancel");
+    Pthread::test_cancel();
+}
+
+/// Call `init_routine` only once for the given `once_control`, other threads
+/// calling it are blocked until `init_routine` returns.
+pub unsafe fn sys_pthread_once(
+    once_control: *mut ctypes::pthread_once_t,
+    init_routine: extern "C" fn(),
+) -> c_int {
+    const INCOMPLETE: i32 = ctypes::PTHREAD_ONCE_INIT as _;
+    const RUNNING: i32 = 1;
+    const COMPLETE: i32 = 2;
+    static ONCE_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+
+    debug!("sys_pthread_once <= {:#x}", once_control as usize);
+    syscall_body!(sys_pthread_once, {
+        crate::utils::check_null_mut_ptr(once_control)?;
+        let state = unsafe { &*(once_control as *const AtomicI32) };
+        match state.compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire) {
+            Ok(_) => {
+                init_routine();
+                state.store(COMPLETE, Ordering::Release);
+                ONCE_WAIT_QUEUE.notify_all(true);
+            }
+            Err(COMPLETE) => {}
+            Err(_) => ONCE_WAIT_QUEUE.wait_until(|| state.load(Ordering::Acquire) == COMPLETE),
+        }
+        Ok(0)
+    })
+}
+
+/// Set the CPU affinity of the given thread.
+///
+/// The thread is migrated if it's not allowed to run on its current CPU.
+pub unsafe fn sys_pthread_setaffinity_np(
+    thread: ctypes::pthread_t,
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> c_int {
+    debug!("sys_pthread_setaffinity_np <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_setaffinity_np, {
+        let mask = unsafe { crate::imp::task::cpu_set_to_mask(cpusetsize, cpuset)? };
+        let thread = unsafe { &*(thread as *const Pthread) };
+        if !axtask::set_affinity(&thread.inner, mask) {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(0)
+    })
+}
+
+/// Get the CPU affinity of the given thread.
+pub unsafe fn sys_pthread_getaffinity_np(
+    thread: ctypes::pthread_t,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> c_int {
+    debug!("sys_pthread_getaffinity_np <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_getaffinity_np, {
+        let thread = unsafe { &*(thread as *const Pthread) };
+        let mask = axtask::get_affinity(&thread.inner);
+        unsafe { crate::imp::task::mask_to_cpu_set(mask, cpusetsize, cpuset)? };
+        Ok(0)
+    })
+}
+
 #[derive(Clone, Copy)]
 struct ForceSendSync<T>(T);
 
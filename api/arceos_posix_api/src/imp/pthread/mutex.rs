@@ -1,51 +1,166 @@
+#[cfg(feature = "irq")]
+use crate::utils::check_null_ptr;
 use crate::{ctypes, utils::check_null_mut_ptr};
 
-use axerrno::LinuxResult;
+use axerrno::{LinuxError, LinuxResult};
 use axsync::Mutex;
 
 use core::ffi::c_int;
 use core::mem::{size_of, ManuallyDrop};
+use core::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "irq")]
+use core::time::Duration;
+
+use super::StaticInit;
 
 static_assertions::const_assert_eq!(
     size_of::<PthreadMutex>(),
     size_of::<ctypes::pthread_mutex_t>()
 );
 
+/// The bits of `pthread_mutexattr_t` that hold the mutex type, other
+/// attributes (e.g. robust or process-shared mutexes) are not supported.
+const MUTEX_TYPE_MASK: u32 = 3;
+
 #[repr(C)]
-pub struct PthreadMutex(Mutex<()>);
+pub struct PthreadMutex {
+    /// One of `PTHREAD_MUTEX_NORMAL`, `PTHREAD_MUTEX_RECURSIVE` and
+    /// `PTHREAD_MUTEX_ERRORCHECK`.
+    kind: u32,
+    /// How many more times a recursive mutex is locked by its owner, only
+    /// accessed by the owner.
+    depth: AtomicU32,
+    inner: StaticInit<Mutex<()>>,
+}
 
 impl PthreadMutex {
-    const fn new() -> Self {
-        Self(Mutex::new(()))
+    const fn new(kind: u32) -> Self {
+        Self {
+            kind,
+            depth: AtomicU32::new(0),
+            inner: StaticInit::new(Mutex::new(())),
+        }
+    }
+
+    /// Locks the mutex again if it's already held by the current task, which
+    /// only succeeds for recursive mutexes, others fail with `err`.
+    ///
+    /// Returns `None` if it's not held by the current task.
+    fn relock(&self, err: LinuxError) -> Option<LinuxResult> {
+        if !self.inner().is_owned_by_current() {
+            return None;
+        }
+        if self.kind != ctypes::PTHREAD_MUTEX_RECURSIVE {
+            return Some(Err(err));
+        }
+        let depth = self.depth.load(Ordering::Relaxed);
+        Some(match depth.checked_add(1) {
+            Some(depth) => {
+                self.depth.store(depth, Ordering::Relaxed);
+                Ok(())
+            }
+            None => Err(LinuxError::EAGAIN),
+        })
     }
 
     fn lock(&self) -> LinuxResult {
-        let _guard = ManuallyDrop::new(self.0.lock());
+        if let Some(res) = self.relock(LinuxError::EDEADLK) {
+            return res;
+        }
+        let _guard = ManuallyDrop::new(self.inner().lock());
+        Ok(())
+    }
+
+    fn try_lock(&self) -> LinuxResult {
+        if let Some(res) = self.relock(LinuxError::EBUSY) {
+            return res;
+        }
+        let _guard = ManuallyDrop::new(self.inner().try_lock().ok_or(LinuxError::EBUSY)?);
+        Ok(())
+    }
+
+    #[cfg(feature = "irq")]
+    fn lock_until(&self, deadline: Duration) -> LinuxResult {
+        if let Some(res) = self.relock(LinuxError::EDEADLK) {
+            return res;
+        }
+        let dur = deadline.saturating_sub(axhal::time::current_time());
+        let _guard = ManuallyDrop::new(
+            self.inner()
+                .try_lock_for(dur)
+                .ok_or(LinuxError::ETIMEDOUT)?,
+        );
         Ok(())
     }
 
     fn unlock(&self) -> LinuxResult {
-        unsafe { self.0.force_unlock() };
+        if !self.inner().is_owned_by_current() {
+            return Err(LinuxError::EPERM);
+        }
+        let depth = self.depth.load(Ordering::Relaxed);
+        if depth > 0 {
+            self.depth.store(depth - 1, Ordering::Relaxed);
+        } else {
+            unsafe { self.inner().force_unlock() };
+        }
         Ok(())
     }
+
+    /// Returns the inner [`Mutex`], for waiting on condition variables.
+    pub(super) fn inner(&self) -> &Mutex<()> {
+        self.inner.get_or_init(|| Mutex::new(()))
+    }
 }
 
 /// Initialize a mutex.
-pub fn sys_pthread_mutex_init(
+///
+/// Returns `EINVAL` if `attr` has attributes other than the mutex type, as
+/// they are not supported.
+pub unsafe fn sys_pthread_mutex_init(
     mutex: *mut ctypes::pthread_mutex_t,
-    _attr: *const ctypes::pthread_mutexattr_t,
+    attr: *const ctypes::pthread_mutexattr_t,
 ) -> c_int {
     debug!("sys_pthread_mutex_init <= {:#x}", mutex as usize);
     syscall_body!(sys_pthread_mutex_init, {
         check_null_mut_ptr(mutex)?;
+        let kind = if attr.is_null() {
+            ctypes::PTHREAD_MUTEX_NORMAL
+        } else {
+            let attr = unsafe { (*attr).__attr };
+            if attr & !MUTEX_TYPE_MASK != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            attr
+        };
+        match kind {
+            ctypes::PTHREAD_MUTEX_NORMAL
+            | ctypes::PTHREAD_MUTEX_RECURSIVE
+            | ctypes::PTHREAD_MUTEX_ERRORCHECK => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
         unsafe {
-            mutex.cast::<PthreadMutex>().write(PthreadMutex::new());
+            mutex.cast::<PthreadMutex>().write(PthreadMutex::new(kind));
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy the given mutex.
+pub fn sys_pthread_mutex_destroy(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
+    debug!("sys_pthread_mutex_destroy <= {:#x}", mutex as usize);
+    syscall_body!(sys_pthread_mutex_destroy, {
+        check_null_mut_ptr(mutex)?;
+        if unsafe { (*mutex.cast::<PthreadMutex>()).inner().is_locked() } {
+            return Err(LinuxError::EBUSY);
         }
         Ok(0)
     })
 }
 
 /// Lock the given mutex.
+///
+/// Returns `EDEADLK` if it's already locked by the current thread, unless it's
+/// a recursive mutex.
 pub fn sys_pthread_mutex_lock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
     debug!("sys_pthread_mutex_lock <= {:#x}", mutex as usize);
     syscall_body!(sys_pthread_mutex_lock, {
@@ -57,7 +172,8 @@ pub fn sys_pthread_mutex_lock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
     })
 }
 
-/// Unlock the given mutex.
+/// Unlock the given mutex, returns `EPERM` if it's not locked by the current
+/// thread.
 pub fn sys_pthread_mutex_unlock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
     debug!("sys_pthread_mutex_unlock <= {:#x}", mutex as usize);
     syscall_body!(sys_pthread_mutex_unlock, {
@@ -68,3 +184,33 @@ pub fn sys_pthread_mutex_unlock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
         Ok(0)
     })
 }
+
+/// Try to lock the given mutex, returns `EBUSY` if it's already locked.
+pub fn sys_pthread_mutex_trylock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
+    debug!("sys_pthread_mutex_trylock <= {:#x}", mutex as usize);
+    syscall_body!(sys_pthread_mutex_trylock, {
+        check_null_mut_ptr(mutex)?;
+        unsafe {
+            (*mutex.cast::<PthreadMutex>()).try_lock()?;
+        }
+        Ok(0)
+    })
+}
+
+/// Lock the given mutex, returns `ETIMEDOUT` if it's not locked before the
+/// absolute time `abstime`.
+#[cfg(feature = "irq")]
+pub unsafe fn sys_pthread_mutex_timedlock(
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!("sys_pthread_mutex_timedlock <= {:#x}", mutex as usize);
+    syscall_body!(sys_pthread_mutex_timedlock, {
+        check_null_mut_ptr(mutex)?;
+        check_null_ptr(abstime)?;
+        unsafe {
+            (*mutex.cast::<PthreadMutex>()).lock_until((*abstime).into())?;
+        }
+        Ok(0)
+    })
+}
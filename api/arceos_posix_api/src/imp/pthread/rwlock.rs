@@ -0,0 +1,134 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::RwLock;
+
+use core::ffi::c_int;
+use core::mem::{size_of, ManuallyDrop};
+
+use super::StaticInit;
+
+static_assertions::const_assert_eq!(
+    size_of::<PthreadRwlock>(),
+    size_of::<ctypes::pthread_rwlock_t>()
+);
+
+#[repr(C)]
+pub struct PthreadRwlock(StaticInit<RwLock<()>>);
+
+impl PthreadRwlock {
+    const fn new() -> Self {
+        Self(StaticInit::new(RwLock::new(())))
+    }
+
+    fn inner(&self) -> &RwLock<()> {
+        self.0.get_or_init(|| RwLock::new(()))
+    }
+
+    fn read(&self) -> LinuxResult {
+        let _guard = ManuallyDrop::new(self.inner().read());
+        Ok(())
+    }
+
+    fn try_read(&self) -> LinuxResult {
+        let _guard = ManuallyDrop::new(self.inner().try_read().ok_or(LinuxError::EBUSY)?);
+        Ok(())
+    }
+
+    fn write(&self) -> LinuxResult {
+        let _guard = ManuallyDrop::new(self.inner().write());
+        Ok(())
+    }
+
+    fn try_write(&self) -> LinuxResult {
+        let _guard = ManuallyDrop::new(self.inner().try_write().ok_or(LinuxError::EBUSY)?);
+        Ok(())
+    }
+
+    fn unlock(&self) -> LinuxResult {
+        // The lock is held by the caller, so it's either held by the caller
+        // exclusively or shared with other readers.
+        if self.inner().is_locked_exclusive() {
+            unsafe { self.inner().force_write_unlock() };
+        } else {
+            unsafe { self.inner().force_read_unlock() };
+        }
+        Ok(())
+    }
+}
+
+/// Initialize a read-write lock.
+pub fn sys_pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    _attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    debug!("sys_pthread_rwlock_init <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_init, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            rwlock.cast::<PthreadRwlock>().write(PthreadRwlock::new());
+        }
+        Ok(0)
+    })
+}
+
+/// Destroy a read-write lock.
+pub fn sys_pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_destroy <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_destroy, {
+        check_null_mut_ptr(rwlock)?;
+        Ok(0)
+    })
+}
+
+/// Lock the read-write lock for reading.
+pub fn sys_pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_rdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_rdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwlock>()).read()? };
+        Ok(0)
+    })
+}
+
+/// Try to lock the read-write lock for reading, returns `EBUSY` if it's
+/// locked by a writer.
+pub fn sys_pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_tryrdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_tryrdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwlock>()).try_read()? };
+        Ok(0)
+    })
+}
+
+/// Lock the read-write lock for writing.
+pub fn sys_pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_wrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_wrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwlock>()).write()? };
+        Ok(0)
+    })
+}
+
+/// Try to lock the read-write lock for writing, returns `EBUSY` if it's
+/// already locked.
+pub fn sys_pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_trywrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_trywrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwlock>()).try_write()? };
+        Ok(0)
+    })
+}
+
+/// Unlock the read-write lock held by the current thread.
+pub fn sys_pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_unlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_unlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwlock>()).unlock()? };
+        Ok(0)
+    })
+}
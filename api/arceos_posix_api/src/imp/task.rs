@@ -1,4 +1,11 @@
-use core::ffi::c_int;
+use axerrno::{LinuxError, LinuxResult};
+use core::ffi::{c_int, c_ulong};
+use core::mem::size_of;
+
+use crate::ctypes;
+
+#[cfg(feature = "multitask")]
+use axtask::AxTaskRef;
 
 /// Relinquish the CPU, and switches to another task.
 ///
@@ -16,6 +23,115 @@ pub fn sys_sched_yield() -> c_int {
     0
 }
 
+/// Converts the CPU set of `cpusetsize` bytes to a CPU mask, bit `i` for CPU
+/// `i`.
+///
+/// Only the first word is used, as there are no more CPUs than the bits of a
+/// word.
+pub(crate) unsafe fn cpu_set_to_mask(
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> LinuxResult<usize> {
+    if cpuset.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    if cpusetsize < size_of::<c_ulong>() {
+        return Err(LinuxError::EINVAL);
+    }
+    let mask = unsafe { (*cpuset).__bits[0] } as usize;
+    if axconfig::SMP < usize::BITS as usize {
+        Ok(mask & ((1 << axconfig::SMP) - 1))
+    } else {
+        Ok(mask)
+    }
+}
+
+/// Stores the CPU mask to the CPU set of `cpusetsize` bytes, the reverse of
+/// [`cpu_set_to_mask`].
+pub(crate) unsafe fn mask_to_cpu_set(
+    mask: usize,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> LinuxResult {
+    if cpuset.is_null() {
+        return Err(LinuxError::EFAULT);
+    }
+    // same as Linux, the set must be able to hold all CPUs
+    if cpusetsize * 8 < axconfig::SMP || cpusetsize % size_of::<c_ulong>() != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    unsafe {
+        core::ptr::write_bytes(cpuset as *mut u8, 0, cpusetsize);
+        *(cpuset as *mut c_ulong) = mask as c_ulong;
+    }
+    Ok(())
+}
+
+/// Finds the task with the given thread ID, or the current task if `pid` is 0.
+#[cfg(feature = "multitask")]
+fn find_task(pid: ctypes::pid_t) -> LinuxResult<AxTaskRef> {
+    let curr = axtask::current();
+    if pid == 0 || pid as u64 == curr.id().as_u64() {
+        return Ok(curr.as_task_ref().clone());
+    }
+    axtask::all_tasks()
+        .into_iter()
+        .find(|t| t.id().as_u64() == pid as u64 && t.state() != axtask::TaskState::Exited)
+        .ok_or(LinuxError::ESRCH)
+}
+
+/// Set the CPU affinity of the thread `pid`, or the current thread if `pid`
+/// is 0.
+///
+/// The thread is migrated if it's not allowed to run on its current CPU.
+pub unsafe fn sys_sched_setaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> c_int {
+    debug!("sys_sched_setaffinity <= {} {}", pid, cpusetsize);
+    syscall_body!(sys_sched_setaffinity, {
+        let mask = unsafe { cpu_set_to_mask(cpusetsize, cpuset)? };
+        #[cfg(feature = "multitask")]
+        if !axtask::set_affinity(&find_task(pid)?, mask) {
+            return Err(LinuxError::EINVAL);
+        }
+        #[cfg(not(feature = "multitask"))]
+        {
+            if pid != 0 && pid != 2 {
+                return Err(LinuxError::ESRCH);
+            }
+            if mask & 1 == 0 {
+                return Err(LinuxError::EINVAL); // only runs on the primary CPU
+            }
+        }
+        Ok(0)
+    })
+}
+
+/// Get the CPU affinity of the thread `pid`, or the current thread if `pid`
+/// is 0.
+pub unsafe fn sys_sched_getaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> c_int {
+    debug!("sys_sched_getaffinity <= {} {}", pid, cpusetsize);
+    syscall_body!(sys_sched_getaffinity, {
+        #[cfg(feature = "multitask")]
+        let mask = axtask::get_affinity(&find_task(pid)?);
+        #[cfg(not(feature = "multitask"))]
+        let mask = {
+            if pid != 0 && pid != 2 {
+                return Err(LinuxError::ESRCH);
+            }
+            1
+        };
+        unsafe { mask_to_cpu_set(mask, cpusetsize, cpuset)? };
+        Ok(0)
+    })
+}
+
 /// Get current thread ID.
 pub fn sys_getpid() -> c_int {
     syscall_body!(sys_getpid,
@@ -30,6 +146,74 @@ pub fn sys_getpid() -> c_int {
     )
 }
 
+/// Get the user ID of the current task.
+///
+/// The real and effective IDs are not distinguished.
+pub fn sys_getuid() -> ctypes::uid_t {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::current().uid()
+    }
+    #[cfg(not(feature = "multitask"))]
+    0 // root
+}
+
+/// Get the group ID of the current task.
+///
+/// The real and effective IDs are not distinguished.
+pub fn sys_getgid() -> ctypes::gid_t {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::current().gid()
+    }
+    #[cfg(not(feature = "multitask"))]
+    0 // root
+}
+
+/// Set the user ID of the current task, only root can do this.
+///
+/// The tasks created by the current task afterwards inherit the new ID.
+pub fn sys_setuid(uid: ctypes::uid_t) -> c_int {
+    debug!("sys_setuid <= {}", uid);
+    syscall_body!(sys_setuid, {
+        #[cfg(feature = "multitask")]
+        {
+            let curr = axtask::current();
+            if curr.uid() != 0 && curr.uid() != uid {
+                return Err(LinuxError::EPERM);
+            }
+            curr.set_ids(uid, curr.gid());
+        }
+        #[cfg(not(feature = "multitask"))]
+        if uid != 0 {
+            return Err(LinuxError::EPERM); // there is only one task
+        }
+        Ok(0)
+    })
+}
+
+/// Set the group ID of the current task, only root can do this.
+///
+/// The tasks created by the current task afterwards inherit the new ID.
+pub fn sys_setgid(gid: ctypes::gid_t) -> c_int {
+    debug!("sys_setgid <= {}", gid);
+    syscall_body!(sys_setgid, {
+        #[cfg(feature = "multitask")]
+        {
+            let curr = axtask::current();
+            if curr.uid() != 0 && curr.gid() != gid {
+                return Err(LinuxError::EPERM);
+            }
+            curr.set_ids(curr.uid(), gid);
+        }
+        #[cfg(not(feature = "multitask"))]
+        if gid != 0 {
+            return Err(LinuxError::EPERM);
+        }
+        Ok(0)
+    })
+}
+
 /// Exit current task
 pub fn sys_exit(exit_code: c_int) -> ! {
     debug!("sys_exit <= {}", exit_code);
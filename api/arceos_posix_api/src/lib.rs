@@ -35,17 +35,27 @@ pub mod ctypes;
 pub use imp::io::{sys_read, sys_write, sys_writev};
 pub use imp::resources::{sys_getrlimit, sys_setrlimit};
 pub use imp::sys::sys_sysconf;
-pub use imp::task::{sys_exit, sys_getpid, sys_sched_yield};
+pub use imp::task::{
+    sys_exit, sys_getgid, sys_getpid, sys_getuid, sys_sched_getaffinity, sys_sched_setaffinity,
+    sys_sched_yield, sys_setgid, sys_setuid,
+};
 pub use imp::time::{sys_clock_gettime, sys_nanosleep};
 
 #[cfg(feature = "fd")]
 pub use imp::fd_ops::{sys_close, sys_dup, sys_dup2, sys_fcntl};
 #[cfg(feature = "fs")]
-pub use imp::fs::{sys_fstat, sys_getcwd, sys_lseek, sys_lstat, sys_open, sys_rename, sys_stat};
+pub use imp::fs::{
+    sys_chmod, sys_chown, sys_fchmod, sys_fchown, sys_fdatasync, sys_flock, sys_fstat, sys_fstatat,
+    sys_fsync, sys_futimens, sys_getcwd, sys_getdents64, sys_lchown, sys_link, sys_lseek,
+    sys_lstat, sys_mkdirat, sys_mount, sys_open, sys_openat, sys_readlink, sys_rename,
+    sys_renameat, sys_stat, sys_symlink, sys_umask, sys_umount, sys_unlinkat, sys_utimensat,
+};
 #[cfg(feature = "select")]
 pub use imp::io_mpx::sys_select;
 #[cfg(feature = "epoll")]
 pub use imp::io_mpx::{sys_epoll_create, sys_epoll_ctl, sys_epoll_wait};
+#[cfg(feature = "mmap")]
+pub use imp::mmap::{sys_mmap, sys_msync, sys_munmap};
 #[cfg(feature = "net")]
 pub use imp::net::{
     sys_accept, sys_bind, sys_connect, sys_freeaddrinfo, sys_getaddrinfo, sys_getpeername,
@@ -54,9 +64,37 @@ pub use imp::net::{
 };
 #[cfg(feature = "pipe")]
 pub use imp::pipe::sys_pipe;
+#[cfg(feature = "process")]
+pub use imp::process::{sys_getppid, sys_posix_spawn, sys_waitpid};
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use imp::pthread::cond::sys_pthread_cond_timedwait;
+#[cfg(feature = "multitask")]
+pub use imp::pthread::cond::{
+    sys_pthread_cond_broadcast, sys_pthread_cond_destroy, sys_pthread_cond_init,
+    sys_pthread_cond_signal, sys_pthread_cond_wait,
+};
+#[cfg(feature = "multitask")]
+pub use imp::pthread::key::{
+    sys_pthread_getspecific, sys_pthread_key_create, sys_pthread_key_delete,
+    sys_pthread_setspecific,
+};
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use imp::pthread::mutex::sys_pthread_mutex_timedlock;
 #[cfg(feature = "multitask")]
 pub use imp::pthread::mutex::{
-    sys_pthread_mutex_init, sys_pthread_mutex_lock, sys_pthread_mutex_unlock,
+    sys_pthread_mutex_destroy, sys_pthread_mutex_init, sys_pthread_mutex_lock,
+    sys_pthread_mutex_trylock, sys_pthread_mutex_unlock,
+};
+#[cfg(feature = "multitask")]
+pub use imp::pthread::rwlock::{
+    sys_pthread_rwlock_destroy, sys_pthread_rwlock_init, sys_pthread_rwlock_rdlock,
+    sys_pthread_rwlock_tryrdlock, sys_pthread_rwlock_trywrlock, sys_pthread_rwlock_unlock,
+    sys_pthread_rwlock_wrlock,
 };
 #[cfg(feature = "multitask")]
-pub use imp::pthread::{sys_pthread_create, sys_pthread_exit, sys_pthread_join, sys_pthread_self};
+pub use imp::pthread::{
+    sys_pthread_cancel, sys_pthread_create, sys_pthread_detach, sys_pthread_exit,
+    sys_pthread_getaffinity_np, sys_pthread_join, sys_pthread_once, sys_pthread_self,
+    sys_pthread_setaffinity_np, sys_pthread_setcancelstate, sys_pthread_setcanceltype,
+    sys_pthread_testcancel,
+};
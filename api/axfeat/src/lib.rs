@@ -19,9 +19,14 @@
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//! - User space
+//!     - `uspace`: Enable user address spaces and running tasks in user mode.
 //! - Upperlayer stacks (fs, net, display)
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext2`: Use ext2 as the main filesystem if it is detected on the disk.
+//!     - `overlay`: Stack a writable RAM filesystem over the main filesystem.
+//!     - `initramfs`: Use a RAM filesystem unpacked from a cpio archive as the main filesystem.
 //!     - `net`: Enable networking support.
 //!     - `display`: Enable graphics support.
 //! - Device drivers
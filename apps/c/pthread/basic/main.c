@@ -1,4 +1,5 @@
 #include <assert.h>
+#include <errno.h>
 #include <pthread.h>
 #include <stdio.h>
 #include <stdlib.h>
@@ -117,6 +118,215 @@ void test_mutex()
     assert(data == NUM_THREADS);
 }
 
+void test_mutex_trylock()
+{
+    pthread_mutex_lock(&lock);
+    assert(pthread_mutex_trylock(&lock) == EBUSY);
+    pthread_mutex_unlock(&lock);
+    assert(pthread_mutex_trylock(&lock) == 0);
+    pthread_mutex_unlock(&lock);
+    puts("test_mutex_trylock: OK");
+}
+
+void test_mutex_type()
+{
+    pthread_mutexattr_t attr;
+    pthread_mutex_t m;
+    int type;
+
+    pthread_mutexattr_init(&attr);
+    assert(pthread_mutexattr_settype(&attr, PTHREAD_MUTEX_RECURSIVE) == 0);
+    assert(pthread_mutexattr_gettype(&attr, &type) == 0 && type == PTHREAD_MUTEX_RECURSIVE);
+    pthread_mutex_init(&m, &attr);
+    assert(pthread_mutex_lock(&m) == 0);
+    assert(pthread_mutex_lock(&m) == 0);
+    assert(pthread_mutex_trylock(&m) == 0);
+    assert(pthread_mutex_unlock(&m) == 0);
+    assert(pthread_mutex_unlock(&m) == 0);
+    assert(pthread_mutex_destroy(&m) == EBUSY);
+    assert(pthread_mutex_unlock(&m) == 0);
+    assert(pthread_mutex_unlock(&m) == EPERM);
+    pthread_mutex_destroy(&m);
+
+    assert(pthread_mutexattr_settype(&attr, PTHREAD_MUTEX_ERRORCHECK) == 0);
+    pthread_mutex_init(&m, &attr);
+    assert(pthread_mutex_lock(&m) == 0);
+    assert(pthread_mutex_lock(&m) == EDEADLK);
+    assert(pthread_mutex_unlock(&m) == 0);
+    assert(pthread_mutex_unlock(&m) == EPERM);
+    pthread_mutex_destroy(&m);
+    pthread_mutexattr_destroy(&attr);
+    puts("test_mutex_type: OK");
+}
+
+static pthread_cond_t cond = PTHREAD_COND_INITIALIZER;
+static int ready = 0;
+
+void *ThreadFunc4(void *arg)
+{
+    pthread_mutex_lock(&lock);
+    ready = 1;
+    pthread_cond_signal(&cond);
+    pthread_mutex_unlock(&lock);
+    return NULL;
+}
+
+void test_cond()
+{
+    pthread_t t;
+    pthread_mutex_lock(&lock);
+    pthread_create(&t, NULL, ThreadFunc4, NULL);
+    while (!ready) pthread_cond_wait(&cond, &lock);
+    pthread_mutex_unlock(&lock);
+    pthread_join(t, NULL);
+    printf("test_cond: ready = %d\n", ready);
+}
+
+static pthread_rwlock_t rwlock = PTHREAD_RWLOCK_INITIALIZER;
+
+void *ThreadFunc5(void *arg)
+{
+    pthread_rwlock_rdlock(&rwlock);
+    int value = *(int *)arg;
+    assert(pthread_rwlock_trywrlock(&rwlock) == EBUSY);
+    pthread_rwlock_unlock(&rwlock);
+
+    pthread_rwlock_wrlock(&rwlock);
+    value = *(int *)arg;
+    for (int i = 0; i < 1000; i++) getpid();
+    *(int *)arg = value + 1;
+    pthread_rwlock_unlock(&rwlock);
+    return NULL;
+}
+
+void test_rwlock()
+{
+    const int NUM_THREADS = 10;
+    int data = 0;
+    pthread_t t[NUM_THREADS];
+
+    for (int i = 0; i < NUM_THREADS; i++) pthread_create(&t[i], NULL, ThreadFunc5, &data);
+    for (int i = 0; i < NUM_THREADS; i++) pthread_join(t[i], NULL);
+
+    printf("test_rwlock: data = %d\n", data);
+    assert(data == NUM_THREADS);
+}
+
+static pthread_key_t key;
+static int destructed = 0;
+
+void key_destructor(void *value)
+{
+    pthread_mutex_lock(&lock);
+    destructed += *(int *)value;
+    pthread_mutex_unlock(&lock);
+}
+
+void *ThreadFunc6(void *arg)
+{
+    assert(pthread_getspecific(key) == NULL);
+    pthread_setspecific(key, arg);
+    assert(pthread_getspecific(key) == arg);
+    return NULL;
+}
+
+void test_key()
+{
+    const int NUM_THREADS = 10;
+    int values[NUM_THREADS];
+    pthread_t t[NUM_THREADS];
+
+    assert(pthread_key_create(&key, key_destructor) == 0);
+    for (int i = 0; i < NUM_THREADS; i++) {
+        values[i] = i + 1;
+        pthread_create(&t[i], NULL, ThreadFunc6, &values[i]);
+    }
+    for (int i = 0; i < NUM_THREADS; i++) pthread_join(t[i], NULL);
+    assert(pthread_key_delete(key) == 0);
+
+    printf("test_key: destructed = %d\n", destructed);
+    assert(destructed == NUM_THREADS * (NUM_THREADS + 1) / 2);
+}
+
+static pthread_once_t once = PTHREAD_ONCE_INIT;
+static int once_calls = 0;
+
+void once_routine()
+{
+    once_calls++;
+}
+
+void *ThreadFunc7(void *arg)
+{
+    pthread_once(&once, once_routine);
+    return NULL;
+}
+
+void test_once()
+{
+    const int NUM_THREADS = 10;
+    pthread_t t[NUM_THREADS];
+
+    for (int i = 0; i < NUM_THREADS; i++) pthread_create(&t[i], NULL, ThreadFunc7, NULL);
+    for (int i = 0; i < NUM_THREADS; i++) pthread_join(t[i], NULL);
+
+    printf("test_once: calls = %d\n", once_calls);
+    assert(once_calls == 1);
+}
+
+static int detached_done = 0;
+
+void *ThreadFunc8(void *arg)
+{
+    pthread_mutex_lock(&lock);
+    detached_done = 1;
+    pthread_cond_signal(&cond);
+    pthread_mutex_unlock(&lock);
+    return NULL;
+}
+
+void test_detach()
+{
+    pthread_t t;
+    pthread_attr_t attr;
+    size_t stack_size;
+
+    pthread_attr_init(&attr);
+    pthread_attr_setstacksize(&attr, 0x10000);
+    pthread_attr_setdetachstate(&attr, PTHREAD_CREATE_DETACHED);
+    pthread_attr_getstacksize(&attr, &stack_size);
+    assert(stack_size == 0x10000);
+
+    pthread_mutex_lock(&lock);
+    pthread_create(&t, &attr, ThreadFunc8, NULL);
+    while (!detached_done) pthread_cond_wait(&cond, &lock);
+    pthread_mutex_unlock(&lock);
+    pthread_attr_destroy(&attr);
+    puts("test_detach: OK");
+}
+
+void *ThreadFunc9(void *arg)
+{
+    while (1) {
+        pthread_testcancel();
+        getpid();
+    }
+    return NULL;
+}
+
+void test_cancel()
+{
+    pthread_t t;
+    void *thread_result;
+
+    pthread_create(&t, NULL, ThreadFunc9, NULL);
+    pthread_cancel(t);
+    pthread_join(t, &thread_result);
+    assert(thread_result == PTHREAD_CANCELED);
+    assert(pthread_cancel(t) == ESRCH);
+    puts("test_cancel: OK");
+}
+
 int main()
 {
     pthread_t main_thread = pthread_self();
@@ -125,6 +335,14 @@ int main()
     test_create_join();
     test_create_exit();
     test_mutex();
+    test_mutex_trylock();
+    test_mutex_type();
+    test_cond();
+    test_rwlock();
+    test_key();
+    test_once();
+    test_detach();
+    test_cancel();
     puts("(C)Pthread basic tests run OK!");
 
     return 0;
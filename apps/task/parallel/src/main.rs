@@ -7,6 +7,7 @@ extern crate axstd as std;
 
 use rand::{rngs::SmallRng, RngCore, SeedableRng};
 use std::thread;
+use std::time::{Duration, Instant};
 use std::{sync::Arc, vec::Vec};
 
 #[cfg(feature = "axstd")]
@@ -47,6 +48,47 @@ fn sqrt(n: &u64) -> u64 {
     }
 }
 
+/// Sums up the square roots of all numbers in `vec` with `num_tasks` tasks,
+/// returns the sum and the elapsed time.
+fn parallel_sum(vec: &Arc<Vec<u64>>, num_tasks: usize) -> (u64, Duration) {
+    let start = Instant::now();
+    let chunk_size = NUM_DATA.div_ceil(num_tasks);
+    let tasks = (0..num_tasks)
+        .map(|i| {
+            let vec = vec.clone();
+            thread::spawn(move || {
+                let left = (i * chunk_size).min(NUM_DATA);
+                let right = (left + chunk_size).min(NUM_DATA);
+                vec[left..right].iter().map(sqrt).sum::<u64>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let sum = tasks.into_iter().map(|t| t.join().unwrap()).sum();
+    (sum, start.elapsed())
+}
+
+/// Measures how the summation scales with the number of tasks. With `N` CPUs
+/// the speedup should be close to `N` once there are at least `N` tasks.
+fn benchmark(vec: &Arc<Vec<u64>>, expect: u64) {
+    let mut base = None;
+    let mut num_tasks = 1;
+    while num_tasks <= NUM_TASKS {
+        let (sum, elapsed) = parallel_sum(vec, num_tasks);
+        assert_eq!(sum, expect);
+        let base = *base.get_or_insert(elapsed);
+        // avoid floating point numbers, as FP/SIMD may be disabled
+        let speedup = base.as_micros() * 100 / elapsed.as_micros().max(1);
+        println!(
+            "benchmark: {:>2} tasks, {:>6} us, speedup {}.{:02}x",
+            num_tasks,
+            elapsed.as_micros(),
+            speedup / 100,
+            speedup % 100
+        );
+        num_tasks *= 2;
+    }
+}
+
 #[cfg_attr(feature = "axstd", no_mangle)]
 fn main() {
     let mut rng = SmallRng::seed_from_u64(0xdead_beef);
@@ -95,4 +137,7 @@ fn main() {
     assert_eq!(expect, actual);
 
     println!("Parallel summation tests run OK!");
+
+    benchmark(&vec, expect);
+    println!("Parallel benchmark run OK!");
 }
@@ -0,0 +1,54 @@
+use alloc::boxed::Box;
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+type ReadFn = dyn Fn(&mut [u8]) -> VfsResult<usize> + Send + Sync;
+type WriteFn = dyn Fn(&[u8]) -> VfsResult<usize> + Send + Sync;
+
+/// A console device behaves like `/dev/console` and `/dev/tty`.
+///
+/// Reads and writes are passed to the callbacks provided by the owner of the
+/// console, regardless of the offset.
+pub struct ConsoleDev {
+    read: Box<ReadFn>,
+    write: Box<WriteFn>,
+}
+
+impl ConsoleDev {
+    /// Create a console device, whose input is read by `read` and output is
+    /// written by `write`.
+    pub fn new<R, W>(read: R, write: W) -> Self
+    where
+        R: Fn(&mut [u8]) -> VfsResult<usize> + Send + Sync + 'static,
+        W: Fn(&[u8]) -> VfsResult<usize> + Send + Sync + 'static,
+    {
+        Self {
+            read: Box::new(read),
+            write: Box::new(write),
+        }
+    }
+}
+
+impl VfsNodeOps for ConsoleDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        (self.read)(buf)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        (self.write)(buf)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
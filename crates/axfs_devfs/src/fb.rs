@@ -0,0 +1,99 @@
+use alloc::boxed::Box;
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+type FlushFn = dyn Fn() + Send + Sync;
+
+/// A framebuffer device behaves like `/dev/fb0`.
+///
+/// Reads and writes access the framebuffer memory directly, the screen is
+/// updated by the flush callback after each write or on [`VfsNodeOps::fsync`].
+/// The framebuffer memory can also be mapped into the address space by
+/// [`FramebufferDev::base_vaddr`] and [`FramebufferDev::size`].
+pub struct FramebufferDev {
+    base_vaddr: usize,
+    size: usize,
+    flush: Box<FlushFn>,
+}
+
+impl FramebufferDev {
+    /// Create a framebuffer device on the memory `[base_vaddr, base_vaddr + size)`,
+    /// `flush` is called to show the contents on the screen.
+    ///
+    /// # Safety
+    ///
+    /// The memory region must be valid to read and write during the lifetime
+    /// of the device.
+    pub unsafe fn new<F>(base_vaddr: usize, size: usize, flush: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Self {
+            base_vaddr,
+            size,
+            flush: Box::new(flush),
+        }
+    }
+
+    /// Returns the virtual address of the framebuffer memory.
+    pub fn base_vaddr(&self) -> usize {
+        self.base_vaddr
+    }
+
+    /// Returns the size of the framebuffer memory in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the range `[start, end)` of the framebuffer memory accessed at
+    /// `offset` with a buffer of length `len`.
+    fn range(&self, offset: u64, len: usize) -> (usize, usize) {
+        let start = offset.min(self.size as u64) as usize;
+        (start, self.size.min(start.saturating_add(len)))
+    }
+}
+
+impl VfsNodeOps for FramebufferDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            self.size as u64,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let (start, end) = self.range(offset, buf.len());
+        // SAFETY: the range is in the framebuffer memory (see `new`).
+        let src = unsafe {
+            core::slice::from_raw_parts((self.base_vaddr + start) as *const u8, end - start)
+        };
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let (start, end) = self.range(offset, buf.len());
+        if start == end && !buf.is_empty() {
+            return Err(VfsError::StorageFull); // beyond the end of the framebuffer
+        }
+        // SAFETY: the range is in the framebuffer memory (see `new`).
+        let dst = unsafe {
+            core::slice::from_raw_parts_mut((self.base_vaddr + start) as *mut u8, end - start)
+        };
+        dst.copy_from_slice(&buf[..dst.len()]);
+        (self.flush)();
+        Ok(dst.len())
+    }
+
+    fn fsync(&self) -> VfsResult {
+        (self.flush)();
+        Ok(())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -6,15 +6,21 @@
 
 extern crate alloc;
 
+mod console;
 mod dir;
+mod fb;
 mod null;
+mod random;
 mod zero;
 
 #[cfg(test)]
 mod tests;
 
+pub use self::console::ConsoleDev;
 pub use self::dir::DirNode;
+pub use self::fb::FramebufferDev;
 pub use self::null::NullDev;
+pub use self::random::RandomDev;
 pub use self::zero::ZeroDev;
 
 use alloc::sync::Arc;
@@ -36,15 +42,20 @@ impl DeviceFileSystem {
         }
     }
 
+    /// Returns the root directory.
+    pub fn root(&self) -> &Arc<DirNode> {
+        &self.root
+    }
+
     /// Create a subdirectory at the root directory.
-    pub fn mkdir(&self, name: &'static str) -> Arc<DirNode> {
+    pub fn mkdir(&self, name: &str) -> Arc<DirNode> {
         self.root.mkdir(name)
     }
 
     /// Add a node to the root directory.
     ///
     /// The node must implement [`axfs_vfs::VfsNodeOps`], and be wrapped in [`Arc`].
-    pub fn add(&self, name: &'static str, node: VfsNodeRef) {
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
         self.root.add(name, node);
     }
 }
@@ -62,6 +73,10 @@ impl VfsOps for DeviceFileSystem {
     fn root_dir(&self) -> VfsNodeRef {
         self.root.clone()
     }
+
+    fn fs_type(&self) -> &str {
+        "devfs"
+    }
 }
 
 impl Default for DeviceFileSystem {
@@ -0,0 +1,64 @@
+use alloc::boxed::Box;
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+type SourceFn = dyn Fn() -> Option<u64> + Send + Sync;
+
+/// A random device behaves like `/dev/urandom` and `/dev/random`.
+///
+/// The bytes are read from an entropy source provided by the owner of the
+/// device, e.g. a hardware random number generator, so it never blocks.
+/// Data written to the device is discarded.
+pub struct RandomDev {
+    source: Box<SourceFn>,
+}
+
+impl RandomDev {
+    /// Create a random device whose bytes are generated by `source`, which
+    /// returns [`None`] if it fails.
+    pub fn new<F>(source: F) -> Self
+    where
+        F: Fn() -> Option<u64> + Send + Sync + 'static,
+    {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+impl VfsNodeOps for RandomDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut read_len = 0;
+        for chunk in buf.chunks_mut(8) {
+            let Some(value) = (self.source)() else {
+                // return what has been read, or an error if nothing
+                return if read_len > 0 {
+                    Ok(read_len)
+                } else {
+                    Err(VfsError::Io)
+                };
+            };
+            chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+            read_len += chunk.len();
+        }
+        Ok(read_len)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
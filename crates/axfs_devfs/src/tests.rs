@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodeOps, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -113,3 +113,63 @@ fn test_devfs() {
     test_devfs_ops(&devfs).unwrap();
     test_get_parent(&devfs).unwrap();
 }
+
+#[test]
+fn test_devices() -> VfsResult {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    // random devices read bytes from the source
+    let counter = AtomicUsize::new(0x0101_0101_0101_0101);
+    let random = RandomDev::new(move || Some(counter.fetch_add(1, Ordering::Relaxed) as u64));
+    let (mut buf1, mut buf2) = ([0; 13], [0; 13]);
+    assert_eq!(random.read_at(0, &mut buf1)?, 13);
+    assert_eq!(random.read_at(0, &mut buf2)?, 13);
+    assert_eq!(buf1, [1, 1, 1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1]);
+    assert_eq!(buf2, [3, 1, 1, 1, 1, 1, 1, 1, 4, 1, 1, 1, 1]);
+    assert_eq!(random.write_at(0, b"entropy")?, 7);
+    let broken = RandomDev::new(|| None);
+    assert_eq!(broken.read_at(0, &mut buf1).err(), Some(VfsError::Io));
+
+    // console devices pass data to the callbacks
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let out = output.clone();
+    let console = ConsoleDev::new(
+        |buf| {
+            buf[0] = b'y';
+            Ok(1)
+        },
+        move |buf| {
+            out.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        },
+    );
+    assert_eq!(console.get_attr()?.file_type(), VfsNodeType::CharDevice);
+    assert_eq!(console.write_at(100, b"hello")?, 5);
+    assert_eq!(output.lock().unwrap().as_slice(), b"hello");
+    assert_eq!(console.read_at(0, &mut buf1)?, 1);
+    assert_eq!(buf1[0], b'y');
+
+    // framebuffer devices access the memory directly
+    static FLUSHED: AtomicUsize = AtomicUsize::new(0);
+    let mut mem = vec![0u8; 64];
+    let fb = unsafe {
+        FramebufferDev::new(mem.as_mut_ptr() as usize, mem.len(), || {
+            FLUSHED.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+    assert_eq!(fb.get_attr()?.size(), 64);
+    assert_eq!(fb.write_at(60, b"pixels")?, 4);
+    assert_eq!(FLUSHED.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        fb.write_at(64, b"pixels").err(),
+        Some(VfsError::StorageFull)
+    );
+    assert_eq!(fb.read_at(58, &mut buf1)?, 6);
+    assert_eq!(&buf1[..6], b"\0\0pixe");
+    fb.fsync()?;
+    assert_eq!(FLUSHED.load(Ordering::Relaxed), 2);
+    drop(fb);
+    assert_eq!(&mem[60..], b"pixe");
+    Ok(())
+}
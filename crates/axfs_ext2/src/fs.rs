@@ -0,0 +1,966 @@
+//! Block, inode and directory management of the filesystem.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeType, VfsResult};
+use core::time::Duration;
+
+use crate::layout::*;
+use crate::BlockDevice;
+
+/// A directory entry read from the disk, with its location.
+pub struct DirEntryInfo {
+    pub ino: u32,
+    pub file_type: u8,
+    pub name: String,
+    /// Logical block of the directory that contains the entry.
+    lblock: u64,
+    /// Offset of the entry in the block.
+    offset: usize,
+    /// Offset of the previous entry in the same block.
+    prev_offset: Option<usize>,
+}
+
+/// The mounted filesystem, all operations are performed with it locked.
+pub struct Ext2Inner {
+    dev: Box<dyn BlockDevice>,
+    sb: SuperBlock,
+    groups: GroupDescTable,
+    block_size: usize,
+    read_only: bool,
+    clock: Option<fn() -> Duration>,
+    /// Number of [`Ext2Node`](crate::Ext2Node)s of each inode that are alive.
+    open_nodes: BTreeMap<u32, usize>,
+    /// Inodes that have no links, which are released once no node of them is
+    /// alive. Inodes that failed to be released are also kept to retry.
+    orphans: BTreeSet<u32>,
+}
+
+impl Ext2Inner {
+    pub fn new(mut dev: Box<dyn BlockDevice>, clock: Option<fn() -> Duration>) -> VfsResult<Self> {
+        let mut raw = [0; SUPERBLOCK_SIZE];
+        dev.read_at(SUPERBLOCK_OFFSET, &mut raw)?;
+        let sb = SuperBlock::from_bytes(raw);
+        if sb.magic() != EXT2_MAGIC {
+            return Err(VfsError::InvalidData);
+        }
+        let incompat = sb.feature_incompat();
+        if incompat & !INCOMPAT_READABLE != 0 {
+            log::warn!("ext2: unsupported incompatible features {:#x}", incompat);
+            return Err(VfsError::Unsupported);
+        }
+        let read_only =
+            incompat & !INCOMPAT_WRITABLE != 0 || sb.feature_ro_compat() & !RO_COMPAT_WRITABLE != 0;
+        if read_only {
+            log::warn!("ext2: features not fully supported, mounted read-only");
+        }
+        if incompat & INCOMPAT_RECOVER != 0 {
+            log::warn!("ext2: the journal needs recovery, which is not supported");
+        }
+
+        let block_size = sb.block_size();
+        let gdt_size = sb.groups_count() as usize * sb.desc_size();
+        let mut gdt = vec![0; gdt_size];
+        let gdt_block = sb.first_data_block() + 1;
+        dev.read_at(gdt_block * block_size as u64, &mut gdt)?;
+        let groups = GroupDescTable::from_bytes(gdt, sb.desc_size());
+        Ok(Self {
+            dev,
+            sb,
+            groups,
+            block_size,
+            read_only,
+            clock,
+            open_nodes: BTreeMap::new(),
+            orphans: BTreeSet::new(),
+        })
+    }
+
+    pub fn check_writable(&self) -> VfsResult {
+        if self.read_only {
+            Err(VfsError::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn flush(&mut self) -> VfsResult {
+        self.release_orphans();
+        self.dev.flush()
+    }
+
+    /// The current time in seconds, if the filesystem has a clock.
+    fn now(&self) -> Option<u32> {
+        self.clock.map(|clock| clock().as_secs() as u32)
+    }
+
+    /// Updates the change time of the inode, and the modification time if
+    /// `modified` is true.
+    pub fn touch(&self, inode: &mut Inode, modified: bool) {
+        let now = self.now();
+        inode.set_times([None, now.filter(|_| modified), now]);
+    }
+
+    /// Sets the access and modification times of the inode `ino`, `None` means
+    /// unchanged. The change time is set to the current time.
+    pub fn set_times(
+        &mut self,
+        ino: u32,
+        atime: Option<Duration>,
+        mtime: Option<Duration>,
+    ) -> VfsResult {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        let secs = |t: Option<Duration>| t.map(|t| t.as_secs() as u32);
+        inode.set_times([secs(atime), secs(mtime), self.now()]);
+        self.write_inode(ino, &inode)
+    }
+
+    // open nodes
+
+    /// Records that a node of the inode `ino` is created.
+    pub fn open_node(&mut self, ino: u32) {
+        *self.open_nodes.entry(ino).or_default() += 1;
+    }
+
+    /// Records that a node of the inode `ino` is dropped, and releases the
+    /// inode if it has no links and it was the last node.
+    pub fn close_node(&mut self, ino: u32) {
+        if let Some(count) = self.open_nodes.get_mut(&ino) {
+            *count -= 1;
+            if *count == 0 {
+                self.open_nodes.remove(&ino);
+                if self.orphans.contains(&ino) {
+                    self.release_orphans();
+                }
+            }
+        }
+    }
+
+    /// Releases the orphan inodes that have no nodes alive.
+    fn release_orphans(&mut self) {
+        let closed: Vec<_> = self
+            .orphans
+            .iter()
+            .copied()
+            .filter(|ino| !self.open_nodes.contains_key(ino))
+            .collect();
+        for ino in closed {
+            match self.read_inode(ino) {
+                Ok(mut inode) => match self.release_inode(ino, &mut inode) {
+                    Ok(()) => {
+                        self.orphans.remove(&ino);
+                    }
+                    Err(e) => log::warn!("ext2: failed to release inode {}: {:?}", ino, e),
+                },
+                Err(e) => log::warn!("ext2: failed to read inode {}: {:?}", ino, e),
+            }
+        }
+    }
+
+    fn block_offset(&self, block: u64) -> u64 {
+        block * self.block_size as u64
+    }
+
+    fn read_block(&mut self, block: u64, buf: &mut [u8]) -> VfsResult {
+        let offset = self.block_offset(block);
+        self.dev.read_at(offset, buf)
+    }
+
+    fn write_block(&mut self, block: u64, buf: &[u8]) -> VfsResult {
+        let offset = self.block_offset(block);
+        self.dev.write_at(offset, buf)
+    }
+
+    fn read_u32(&mut self, offset: u64) -> VfsResult<u32> {
+        let mut buf = [0; 4];
+        self.dev.read_at(offset, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn write_u32(&mut self, offset: u64, value: u32) -> VfsResult {
+        self.dev.write_at(offset, &value.to_le_bytes())
+    }
+
+    fn sync_super(&mut self) -> VfsResult {
+        self.dev.write_at(SUPERBLOCK_OFFSET, self.sb.as_bytes())
+    }
+
+    fn sync_group(&mut self, group: u32) -> VfsResult {
+        let gdt_block = self.sb.first_data_block() + 1;
+        let (offset, desc) = self.groups.desc_bytes(group);
+        let offset = self.block_offset(gdt_block) + offset as u64;
+        self.dev.write_at(offset, desc)
+    }
+
+    // inodes
+
+    fn inode_offset(&self, ino: u32) -> VfsResult<u64> {
+        if ino == 0 || ino > self.sb.inodes_count() {
+            return Err(VfsError::InvalidData);
+        }
+        let group = (ino - 1) / self.sb.inodes_per_group();
+        let idx = (ino - 1) % self.sb.inodes_per_group();
+        let table = self.groups.inode_table(group);
+        Ok(self.block_offset(table) + idx as u64 * self.sb.inode_size() as u64)
+    }
+
+    pub fn read_inode(&mut self, ino: u32) -> VfsResult<Inode> {
+        let offset = self.inode_offset(ino)?;
+        let mut raw = [0; INODE_BASE_SIZE];
+        self.dev.read_at(offset, &mut raw)?;
+        Ok(Inode::from_bytes(raw))
+    }
+
+    pub fn write_inode(&mut self, ino: u32, inode: &Inode) -> VfsResult {
+        let offset = self.inode_offset(ino)?;
+        self.dev.write_at(offset, inode.as_bytes())
+    }
+
+    fn group_of_inode(&self, ino: u32) -> u32 {
+        (ino - 1) / self.sb.inodes_per_group()
+    }
+
+    /// Allocates an inode, preferably in the block group `goal`.
+    fn alloc_inode(&mut self, goal: u32, is_dir: bool) -> VfsResult<u32> {
+        let groups = self.sb.groups_count();
+        let per_group = self.sb.inodes_per_group();
+        for group in (goal..groups).chain(0..goal) {
+            if self.groups.free_inodes_count(group) == 0 {
+                continue;
+            }
+            let bitmap_block = self.groups.inode_bitmap(group);
+            let mut bitmap = vec![0; self.block_size];
+            self.read_block(bitmap_block, &mut bitmap)?;
+            let first = self.sb.first_ino();
+            let found = (0..per_group).find(|&i| {
+                let ino = group * per_group + i + 1;
+                ino >= first && bitmap[i as usize / 8] & (1 << (i % 8)) == 0
+            });
+            if let Some(i) = found {
+                bitmap[i as usize / 8] |= 1 << (i % 8);
+                self.write_block(bitmap_block, &bitmap)?;
+
+                let free = self.groups.free_inodes_count(group);
+                self.groups.set_free_inodes_count(group, free - 1);
+                if is_dir {
+                    let dirs = self.groups.used_dirs_count(group);
+                    self.groups.set_used_dirs_count(group, dirs + 1);
+                }
+                self.sync_group(group)?;
+                let free = self.sb.free_inodes_count();
+                self.sb.set_free_inodes_count(free - 1);
+                self.sync_super()?;
+
+                // clear the whole on-disk inode, including the extra fields
+                let ino = group * per_group + i + 1;
+                let zeros = vec![0; self.sb.inode_size()];
+                let offset = self.inode_offset(ino)?;
+                self.dev.write_at(offset, &zeros)?;
+                return Ok(ino);
+            }
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_inode(&mut self, ino: u32, is_dir: bool) -> VfsResult {
+        let group = self.group_of_inode(ino);
+        let i = (ino - 1) % self.sb.inodes_per_group();
+        let bitmap_block = self.groups.inode_bitmap(group);
+        let mut bitmap = vec![0; self.block_size];
+        self.read_block(bitmap_block, &mut bitmap)?;
+        bitmap[i as usize / 8] &= !(1 << (i % 8));
+        self.write_block(bitmap_block, &bitmap)?;
+
+        let free = self.groups.free_inodes_count(group);
+        self.groups.set_free_inodes_count(group, free + 1);
+        if is_dir {
+            let dirs = self.groups.used_dirs_count(group);
+            self.groups
+                .set_used_dirs_count(group, dirs.saturating_sub(1));
+        }
+        self.sync_group(group)?;
+        let free = self.sb.free_inodes_count();
+        self.sb.set_free_inodes_count(free + 1);
+        self.sync_super()
+    }
+
+    // blocks
+
+    fn sectors_per_block(&self) -> u64 {
+        self.block_size as u64 / 512
+    }
+
+    /// Allocates a zeroed block, preferably in the block group `goal`.
+    fn alloc_block(&mut self, goal: u32) -> VfsResult<u64> {
+        let groups = self.sb.groups_count();
+        let per_group = self.sb.blocks_per_group();
+        let first_data_block = self.sb.first_data_block();
+        for group in (goal..groups).chain(0..goal) {
+            if self.groups.free_blocks_count(group) == 0 {
+                continue;
+            }
+            let group_start = first_data_block + group as u64 * per_group;
+            let count = per_group.min(self.sb.blocks_count() - group_start);
+            let bitmap_block = self.groups.block_bitmap(group);
+            let mut bitmap = vec![0; self.block_size];
+            self.read_block(bitmap_block, &mut bitmap)?;
+            let found = (0..count as usize).find(|&i| bitmap[i / 8] & (1 << (i % 8)) == 0);
+            if let Some(i) = found {
+                bitmap[i / 8] |= 1 << (i % 8);
+                self.write_block(bitmap_block, &bitmap)?;
+
+                let free = self.groups.free_blocks_count(group);
+                self.groups.set_free_blocks_count(group, free - 1);
+                self.sync_group(group)?;
+                let free = self.sb.free_blocks_count();
+                self.sb.set_free_blocks_count(free - 1);
+                self.sync_super()?;
+
+                let block = group_start + i as u64;
+                let zeros = vec![0; self.block_size];
+                self.write_block(block, &zeros)?;
+                return Ok(block);
+            }
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_block(&mut self, block: u64) -> VfsResult {
+        let rel = block - self.sb.first_data_block();
+        let group = (rel / self.sb.blocks_per_group()) as u32;
+        let i = (rel % self.sb.blocks_per_group()) as usize;
+        let bitmap_block = self.groups.block_bitmap(group);
+        let mut bitmap = vec![0; self.block_size];
+        self.read_block(bitmap_block, &mut bitmap)?;
+        bitmap[i / 8] &= !(1 << (i % 8));
+        self.write_block(bitmap_block, &bitmap)?;
+
+        let free = self.groups.free_blocks_count(group);
+        self.groups.set_free_blocks_count(group, free + 1);
+        self.sync_group(group)?;
+        let free = self.sb.free_blocks_count();
+        self.sb.set_free_blocks_count(free + 1);
+        self.sync_super()
+    }
+
+    /// Number of block pointers in an indirect block.
+    fn ptrs_per_block(&self) -> u64 {
+        self.block_size as u64 / 4
+    }
+
+    /// Returns the physical block of the logical block `lblock` of the file,
+    /// or 0 if it is a hole.
+    ///
+    /// If `alloc` is `true`, missing blocks are allocated and the changes of
+    /// `inode` must be written back by the caller.
+    fn map_block(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        lblock: u64,
+        alloc: bool,
+    ) -> VfsResult<u64> {
+        if inode.uses_extents() {
+            return self.map_extent(inode, lblock);
+        }
+
+        let p = self.ptrs_per_block();
+        let direct = DIRECT_BLOCKS as u64;
+        let (slot, indices) = if lblock < direct {
+            (lblock as usize, vec![])
+        } else if lblock - direct < p {
+            (DIRECT_BLOCKS, vec![lblock - direct])
+        } else if lblock - direct - p < p * p {
+            let n = lblock - direct - p;
+            (DIRECT_BLOCKS + 1, vec![n / p, n % p])
+        } else if lblock - direct - p - p * p < p * p * p {
+            let n = lblock - direct - p - p * p;
+            (DIRECT_BLOCKS + 2, vec![n / (p * p), n / p % p, n % p])
+        } else {
+            return Err(VfsError::InvalidInput);
+        };
+
+        let goal = self.group_of_inode(ino);
+        let mut block = inode.block(slot) as u64;
+        if block == 0 {
+            if !alloc {
+                return Ok(0);
+            }
+            block = self.alloc_block(goal)?;
+            inode.set_block(slot, block as u32);
+            inode.set_sectors(inode.sectors() + self.sectors_per_block());
+        }
+        for idx in indices {
+            let entry_offset = self.block_offset(block) + idx * 4;
+            let mut next = self.read_u32(entry_offset)? as u64;
+            if next == 0 {
+                if !alloc {
+                    return Ok(0);
+                }
+                next = self.alloc_block(goal)?;
+                self.write_u32(entry_offset, next as u32)?;
+                inode.set_sectors(inode.sectors() + self.sectors_per_block());
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+
+    fn map_extent(&mut self, inode: &Inode, lblock: u64) -> VfsResult<u64> {
+        let mut node = inode.block_area().to_vec();
+        loop {
+            let header = ExtentHeader::parse(&node).ok_or(VfsError::InvalidData)?;
+            let entries = (header.entries as usize).min((node.len() - 12) / 12);
+            if header.depth == 0 {
+                for i in 0..entries {
+                    let (first, len, start, init) = extent_leaf(&node, i);
+                    let first = first as u64;
+                    if lblock >= first && lblock < first + len as u64 {
+                        // uninitialized extents are read as zeros
+                        return Ok(if init { start + lblock - first } else { 0 });
+                    }
+                }
+                return Ok(0);
+            }
+            let child = (0..entries)
+                .map(|i| extent_index(&node, i))
+                .take_while(|&(first, _)| first as u64 <= lblock)
+                .last();
+            match child {
+                Some((_, block)) => {
+                    node = vec![0; self.block_size];
+                    self.read_block(block, &mut node)?;
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+
+    /// Frees all blocks of the file from the logical block `from`.
+    fn free_blocks_from(&mut self, inode: &mut Inode, from: u64) -> VfsResult {
+        if inode.uses_extents() {
+            return Err(VfsError::PermissionDenied);
+        }
+        let mut freed = 0;
+        for i in (from as usize)..DIRECT_BLOCKS {
+            let block = inode.block(i) as u64;
+            if block != 0 {
+                self.free_block(block)?;
+                inode.set_block(i, 0);
+                freed += 1;
+            }
+        }
+        let p = self.ptrs_per_block();
+        let mut base = DIRECT_BLOCKS as u64;
+        let mut span = p;
+        for level in 1..=3 {
+            let slot = DIRECT_BLOCKS + level - 1;
+            let block = inode.block(slot) as u64;
+            if block != 0
+                && from < base + span
+                && self.free_indirect(block, level, from.saturating_sub(base), &mut freed)?
+            {
+                self.free_block(block)?;
+                inode.set_block(slot, 0);
+                freed += 1;
+            }
+            base += span;
+            span *= p;
+        }
+        let sectors = inode.sectors() - freed * self.sectors_per_block();
+        inode.set_sectors(sectors);
+        Ok(())
+    }
+
+    /// Frees the blocks referred by the indirect block `block` from the
+    /// relative logical block `from`, returns whether it becomes empty.
+    fn free_indirect(
+        &mut self,
+        block: u64,
+        level: usize,
+        from: u64,
+        freed: &mut u64,
+    ) -> VfsResult<bool> {
+        let mut buf = vec![0; self.block_size];
+        self.read_block(block, &mut buf)?;
+        let child_span = self.ptrs_per_block().pow(level as u32 - 1);
+        let mut empty = true;
+        let mut modified = false;
+        for i in 0..self.ptrs_per_block() as usize {
+            let entry = &mut buf[i * 4..i * 4 + 4];
+            let child = u32::from_le_bytes(entry.try_into().unwrap()) as u64;
+            if child == 0 {
+                continue;
+            }
+            let child_start = i as u64 * child_span;
+            let free_child = if child_start + child_span <= from {
+                false
+            } else if level == 1 {
+                true
+            } else {
+                self.free_indirect(child, level - 1, from.saturating_sub(child_start), freed)?
+            };
+            if free_child {
+                self.free_block(child)?;
+                entry.fill(0);
+                *freed += 1;
+                modified = true;
+            } else {
+                empty = false;
+            }
+        }
+        if modified && !empty {
+            self.write_block(block, &buf)?;
+        }
+        Ok(empty)
+    }
+
+    // file contents
+
+    /// Whether the inode is a symbolic link whose target is stored in the
+    /// inode itself.
+    fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        let acl_sectors = if inode.file_acl() != 0 {
+            self.sectors_per_block()
+        } else {
+            0
+        };
+        inode.file_type().is_symlink()
+            && inode.size() < INODE_BLOCK_AREA as u64
+            && inode.sectors() == acl_sectors
+    }
+
+    pub fn read_at(&mut self, ino: u32, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut inode = self.read_inode(ino)?;
+        let size = inode.size();
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        if self.is_fast_symlink(&inode) {
+            let start = offset as usize;
+            buf[..len].copy_from_slice(&inode.block_area()[start..start + len]);
+            return Ok(len);
+        }
+
+        let bs = self.block_size as u64;
+        let mut pos = 0;
+        while pos < len {
+            let file_pos = offset + pos as u64;
+            let in_block = (file_pos % bs) as usize;
+            let n = (len - pos).min(self.block_size - in_block);
+            let block = self.map_block(ino, &mut inode, file_pos / bs, false)?;
+            if block == 0 {
+                buf[pos..pos + n].fill(0); // hole
+            } else {
+                let disk_pos = self.block_offset(block) + in_block as u64;
+                self.dev.read_at(disk_pos, &mut buf[pos..pos + n])?;
+            }
+            pos += n;
+        }
+        Ok(len)
+    }
+
+    pub fn write_at(&mut self, ino: u32, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        let bs = self.block_size as u64;
+        let mut pos = 0;
+        let result = loop {
+            if pos >= buf.len() {
+                break Ok(());
+            }
+            let file_pos = offset + pos as u64;
+            let in_block = (file_pos % bs) as usize;
+            let n = (buf.len() - pos).min(self.block_size - in_block);
+            let block = match self.map_block(ino, &mut inode, file_pos / bs, true) {
+                Ok(block) => block,
+                Err(e) => break Err(e),
+            };
+            let disk_pos = self.block_offset(block) + in_block as u64;
+            if let Err(e) = self.dev.write_at(disk_pos, &buf[pos..pos + n]) {
+                break Err(e);
+            }
+            pos += n;
+        };
+
+        // keep the allocated blocks and the written data even on errors
+        let end = offset + pos as u64;
+        if end > inode.size() {
+            inode.set_size(end);
+        }
+        if pos > 0 {
+            self.touch(&mut inode, true);
+        }
+        self.write_inode(ino, &inode)?;
+        match result {
+            Err(e) if pos == 0 => Err(e),
+            _ => Ok(pos),
+        }
+    }
+
+    pub fn truncate(&mut self, ino: u32, size: u64) -> VfsResult {
+        self.check_writable()?;
+        let mut inode = self.read_inode(ino)?;
+        if size < inode.size() {
+            let bs = self.block_size as u64;
+            self.free_blocks_from(&mut inode, size.div_ceil(bs))?;
+            // clear the tail of the last block, it may be exposed by extending
+            let in_block = (size % bs) as usize;
+            if in_block != 0 {
+                let block = self.map_block(ino, &mut inode, size / bs, false)?;
+                if block != 0 {
+                    let zeros = vec![0; self.block_size - in_block];
+                    self.dev
+                        .write_at(self.block_offset(block) + in_block as u64, &zeros)?;
+                }
+            }
+        }
+        inode.set_size(size);
+        self.touch(&mut inode, true);
+        self.write_inode(ino, &inode)
+    }
+
+    pub fn read_link(&mut self, ino: u32) -> VfsResult<Vec<u8>> {
+        let size = self.read_inode(ino)?.size() as usize;
+        let mut buf = vec![0; size];
+        let len = self.read_at(ino, 0, &mut buf)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    // directories
+
+    pub fn read_dir_entries(&mut self, dir: u32) -> VfsResult<Vec<DirEntryInfo>> {
+        let mut inode = self.read_inode(dir)?;
+        if !inode.file_type().is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let has_filetype = self.sb.has_filetype();
+        let nblocks = inode.size() / self.block_size as u64;
+        let mut entries = Vec::new();
+        let mut buf = vec![0; self.block_size];
+        for lblock in 0..nblocks {
+            let block = self.map_block(dir, &mut inode, lblock, false)?;
+            if block == 0 {
+                continue;
+            }
+            self.read_block(block, &mut buf)?;
+            let mut offset = 0;
+            let mut prev_offset = None;
+            while offset < self.block_size {
+                let entry =
+                    DirEntry::parse(&buf, offset, has_filetype).ok_or(VfsError::InvalidData)?;
+                if entry.ino != 0 {
+                    entries.push(DirEntryInfo {
+                        ino: entry.ino,
+                        file_type: entry.file_type,
+                        name: String::from_utf8_lossy(entry.name).into(),
+                        lblock,
+                        offset,
+                        prev_offset,
+                    });
+                }
+                prev_offset = Some(offset);
+                offset += entry.rec_len;
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn find_entry(&mut self, dir: u32, name: &str) -> VfsResult<DirEntryInfo> {
+        self.read_dir_entries(dir)?
+            .into_iter()
+            .find(|e| e.name == name)
+            .ok_or(VfsError::NotFound)
+    }
+
+    pub fn lookup(&mut self, dir: u32, name: &str) -> VfsResult<u32> {
+        self.find_entry(dir, name).map(|e| e.ino)
+    }
+
+    fn add_entry(&mut self, dir: u32, name: &str, ino: u32, ty: VfsNodeType) -> VfsResult {
+        let name = name.as_bytes();
+        if name.len() > 255 {
+            return Err(VfsError::InvalidInput);
+        }
+        let ty = if self.sb.has_filetype() {
+            type_to_dirent_type(ty)
+        } else {
+            0
+        };
+        let required = DirEntry::required_len(name.len());
+        let mut inode = self.read_inode(dir)?;
+        if inode.links_count() == 0 {
+            return Err(VfsError::NotFound); // the directory has been removed
+        }
+        if inode.flags() & INODE_FLAG_INDEX != 0 {
+            // the hash index is not maintained, fall back to linear lookups
+            inode.set_flags(inode.flags() & !INODE_FLAG_INDEX);
+        }
+        self.touch(&mut inode, true);
+        self.write_inode(dir, &inode)?;
+
+        let has_filetype = self.sb.has_filetype();
+        let nblocks = inode.size() / self.block_size as u64;
+        let mut buf = vec![0; self.block_size];
+        for lblock in 0..nblocks {
+            let block = self.map_block(dir, &mut inode, lblock, false)?;
+            if block == 0 {
+                continue;
+            }
+            self.read_block(block, &mut buf)?;
+            let mut offset = 0;
+            while offset < self.block_size {
+                let entry =
+                    DirEntry::parse(&buf, offset, has_filetype).ok_or(VfsError::InvalidData)?;
+                let (entry_ino, rec_len) = (entry.ino, entry.rec_len);
+                let used = if entry_ino == 0 {
+                    0
+                } else {
+                    DirEntry::required_len(entry.name.len())
+                };
+                if rec_len - used >= required {
+                    if used == 0 {
+                        DirEntry::write(&mut buf, offset, ino, rec_len, ty, name);
+                    } else {
+                        DirEntry::set_rec_len(&mut buf, offset, used);
+                        DirEntry::write(&mut buf, offset + used, ino, rec_len - used, ty, name);
+                    }
+                    return self.write_block(block, &buf);
+                }
+                offset += rec_len;
+            }
+        }
+
+        // no space in existing blocks, append a new one
+        let block = self.map_block(dir, &mut inode, nblocks, true)?;
+        buf.fill(0);
+        DirEntry::write(&mut buf, 0, ino, self.block_size, ty, name);
+        self.write_block(block, &buf)?;
+        inode.set_size((nblocks + 1) * self.block_size as u64);
+        self.write_inode(dir, &inode)
+    }
+
+    fn remove_entry(&mut self, dir: u32, entry: &DirEntryInfo) -> VfsResult {
+        let mut inode = self.read_inode(dir)?;
+        self.touch(&mut inode, true);
+        self.write_inode(dir, &inode)?;
+        let block = self.map_block(dir, &mut inode, entry.lblock, false)?;
+        let mut buf = vec![0; self.block_size];
+        self.read_block(block, &mut buf)?;
+        let has_filetype = self.sb.has_filetype();
+        let rec_len = DirEntry::parse(&buf, entry.offset, has_filetype)
+            .ok_or(VfsError::InvalidData)?
+            .rec_len;
+        match entry.prev_offset {
+            Some(prev) => {
+                // merge into the previous entry
+                let prev_len = DirEntry::parse(&buf, prev, has_filetype)
+                    .ok_or(VfsError::InvalidData)?
+                    .rec_len;
+                DirEntry::set_rec_len(&mut buf, prev, prev_len + rec_len);
+            }
+            None => DirEntry::set_ino(&mut buf, entry.offset, 0),
+        }
+        self.write_block(block, &buf)
+    }
+
+    fn is_dir_empty(&mut self, dir: u32) -> VfsResult<bool> {
+        Ok(self
+            .read_dir_entries(dir)?
+            .iter()
+            .all(|e| e.name == "." || e.name == ".."))
+    }
+
+    fn update_links(&mut self, ino: u32, delta: i32) -> VfsResult<Inode> {
+        let mut inode = self.read_inode(ino)?;
+        let links = (inode.links_count() as i32 + delta).max(0);
+        inode.set_links_count(links as u16);
+        self.touch(&mut inode, false);
+        self.write_inode(ino, &inode)?;
+        Ok(inode)
+    }
+
+    /// Creates a new node named `name` in the directory `dir`, returns its
+    /// inode number.
+    pub fn create(&mut self, dir: u32, name: &str, ty: VfsNodeType) -> VfsResult<u32> {
+        self.check_writable()?;
+        let perm = match ty {
+            VfsNodeType::File => VfsNodePerm::default_file(),
+            VfsNodeType::Dir => VfsNodePerm::default_dir(),
+            VfsNodeType::SymLink => VfsNodePerm::default_symlink(),
+            _ => return Err(VfsError::Unsupported),
+        };
+        if self.find_entry(dir, name).is_ok() {
+            return Err(VfsError::AlreadyExists);
+        }
+
+        let ino = self.alloc_inode(self.group_of_inode(dir), ty.is_dir())?;
+        let mut inode = Inode::new(ty, perm);
+        let now = self.now();
+        inode.set_times([now; 3]);
+        if ty.is_dir() {
+            inode.set_links_count(2);
+            let block = self.map_block(ino, &mut inode, 0, true)?;
+            let has_filetype = self.sb.has_filetype();
+            let dir_ty = if has_filetype { 2 } else { 0 };
+            let mut buf = vec![0; self.block_size];
+            DirEntry::write(&mut buf, 0, ino, 12, dir_ty, b".");
+            DirEntry::write(&mut buf, 12, dir, self.block_size - 12, dir_ty, b"..");
+            self.write_block(block, &buf)?;
+            inode.set_size(self.block_size as u64);
+        } else {
+            inode.set_links_count(1);
+        }
+        self.write_inode(ino, &inode)?;
+        if let Err(e) = self.add_entry(dir, name, ino, ty) {
+            self.release_inode(ino, &mut inode)?;
+            return Err(e);
+        }
+        if ty.is_dir() {
+            self.update_links(dir, 1)?;
+        }
+        Ok(ino)
+    }
+
+    pub fn symlink(&mut self, dir: u32, name: &str, target: &str) -> VfsResult {
+        let ino = self.create(dir, name, VfsNodeType::SymLink)?;
+        let target = target.as_bytes();
+        if target.len() < INODE_BLOCK_AREA {
+            let mut inode = self.read_inode(ino)?;
+            inode.block_area_mut()[..target.len()].copy_from_slice(target);
+            inode.set_size(target.len() as u64);
+            self.write_inode(ino, &inode)
+        } else {
+            self.write_at(ino, 0, target).map(|_| ())
+        }
+    }
+
+    pub fn link(&mut self, dir: u32, name: &str, ino: u32) -> VfsResult {
+        self.check_writable()?;
+        let inode = self.read_inode(ino)?;
+        if inode.file_type().is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        if self.find_entry(dir, name).is_ok() {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.add_entry(dir, name, ino, inode.file_type())?;
+        self.update_links(ino, 1).map(|_| ())
+    }
+
+    /// Frees the blocks and the inode `ino` which is no longer referenced.
+    fn release_inode(&mut self, ino: u32, inode: &mut Inode) -> VfsResult {
+        if !self.is_fast_symlink(inode) {
+            let res = self.free_blocks_from(inode, 0);
+            // do not free the blocks again when it is retried
+            self.write_inode(ino, inode)?;
+            res?;
+        }
+        // clear the inode other than the deletion time, so it will not be
+        // taken as a deleted inode with zero deletion time
+        let is_dir = inode.file_type().is_dir();
+        let mut deleted = Inode::from_bytes([0; INODE_BASE_SIZE]);
+        deleted.set_dtime(self.now().unwrap_or(0));
+        self.write_inode(ino, &deleted)?;
+        self.free_inode(ino, is_dir)
+    }
+
+    /// Removes the entry `name` from the directory `dir`.
+    ///
+    /// If the node has no links left, it is released when no node of it is
+    /// alive (see [`close_node`](Self::close_node)).
+    pub fn unlink(&mut self, dir: u32, name: &str) -> VfsResult {
+        self.check_writable()?;
+        if name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let entry = self.find_entry(dir, name)?;
+        let ino = entry.ino;
+        let inode = self.read_inode(ino)?;
+        let is_dir = inode.file_type().is_dir();
+        if is_dir && !self.is_dir_empty(ino)? {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        self.remove_entry(dir, &entry)?;
+        let last_link = is_dir || inode.links_count() <= 1;
+        if last_link {
+            // released later even if the following updates fail
+            self.orphans.insert(ino);
+        }
+        if is_dir {
+            self.update_links(dir, -1)?;
+            // the `.` entry of the directory itself is gone as well
+            self.update_links(ino, -(inode.links_count() as i32))?;
+        } else {
+            self.update_links(ino, -1)?;
+        }
+        if last_link {
+            self.release_orphans();
+        }
+        Ok(())
+    }
+
+    /// Whether the directory `dir` is `ancestor` or in it.
+    fn is_descendant(&mut self, mut dir: u32, ancestor: u32) -> VfsResult<bool> {
+        loop {
+            if dir == ancestor {
+                return Ok(true);
+            } else if dir == ROOT_INO {
+                return Ok(false);
+            }
+            dir = self.lookup(dir, "..")?;
+        }
+    }
+
+    pub fn rename(
+        &mut self,
+        src_dir: u32,
+        src_name: &str,
+        dst_dir: u32,
+        dst_name: &str,
+    ) -> VfsResult {
+        self.check_writable()?;
+        let bad_name = |name: &str| name.is_empty() || name == "." || name == "..";
+        if bad_name(src_name) || bad_name(dst_name) {
+            return Err(VfsError::InvalidInput);
+        }
+        let entry = self.find_entry(src_dir, src_name)?;
+        let ino = entry.ino;
+        let ty = self.read_inode(ino)?.file_type();
+        if ty.is_dir() && self.is_descendant(dst_dir, ino)? {
+            return Err(VfsError::InvalidInput); // move a directory into itself
+        }
+
+        match self.find_entry(dst_dir, dst_name) {
+            Ok(dst) if dst.ino == ino => return Ok(()),
+            Ok(dst) => {
+                let dst_ty = self.read_inode(dst.ino)?.file_type();
+                if dst_ty.is_dir() && !ty.is_dir() {
+                    return Err(VfsError::IsADirectory);
+                } else if !dst_ty.is_dir() && ty.is_dir() {
+                    return Err(VfsError::NotADirectory);
+                }
+                self.unlink(dst_dir, dst_name)?
+            }
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        self.add_entry(dst_dir, dst_name, ino, ty)?;
+        // the source entry may be moved if both are in the same directory
+        let entry = self.find_entry(src_dir, src_name)?;
+        self.remove_entry(src_dir, &entry)?;
+
+        if ty.is_dir() && src_dir != dst_dir {
+            let parent = self.find_entry(ino, "..")?;
+            let mut inode = self.read_inode(ino)?;
+            let block = self.map_block(ino, &mut inode, parent.lblock, false)?;
+            self.write_u32(self.block_offset(block) + parent.offset as u64, dst_dir)?;
+            self.update_links(src_dir, -1)?;
+            self.update_links(dst_dir, 1)?;
+        }
+        Ok(())
+    }
+}
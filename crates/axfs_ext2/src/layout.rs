@@ -0,0 +1,593 @@
+//! On-disk data structures of ext2 (and the parts of ext4 that can be read).
+//!
+//! All structures are kept as raw little-endian bytes and accessed by field
+//! offsets, so the fields that are not understood are preserved when written
+//! back.
+
+use alloc::vec::Vec;
+use axfs_vfs::{VfsNodePerm, VfsNodeType};
+
+/// Byte offset of the superblock on the disk.
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+/// Size of the superblock in bytes.
+pub const SUPERBLOCK_SIZE: usize = 1024;
+/// Magic number in the superblock.
+pub const EXT2_MAGIC: u16 = 0xef53;
+/// Inode number of the root directory.
+pub const ROOT_INO: u32 = 2;
+
+/// Size of the inode fields defined by ext2, larger inodes have extra fields
+/// following them.
+pub const INODE_BASE_SIZE: usize = 128;
+/// Number of direct block pointers in an inode.
+pub const DIRECT_BLOCKS: usize = 12;
+/// Size of the block pointer array (`i_block`) in an inode.
+pub const INODE_BLOCK_AREA: usize = 60;
+
+// Incompatible features.
+pub const INCOMPAT_FILETYPE: u32 = 0x2;
+pub const INCOMPAT_RECOVER: u32 = 0x4;
+pub const INCOMPAT_EXTENTS: u32 = 0x40;
+pub const INCOMPAT_64BIT: u32 = 0x80;
+pub const INCOMPAT_FLEX_BG: u32 = 0x200;
+pub const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+
+// Read-only compatible features.
+pub const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+
+/// Incompatible features required to read the filesystem.
+pub const INCOMPAT_READABLE: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED;
+/// Incompatible features required to write the filesystem.
+pub const INCOMPAT_WRITABLE: u32 = INCOMPAT_FILETYPE;
+/// Read-only compatible features required to write the filesystem.
+pub const RO_COMPAT_WRITABLE: u32 = RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE;
+
+// Inode flags.
+pub const INODE_FLAG_INDEX: u32 = 0x1000;
+pub const INODE_FLAG_EXTENTS: u32 = 0x8_0000;
+
+// Inode file types (in `i_mode`).
+const S_IFMT: u16 = 0o170000;
+const S_IFIFO: u16 = 0o010000;
+const S_IFCHR: u16 = 0o020000;
+const S_IFDIR: u16 = 0o040000;
+const S_IFBLK: u16 = 0o060000;
+const S_IFREG: u16 = 0o100000;
+const S_IFLNK: u16 = 0o120000;
+const S_IFSOCK: u16 = 0o140000;
+
+/// Magic number in the header of extent tree nodes.
+pub const EXTENT_MAGIC: u16 = 0xf30a;
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn set_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn set_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// The superblock.
+pub struct SuperBlock {
+    raw: [u8; SUPERBLOCK_SIZE],
+}
+
+impl SuperBlock {
+    pub fn from_bytes(raw: [u8; SUPERBLOCK_SIZE]) -> Self {
+        Self { raw }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn magic(&self) -> u16 {
+        get_u16(&self.raw, 56)
+    }
+
+    pub fn inodes_count(&self) -> u32 {
+        get_u32(&self.raw, 0)
+    }
+
+    pub fn blocks_count(&self) -> u64 {
+        let hi = if self.is_64bit() {
+            get_u32(&self.raw, 0x150)
+        } else {
+            0
+        };
+        (hi as u64) << 32 | get_u32(&self.raw, 4) as u64
+    }
+
+    pub fn free_blocks_count(&self) -> u64 {
+        let hi = if self.is_64bit() {
+            get_u32(&self.raw, 0x158)
+        } else {
+            0
+        };
+        (hi as u64) << 32 | get_u32(&self.raw, 12) as u64
+    }
+
+    pub fn set_free_blocks_count(&mut self, count: u64) {
+        set_u32(&mut self.raw, 12, count as u32);
+        if self.is_64bit() {
+            set_u32(&mut self.raw, 0x158, (count >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes_count(&self) -> u32 {
+        get_u32(&self.raw, 16)
+    }
+
+    pub fn set_free_inodes_count(&mut self, count: u32) {
+        set_u32(&mut self.raw, 16, count);
+    }
+
+    pub fn first_data_block(&self) -> u64 {
+        get_u32(&self.raw, 20) as u64
+    }
+
+    pub fn block_size(&self) -> usize {
+        1024 << get_u32(&self.raw, 24)
+    }
+
+    pub fn blocks_per_group(&self) -> u64 {
+        get_u32(&self.raw, 32) as u64
+    }
+
+    pub fn inodes_per_group(&self) -> u32 {
+        get_u32(&self.raw, 40)
+    }
+
+    fn rev_level(&self) -> u32 {
+        get_u32(&self.raw, 76)
+    }
+
+    /// The first non-reserved inode number.
+    pub fn first_ino(&self) -> u32 {
+        if self.rev_level() == 0 {
+            11
+        } else {
+            get_u32(&self.raw, 84)
+        }
+    }
+
+    pub fn inode_size(&self) -> usize {
+        if self.rev_level() == 0 {
+            INODE_BASE_SIZE
+        } else {
+            get_u16(&self.raw, 88) as usize
+        }
+    }
+
+    pub fn feature_incompat(&self) -> u32 {
+        if self.rev_level() == 0 {
+            0
+        } else {
+            get_u32(&self.raw, 96)
+        }
+    }
+
+    pub fn feature_ro_compat(&self) -> u32 {
+        if self.rev_level() == 0 {
+            0
+        } else {
+            get_u32(&self.raw, 100)
+        }
+    }
+
+    pub fn has_filetype(&self) -> bool {
+        self.feature_incompat() & INCOMPAT_FILETYPE != 0
+    }
+
+    fn is_64bit(&self) -> bool {
+        self.feature_incompat() & INCOMPAT_64BIT != 0
+    }
+
+    /// Size of a group descriptor in bytes.
+    pub fn desc_size(&self) -> usize {
+        if self.is_64bit() {
+            (get_u16(&self.raw, 254) as usize).max(32)
+        } else {
+            32
+        }
+    }
+
+    pub fn groups_count(&self) -> u32 {
+        let data_blocks = self.blocks_count() - self.first_data_block();
+        data_blocks.div_ceil(self.blocks_per_group()) as u32
+    }
+}
+
+/// The block group descriptor table.
+pub struct GroupDescTable {
+    raw: Vec<u8>,
+    desc_size: usize,
+}
+
+impl GroupDescTable {
+    pub fn from_bytes(raw: Vec<u8>, desc_size: usize) -> Self {
+        Self { raw, desc_size }
+    }
+
+    /// Returns the raw bytes of the descriptor of `group`, and the offset of
+    /// them in the table.
+    pub fn desc_bytes(&self, group: u32) -> (usize, &[u8]) {
+        let offset = group as usize * self.desc_size;
+        (offset, &self.raw[offset..offset + self.desc_size])
+    }
+
+    fn get_lo_hi(&self, group: u32, lo: usize, hi: usize) -> u64 {
+        let desc = self.desc_bytes(group).1;
+        let hi = if self.desc_size >= 64 {
+            get_u32(desc, hi) as u64
+        } else {
+            0
+        };
+        hi << 32 | get_u32(desc, lo) as u64
+    }
+
+    fn get_lo_hi16(&self, group: u32, lo: usize, hi: usize) -> u32 {
+        let desc = self.desc_bytes(group).1;
+        let hi = if self.desc_size >= 64 {
+            get_u16(desc, hi) as u32
+        } else {
+            0
+        };
+        hi << 16 | get_u16(desc, lo) as u32
+    }
+
+    fn set_lo_hi16(&mut self, group: u32, lo: usize, hi: usize, value: u32) {
+        let offset = group as usize * self.desc_size;
+        set_u16(&mut self.raw, offset + lo, value as u16);
+        if self.desc_size >= 64 {
+            set_u16(&mut self.raw, offset + hi, (value >> 16) as u16);
+        }
+    }
+
+    pub fn block_bitmap(&self, group: u32) -> u64 {
+        self.get_lo_hi(group, 0, 0x20)
+    }
+
+    pub fn inode_bitmap(&self, group: u32) -> u64 {
+        self.get_lo_hi(group, 4, 0x24)
+    }
+
+    pub fn inode_table(&self, group: u32) -> u64 {
+        self.get_lo_hi(group, 8, 0x28)
+    }
+
+    pub fn free_blocks_count(&self, group: u32) -> u32 {
+        self.get_lo_hi16(group, 12, 0x2c)
+    }
+
+    pub fn set_free_blocks_count(&mut self, group: u32, count: u32) {
+        self.set_lo_hi16(group, 12, 0x2c, count)
+    }
+
+    pub fn free_inodes_count(&self, group: u32) -> u32 {
+        self.get_lo_hi16(group, 14, 0x2e)
+    }
+
+    pub fn set_free_inodes_count(&mut self, group: u32, count: u32) {
+        self.set_lo_hi16(group, 14, 0x2e, count)
+    }
+
+    pub fn used_dirs_count(&self, group: u32) -> u32 {
+        self.get_lo_hi16(group, 16, 0x30)
+    }
+
+    pub fn set_used_dirs_count(&mut self, group: u32, count: u32) {
+        self.set_lo_hi16(group, 16, 0x30, count)
+    }
+}
+
+/// The fields of an inode defined by ext2.
+#[derive(Clone)]
+pub struct Inode {
+    raw: [u8; INODE_BASE_SIZE],
+}
+
+impl Inode {
+    pub fn from_bytes(raw: [u8; INODE_BASE_SIZE]) -> Self {
+        Self { raw }
+    }
+
+    /// Creates an inode of the given type and permission, with no blocks.
+    pub fn new(ty: VfsNodeType, perm: VfsNodePerm) -> Self {
+        let mut inode = Self {
+            raw: [0; INODE_BASE_SIZE],
+        };
+        set_u16(&mut inode.raw, 0, type_to_mode(ty) | perm.bits());
+        inode
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn mode(&self) -> u16 {
+        get_u16(&self.raw, 0)
+    }
+
+    pub fn file_type(&self) -> VfsNodeType {
+        match self.mode() & S_IFMT {
+            S_IFIFO => VfsNodeType::Fifo,
+            S_IFCHR => VfsNodeType::CharDevice,
+            S_IFDIR => VfsNodeType::Dir,
+            S_IFBLK => VfsNodeType::BlockDevice,
+            S_IFLNK => VfsNodeType::SymLink,
+            S_IFSOCK => VfsNodeType::Socket,
+            _ => VfsNodeType::File,
+        }
+    }
+
+    pub fn perm(&self) -> VfsNodePerm {
+        VfsNodePerm::from_bits_truncate(self.mode() & 0o777)
+    }
+
+    pub fn set_perm(&mut self, perm: VfsNodePerm) {
+        let mode = self.mode() & !0o777 | perm.bits();
+        set_u16(&mut self.raw, 0, mode)
+    }
+
+    /// The owner IDs, the high 16 bits are in the Linux-specific `osd2` area.
+    pub fn uid(&self) -> u32 {
+        (get_u16(&self.raw, 120) as u32) << 16 | get_u16(&self.raw, 2) as u32
+    }
+
+    pub fn gid(&self) -> u32 {
+        (get_u16(&self.raw, 122) as u32) << 16 | get_u16(&self.raw, 24) as u32
+    }
+
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        set_u16(&mut self.raw, 2, uid as u16);
+        set_u16(&mut self.raw, 120, (uid >> 16) as u16);
+        set_u16(&mut self.raw, 24, gid as u16);
+        set_u16(&mut self.raw, 122, (gid >> 16) as u16);
+    }
+
+    /// Returns the access, modification and change times, in seconds since the
+    /// UNIX epoch.
+    pub fn times(&self) -> [u32; 3] {
+        [8, 16, 12].map(|offset| get_u32(&self.raw, offset))
+    }
+
+    /// Sets the access, modification and change times, `None` means
+    /// unchanged.
+    pub fn set_times(&mut self, times: [Option<u32>; 3]) {
+        for (offset, time) in [8, 16, 12].into_iter().zip(times) {
+            if let Some(time) = time {
+                set_u32(&mut self.raw, offset, time);
+            }
+        }
+    }
+
+    pub fn set_dtime(&mut self, dtime: u32) {
+        set_u32(&mut self.raw, 20, dtime)
+    }
+
+    pub fn size(&self) -> u64 {
+        let hi = if self.file_type().is_file() {
+            get_u32(&self.raw, 108)
+        } else {
+            0
+        };
+        (hi as u64) << 32 | get_u32(&self.raw, 4) as u64
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        set_u32(&mut self.raw, 4, size as u32);
+        if self.file_type().is_file() {
+            set_u32(&mut self.raw, 108, (size >> 32) as u32);
+        }
+    }
+
+    pub fn links_count(&self) -> u16 {
+        get_u16(&self.raw, 26)
+    }
+
+    pub fn set_links_count(&mut self, count: u16) {
+        set_u16(&mut self.raw, 26, count)
+    }
+
+    /// Number of 512-byte sectors allocated.
+    pub fn sectors(&self) -> u64 {
+        get_u32(&self.raw, 28) as u64
+    }
+
+    pub fn set_sectors(&mut self, sectors: u64) {
+        set_u32(&mut self.raw, 28, sectors as u32)
+    }
+
+    pub fn flags(&self) -> u32 {
+        get_u32(&self.raw, 32)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        set_u32(&mut self.raw, 32, flags)
+    }
+
+    pub fn block(&self, idx: usize) -> u32 {
+        get_u32(&self.raw, 40 + idx * 4)
+    }
+
+    pub fn set_block(&mut self, idx: usize, block: u32) {
+        set_u32(&mut self.raw, 40 + idx * 4, block)
+    }
+
+    /// The raw `i_block` area, which holds the target of fast symbolic links,
+    /// or the root of the extent tree.
+    pub fn block_area(&self) -> &[u8] {
+        &self.raw[40..40 + INODE_BLOCK_AREA]
+    }
+
+    pub fn block_area_mut(&mut self) -> &mut [u8] {
+        &mut self.raw[40..40 + INODE_BLOCK_AREA]
+    }
+
+    pub fn file_acl(&self) -> u32 {
+        get_u32(&self.raw, 104)
+    }
+
+    pub fn uses_extents(&self) -> bool {
+        self.flags() & INODE_FLAG_EXTENTS != 0
+    }
+}
+
+/// Header of an extent tree node.
+pub struct ExtentHeader {
+    pub entries: u16,
+    pub depth: u16,
+}
+
+impl ExtentHeader {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 12 || get_u16(buf, 0) != EXTENT_MAGIC {
+            return None;
+        }
+        Some(Self {
+            entries: get_u16(buf, 2),
+            depth: get_u16(buf, 6),
+        })
+    }
+}
+
+/// Returns `(first logical block, length, first physical block, initialized)`
+/// of the `idx`-th extent in a leaf node.
+pub fn extent_leaf(buf: &[u8], idx: usize) -> (u32, u32, u64, bool) {
+    let e = &buf[12 + idx * 12..];
+    let len = get_u16(e, 4) as u32;
+    let start = (get_u16(e, 6) as u64) << 32 | get_u32(e, 8) as u64;
+    if len > 32768 {
+        (get_u32(e, 0), len - 32768, start, false)
+    } else {
+        (get_u32(e, 0), len, start, true)
+    }
+}
+
+/// Returns `(first logical block, child node block)` of the `idx`-th index in
+/// an internal node.
+pub fn extent_index(buf: &[u8], idx: usize) -> (u32, u64) {
+    let e = &buf[12 + idx * 12..];
+    let leaf = (get_u16(e, 8) as u64) << 32 | get_u32(e, 4) as u64;
+    (get_u32(e, 0), leaf)
+}
+
+/// Size of the fixed part of a directory entry.
+pub const DIRENT_HEADER_SIZE: usize = 8;
+
+/// A directory entry in a directory block.
+pub struct DirEntry<'a> {
+    pub ino: u32,
+    pub rec_len: usize,
+    pub file_type: u8,
+    pub name: &'a [u8],
+}
+
+impl<'a> DirEntry<'a> {
+    /// Parses the entry at `offset` of a directory block, returns `None` if it
+    /// is corrupted.
+    pub fn parse(block: &'a [u8], offset: usize, has_filetype: bool) -> Option<Self> {
+        if offset + DIRENT_HEADER_SIZE > block.len() {
+            return None;
+        }
+        let rec_len = get_u16(block, offset + 4) as usize;
+        let (name_len, file_type) = if has_filetype {
+            (block[offset + 6] as usize, block[offset + 7])
+        } else {
+            (get_u16(block, offset + 6) as usize, 0)
+        };
+        if rec_len < DIRENT_HEADER_SIZE
+            || offset + rec_len > block.len()
+            || DIRENT_HEADER_SIZE + name_len > rec_len
+        {
+            return None;
+        }
+        let name_start = offset + DIRENT_HEADER_SIZE;
+        Some(Self {
+            ino: get_u32(block, offset),
+            rec_len,
+            file_type,
+            name: &block[name_start..name_start + name_len],
+        })
+    }
+
+    /// Writes an entry at `offset` of a directory block.
+    pub fn write(block: &mut [u8], offset: usize, ino: u32, rec_len: usize, ty: u8, name: &[u8]) {
+        set_u32(block, offset, ino);
+        set_u16(block, offset + 4, rec_len as u16);
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = ty;
+        let name_start = offset + DIRENT_HEADER_SIZE;
+        block[name_start..name_start + name.len()].copy_from_slice(name);
+    }
+
+    /// Sets the `rec_len` field of the entry at `offset`.
+    pub fn set_rec_len(block: &mut [u8], offset: usize, rec_len: usize) {
+        set_u16(block, offset + 4, rec_len as u16)
+    }
+
+    /// Sets the inode number of the entry at `offset`.
+    pub fn set_ino(block: &mut [u8], offset: usize, ino: u32) {
+        set_u32(block, offset, ino)
+    }
+
+    /// The minimum space required by an entry with a name of `name_len` bytes.
+    pub const fn required_len(name_len: usize) -> usize {
+        (DIRENT_HEADER_SIZE + name_len + 3) & !3
+    }
+}
+
+fn type_to_mode(ty: VfsNodeType) -> u16 {
+    match ty {
+        VfsNodeType::Fifo => S_IFIFO,
+        VfsNodeType::CharDevice => S_IFCHR,
+        VfsNodeType::Dir => S_IFDIR,
+        VfsNodeType::BlockDevice => S_IFBLK,
+        VfsNodeType::File => S_IFREG,
+        VfsNodeType::SymLink => S_IFLNK,
+        VfsNodeType::Socket => S_IFSOCK,
+    }
+}
+
+/// Converts a node type to the file type in directory entries.
+pub fn type_to_dirent_type(ty: VfsNodeType) -> u8 {
+    match ty {
+        VfsNodeType::File => 1,
+        VfsNodeType::Dir => 2,
+        VfsNodeType::CharDevice => 3,
+        VfsNodeType::BlockDevice => 4,
+        VfsNodeType::Fifo => 5,
+        VfsNodeType::Socket => 6,
+        VfsNodeType::SymLink => 7,
+    }
+}
+
+/// Converts the file type in directory entries to a node type, returns `None`
+/// if it is unknown.
+pub fn dirent_type_to_type(ty: u8) -> Option<VfsNodeType> {
+    match ty {
+        1 => Some(VfsNodeType::File),
+        2 => Some(VfsNodeType::Dir),
+        3 => Some(VfsNodeType::CharDevice),
+        4 => Some(VfsNodeType::BlockDevice),
+        5 => Some(VfsNodeType::Fifo),
+        6 => Some(VfsNodeType::Socket),
+        7 => Some(VfsNodeType::SymLink),
+        _ => None,
+    }
+}
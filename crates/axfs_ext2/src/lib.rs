@@ -0,0 +1,100 @@
+//! [ext2] filesystem used by [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! The implementation is based on [`axfs_vfs`], and works on any device that
+//! implements [`BlockDevice`].
+//!
+//! ext2 filesystems are readable and writable. ext3 and ext4 filesystems are
+//! mounted read-only if they use features beyond ext2 (e.g., extents or
+//! metadata checksums), and the journal is never replayed.
+//!
+//! [ext2]: https://en.wikipedia.org/wiki/Ext2
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod fs;
+mod layout;
+mod node;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::node::Ext2Node;
+
+use alloc::{boxed::Box, sync::Arc};
+use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use core::time::Duration;
+use spin::Mutex;
+
+use self::fs::Ext2Inner;
+
+/// A storage device that the filesystem reads from and writes to.
+pub trait BlockDevice: Send {
+    /// Reads `buf.len()` bytes from the device at the byte `offset`.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> VfsResult;
+
+    /// Writes all bytes of `buf` to the device at the byte `offset`.
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> VfsResult;
+
+    /// Flushes the buffered data to the device.
+    fn flush(&mut self) -> VfsResult {
+        Ok(())
+    }
+}
+
+/// An ext2 filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct Ext2FileSystem {
+    /// The state shared by all nodes, each operation locks it as a whole.
+    inner: Arc<Mutex<Ext2Inner>>,
+}
+
+impl Ext2FileSystem {
+    /// Opens the filesystem on the device.
+    ///
+    /// Returns [`InvalidData`](axfs_vfs::VfsError::InvalidData) if the device
+    /// does not contain an ext2 filesystem, or
+    /// [`Unsupported`](axfs_vfs::VfsError::Unsupported) if it uses features
+    /// that cannot be read.
+    pub fn new(dev: Box<dyn BlockDevice>) -> VfsResult<Self> {
+        let inner = Ext2Inner::new(dev, None)?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    /// Opens the filesystem on the device like [`new`](Self::new), and
+    /// updates the timestamps of the nodes with the current time from
+    /// `clock`.
+    ///
+    /// Without a clock, the timestamps are only changed by
+    /// [`set_times`](axfs_vfs::VfsNodeOps::set_times).
+    pub fn with_clock(dev: Box<dyn BlockDevice>, clock: fn() -> Duration) -> VfsResult<Self> {
+        let inner = Ext2Inner::new(dev, Some(clock))?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    /// Checks whether the device contains an ext2 (or ext3/ext4) filesystem.
+    pub fn probe(dev: &mut dyn BlockDevice) -> bool {
+        let mut magic = [0; 2];
+        dev.read_at(layout::SUPERBLOCK_OFFSET + 56, &mut magic)
+            .is_ok()
+            && u16::from_le_bytes(magic) == layout::EXT2_MAGIC
+    }
+}
+
+impl VfsOps for Ext2FileSystem {
+    fn umount(&self) -> VfsResult {
+        self.inner.lock().flush()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        Arc::new(Ext2Node::new(self.inner.clone(), layout::ROOT_INO))
+    }
+
+    fn fs_type(&self) -> &str {
+        "ext2"
+    }
+}
@@ -0,0 +1,233 @@
+use alloc::sync::Arc;
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsResult};
+use core::time::Duration;
+use spin::Mutex;
+
+use crate::fs::Ext2Inner;
+use crate::layout::{dirent_type_to_type, ROOT_INO};
+
+/// A file, directory or symbolic link in the ext2 filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`]. A removed node can still be
+/// accessed until it is dropped, then its inode is released.
+pub struct Ext2Node {
+    fs: Arc<Mutex<Ext2Inner>>,
+    ino: u32,
+}
+
+impl Ext2Node {
+    /// Creates a node of the inode `ino`, the filesystem must not be locked.
+    pub(crate) fn new(fs: Arc<Mutex<Ext2Inner>>, ino: u32) -> Self {
+        fs.lock().open_node(ino);
+        Self { fs, ino }
+    }
+
+    /// Returns the inode number of the node.
+    pub fn ino(&self) -> u32 {
+        self.ino
+    }
+
+    fn node(&self, ino: u32) -> VfsNodeRef {
+        Arc::new(Self::new(self.fs.clone(), ino))
+    }
+
+    fn check_not_dir(&self, fs: &mut Ext2Inner) -> VfsResult {
+        if fs.read_inode(self.ino)?.file_type().is_dir() {
+            Err(VfsError::IsADirectory)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Walks along `path` from the directory `ino`, returns the inode number of
+/// the last component.
+fn walk(fs: &mut Ext2Inner, mut ino: u32, path: &str) -> VfsResult<u32> {
+    for name in path.split('/') {
+        if !name.is_empty() && name != "." {
+            ino = fs.lookup(ino, name)?;
+        }
+    }
+    Ok(ino)
+}
+
+/// Walks to the directory that contains the last component of `path`, returns
+/// its inode number and the name of the last component.
+fn walk_parent<'a>(fs: &mut Ext2Inner, ino: u32, path: &'a str) -> VfsResult<(u32, &'a str)> {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(n) => Ok((walk(fs, ino, &trimmed[..n])?, &trimmed[n + 1..])),
+        None => Ok((ino, trimmed)),
+    }
+}
+
+impl VfsNodeOps for Ext2Node {
+    fn ino(&self) -> Option<u64> {
+        Some(self.ino as u64)
+    }
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let inode = self.fs.lock().read_inode(self.ino)?;
+        let mut attr = VfsNodeAttr::new(
+            inode.perm(),
+            inode.file_type(),
+            inode.size(),
+            inode.sectors(),
+        );
+        attr.set_nlink(inode.links_count() as u64);
+        attr.set_owner(inode.uid(), inode.gid());
+        let [atime, mtime, ctime] = inode.times().map(|t| Duration::from_secs(t as u64));
+        attr.set_times(atime, mtime, ctime);
+        Ok(attr)
+    }
+
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        let mut fs = self.fs.lock();
+        fs.check_writable()?;
+        let mut inode = fs.read_inode(self.ino)?;
+        inode.set_perm(perm);
+        fs.touch(&mut inode, false);
+        fs.write_inode(self.ino, &inode)
+    }
+
+    fn set_owner(&self, uid: u32, gid: u32) -> VfsResult {
+        let mut fs = self.fs.lock();
+        fs.check_writable()?;
+        let mut inode = fs.read_inode(self.ino)?;
+        inode.set_owner(uid, gid);
+        fs.touch(&mut inode, false);
+        fs.write_inode(self.ino, &inode)
+    }
+
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        self.fs.lock().set_times(self.ino, atime, mtime)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        self.check_not_dir(&mut fs)?;
+        fs.read_at(self.ino, offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        self.check_not_dir(&mut fs)?;
+        fs.write_at(self.ino, offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.fs.lock().flush()
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut fs = self.fs.lock();
+        self.check_not_dir(&mut fs)?;
+        fs.truncate(self.ino, size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.ino == ROOT_INO {
+            return None;
+        }
+        let parent = self.fs.lock().lookup(self.ino, "..").ok()?;
+        Some(self.node(parent))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let ino = walk(&mut self.fs.lock(), self.ino, path)?;
+        if ino == self.ino {
+            Ok(self)
+        } else {
+            Ok(self.node(ino))
+        }
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        log::debug!("create {:?} at ext2: {}", ty, path);
+        let mut fs = self.fs.lock();
+        let (dir, name) = walk_parent(&mut fs, self.ino, path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Ok(()); // already exists
+        }
+        fs.create(dir, name, ty).map(|_| ())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        log::debug!("remove at ext2: {}", path);
+        let mut fs = self.fs.lock();
+        let (dir, name) = walk_parent(&mut fs, self.ino, path)?;
+        if name.is_empty() {
+            return Err(VfsError::InvalidInput);
+        }
+        fs.unlink(dir, name)
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        let entries = fs.read_dir_entries(self.ino)?;
+        let mut count = 0;
+        for (entry, dirent) in entries.iter().skip(start_idx).zip(dirents.iter_mut()) {
+            let ty = match dirent_type_to_type(entry.file_type) {
+                Some(ty) => ty,
+                None => fs.read_inode(entry.ino)?.file_type(),
+            };
+            *dirent = VfsDirEntry::new(&entry.name, ty).with_ino(entry.ino as u64);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        log::debug!("rename at ext2: {} -> {}", src_path, dst_path);
+        let mut fs = self.fs.lock();
+        let (src_dir, src_name) = walk_parent(&mut fs, self.ino, src_path)?;
+        let (dst_dir, dst_name) = walk_parent(&mut fs, self.ino, dst_path)?;
+        fs.rename(src_dir, src_name, dst_dir, dst_name)
+    }
+
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("symlink at ext2: {} -> {}", path, target);
+        let mut fs = self.fs.lock();
+        let (dir, name) = walk_parent(&mut fs, self.ino, path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        fs.symlink(dir, name, target)
+    }
+
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        log::debug!("link at ext2: {}", path);
+        let ino = match node.as_any().downcast_ref::<Self>() {
+            Some(node) if Arc::ptr_eq(&node.fs, &self.fs) => node.ino,
+            _ => return Err(VfsError::CrossesDevices),
+        };
+        let mut fs = self.fs.lock();
+        let (dir, name) = walk_parent(&mut fs, self.ino, path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        fs.link(dir, name, ino)
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut fs = self.fs.lock();
+        if !fs.read_inode(self.ino)?.file_type().is_symlink() {
+            return Err(VfsError::InvalidInput);
+        }
+        let target = fs.read_link(self.ino)?;
+        let len = target.len().min(buf.len());
+        buf[..len].copy_from_slice(&target[..len]);
+        Ok(len)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+impl Drop for Ext2Node {
+    fn drop(&mut self) {
+        self.fs.lock().close_node(self.ino);
+    }
+}
@@ -0,0 +1,379 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+use crate::*;
+
+const LONG_LINE: &str = "Rust is cool!\n";
+
+struct MemDisk(Arc<Mutex<Vec<u8>>>);
+
+impl BlockDevice for MemDisk {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> VfsResult {
+        let data = self.0.lock().unwrap();
+        let start = offset as usize;
+        let src = data.get(start..start + buf.len()).ok_or(VfsError::Io)?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> VfsResult {
+        let mut data = self.0.lock().unwrap();
+        let start = offset as usize;
+        let dst = data.get_mut(start..start + buf.len()).ok_or(VfsError::Io)?;
+        dst.copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// Creates a filesystem image by `mkfs_cmd` (e.g. `mkfs.ext2`) on the host,
+/// populated with some files. Returns `None` if the command is unavailable.
+fn make_image(name: &str, mkfs_cmd: &str) -> Option<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("axfs_ext2_{}_{}", name, std::process::id()));
+    let root = dir.join("root");
+    std::fs::create_dir_all(root.join("very/long/path")).unwrap();
+    std::fs::write(root.join("short.txt"), LONG_LINE).unwrap();
+    std::fs::write(root.join("long.txt"), LONG_LINE.repeat(1000)).unwrap();
+    std::fs::write(root.join("very/long/path/test.txt"), LONG_LINE).unwrap();
+    std::os::unix::fs::symlink("very/long/path/test.txt", root.join("link")).unwrap();
+
+    let image = dir.join("disk.img");
+    let status = Command::new(mkfs_cmd)
+        .args(["-q", "-F", "-b", "1024", "-d"])
+        .arg(&root)
+        .arg(&image)
+        .arg("4096")
+        .status();
+    match status {
+        Ok(status) if status.success() => Some(image),
+        _ => {
+            println!("{} is not available, skipped", mkfs_cmd);
+            None
+        }
+    }
+}
+
+/// Checks the consistency of the filesystem image by `e2fsck`.
+fn check_image(image: &Path, data: &[u8]) {
+    std::fs::write(image, data).unwrap();
+    let output = Command::new("e2fsck")
+        .args(["-f", "-n"])
+        .arg(image)
+        .output()
+        .expect("failed to run e2fsck");
+    assert!(
+        output.status.success(),
+        "e2fsck failed:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+fn open_image(image: &Path) -> (Ext2FileSystem, Arc<Mutex<Vec<u8>>>) {
+    let data = Arc::new(Mutex::new(std::fs::read(image).unwrap()));
+    let mut disk = MemDisk(data.clone());
+    assert!(Ext2FileSystem::probe(&mut disk));
+    let fs = Ext2FileSystem::new(Box::new(disk)).expect("failed to open ext2 image");
+    (fs, data)
+}
+
+fn read_to_string(node: &VfsNodeRef) -> String {
+    let mut buf = vec![0; node.get_attr().unwrap().size() as usize];
+    assert_eq!(node.read_at(0, &mut buf), Ok(buf.len()));
+    String::from_utf8(buf).unwrap()
+}
+
+fn entries(dir: &VfsNodeRef) -> Vec<String> {
+    let mut dirents: Vec<_> = (0..8).map(|_| VfsDirEntry::default()).collect();
+    let mut names = Vec::new();
+    loop {
+        let n = dir.read_dir(names.len(), &mut dirents).unwrap();
+        if n == 0 {
+            return names;
+        }
+        for ent in &dirents[..n] {
+            names.push(String::from_utf8(ent.name_as_bytes().to_vec()).unwrap());
+        }
+    }
+}
+
+fn test_read(root: &VfsNodeRef) {
+    let names = entries(root);
+    for name in [
+        ".",
+        "..",
+        "short.txt",
+        "long.txt",
+        "very",
+        "link",
+        "lost+found",
+    ] {
+        assert!(names.contains(&name.into()), "{} not found", name);
+    }
+
+    let short = root.clone().lookup("short.txt").unwrap();
+    assert_eq!(read_to_string(&short), LONG_LINE);
+    let mut dirents: Vec<_> = (0..16).map(|_| VfsDirEntry::default()).collect();
+    let n = root.read_dir(0, &mut dirents).unwrap();
+    assert!(dirents[..n]
+        .iter()
+        .any(|e| e.name_as_bytes() == b"short.txt" && Some(e.ino()) == short.ino()));
+    assert!(short.get_attr().unwrap().mtime().as_secs() > 0);
+    let long = root.clone().lookup("/long.txt").unwrap();
+    assert_eq!(read_to_string(&long), LONG_LINE.repeat(1000));
+    let mut buf = [0; 7];
+    assert_eq!(long.read_at(14 * 999 + 8, &mut buf), Ok(6));
+    assert_eq!(&buf[..6], b"cool!\n");
+
+    let file = root
+        .clone()
+        .lookup("very/./long/../long/path/test.txt")
+        .unwrap();
+    assert_eq!(read_to_string(&file), LONG_LINE);
+    assert!(file.get_attr().unwrap().is_file());
+    assert!(root
+        .clone()
+        .lookup("very/long")
+        .unwrap()
+        .get_attr()
+        .unwrap()
+        .is_dir());
+    assert_eq!(
+        root.clone().lookup("not-exist").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(
+        file.clone().lookup("x").err(),
+        Some(VfsError::NotADirectory)
+    );
+
+    let link = root.clone().lookup("link").unwrap();
+    assert!(link.get_attr().unwrap().is_symlink());
+    let mut buf = [0; 64];
+    let n = link.readlink(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"very/long/path/test.txt");
+    assert_eq!(short.readlink(&mut buf).err(), Some(VfsError::InvalidInput));
+}
+
+#[test]
+fn test_ext2_read() {
+    let Some(image) = make_image("read", "mkfs.ext2") else {
+        return;
+    };
+    let (fs, _) = open_image(&image);
+    test_read(&fs.root_dir());
+}
+
+#[test]
+fn test_ext2_write() {
+    let Some(image) = make_image("write", "mkfs.ext2") else {
+        return;
+    };
+    let (fs, data) = open_image(&image);
+    let root = fs.root_dir();
+
+    // create files and directories
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/sub", VfsNodeType::Dir).unwrap();
+    root.create("dir/sub/file.txt", VfsNodeType::File).unwrap();
+    assert_eq!(
+        root.create("dir/sub", VfsNodeType::Dir).err(),
+        Some(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.create("dir/fifo", VfsNodeType::Fifo).err(),
+        Some(VfsError::Unsupported)
+    );
+    assert_eq!(
+        entries(&root.clone().lookup("dir").unwrap()),
+        [".", "..", "sub"]
+    );
+
+    // large file spanning the double indirect blocks
+    let file = root.clone().lookup("dir/sub/file.txt").unwrap();
+    let content: Vec<u8> = (0..300 * 1024).map(|i| (i % 251) as u8).collect();
+    assert_eq!(file.write_at(0, &content), Ok(content.len()));
+    let mut buf = vec![0; content.len() + 10];
+    assert_eq!(file.read_at(0, &mut buf), Ok(content.len()));
+    assert_eq!(&buf[..content.len()], content);
+
+    // truncate and extend
+    file.truncate(5000).unwrap();
+    assert_eq!(file.get_attr().unwrap().size(), 5000);
+    file.truncate(8000).unwrap();
+    assert_eq!(file.read_at(0, &mut buf), Ok(8000));
+    assert_eq!(&buf[..5000], &content[..5000]);
+    assert!(buf[5000..8000].iter().all(|&b| b == 0));
+
+    // sparse file
+    let sparse = root.clone().lookup("short.txt").unwrap();
+    assert_eq!(sparse.write_at(100_000, b"end"), Ok(3));
+    assert_eq!(sparse.read_at(50_000, &mut buf[..10]), Ok(10));
+    assert!(buf[..10].iter().all(|&b| b == 0));
+
+    // links
+    root.link("dir/hard", file.clone()).unwrap();
+    assert_eq!(file.get_attr().unwrap().nlink(), 2);
+    root.symlink("dir/sym", "sub/file.txt").unwrap();
+    let long_target = "long/".repeat(30);
+    root.symlink("dir/long_sym", &long_target).unwrap();
+    let mut buf = [0; 256];
+    let n = root
+        .clone()
+        .lookup("dir/long_sym")
+        .unwrap()
+        .readlink(&mut buf)
+        .unwrap();
+    assert_eq!(&buf[..n], long_target.as_bytes());
+    assert_eq!(
+        root.link("dir/dir_link", root.clone().lookup("dir/sub").unwrap())
+            .err(),
+        Some(VfsError::PermissionDenied)
+    );
+
+    // rename
+    root.rename("dir/sub", "very/moved").unwrap();
+    assert_eq!(
+        root.clone().lookup("dir/sub").err(),
+        Some(VfsError::NotFound)
+    );
+    let moved = root.clone().lookup("very/moved/file.txt").unwrap();
+    assert_eq!(moved.get_attr().unwrap().size(), 8000);
+    assert_eq!(
+        root.rename("very", "very/moved/very").err(),
+        Some(VfsError::InvalidInput)
+    );
+    root.rename("long.txt", "dir/long.txt").unwrap();
+
+    // remove
+    assert_eq!(
+        root.remove("very/moved").err(),
+        Some(VfsError::DirectoryNotEmpty)
+    );
+    root.remove("very/moved/file.txt").unwrap();
+    let hard = root.clone().lookup("dir/hard").unwrap();
+    assert_eq!(hard.get_attr().unwrap().size(), 8000);
+    root.remove("very/moved").unwrap();
+    root.remove("dir/sym").unwrap();
+    assert_eq!(root.remove("dir/sym").err(), Some(VfsError::NotFound));
+
+    // many entries in one directory
+    for i in 0..100 {
+        root.create(
+            &format!("dir/file-with-a-long-name-{}", i),
+            VfsNodeType::File,
+        )
+        .unwrap();
+    }
+    for i in (0..100).step_by(2) {
+        root.remove(&format!("dir/file-with-a-long-name-{}", i))
+            .unwrap();
+    }
+    assert_eq!(
+        entries(&root.clone().lookup("dir").unwrap()).len(),
+        2 + 3 + 50
+    );
+
+    // permissions and owners (with the high 16 bits of the IDs)
+    let dir = root.clone().lookup("dir").unwrap();
+    dir.set_perm(VfsNodePerm::from_bits_truncate(0o750))
+        .unwrap();
+    dir.set_owner(100_000, 1000).unwrap();
+
+    fs.umount().unwrap();
+    check_image(&image, &data.lock().unwrap());
+
+    // reopen and check
+    let (fs, _) = open_image(&image);
+    let root = fs.root_dir();
+    assert_eq!(
+        read_to_string(&root.clone().lookup("dir/long.txt").unwrap()),
+        LONG_LINE.repeat(1000)
+    );
+    let attr = root.clone().lookup("dir").unwrap().get_attr().unwrap();
+    assert!(attr.is_dir());
+    assert_eq!(attr.perm().mode(), 0o750);
+    assert_eq!((attr.uid(), attr.gid()), (100_000, 1000));
+    assert!(root.clone().lookup("dir/file-with-a-long-name-99").is_ok());
+    std::fs::remove_dir_all(image.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_ext2_remove_open() {
+    let Some(image) = make_image("remove_open", "mkfs.ext2") else {
+        return;
+    };
+    let data = Arc::new(Mutex::new(std::fs::read(&image).unwrap()));
+    let clock = || Duration::from_secs(1_000_000);
+    let fs = Ext2FileSystem::with_clock(Box::new(MemDisk(data.clone())), clock).unwrap();
+    let root = fs.root_dir();
+
+    // timestamps
+    root.create("file", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file").unwrap();
+    assert_eq!(file.write_at(0, b"hello"), Ok(5));
+    let attr = file.get_attr().unwrap();
+    assert_eq!(attr.mtime(), clock());
+    assert_eq!(attr.ctime(), clock());
+    file.set_times(Some(Duration::from_secs(100)), None)
+        .unwrap();
+    let attr = file.get_attr().unwrap();
+    assert_eq!((attr.atime().as_secs(), attr.mtime()), (100, clock()));
+
+    // the inode is released after the last node is dropped
+    let ino = file.ino().unwrap();
+    root.remove("file").unwrap();
+    assert_eq!(root.clone().lookup("file").err(), Some(VfsError::NotFound));
+    let mut buf = [0; 8];
+    assert_eq!(file.read_at(0, &mut buf), Ok(5));
+    assert_eq!(&buf[..5], b"hello");
+    root.create("other", VfsNodeType::File).unwrap();
+    let other = root.clone().lookup("other").unwrap();
+    assert_ne!(other.ino(), Some(ino));
+    drop(file);
+    root.create("reused", VfsNodeType::File).unwrap();
+    let reused = root.clone().lookup("reused").unwrap();
+    assert_eq!(reused.ino(), Some(ino));
+    assert_eq!(reused.get_attr().unwrap().size(), 0);
+
+    // nothing can be created in a removed directory
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    let dir = root.clone().lookup("dir").unwrap();
+    root.remove("dir").unwrap();
+    assert_eq!(
+        dir.create("file", VfsNodeType::File).err(),
+        Some(VfsError::NotFound)
+    );
+    drop(dir);
+
+    fs.umount().unwrap();
+    check_image(&image, &data.lock().unwrap());
+    std::fs::remove_dir_all(image.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_ext4_read_only() {
+    let Some(image) = make_image("ext4", "mkfs.ext4") else {
+        return;
+    };
+    let (fs, _) = open_image(&image);
+    let root = fs.root_dir();
+    test_read(&root);
+
+    let file = root.clone().lookup("short.txt").unwrap();
+    assert_eq!(
+        file.write_at(0, b"x").err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.create("new", VfsNodeType::File).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        file.set_perm(VfsNodePerm::from_bits_truncate(0o600)).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    std::fs::remove_dir_all(image.parent().unwrap()).unwrap();
+}
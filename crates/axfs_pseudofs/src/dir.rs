@@ -0,0 +1,217 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsResult};
+use spin::RwLock;
+
+type ListFn = dyn Fn() -> Vec<String> + Send + Sync;
+type PopulateFn = dyn Fn(&str, &Arc<DirNode>) + Send + Sync;
+
+/// Subdirectories that are generated on access.
+struct DynamicDirs {
+    list: Box<ListFn>,
+    populate: Box<PopulateFn>,
+}
+
+/// The directory node in the pseudo filesystem.
+///
+/// Besides the nodes added by [`DirNode::add`], a directory can have dynamic
+/// subdirectories (see [`DirNode::set_dynamic_dirs`]), such as the per-task
+/// directories in `/proc`.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct DirNode {
+    parent: RwLock<Weak<dyn VfsNodeOps>>,
+    children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    dynamic: RwLock<Option<DynamicDirs>>,
+}
+
+impl DirNode {
+    /// Create an empty directory, whose parent is `parent`.
+    pub fn new(parent: Option<&VfsNodeRef>) -> Arc<Self> {
+        let parent = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+        Arc::new(Self {
+            parent: RwLock::new(parent),
+            children: RwLock::new(BTreeMap::new()),
+            dynamic: RwLock::new(None),
+        })
+    }
+
+    pub(super) fn set_parent(&self, parent: Option<&VfsNodeRef>) {
+        *self.parent.write() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
+    }
+
+    /// Create a subdirectory at this directory, or returns the existing one.
+    pub fn mkdir(self: &Arc<Self>, name: &str) -> Arc<Self> {
+        let mut children = self.children.write();
+        if let Some(dir) = children.get(name).and_then(as_dir) {
+            return dir;
+        }
+        let parent = self.clone() as VfsNodeRef;
+        let node = Self::new(Some(&parent));
+        children.insert(name.into(), node.clone());
+        node
+    }
+
+    /// Add a node to this directory, replacing the existing one with the same
+    /// name.
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
+        self.children.write().insert(name.into(), node);
+    }
+
+    /// Sets the generator of the dynamic subdirectories.
+    ///
+    /// `list` returns the names of the subdirectories. On each lookup of a
+    /// name in the list, a new empty directory is created and filled by
+    /// `populate`. The nodes added by [`DirNode::add`] take precedence over
+    /// the dynamic ones with the same name.
+    pub fn set_dynamic_dirs<L, P>(&self, list: L, populate: P)
+    where
+        L: Fn() -> Vec<String> + Send + Sync + 'static,
+        P: Fn(&str, &Arc<DirNode>) + Send + Sync + 'static,
+    {
+        *self.dynamic.write() = Some(DynamicDirs {
+            list: Box::new(list),
+            populate: Box::new(populate),
+        });
+    }
+
+    fn lookup_child(self: &Arc<Self>, name: &str) -> VfsResult<VfsNodeRef> {
+        if let Some(node) = self.children.read().get(name) {
+            return Ok(node.clone());
+        }
+        let dynamic = self.dynamic.read();
+        match dynamic.as_ref() {
+            Some(dynamic) if (dynamic.list)().iter().any(|n| n == name) => {
+                let parent = self.clone() as VfsNodeRef;
+                let dir = Self::new(Some(&parent));
+                (dynamic.populate)(name, &dir);
+                Ok(dir)
+            }
+            _ => Err(VfsError::NotFound),
+        }
+    }
+
+    fn entries(&self) -> Vec<(String, VfsNodeType)> {
+        let children = self.children.read();
+        let mut entries: Vec<_> = children
+            .iter()
+            .map(|(name, node)| (name.clone(), node.get_attr().unwrap().file_type()))
+            .collect();
+        if let Some(dynamic) = self.dynamic.read().as_ref() {
+            let names = (dynamic.list)();
+            entries.extend(
+                names
+                    .into_iter()
+                    .filter(|name| !children.contains_key(name))
+                    .map(|name| (name, VfsNodeType::Dir)),
+            );
+        }
+        entries
+    }
+}
+
+impl VfsNodeOps for DirNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new_dir(4096, 0))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.read().upgrade()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.lookup_child(name),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries();
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, ty)) = entries.next() {
+                        *ent = VfsDirEntry::new(name, *ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        log::debug!("create {:?} at pseudofs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => self
+                    .children
+                    .read()
+                    .get(name)
+                    .ok_or(VfsError::NotFound)?
+                    .create(rest, ty),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            Err(VfsError::PermissionDenied) // do not support to create nodes dynamically
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        log::debug!("remove at pseudofs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => self
+                    .children
+                    .read()
+                    .get(name)
+                    .ok_or(VfsError::NotFound)?
+                    .remove(rest),
+            }
+        } else {
+            Err(VfsError::PermissionDenied) // do not support to remove nodes dynamically
+        }
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// Converts the node to a [`DirNode`] if it is one.
+fn as_dir(node: &VfsNodeRef) -> Option<Arc<DirNode>> {
+    if node.as_any().is::<DirNode>() {
+        let ptr = Arc::into_raw(node.clone()) as *const DirNode;
+        // SAFETY: the type of the pointee has just been checked.
+        Some(unsafe { Arc::from_raw(ptr) })
+    } else {
+        None
+    }
+}
+
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
+    })
+}
@@ -0,0 +1,80 @@
+use alloc::{boxed::Box, string::String};
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+
+type ReadFn = dyn Fn() -> String + Send + Sync;
+type WriteFn = dyn Fn(&str) -> VfsResult + Send + Sync;
+
+/// The file node in the pseudo filesystem.
+///
+/// The content is generated by a callback on each read, so the size of the
+/// file is always reported as 0 like Linux. A writable file passes the written
+/// data to another callback as a whole, regardless of the offset.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct FileNode {
+    read: Box<ReadFn>,
+    write: Option<Box<WriteFn>>,
+}
+
+impl FileNode {
+    /// Create a read-only file whose content is generated by `read`.
+    pub fn new<R>(read: R) -> Self
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+    {
+        Self {
+            read: Box::new(read),
+            write: None,
+        }
+    }
+
+    /// Create a writable file whose content is generated by `read`, and the
+    /// written data is handled by `write`.
+    pub fn new_writable<R, W>(read: R, write: W) -> Self
+    where
+        R: Fn() -> String + Send + Sync + 'static,
+        W: Fn(&str) -> VfsResult + Send + Sync + 'static,
+    {
+        Self {
+            read: Box::new(read),
+            write: Some(Box::new(write)),
+        }
+    }
+}
+
+impl VfsNodeOps for FileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mode = if self.write.is_some() { 0o644 } else { 0o444 };
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(mode),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.read)();
+        let start = content.len().min(offset as usize);
+        let end = content.len().min(start + buf.len());
+        let src = &content.as_bytes()[start..end];
+        buf[..src.len()].copy_from_slice(src);
+        Ok(src.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let write = self.write.as_ref().ok_or(VfsError::PermissionDenied)?;
+        let data = core::str::from_utf8(buf).map_err(|_| VfsError::InvalidInput)?;
+        write(data)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        match self.write {
+            Some(_) => Ok(()), // opened with `O_TRUNC` before writing
+            None => Err(VfsError::PermissionDenied),
+        }
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
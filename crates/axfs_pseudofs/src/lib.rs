@@ -0,0 +1,65 @@
+//! Pseudo filesystem used by [ArceOS](https://github.com/rcore-os/arceos),
+//! whose contents are generated on access, such as `procfs` and `sysfs`.
+//!
+//! The implementation is based on [`axfs_vfs`].
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod dir;
+mod file;
+mod symlink;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::dir::DirNode;
+pub use self::file::FileNode;
+pub use self::symlink::SymlinkNode;
+
+use alloc::sync::Arc;
+use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use spin::once::Once;
+
+/// A pseudo filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct PseudoFileSystem {
+    fs_type: &'static str,
+    parent: Once<VfsNodeRef>,
+    root: Arc<DirNode>,
+}
+
+impl PseudoFileSystem {
+    /// Create a new instance with the filesystem type name (e.g., `"proc"`).
+    pub fn new(fs_type: &'static str) -> Self {
+        Self {
+            fs_type,
+            parent: Once::new(),
+            root: DirNode::new(None),
+        }
+    }
+
+    /// Returns the root directory.
+    pub fn root(&self) -> &Arc<DirNode> {
+        &self.root
+    }
+}
+
+impl VfsOps for PseudoFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            self.root.set_parent(Some(self.parent.call_once(|| parent)));
+        } else {
+            self.root.set_parent(None);
+        }
+        Ok(())
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+
+    fn fs_type(&self) -> &str {
+        self.fs_type
+    }
+}
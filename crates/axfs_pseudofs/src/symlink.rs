@@ -0,0 +1,39 @@
+use alloc::{boxed::Box, string::String};
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsResult};
+
+type TargetFn = dyn Fn() -> String + Send + Sync;
+
+/// The symbolic link node in the pseudo filesystem, whose target is generated
+/// on each access (e.g., `/proc/self`).
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct SymlinkNode {
+    target: Box<TargetFn>,
+}
+
+impl SymlinkNode {
+    /// Create a symbolic link whose target is generated by `target`.
+    pub fn new<T>(target: T) -> Self
+    where
+        T: Fn() -> String + Send + Sync + 'static,
+    {
+        Self {
+            target: Box::new(target),
+        }
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new_symlink((self.target)().len() as u64))
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let target = (self.target)();
+        let len = target.len().min(buf.len());
+        buf[..len].copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
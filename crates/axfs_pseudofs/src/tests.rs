@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+use crate::*;
+
+fn read_to_string(node: &VfsNodeRef) -> VfsResult<String> {
+    let mut buf = vec![0; 256];
+    let n = node.read_at(0, &mut buf)?;
+    Ok(String::from_utf8(buf[..n].to_vec()).unwrap())
+}
+
+fn entries(dir: &VfsNodeRef) -> Vec<String> {
+    let mut dirents: Vec<_> = (0..4).map(|_| VfsDirEntry::default()).collect();
+    let mut names = Vec::new();
+    loop {
+        let n = dir.read_dir(names.len(), &mut dirents).unwrap();
+        if n == 0 {
+            return names;
+        }
+        for ent in &dirents[..n] {
+            names.push(String::from_utf8(ent.name_as_bytes().to_vec()).unwrap());
+        }
+    }
+}
+
+#[test]
+fn test_pseudofs() -> VfsResult {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let value = Arc::new(Mutex::new(String::from("1\n")));
+    let tasks = Arc::new(Mutex::new(vec![1, 2]));
+
+    let fs = PseudoFileSystem::new("pseudofs");
+    let root = fs.root();
+    root.add(
+        "counter",
+        Arc::new(FileNode::new(|| {
+            format!("{}\n", COUNTER.fetch_add(1, Ordering::Relaxed))
+        })),
+    );
+    let (v1, v2) = (value.clone(), value.clone());
+    root.mkdir("sys").add(
+        "value",
+        Arc::new(FileNode::new_writable(
+            move || v1.lock().unwrap().clone(),
+            move |data| {
+                data.trim()
+                    .parse::<u32>()
+                    .map_err(|_| VfsError::InvalidInput)?;
+                *v2.lock().unwrap() = data.into();
+                Ok(())
+            },
+        )),
+    );
+    let t = tasks.clone();
+    root.set_dynamic_dirs(
+        move || t.lock().unwrap().iter().map(|id| id.to_string()).collect(),
+        |name, dir| {
+            let name = String::from(name);
+            dir.add("name", Arc::new(FileNode::new(move || name.clone())));
+        },
+    );
+    root.add("self", Arc::new(SymlinkNode::new(|| "1".into())));
+
+    let root = fs.root_dir();
+    assert_eq!(
+        entries(&root),
+        [".", "..", "counter", "self", "sys", "1", "2"]
+    );
+
+    // contents are generated on each read
+    let counter = root.clone().lookup("counter")?;
+    let first = read_to_string(&counter)?;
+    assert_ne!(read_to_string(&counter)?, first);
+    assert_eq!(
+        counter.write_at(0, b"0").err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(counter.truncate(0).err(), Some(VfsError::PermissionDenied));
+    assert_eq!(counter.get_attr()?.perm().mode(), 0o444);
+    assert_eq!(counter.get_attr()?.size(), 0);
+
+    // writable files
+    let file = root.clone().lookup("sys/value")?;
+    assert_eq!(file.get_attr()?.perm().mode(), 0o644);
+    assert_eq!(read_to_string(&file)?, "1\n");
+    file.truncate(0)?;
+    assert_eq!(file.write_at(0, b"42\n")?, 3);
+    assert_eq!(read_to_string(&file)?, "42\n");
+    assert_eq!(file.write_at(0, b"abc").err(), Some(VfsError::InvalidInput));
+    assert_eq!(read_to_string(&file)?, "42\n");
+    let mut buf = [0; 8];
+    assert_eq!(file.read_at(1, &mut buf)?, 2);
+    assert_eq!(file.read_at(10, &mut buf)?, 0);
+
+    // dynamic directories
+    let task = root.clone().lookup("2/name")?;
+    assert_eq!(read_to_string(&task)?, "2");
+    assert!(Arc::ptr_eq(
+        &root.clone().lookup("2/..")?,
+        &(fs.root().clone() as VfsNodeRef)
+    ));
+    tasks.lock().unwrap().push(3);
+    assert_eq!(read_to_string(&root.clone().lookup("3/name")?)?, "3");
+    tasks.lock().unwrap().remove(0);
+    assert_eq!(root.clone().lookup("1").err(), Some(VfsError::NotFound));
+    assert_eq!(
+        entries(&root),
+        [".", "..", "counter", "self", "sys", "2", "3"]
+    );
+
+    // symbolic links
+    let link = root.clone().lookup("self")?;
+    assert!(link.get_attr()?.is_symlink());
+    assert_eq!(link.readlink(&mut buf)?, 1);
+    assert_eq!(&buf[..1], b"1");
+
+    // the tree cannot be modified by users
+    assert_eq!(
+        root.create("new", VfsNodeType::File).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.remove("sys/value").err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.create("sys", VfsNodeType::Dir).err(),
+        Some(VfsError::PermissionDenied)
+    );
+
+    assert_eq!(fs.fs_type(), "pseudofs");
+
+    // `mkdir` returns the existing directory
+    assert!(Arc::ptr_eq(
+        &(fs.root().mkdir("sys") as VfsNodeRef),
+        &root.clone().lookup("sys")?
+    ));
+    Ok(())
+}
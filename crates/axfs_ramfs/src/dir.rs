@@ -1,12 +1,15 @@
 use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
 use alloc::{string::String, vec::Vec};
+use core::time::Duration;
 
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
 use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
 use crate::file::FileNode;
+use crate::meta::{Clock, NodeMeta};
+use crate::symlink::SymlinkNode;
 
 /// The directory node in the RAM filesystem.
 ///
@@ -15,17 +18,23 @@ pub struct DirNode {
     this: Weak<DirNode>,
     parent: RwLock<Weak<dyn VfsNodeOps>>,
     children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    meta: RwLock<NodeMeta>,
 }
 
 impl DirNode {
-    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>) -> Arc<Self> {
+    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>, clock: Clock) -> Arc<Self> {
         Arc::new_cyclic(|this| Self {
             this: this.clone(),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
             children: RwLock::new(BTreeMap::new()),
+            meta: RwLock::new(NodeMeta::new(VfsNodePerm::default_dir(), clock)),
         })
     }
 
+    fn clock(&self) -> Clock {
+        self.meta.read().clock()
+    }
+
     pub(super) fn set_parent(&self, parent: Option<&VfsNodeRef>) {
         *self.parent.write() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
     }
@@ -47,11 +56,46 @@ impl DirNode {
             return Err(VfsError::AlreadyExists);
         }
         let node: VfsNodeRef = match ty {
-            VfsNodeType::File => Arc::new(FileNode::new()),
-            VfsNodeType::Dir => Self::new(Some(self.this.clone())),
+            VfsNodeType::File => Arc::new(FileNode::new(self.clock())),
+            VfsNodeType::Dir => Self::new(Some(self.this.clone()), self.clock()),
             _ => return Err(VfsError::Unsupported),
         };
         self.children.write().insert(name.into(), node);
+        self.meta.write().touch_modify();
+        Ok(())
+    }
+
+    /// Creates a new symbolic link with the given name in this directory,
+    /// which points to `target`.
+    pub fn create_symlink(&self, name: &str, target: &str) -> VfsResult {
+        if self.exist(name) {
+            log::error!("AlreadyExists {}", name);
+            return Err(VfsError::AlreadyExists);
+        }
+        let node = Arc::new(SymlinkNode::new(target, self.clock()));
+        self.children.write().insert(name.into(), node);
+        self.meta.write().touch_modify();
+        Ok(())
+    }
+
+    /// Adds a hard link with the given name in this directory, which refers to
+    /// the existing `node`.
+    ///
+    /// Only files and symbolic links of the RAM filesystem can be linked.
+    pub fn link_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        if node.as_any().is::<DirNode>() {
+            return Err(VfsError::PermissionDenied);
+        }
+        if !node.as_any().is::<FileNode>() && !node.as_any().is::<SymlinkNode>() {
+            return Err(VfsError::CrossesDevices);
+        }
+        if self.exist(name) {
+            log::error!("AlreadyExists {}", name);
+            return Err(VfsError::AlreadyExists);
+        }
+        update_links(&node, 1);
+        self.children.write().insert(name.into(), node);
+        self.meta.write().touch_modify();
         Ok(())
     }
 
@@ -64,14 +108,108 @@ impl DirNode {
                 return Err(VfsError::DirectoryNotEmpty);
             }
         }
-        children.remove(name);
+        if let Some(node) = children.remove(name) {
+            update_links(&node, -1);
+        }
+        self.meta.write().touch_modify();
+        Ok(())
+    }
+
+    /// Moves the node `name` in this directory to `dst_name` in the directory
+    /// `dst`, replacing the node at the destination if it exists.
+    ///
+    /// A directory can only replace an empty directory, and cannot be moved
+    /// into itself or its subdirectories.
+    pub fn move_node(&self, name: &str, dst: &DirNode, dst_name: &str) -> VfsResult {
+        let node = self
+            .children
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+        let is_dir = node.as_any().is::<DirNode>();
+        if let Some(old) = dst.children.read().get(dst_name) {
+            if core::ptr::addr_eq(Arc::as_ptr(old), Arc::as_ptr(&node)) {
+                return Ok(()); // the same node
+            }
+            match (is_dir, old.as_any().downcast_ref::<DirNode>()) {
+                (true, Some(old)) if !old.children.read().is_empty() => {
+                    return Err(VfsError::DirectoryNotEmpty)
+                }
+                (true, None) => return Err(VfsError::NotADirectory),
+                (false, Some(_)) => return Err(VfsError::IsADirectory),
+                _ => {}
+            }
+        }
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            let dst_node: VfsNodeRef = dst.this.upgrade().ok_or(VfsError::NotFound)?;
+            let mut ancestor = Some(dst_node.clone());
+            while let Some(dir) = ancestor {
+                if core::ptr::addr_eq(Arc::as_ptr(&dir), Arc::as_ptr(&node)) {
+                    return Err(VfsError::InvalidInput);
+                }
+                ancestor = dir.parent();
+            }
+            dir.set_parent(Some(&dst_node));
+        }
+        self.children.write().remove(name);
+        if let Some(old) = dst.children.write().insert(dst_name.into(), node) {
+            update_links(&old, -1);
+        }
+        self.meta.write().touch_modify();
+        dst.meta.write().touch_modify();
         Ok(())
     }
+
+    /// Looks up the parent directory of `path`, returns it with the last
+    /// component of `path`.
+    fn lookup_parent<'a>(&self, path: &'a str) -> VfsResult<(Arc<DirNode>, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let parent = this.lookup(parent)?;
+        match parent.as_any().downcast_ref::<DirNode>() {
+            Some(dir) => Ok((dir.this.upgrade().ok_or(VfsError::NotFound)?, name)),
+            None if parent.get_attr()?.is_dir() => Err(VfsError::CrossesDevices),
+            None => Err(VfsError::NotADirectory),
+        }
+    }
 }
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        let mut attr = self.meta.read().fill(VfsNodeAttr::new_dir(4096, 0));
+        // `.` and the entry in the parent, plus `..` of the subdirectories
+        let subdirs = self
+            .children
+            .read()
+            .values()
+            .filter(|n| n.as_any().is::<DirNode>())
+            .count();
+        attr.set_nlink(2 + subdirs as u64);
+        Ok(attr)
+    }
+
+    fn ino(&self) -> Option<u64> {
+        Some(self as *const Self as u64)
+    }
+
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        self.meta.write().set_perm(perm);
+        Ok(())
+    }
+
+    fn set_owner(&self, uid: u32, gid: u32) -> VfsResult {
+        self.meta.write().set_owner(uid, gid);
+        Ok(())
+    }
+
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        self.meta.write().set_times(atime, mtime);
+        Ok(())
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
@@ -99,15 +237,22 @@ impl VfsNodeOps for DirNode {
     }
 
     fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.meta.write().touch_access();
         let children = self.children.read();
         let mut children = children.iter().skip(start_idx.max(2) - 2);
+        let this_ino = self.ino().unwrap_or_default();
         for (i, ent) in dirents.iter_mut().enumerate() {
             match i + start_idx {
-                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
-                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir).with_ino(this_ino),
+                1 => {
+                    // the root directory is its own parent
+                    let ino = self.parent().and_then(|p| p.ino()).unwrap_or(this_ino);
+                    *ent = VfsDirEntry::new("..", VfsNodeType::Dir).with_ino(ino);
+                }
                 _ => {
                     if let Some((name, node)) = children.next() {
-                        *ent = VfsDirEntry::new(name, node.get_attr().unwrap().file_type());
+                        let ty = node.get_attr().unwrap().file_type();
+                        *ent = VfsDirEntry::new(name, ty).with_ino(node.ino().unwrap_or_default());
                     } else {
                         return Ok(i);
                     }
@@ -165,6 +310,64 @@ impl VfsNodeOps for DirNode {
         }
     }
 
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("symlink at ramfs: {} -> {}", path, target);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.symlink(rest, target),
+                ".." => self
+                    .parent()
+                    .ok_or(VfsError::NotFound)?
+                    .symlink(rest, target),
+                _ => {
+                    let subdir = self
+                        .children
+                        .read()
+                        .get(name)
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.symlink(rest, target)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.create_symlink(name, target)
+        }
+    }
+
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        log::debug!("link at ramfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.link(rest, node),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.link(rest, node),
+                _ => {
+                    let subdir = self
+                        .children
+                        .read()
+                        .get(name)
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.link(rest, node)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::AlreadyExists)
+        } else {
+            self.link_node(name, node)
+        }
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        log::debug!("rename at ramfs: {} -> {}", src_path, dst_path);
+        let (src_dir, src_name) = self.lookup_parent(src_path)?;
+        let (dst_dir, dst_name) = self.lookup_parent(dst_path)?;
+        src_dir.move_node(src_name, &dst_dir, dst_name)
+    }
+
     axfs_vfs::impl_vfs_dir_default! {}
 }
 
@@ -174,3 +377,14 @@ fn split_path(path: &str) -> (&str, Option<&str>) {
         (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
     })
 }
+
+/// Updates the number of hard links of the file or symbolic link `node` by
+/// `delta`, as directories count their links themselves.
+fn update_links(node: &VfsNodeRef, delta: i64) {
+    let any = node.as_any();
+    if let Some(file) = any.downcast_ref::<FileNode>() {
+        file.meta.write().update_links(delta);
+    } else if let Some(symlink) = any.downcast_ref::<SymlinkNode>() {
+        symlink.meta.write().update_links(delta);
+    }
+}
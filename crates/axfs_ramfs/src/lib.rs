@@ -8,15 +8,19 @@ extern crate alloc;
 
 mod dir;
 mod file;
+mod meta;
+mod symlink;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::dir::DirNode;
 pub use self::file::FileNode;
+pub use self::symlink::SymlinkNode;
 
 use alloc::sync::Arc;
 use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use core::time::Duration;
 use spin::once::Once;
 
 /// A RAM filesystem that implements [`axfs_vfs::VfsOps`].
@@ -26,11 +30,17 @@ pub struct RamFileSystem {
 }
 
 impl RamFileSystem {
-    /// Create a new instance.
+    /// Create a new instance, all the timestamps of the nodes are zero.
     pub fn new() -> Self {
+        Self::with_clock(|| Duration::ZERO)
+    }
+
+    /// Create a new instance, whose nodes get the current time from `clock`
+    /// to maintain their timestamps.
+    pub fn with_clock(clock: fn() -> Duration) -> Self {
         Self {
             parent: Once::new(),
-            root: DirNode::new(None),
+            root: DirNode::new(None, clock),
         }
     }
 
@@ -53,6 +63,10 @@ impl VfsOps for RamFileSystem {
     fn root_dir(&self) -> VfsNodeRef {
         self.root.clone()
     }
+
+    fn fs_type(&self) -> &str {
+        "ramfs"
+    }
 }
 
 impl Default for RamFileSystem {
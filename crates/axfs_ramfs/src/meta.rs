@@ -0,0 +1,86 @@
+use core::time::Duration;
+
+use axfs_vfs::{VfsNodeAttr, VfsNodePerm};
+
+/// The clock to get the current time for the timestamps of the nodes.
+pub(crate) type Clock = fn() -> Duration;
+
+/// The metadata of a node that can be changed after it is created.
+pub(crate) struct NodeMeta {
+    perm: VfsNodePerm,
+    uid: u32,
+    gid: u32,
+    nlink: u64,
+    clock: Clock,
+    atime: Duration,
+    mtime: Duration,
+    ctime: Duration,
+}
+
+impl NodeMeta {
+    pub fn new(perm: VfsNodePerm, clock: Clock) -> Self {
+        let now = clock();
+        Self {
+            perm,
+            uid: 0,
+            gid: 0,
+            nlink: 1,
+            clock,
+            atime: now,
+            mtime: now,
+            ctime: now,
+        }
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    pub fn set_perm(&mut self, perm: VfsNodePerm) {
+        self.perm = perm;
+        self.touch_change();
+    }
+
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+        self.touch_change();
+    }
+
+    pub fn set_times(&mut self, atime: Option<Duration>, mtime: Option<Duration>) {
+        self.atime = atime.unwrap_or(self.atime);
+        self.mtime = mtime.unwrap_or(self.mtime);
+        self.touch_change();
+    }
+
+    /// Updates the number of hard links by `delta` on linking or unlinking.
+    pub fn update_links(&mut self, delta: i64) {
+        self.nlink = self.nlink.saturating_add_signed(delta);
+        self.touch_change();
+    }
+
+    /// Updates the access time on reading the content.
+    pub fn touch_access(&mut self) {
+        self.atime = (self.clock)();
+    }
+
+    /// Updates the modification and change times on changing the content.
+    pub fn touch_modify(&mut self) {
+        self.mtime = (self.clock)();
+        self.ctime = self.mtime;
+    }
+
+    /// Updates the change time on changing the attributes.
+    pub fn touch_change(&mut self) {
+        self.ctime = (self.clock)();
+    }
+
+    /// Fills the metadata into the attributes.
+    pub fn fill(&self, mut attr: VfsNodeAttr) -> VfsNodeAttr {
+        attr.set_perm(self.perm);
+        attr.set_owner(self.uid, self.gid);
+        attr.set_nlink(self.nlink);
+        attr.set_times(self.atime, self.mtime, self.ctime);
+        attr
+    }
+}
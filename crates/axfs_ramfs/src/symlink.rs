@@ -0,0 +1,60 @@
+use alloc::string::String;
+use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsResult};
+use core::time::Duration;
+use spin::RwLock;
+
+use crate::meta::{Clock, NodeMeta};
+
+/// The symbolic link node in the RAM filesystem.
+///
+/// It implements [`axfs_vfs::VfsNodeOps`].
+pub struct SymlinkNode {
+    target: String,
+    pub(crate) meta: RwLock<NodeMeta>,
+}
+
+impl SymlinkNode {
+    pub(super) fn new(target: &str, clock: Clock) -> Self {
+        Self {
+            target: target.into(),
+            meta: RwLock::new(NodeMeta::new(VfsNodePerm::default_symlink(), clock)),
+        }
+    }
+
+    /// Returns the target path of the symbolic link.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+impl VfsNodeOps for SymlinkNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = VfsNodeAttr::new_symlink(self.target.len() as _);
+        Ok(self.meta.read().fill(attr))
+    }
+
+    fn ino(&self) -> Option<u64> {
+        Some(self as *const Self as u64)
+    }
+
+    // the permission of a symbolic link is never used, but the owner is
+    fn set_owner(&self, uid: u32, gid: u32) -> VfsResult {
+        self.meta.write().set_owner(uid, gid);
+        Ok(())
+    }
+
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        self.meta.write().set_times(atime, mtime);
+        Ok(())
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let src = self.target.as_bytes();
+        let len = src.len().min(buf.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        self.meta.write().touch_access();
+        Ok(len)
+    }
+
+    impl_vfs_non_dir_default! {}
+}
@@ -1,6 +1,8 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -88,6 +90,140 @@ fn test_get_parent(devfs: &RamFileSystem) -> VfsResult {
     Ok(())
 }
 
+fn test_links(devfs: &RamFileSystem) -> VfsResult {
+    let root = devfs.root_dir();
+    let mut buf = [0; 32];
+
+    root.symlink("foo/link", "../f2")?;
+    let link = root.clone().lookup("foo/link")?;
+    assert!(link.get_attr()?.is_symlink());
+    assert_eq!(link.get_attr()?.size(), 5);
+    assert_eq!(link.readlink(&mut buf)?, 5);
+    assert_eq!(&buf[..5], b"../f2");
+    assert_eq!(link.readlink(&mut buf[..2])?, 2);
+    assert_eq!(&buf[..2], b"..");
+    assert_eq!(
+        link.read_at(0, &mut buf).err(),
+        Some(VfsError::InvalidInput)
+    );
+    assert_eq!(
+        link.clone().lookup("f2").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.symlink("foo/link", "f1").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.symlink("no/link", "f1").err(),
+        Some(VfsError::NotFound)
+    );
+
+    let f1 = root.clone().lookup("f1")?;
+    root.link("foo/bar/f1_link", f1.clone())?;
+    let f1_link = root.clone().lookup("foo/bar/f1_link")?;
+    assert!(Arc::ptr_eq(&f1, &f1_link));
+    assert_eq!(f1_link.read_at(0, &mut buf)?, 32);
+    assert_eq!(f1.get_attr()?.nlink(), 2);
+    // `foo` has `bar` as the only subdirectory
+    assert_eq!(root.clone().lookup("foo")?.get_attr()?.nlink(), 3);
+    assert_eq!(
+        root.link("foo/dir_link", root.clone().lookup("foo/bar")?)
+            .err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.link("f1", f1.clone()).err(),
+        Some(VfsError::AlreadyExists)
+    );
+
+    const EMPTY: VfsDirEntry = VfsDirEntry::default();
+    let mut dirents = [EMPTY; 8];
+    let n = root.clone().lookup("foo")?.read_dir(0, &mut dirents)?;
+    assert!(dirents[..n].iter().any(|e| e.name_as_bytes() == b"link"
+        && e.entry_type() == VfsNodeType::SymLink
+        && Some(e.ino()) == link.ino()));
+
+    root.remove("foo/link")?;
+    root.remove("foo/bar/f1_link")?;
+    assert_eq!(f1.get_attr()?.nlink(), 1);
+    assert!(root.lookup("f1").is_ok());
+    Ok(())
+}
+
+fn test_perm_owner(devfs: &RamFileSystem) -> VfsResult {
+    let root = devfs.root_dir();
+    let f1 = root.clone().lookup("f1")?;
+    let attr = f1.get_attr()?;
+    assert_eq!(attr.perm().mode(), 0o666);
+    assert_eq!((attr.uid(), attr.gid()), (0, 0));
+
+    f1.set_perm(VfsNodePerm::from_bits_truncate(0o640))?;
+    f1.set_owner(1000, 100)?;
+    let attr = f1.get_attr()?;
+    assert_eq!(attr.perm().mode(), 0o640);
+    assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+    assert_eq!(attr.size(), 32); // the content is kept
+
+    let foo = root.clone().lookup("foo")?;
+    foo.set_perm(VfsNodePerm::from_bits_truncate(0o700))?;
+    assert_eq!(foo.get_attr()?.perm().mode(), 0o700);
+    assert!(foo.get_attr()?.is_dir());
+
+    root.symlink("link", "f1")?;
+    let link = root.clone().lookup("link")?;
+    link.set_owner(1000, 1000)?;
+    assert_eq!(link.get_attr()?.uid(), 1000);
+    assert_eq!(
+        link.set_perm(VfsNodePerm::from_bits_truncate(0o600)).err(),
+        Some(VfsError::Unsupported)
+    );
+    root.remove("link")
+}
+
+fn test_rename(devfs: &RamFileSystem) -> VfsResult {
+    let root = devfs.root_dir();
+    let f2 = root.clone().lookup("f2")?;
+    root.rename("f2", "foo/bar/f2_moved")?;
+    assert_eq!(root.clone().lookup("f2").err(), Some(VfsError::NotFound));
+    let moved = root.clone().lookup("foo/bar/f2_moved")?;
+    assert!(Arc::ptr_eq(&f2, &moved));
+
+    root.create("dir", VfsNodeType::Dir)?;
+    root.create("dir/f5", VfsNodeType::File)?;
+    root.rename("dir", "foo/dir2")?;
+    let f5 = root.clone().lookup("foo/dir2/f5")?;
+    assert!(f5.parent().is_none());
+    let dir2 = root.clone().lookup("foo/dir2")?;
+    let foo = root.clone().lookup("foo")?;
+    assert!(Arc::ptr_eq(&dir2.parent().unwrap(), &foo));
+    assert_eq!(
+        root.rename("foo", "foo/dir2/foo").err(),
+        Some(VfsError::InvalidInput)
+    );
+    assert_eq!(
+        root.rename("foo/dir2", "foo/bar").err(),
+        Some(VfsError::DirectoryNotEmpty)
+    );
+    assert_eq!(
+        root.rename("foo/dir2", "f1").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.rename("f1", "foo/dir2").err(),
+        Some(VfsError::IsADirectory)
+    );
+    assert_eq!(root.rename("no", "f1").err(), Some(VfsError::NotFound));
+
+    // replace an existing file
+    root.create("foo/dir2/f6", VfsNodeType::File)?;
+    root.rename("foo/dir2/f6", "foo/dir2/f5")?;
+    assert!(root.clone().lookup("foo/dir2/f6").is_err());
+    root.remove("foo/dir2/f5")?;
+    root.remove("foo/dir2")?;
+    root.rename("foo/bar/f2_moved", "f2")
+}
+
 #[test]
 fn test_ramfs() {
     // .
@@ -117,6 +253,9 @@ fn test_ramfs() {
 
     test_ramfs_ops(&ramfs).unwrap();
     test_get_parent(&ramfs).unwrap();
+    test_links(&ramfs).unwrap();
+    test_perm_owner(&ramfs).unwrap();
+    test_rename(&ramfs).unwrap();
 
     let root = ramfs.root_dir();
     assert_eq!(root.remove("f1"), Ok(()));
@@ -134,3 +273,50 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+#[test]
+fn test_timestamps() {
+    static NOW: AtomicU64 = AtomicU64::new(1);
+    let tick = |secs| NOW.store(secs, Ordering::SeqCst);
+    let times = |node: &VfsNodeRef| {
+        let attr = node.get_attr().unwrap();
+        [attr.atime(), attr.mtime(), attr.ctime()].map(|t| t.as_secs())
+    };
+
+    let ramfs = RamFileSystem::with_clock(|| Duration::from_secs(NOW.load(Ordering::SeqCst)));
+    let root = ramfs.root_dir();
+    assert_eq!(times(&root), [1; 3]);
+
+    tick(2);
+    root.create("f1", VfsNodeType::File).unwrap();
+    let f1 = root.clone().lookup("f1").unwrap();
+    assert_eq!(times(&f1), [2; 3]);
+    assert_eq!(times(&root), [1, 2, 2]);
+
+    tick(3);
+    f1.write_at(0, b"hello").unwrap();
+    assert_eq!(times(&f1), [2, 3, 3]);
+    tick(4);
+    f1.read_at(0, &mut [0; 5]).unwrap();
+    assert_eq!(times(&f1), [4, 3, 3]);
+    tick(5);
+    f1.truncate(1).unwrap();
+    assert_eq!(times(&f1), [4, 5, 5]);
+    tick(6);
+    f1.set_perm(VfsNodePerm::from_bits_truncate(0o600)).unwrap();
+    assert_eq!(times(&f1), [4, 5, 6]);
+
+    tick(7);
+    root.read_dir(0, &mut [VfsDirEntry::default()]).unwrap();
+    assert_eq!(times(&root), [7, 2, 2]);
+
+    tick(8);
+    f1.set_times(Some(Duration::from_secs(100)), None).unwrap();
+    assert_eq!(times(&f1), [100, 5, 8]);
+    f1.set_times(None, Some(Duration::from_secs(200))).unwrap();
+    assert_eq!(times(&f1), [100, 200, 8]);
+
+    tick(9);
+    root.remove("f1").unwrap();
+    assert_eq!(times(&root), [7, 9, 9]);
+}
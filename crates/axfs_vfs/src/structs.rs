@@ -1,3 +1,5 @@
+use core::time::Duration;
+
 /// Filesystem attributes.
 ///
 /// Currently not used.
@@ -12,10 +14,24 @@ pub struct VfsNodeAttr {
     mode: VfsNodePerm,
     /// File type.
     ty: VfsNodeType,
+    /// Inode number.
+    ino: u64,
+    /// Number of hard links.
+    nlink: u64,
     /// Total size, in bytes.
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// User ID of the owner.
+    uid: u32,
+    /// Group ID of the owner.
+    gid: u32,
+    /// Time of the last access.
+    atime: Duration,
+    /// Time of the last modification of the content.
+    mtime: Duration,
+    /// Time of the last change of the content or the attributes.
+    ctime: Duration,
 }
 
 bitflags::bitflags! {
@@ -67,6 +83,7 @@ pub enum VfsNodeType {
 
 /// Directory entry.
 pub struct VfsDirEntry {
+    d_ino: u64,
     d_type: VfsNodeType,
     d_name: [u8; 63],
 }
@@ -87,6 +104,14 @@ impl VfsNodePerm {
         Self::from_bits_truncate(0o755)
     }
 
+    /// Returns the default permission for a symbolic link.
+    ///
+    /// The default permission is `0o777` (the permission of a symbolic link
+    /// is never checked, the one of its target is used instead).
+    pub const fn default_symlink() -> Self {
+        Self::from_bits_truncate(0o777)
+    }
+
     /// Returns the underlying raw `st_mode` bits that contain the standard
     /// Unix permissions for this file.
     pub const fn mode(&self) -> u32 {
@@ -198,13 +223,25 @@ impl VfsNodeType {
 
 impl VfsNodeAttr {
     /// Creates a new `VfsNodeAttr` with the given permission mode, type, size
-    /// and number of blocks.
+    /// and number of blocks, owned by the root user (0) and group (0).
+    ///
+    /// All the timestamps are zero, which can be set by [`set_times`]. The
+    /// inode number is zero and there is one hard link.
+    ///
+    /// [`set_times`]: VfsNodeAttr::set_times
     pub const fn new(mode: VfsNodePerm, ty: VfsNodeType, size: u64, blocks: u64) -> Self {
         Self {
             mode,
             ty,
             size,
             blocks,
+            ino: 0,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -215,6 +252,13 @@ impl VfsNodeAttr {
             ty: VfsNodeType::File,
             size,
             blocks,
+            ino: 0,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -226,6 +270,33 @@ impl VfsNodeAttr {
             ty: VfsNodeType::Dir,
             size,
             blocks,
+            ino: 0,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
+        }
+    }
+
+    /// Creates a new `VfsNodeAttr` for a symbolic link, with the default
+    /// symbolic link permission.
+    ///
+    /// The `size` is the length of the target path.
+    pub const fn new_symlink(size: u64) -> Self {
+        Self {
+            mode: VfsNodePerm::default_symlink(),
+            ty: VfsNodeType::SymLink,
+            size,
+            blocks: 0,
+            ino: 0,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -249,6 +320,71 @@ impl VfsNodeAttr {
         self.mode = perm
     }
 
+    /// Returns the inode number of the node.
+    ///
+    /// It's not filled by [`VfsNodeOps::get_attr`], but by the caller from
+    /// [`VfsNodeOps::ino`].
+    ///
+    /// [`VfsNodeOps::get_attr`]: crate::VfsNodeOps::get_attr
+    /// [`VfsNodeOps::ino`]: crate::VfsNodeOps::ino
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Sets the inode number of the node.
+    pub fn set_ino(&mut self, ino: u64) {
+        self.ino = ino;
+    }
+
+    /// Returns the number of hard links to the node.
+    pub const fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    /// Sets the number of hard links to the node.
+    pub fn set_nlink(&mut self, nlink: u64) {
+        self.nlink = nlink;
+    }
+
+    /// Returns the user ID of the owner.
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group ID of the owner.
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Sets the user ID and group ID of the owner.
+    pub fn set_owner(&mut self, uid: u32, gid: u32) {
+        self.uid = uid;
+        self.gid = gid;
+    }
+
+    /// Returns the time of the last access.
+    pub const fn atime(&self) -> Duration {
+        self.atime
+    }
+
+    /// Returns the time of the last modification of the content.
+    pub const fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    /// Returns the time of the last change of the content or the attributes
+    /// (e.g., permission and owner).
+    pub const fn ctime(&self) -> Duration {
+        self.ctime
+    }
+
+    /// Sets the time of the last access, modification and change.
+    pub fn set_times(&mut self, atime: Duration, mtime: Duration, ctime: Duration) {
+        self.atime = atime;
+        self.mtime = mtime;
+        self.ctime = ctime;
+    }
+
     /// Returns the type of the node.
     pub const fn file_type(&self) -> VfsNodeType {
         self.ty
@@ -263,18 +399,28 @@ impl VfsNodeAttr {
     pub const fn is_dir(&self) -> bool {
         self.ty.is_dir()
     }
+
+    /// Whether the node is a symbolic link.
+    pub const fn is_symlink(&self) -> bool {
+        self.ty.is_symlink()
+    }
 }
 
 impl VfsDirEntry {
     /// Creates an empty `VfsDirEntry`.
     pub const fn default() -> Self {
         Self {
+            d_ino: 0,
             d_type: VfsNodeType::File,
             d_name: [0; 63],
         }
     }
 
     /// Creates a new `VfsDirEntry` with the given name and type.
+    ///
+    /// The inode number is unknown (zero), which can be set by [`with_ino`].
+    ///
+    /// [`with_ino`]: VfsDirEntry::with_ino
     pub fn new(name: &str, ty: VfsNodeType) -> Self {
         let mut d_name = [0; 63];
         if name.len() > d_name.len() {
@@ -285,7 +431,26 @@ impl VfsDirEntry {
             );
         }
         d_name[..name.len()].copy_from_slice(name.as_bytes());
-        Self { d_type: ty, d_name }
+        Self {
+            d_ino: 0,
+            d_type: ty,
+            d_name,
+        }
+    }
+
+    /// Sets the inode number of the node that the entry refers to, see
+    /// [`VfsNodeOps::ino`].
+    ///
+    /// [`VfsNodeOps::ino`]: crate::VfsNodeOps::ino
+    pub const fn with_ino(mut self, ino: u64) -> Self {
+        self.d_ino = ino;
+        self
+    }
+
+    /// Returns the inode number of the node that the entry refers to, or zero
+    /// if it's unknown.
+    pub const fn ino(&self) -> u64 {
+        self.d_ino
     }
 
     /// Returns the type of the entry.
@@ -32,6 +32,11 @@ impl<const N: usize> HandlerTable<N> {
             .is_ok()
     }
 
+    /// Returns `true` if a handler is registered for the given index.
+    pub fn is_registered(&self, idx: usize) -> bool {
+        self.handlers[idx].load(Ordering::Acquire) != 0
+    }
+
     /// Handles the event with the given index.
     ///
     /// Returns `true` if the event is handled, `false` if no handler is
@@ -224,6 +224,30 @@ impl<M: PagingMetaData, PTE: GenericPTE, IF: PagingIf> PageTable64<M, PTE, IF> {
         Ok(())
     }
 
+    /// Copies the root-level entries that cover the virtual memory range
+    /// `[start, start + size)` from another page table.
+    ///
+    /// The copied entries share the lower level tables with `other`, which
+    /// are not tracked (and not deallocated) by this page table. It is usually
+    /// used to share the kernel mappings between page tables.
+    pub fn copy_from(&mut self, other: &Self, start: VirtAddr, size: usize) {
+        if size == 0 {
+            return;
+        }
+        let index_fn = if M::LEVELS == 3 {
+            p3_index
+        } else if M::LEVELS == 4 {
+            p4_index
+        } else {
+            unreachable!()
+        };
+        let start_idx = index_fn(start);
+        let end_idx = index_fn(start + (size - 1)) + 1;
+        let src_table = self.table_of(other.root_paddr());
+        let dst_table = self.table_of_mut(self.root_paddr());
+        dst_table[start_idx..end_idx].copy_from_slice(&src_table[start_idx..end_idx]);
+    }
+
     /// Walk the page table recursively.
     ///
     /// When reaching the leaf page table, call `func` on the current page table
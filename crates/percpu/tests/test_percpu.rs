@@ -99,4 +99,24 @@ fn test_percpu() {
         assert_eq!(s.foo, 0x2333);
         assert_eq!(s.bar, 100);
     });
+
+    // the data on the current CPU can also be accessed remotely
+    unsafe {
+        assert_eq!(*U32.remote_ref_raw(0), 0xdead_beef);
+        assert_eq!(STRUCT.remote_ref_raw(0).foo, 0x2333);
+    }
+
+    #[cfg(not(feature = "sp-naive"))]
+    unsafe {
+        assert_eq!(
+            percpu_area_base(1) + USIZE.offset(),
+            USIZE.remote_ptr(1) as usize
+        );
+        *USIZE.remote_ref_mut_raw(1) = 0x1234;
+        assert_eq!(USIZE.read_current(), 0xffff_0000);
+
+        set_local_thread_pointer(1);
+        assert_eq!(USIZE.read_current(), 0x1234);
+        set_local_thread_pointer(0);
+    }
 }
@@ -107,7 +107,12 @@ impl AllDevices {
                                 bdf,
                                 dev.device_name(),
                             );
-                            self.add_device(dev);
+                            let bus = crate::DeviceBus::Pci {
+                                bus: bdf.bus,
+                                device: bdf.device,
+                                function: bdf.function,
+                            };
+                            self.add_device(dev, bus);
                             continue; // skip to the next device
                         }
                     }),
@@ -61,7 +61,6 @@
 #[macro_use]
 extern crate log;
 
-#[cfg(feature = "dyn")]
 extern crate alloc;
 
 #[macro_use]
@@ -80,6 +79,8 @@ mod ixgbe;
 
 pub mod prelude;
 
+use alloc::{string::String, vec::Vec};
+
 #[allow(unused_imports)]
 use self::prelude::*;
 pub use self::structs::{AxDeviceContainer, AxDeviceEnum};
@@ -91,6 +92,29 @@ pub use self::structs::AxDisplayDevice;
 #[cfg(feature = "net")]
 pub use self::structs::AxNetDevice;
 
+/// The bus on which a device is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceBus {
+    /// The device is not found on a bus (e.g., a RAM disk).
+    Platform,
+    /// A memory-mapped device, with the base physical address and size of its
+    /// MMIO region.
+    Mmio { base: usize, size: usize },
+    /// A PCI device, with its bus, device, and function numbers.
+    Pci { bus: u8, device: u8, function: u8 },
+}
+
+/// The information of a probed device.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// The category of the device.
+    pub device_type: DeviceType,
+    /// The name of the device (e.g., `"virtio-net"`).
+    pub name: String,
+    /// The bus on which the device is found.
+    pub bus: DeviceBus,
+}
+
 /// A structure that contains all device drivers, organized by their category.
 #[derive(Default)]
 pub struct AllDevices {
@@ -103,6 +127,8 @@ pub struct AllDevices {
     /// All graphics device drivers.
     #[cfg(feature = "display")]
     pub display: AxDeviceContainer<AxDisplayDevice>,
+    /// The information of all devices, in the order they are probed.
+    pub info: Vec<DeviceInfo>,
 }
 
 impl AllDevices {
@@ -126,7 +152,7 @@ impl AllDevices {
                     dev.device_type(),
                     dev.device_name(),
                 );
-                self.add_device(dev);
+                self.add_device(dev, DeviceBus::Platform);
             }
         });
 
@@ -135,7 +161,12 @@ impl AllDevices {
 
     /// Adds one device into the corresponding container, according to its device category.
     #[allow(dead_code)]
-    fn add_device(&mut self, dev: AxDeviceEnum) {
+    fn add_device(&mut self, dev: AxDeviceEnum, bus: DeviceBus) {
+        self.info.push(DeviceInfo {
+            device_type: dev.device_type(),
+            name: dev.device_name().into(),
+            bus,
+        });
         match dev {
             #[cfg(feature = "net")]
             AxDeviceEnum::Net(dev) => self.net.push(dev),
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=AX_INITRAMFS");
+    if std::env::var("CARGO_FEATURE_INITRAMFS").is_err() {
+        return;
+    }
+
+    // the initramfs archive to be linked into the kernel image, or an empty
+    // file if not specified
+    let path = match std::env::var("AX_INITRAMFS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={}", path);
+            std::fs::canonicalize(&path)
+                .unwrap_or_else(|e| panic!("failed to find initramfs {:?}: {}", path, e))
+        }
+        _ => {
+            let path = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("empty.cpio");
+            std::fs::write(&path, []).unwrap();
+            path
+        }
+    };
+    println!("cargo:rustc-env=AX_INITRAMFS_FILE={}", path.display());
+}
@@ -1,5 +1,6 @@
 use axio::{prelude::*, Result, SeekFrom};
 use core::fmt;
+use core::time::Duration;
 
 use crate::fops;
 
@@ -16,7 +17,7 @@ pub struct File {
 }
 
 /// Metadata information about a file.
-pub struct Metadata(fops::FileAttr);
+pub struct Metadata(pub(super) fops::FileAttr);
 
 /// Options and flags which can be used to configure how a file is opened.
 #[derive(Clone, Debug)]
@@ -64,6 +65,13 @@ impl OpenOptions {
         self
     }
 
+    /// Sets the mode bits that a new file will be created with, which are
+    /// masked by the umask of the current task (default: `0o666`).
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.0.mode(mode);
+        self
+    }
+
     /// Opens a file at `path` with the options specified by `self`.
     pub fn open(&self, path: &str) -> Result<File> {
         fops::File::open(path, &self.0).map(|inner| File { inner })
@@ -90,6 +98,14 @@ impl Metadata {
         self.0.is_file()
     }
 
+    /// Returns `true` if this metadata is for a symbolic link.
+    ///
+    /// It can only be `true` for the metadata returned by
+    /// [`symlink_metadata`](super::symlink_metadata).
+    pub const fn is_symlink(&self) -> bool {
+        self.0.is_symlink()
+    }
+
     /// Returns the size of the file, in bytes, this metadata is for.
     #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> u64 {
@@ -110,6 +126,39 @@ impl Metadata {
     pub const fn blocks(&self) -> u64 {
         self.0.blocks()
     }
+
+    /// Returns the user ID of the owner of this file.
+    pub const fn uid(&self) -> u32 {
+        self.0.uid()
+    }
+
+    /// Returns the group ID of the owner of this file.
+    pub const fn gid(&self) -> u32 {
+        self.0.gid()
+    }
+
+    /// Returns the last access time of this file, as the duration since the
+    /// UNIX epoch.
+    pub const fn accessed(&self) -> Duration {
+        self.0.atime()
+    }
+
+    /// Returns the last modification time of this file, as the duration since
+    /// the UNIX epoch.
+    pub const fn modified(&self) -> Duration {
+        self.0.mtime()
+    }
+
+    /// Returns the last status change time of this file, as the duration
+    /// since the UNIX epoch.
+    pub const fn changed(&self) -> Duration {
+        self.0.ctime()
+    }
+
+    /// Returns the raw [`FileAttr`](fops::FileAttr) of this metadata.
+    pub const fn raw_metadata(&self) -> &fops::FileAttr {
+        &self.0
+    }
 }
 
 impl fmt::Debug for Metadata {
@@ -119,6 +168,11 @@ impl fmt::Debug for Metadata {
             .field("is_dir", &self.is_dir())
             .field("is_file", &self.is_file())
             .field("permissions", &self.permissions())
+            .field("uid", &self.uid())
+            .field("gid", &self.gid())
+            .field("accessed", &self.accessed())
+            .field("modified", &self.modified())
+            .field("changed", &self.changed())
             .finish_non_exhaustive()
     }
 }
@@ -162,6 +216,58 @@ impl File {
     pub fn metadata(&self) -> Result<Metadata> {
         self.inner.get_attr().map(Metadata)
     }
+
+    /// Changes the permissions on the underlying file.
+    pub fn set_permissions(&self, perm: Permissions) -> Result<()> {
+        self.inner.set_perm(perm)
+    }
+
+    /// Changes the owner and the group of the underlying file, `None` means
+    /// unchanged.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.inner.set_owner(uid, gid)
+    }
+
+    /// Changes the access time and the modification time of the underlying
+    /// file, `None` means unchanged.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> Result<()> {
+        self.inner.set_times(atime, mtime)
+    }
+
+    /// Sets all the timestamps of the underlying file to the current time.
+    pub fn touch(&self) -> Result<()> {
+        self.inner.touch()
+    }
+
+    /// Attempts to sync all data and metadata of the file to the disk.
+    pub fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all()
+    }
+
+    /// Tries to acquire an exclusive advisory lock on the file, fails with
+    /// [`WouldBlock`](axio::Error::WouldBlock) if any other handle holds a
+    /// lock on it.
+    ///
+    /// The lock is released by [`File::unlock`] or when the file is closed.
+    pub fn try_lock(&self) -> Result<()> {
+        let locks = self.inner.locks();
+        locks.lock_whole(fops::LockType::Exclusive, locks.owner())
+    }
+
+    /// Tries to acquire a shared advisory lock on the file, fails with
+    /// [`WouldBlock`](axio::Error::WouldBlock) if any other handle holds an
+    /// exclusive lock on it.
+    pub fn try_lock_shared(&self) -> Result<()> {
+        let locks = self.inner.locks();
+        locks.lock_whole(fops::LockType::Shared, locks.owner())
+    }
+
+    /// Releases the advisory lock on the file.
+    pub fn unlock(&self) -> Result<()> {
+        let locks = self.inner.locks();
+        locks.unlock_whole(locks.owner());
+        Ok(())
+    }
 }
 
 impl Read for File {
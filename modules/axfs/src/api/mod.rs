@@ -6,8 +6,10 @@ mod file;
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
 
-use alloc::{string::String, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{VfsNodeRef, VfsOps};
 use axio::{self as io, prelude::*};
+use core::time::Duration;
 
 /// Returns an iterator over the entries within a directory.
 pub fn read_dir(path: &str) -> io::Result<ReadDir> {
@@ -56,7 +58,98 @@ pub fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> io::Result<()> {
 /// Given a path, query the file system to get information about a file,
 /// directory, etc.
 pub fn metadata(path: &str) -> io::Result<Metadata> {
-    File::open(path)?.metadata()
+    crate::fops::node_attr(&crate::root::lookup(None, path)?.node).map(Metadata)
+}
+
+/// Query the metadata about a file without following symbolic links.
+pub fn symlink_metadata(path: &str) -> io::Result<Metadata> {
+    crate::fops::node_attr(&crate::root::lookup_no_follow(None, path)?.node).map(Metadata)
+}
+
+/// Changes the permissions found on a file or a directory.
+///
+/// Only the owner of the file and root can do this.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    let node = crate::root::lookup(None, path)?.node;
+    crate::perm::check_change(&node.get_attr()?, None, None)?;
+    node.set_perm(perm)
+}
+
+/// Changes the owner and the group of a file or a directory, `None` means
+/// unchanged.
+///
+/// Only root can change the owner, but the owner of the file can change the
+/// group to its own group.
+pub fn chown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    set_owner(crate::root::lookup(None, path)?.node, uid, gid)
+}
+
+/// Changes the owner and the group of a file or a directory like [`chown`],
+/// but does not follow symbolic links.
+pub fn lchown(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    set_owner(crate::root::lookup_no_follow(None, path)?.node, uid, gid)
+}
+
+fn set_owner(node: VfsNodeRef, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    let attr = node.get_attr()?;
+    crate::perm::check_change(&attr, uid, gid)?;
+    node.set_owner(uid.unwrap_or(attr.uid()), gid.unwrap_or(attr.gid()))
+}
+
+/// Changes the access time and the modification time of a file or a
+/// directory, `None` means unchanged. The change time is set to the current
+/// time.
+///
+/// Only the owner of the file and root can do this.
+pub fn set_times(path: &str, atime: Option<Duration>, mtime: Option<Duration>) -> io::Result<()> {
+    set_node_times(crate::root::lookup(None, path)?.node, atime, mtime)
+}
+
+/// Changes the access time and the modification time of a file or a
+/// directory like [`set_times`], but does not follow symbolic links.
+pub fn lset_times(path: &str, atime: Option<Duration>, mtime: Option<Duration>) -> io::Result<()> {
+    set_node_times(
+        crate::root::lookup_no_follow(None, path)?.node,
+        atime,
+        mtime,
+    )
+}
+
+fn set_node_times(
+    node: VfsNodeRef,
+    atime: Option<Duration>,
+    mtime: Option<Duration>,
+) -> io::Result<()> {
+    crate::perm::check_set_times(&node.get_attr()?, false)?;
+    node.set_times(atime, mtime)
+}
+
+/// Sets all the timestamps of a file or a directory to the current time.
+///
+/// Anyone who has the write permission of the file can do this.
+pub fn touch(path: &str) -> io::Result<()> {
+    touch_node(crate::root::lookup(None, path)?.node)
+}
+
+/// Sets all the timestamps of a file or a directory to the current time like
+/// [`touch`], but does not follow symbolic links.
+pub fn ltouch(path: &str) -> io::Result<()> {
+    touch_node(crate::root::lookup_no_follow(None, path)?.node)
+}
+
+fn touch_node(node: VfsNodeRef) -> io::Result<()> {
+    crate::perm::check_set_times(&node.get_attr()?, true)?;
+    let now = crate::time::current_time();
+    node.set_times(Some(now), Some(now))
+}
+
+/// Sets the file mode creation mask of the current task, returns the previous
+/// mask.
+///
+/// The permission bits in the mask are cleared from the mode of newly created
+/// files and directories.
+pub fn umask(mask: u32) -> u32 {
+    crate::perm::set_current_umask(mask & 0o777)
 }
 
 /// Creates a new, empty directory at the provided path.
@@ -85,5 +178,63 @@ pub fn remove_file(path: &str) -> io::Result<()> {
 ///
 /// This only works then the new path is in the same mounted fs.
 pub fn rename(old: &str, new: &str) -> io::Result<()> {
-    crate::root::rename(old, new)
+    crate::root::rename(None, old, None, new)
+}
+
+/// Creates a new symbolic link on the filesystem.
+///
+/// The `link` path will be a symbolic link pointing to the `original` path.
+pub fn symlink(original: &str, link: &str) -> io::Result<()> {
+    crate::root::symlink(None, original, link)
+}
+
+/// Creates a new hard link on the filesystem.
+///
+/// The `link` path will be a link pointing to the `original` path. Both paths
+/// must be in the same mounted fs.
+pub fn hard_link(original: &str, link: &str) -> io::Result<()> {
+    crate::root::link(None, original, link)
+}
+
+/// Reads a symbolic link, returning the file that the link points to.
+pub fn read_link(path: &str) -> io::Result<String> {
+    crate::root::read_link(None, path)
+}
+
+/// Mounts the filesystem `fs` at the directory `path`.
+///
+/// The directory is created if it does not exist. Filesystems can also be
+/// mounted inside other mounted filesystems.
+pub fn mount(path: &str, fs: Arc<dyn VfsOps>) -> io::Result<()> {
+    crate::root::mount(path, fs)
+}
+
+/// Creates a new filesystem instance of type `fstype`, which can be mounted
+/// by [`mount`].
+///
+/// The supported types are `ramfs` (or `tmpfs`), whose `source` is ignored,
+/// and the filesystems on block devices: `vfat` (or `fat`) and `ext2` (or
+/// `ext3`, `ext4`), whose `source` is the name of the device or partition
+/// (e.g., `/dev/vda2`).
+pub fn create_filesystem(fstype: &str, source: &str) -> io::Result<Arc<dyn VfsOps>> {
+    crate::mounts::create_filesystem(fstype, source)
+}
+
+/// Unmounts the filesystem mounted at the directory `path`.
+///
+/// Returns [`ResourceBusy`](io::Error::ResourceBusy) if the filesystem is
+/// still in use.
+pub fn umount(path: &str) -> io::Result<()> {
+    crate::root::umount(path)
+}
+
+/// Writes all cached data of the filesystems back to the disks, like `sync` in
+/// Unix.
+pub fn sync() -> io::Result<()> {
+    crate::cache::flush_all().map_err(|_| io::Error::Io)
+}
+
+/// Returns the statistics of the block caches of all disks.
+pub fn block_cache_stats() -> crate::fops::CacheStats {
+    crate::cache::stats()
 }
@@ -0,0 +1,503 @@
+//! A size-bounded LRU block cache with write-back.
+
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use axdriver::prelude::*;
+use axsync::Mutex;
+
+/// The size of each cached block in bytes.
+pub const BLOCK_SIZE: usize = 512;
+
+/// The default number of blocks held by a [`BlockCache`] (512 KiB).
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+const NIL: usize = usize::MAX;
+
+/// All block caches that are alive, used to collect the statistics.
+static CACHES: Mutex<Vec<Weak<BlockCache>>> = Mutex::new(Vec::new());
+
+/// Statistics of block caches.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of block accesses served by the cache.
+    pub hits: u64,
+    /// Number of block accesses that missed the cache.
+    pub misses: u64,
+    /// Number of dirty blocks written back to the device.
+    pub writebacks: u64,
+}
+
+impl core::ops::AddAssign for CacheStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.hits += rhs.hits;
+        self.misses += rhs.misses;
+        self.writebacks += rhs.writebacks;
+    }
+}
+
+struct Slot {
+    block_id: u64,
+    dirty: bool,
+    prev: usize,
+    next: usize,
+    data: Box<[u8]>,
+}
+
+/// The cache of the sectors of the device, each sector holds one or more
+/// blocks.
+struct CacheInner {
+    dev: Box<dyn BlockDriverOps>,
+    sector_size: usize,
+    capacity: usize,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    map: BTreeMap<u64, usize>,
+    /// The most recently used slot.
+    head: usize,
+    /// The least recently used slot.
+    tail: usize,
+    stats: CacheStats,
+}
+
+impl CacheInner {
+    fn detach(&mut self, idx: usize) {
+        let (prev, next) = (self.slots[idx].prev, self.slots[idx].next);
+        match prev {
+            NIL => self.head = next,
+            _ => self.slots[prev].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            _ => self.slots[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, idx: usize) {
+        self.slots[idx].prev = NIL;
+        self.slots[idx].next = self.head;
+        match self.head {
+            NIL => self.tail = idx,
+            head => self.slots[head].prev = idx,
+        }
+        self.head = idx;
+    }
+
+    /// Finds a slot that can hold a new block, evicting the least recently
+    /// used block if the cache is full.
+    fn alloc_slot(&mut self) -> DevResult<usize> {
+        if let Some(idx) = self.free.pop() {
+            return Ok(idx);
+        }
+        if self.slots.len() < self.capacity {
+            self.slots.push(Slot {
+                block_id: 0,
+                dirty: false,
+                prev: NIL,
+                next: NIL,
+                data: alloc::vec![0; self.sector_size].into_boxed_slice(),
+            });
+            return Ok(self.slots.len() - 1);
+        }
+        let idx = self.tail;
+        self.write_back(idx)?;
+        self.detach(idx);
+        self.map.remove(&self.slots[idx].block_id);
+        Ok(idx)
+    }
+
+    fn write_back(&mut self, idx: usize) -> DevResult {
+        let slot = &mut self.slots[idx];
+        if slot.dirty {
+            self.dev.write_block(slot.block_id, &slot.data)?;
+            slot.dirty = false;
+            self.stats.writebacks += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the slot that holds the sector `block_id`. The sector is read
+    /// from the device on a miss, unless `fill` is false (it will be
+    /// overwritten).
+    fn get_slot(&mut self, block_id: u64, fill: bool) -> DevResult<usize> {
+        if let Some(&idx) = self.map.get(&block_id) {
+            self.stats.hits += 1;
+            self.detach(idx);
+            self.push_front(idx);
+            return Ok(idx);
+        }
+
+        self.stats.misses += 1;
+        let idx = self.alloc_slot()?;
+        if fill {
+            let data = &mut self.slots[idx].data;
+            if let Err(e) = self.dev.read_block(block_id, data) {
+                self.free.push(idx);
+                return Err(e);
+            }
+        }
+        self.slots[idx].block_id = block_id;
+        self.map.insert(block_id, idx);
+        self.push_front(idx);
+        Ok(idx)
+    }
+
+    fn flush(&mut self) -> DevResult {
+        for idx in 0..self.slots.len() {
+            self.write_back(idx)?;
+        }
+        self.dev.flush()
+    }
+}
+
+/// A block cache on a block device.
+///
+/// Blocks are kept in the cache until they are evicted in LRU order. Writes
+/// only modify the cached blocks and mark them dirty, the dirty blocks are
+/// written back to the device when they are evicted or [flushed].
+///
+/// The cache is accessed by blocks of [`BLOCK_SIZE`] bytes. If the sectors of
+/// the device are larger (e.g., 4 KiB), it caches whole sectors, each holding
+/// several consecutive blocks.
+///
+/// [flushed]: BlockCache::flush
+pub struct BlockCache {
+    inner: Mutex<CacheInner>,
+    /// The number of blocks in each sector.
+    blocks_per_sector: u64,
+    num_blocks: u64,
+}
+
+impl BlockCache {
+    /// Creates a new block cache on the device that can hold at most
+    /// `capacity` blocks (at least one sector).
+    ///
+    /// Returns [`DevError::Unsupported`] if the sector size of the device is
+    /// not a multiple of [`BLOCK_SIZE`].
+    pub fn new(dev: Box<dyn BlockDriverOps>, capacity: usize) -> DevResult<Arc<Self>> {
+        let sector_size = dev.block_size();
+        if sector_size == 0 || sector_size % BLOCK_SIZE != 0 {
+            warn!("unsupported sector size of block device: {}", sector_size);
+            return Err(DevError::Unsupported);
+        }
+        let blocks_per_sector = sector_size / BLOCK_SIZE;
+        let num_blocks = dev
+            .num_blocks()
+            .checked_mul(blocks_per_sector as u64)
+            .ok_or(DevError::Unsupported)?;
+        let cache = Arc::new(Self {
+            blocks_per_sector: blocks_per_sector as u64,
+            num_blocks,
+            inner: Mutex::new(CacheInner {
+                dev,
+                sector_size,
+                capacity: (capacity / blocks_per_sector).max(1),
+                slots: Vec::new(),
+                free: Vec::new(),
+                map: BTreeMap::new(),
+                head: NIL,
+                tail: NIL,
+                stats: CacheStats::default(),
+            }),
+        });
+        let mut caches = CACHES.lock();
+        caches.retain(|c| c.strong_count() > 0);
+        caches.push(Arc::downgrade(&cache));
+        Ok(cache)
+    }
+
+    /// The number of blocks in the underlying device.
+    pub fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    /// Returns the sector holding the block `block_id`, and the offset of
+    /// `offset` within the block in the sector.
+    fn locate(&self, block_id: u64, offset: usize) -> (u64, usize) {
+        let sector = block_id / self.blocks_per_sector;
+        let index = (block_id % self.blocks_per_sector) as usize;
+        (sector, index * BLOCK_SIZE + offset)
+    }
+
+    /// Reads data from the block `block_id` starting at `offset` within the
+    /// block. `offset + buf.len()` must not exceed the block size.
+    pub fn read(&self, block_id: u64, offset: usize, buf: &mut [u8]) -> DevResult {
+        let (sector, offset) = self.locate(block_id, offset);
+        let mut inner = self.inner.lock();
+        let idx = inner.get_slot(sector, true)?;
+        buf.copy_from_slice(&inner.slots[idx].data[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    /// Writes data to the block `block_id` starting at `offset` within the
+    /// block. `offset + buf.len()` must not exceed the block size.
+    ///
+    /// The data is not written to the device until the block is evicted or
+    /// flushed.
+    pub fn write(&self, block_id: u64, offset: usize, buf: &[u8]) -> DevResult {
+        let (sector, offset) = self.locate(block_id, offset);
+        let mut inner = self.inner.lock();
+        let whole_sector = offset == 0 && buf.len() == inner.sector_size;
+        let idx = inner.get_slot(sector, !whole_sector)?;
+        let slot = &mut inner.slots[idx];
+        slot.data[offset..offset + buf.len()].copy_from_slice(buf);
+        slot.dirty = true;
+        Ok(())
+    }
+
+    /// Writes all dirty blocks back to the device and flushes the device.
+    pub fn flush(&self) -> DevResult {
+        self.inner.lock().flush()
+    }
+
+    /// Returns the statistics of the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.inner.lock().stats
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        if self.inner.get_mut().flush().is_err() {
+            warn!("failed to write back the block cache");
+        }
+    }
+}
+
+/// Writes the dirty blocks of all block caches back to their devices.
+///
+/// All caches are flushed even if some of them fail, and the first error is
+/// returned.
+pub fn flush_all() -> DevResult {
+    let caches: Vec<_> = CACHES.lock().iter().filter_map(Weak::upgrade).collect();
+    let mut res = Ok(());
+    for cache in caches {
+        if let Err(e) = cache.flush() {
+            warn!("failed to write back the block cache: {:?}", e);
+            res = res.and(Err(e));
+        }
+    }
+    res
+}
+
+/// Returns the sum of the statistics of all block caches.
+pub fn stats() -> CacheStats {
+    let mut stats = CacheStats::default();
+    for cache in CACHES.lock().iter().filter_map(Weak::upgrade) {
+        stats += cache.stats();
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex as StdMutex, Once};
+
+    use super::*;
+
+    static INIT: Once = Once::new();
+    static SERIAL: StdMutex<()> = StdMutex::new(());
+
+    /// What the device has done, shared with the test.
+    #[derive(Default)]
+    struct DevLog {
+        data: Vec<u8>,
+        reads: Vec<u64>,
+        writes: Vec<u64>,
+        flushes: usize,
+    }
+
+    /// A disk with the given sector size.
+    struct MockDisk(Arc<StdMutex<DevLog>>, usize);
+
+    impl BaseDriverOps for MockDisk {
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Block
+        }
+
+        fn device_name(&self) -> &str {
+            "mock"
+        }
+    }
+
+    impl BlockDriverOps for MockDisk {
+        fn num_blocks(&self) -> u64 {
+            (self.0.lock().unwrap().data.len() / self.1) as u64
+        }
+
+        fn block_size(&self) -> usize {
+            self.1
+        }
+
+        fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+            let mut log = self.0.lock().unwrap();
+            let offset = block_id as usize * self.1;
+            buf.copy_from_slice(&log.data[offset..offset + self.1]);
+            log.reads.push(block_id);
+            Ok(())
+        }
+
+        fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+            let mut log = self.0.lock().unwrap();
+            let offset = block_id as usize * self.1;
+            log.data[offset..offset + self.1].copy_from_slice(buf);
+            log.writes.push(block_id);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> DevResult {
+            self.0.lock().unwrap().flushes += 1;
+            Ok(())
+        }
+    }
+
+    /// Creates a cache of `capacity` blocks on a 16-block device, where each
+    /// byte of block `i` is `i`.
+    fn new_cache(capacity: usize) -> (Arc<BlockCache>, Arc<StdMutex<DevLog>>) {
+        new_cache_with_sector_size(capacity, BLOCK_SIZE).unwrap()
+    }
+
+    fn new_cache_with_sector_size(
+        capacity: usize,
+        sector_size: usize,
+    ) -> DevResult<(Arc<BlockCache>, Arc<StdMutex<DevLog>>)> {
+        INIT.call_once(axtask::init_scheduler);
+        let data = (0..16u8).flat_map(|i| [i; BLOCK_SIZE]).collect();
+        let log = Arc::new(StdMutex::new(DevLog {
+            data,
+            ..Default::default()
+        }));
+        let cache = BlockCache::new(Box::new(MockDisk(log.clone(), sector_size)), capacity)?;
+        Ok((cache, log))
+    }
+
+    fn read_byte(cache: &BlockCache, block_id: u64) -> u8 {
+        let mut buf = [0; 1];
+        cache.read(block_id, 0, &mut buf).unwrap();
+        buf[0]
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let _lock = SERIAL.lock();
+        let (cache, log) = new_cache(2);
+
+        assert_eq!(read_byte(&cache, 0), 0);
+        assert_eq!(read_byte(&cache, 1), 1);
+        assert_eq!(read_byte(&cache, 0), 0); // hit, block 1 is now the LRU
+        assert_eq!(read_byte(&cache, 2), 2); // evicts block 1
+        assert_eq!(read_byte(&cache, 0), 0); // hit
+        assert_eq!(read_byte(&cache, 1), 1); // evicts block 2
+
+        assert_eq!(log.lock().unwrap().reads, [0, 1, 2, 1]);
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.writebacks), (2, 4, 0));
+    }
+
+    #[test]
+    fn test_write_back_on_eviction() {
+        let _lock = SERIAL.lock();
+        let (cache, log) = new_cache(2);
+
+        cache.write(0, 4, &[0xaa; 4]).unwrap();
+        cache.write(1, 4, &[0xbb; 4]).unwrap();
+        assert!(log.lock().unwrap().writes.is_empty());
+        assert_eq!(log.lock().unwrap().data[4], 0);
+
+        read_byte(&cache, 2); // evicts the dirty block 0
+        {
+            let log = log.lock().unwrap();
+            assert_eq!(log.writes, [0]);
+            assert_eq!(log.data[..8], [0, 0, 0, 0, 0xaa, 0xaa, 0xaa, 0xaa]);
+        }
+
+        read_byte(&cache, 3); // evicts the dirty block 1
+        read_byte(&cache, 4); // evicts the clean block 2
+        let log = log.lock().unwrap();
+        assert_eq!(log.writes, [0, 1]);
+        assert_eq!(log.data[BLOCK_SIZE + 4], 0xbb);
+        assert_eq!(cache.stats().writebacks, 2);
+    }
+
+    #[test]
+    fn test_flush() {
+        let _lock = SERIAL.lock();
+        let (cache, log) = new_cache(4);
+
+        cache.write(3, 0, &[0xcc; 2]).unwrap();
+        cache.write(5, 0, &[0xdd; 2]).unwrap();
+        read_byte(&cache, 7);
+        cache.flush().unwrap();
+        {
+            let mut log = log.lock().unwrap();
+            log.writes.sort();
+            assert_eq!(log.writes, [3, 5]);
+            assert_eq!(log.flushes, 1);
+            assert_eq!(log.data[3 * BLOCK_SIZE], 0xcc);
+            assert_eq!(log.data[5 * BLOCK_SIZE], 0xdd);
+        }
+
+        // The blocks are clean now, and still cached
+        cache.flush().unwrap();
+        assert_eq!(read_byte(&cache, 3), 0xcc);
+        let log = log.lock().unwrap();
+        assert_eq!(log.writes.len(), 2);
+        assert_eq!(log.flushes, 2);
+        assert_eq!(log.reads, [3, 5, 7]);
+    }
+
+    #[test]
+    fn test_whole_block_write() {
+        let _lock = SERIAL.lock();
+        let (cache, log) = new_cache(4);
+
+        cache.write(5, 0, &[0xee; BLOCK_SIZE]).unwrap();
+        assert!(log.lock().unwrap().reads.is_empty());
+        cache.write(6, 1, &[0xee; BLOCK_SIZE - 1]).unwrap();
+        assert_eq!(log.lock().unwrap().reads, [6]);
+
+        assert_eq!(read_byte(&cache, 5), 0xee);
+        assert_eq!(read_byte(&cache, 6), 6);
+        assert_eq!(log.lock().unwrap().reads, [6]);
+
+        drop(cache); // writes back on drop
+        let log = log.lock().unwrap();
+        assert_eq!(log.data[5 * BLOCK_SIZE..6 * BLOCK_SIZE], [0xee; BLOCK_SIZE]);
+        assert_eq!(log.data[6 * BLOCK_SIZE + 1], 0xee);
+    }
+
+    #[test]
+    fn test_large_sectors() {
+        let _lock = SERIAL.lock();
+        // 4 sectors of 8 blocks, 1 sector can be cached
+        let (cache, log) = new_cache_with_sector_size(8, 8 * BLOCK_SIZE).unwrap();
+        assert_eq!(cache.num_blocks(), 16);
+
+        assert_eq!(read_byte(&cache, 9), 9);
+        assert_eq!(read_byte(&cache, 15), 15); // in the same sector
+        cache.write(10, 1, &[0xaa; 2]).unwrap();
+        assert_eq!(read_byte(&cache, 3), 3); // evicts the dirty sector 1
+        {
+            let log = log.lock().unwrap();
+            assert_eq!(log.reads, [1, 0]);
+            assert_eq!(log.writes, [1]);
+            assert_eq!(
+                log.data[10 * BLOCK_SIZE..10 * BLOCK_SIZE + 4],
+                [10, 0xaa, 0xaa, 10]
+            );
+        }
+
+        // writing a whole block does not cover the whole sector
+        cache.write(12, 0, &[0xbb; BLOCK_SIZE]).unwrap();
+        assert_eq!(log.lock().unwrap().reads, [1, 0, 1]);
+        assert_eq!(read_byte(&cache, 13), 13);
+    }
+
+    #[test]
+    fn test_unsupported_sector_size() {
+        let _lock = SERIAL.lock();
+        assert!(matches!(
+            new_cache_with_sector_size(8, BLOCK_SIZE + 1),
+            Err(DevError::Unsupported)
+        ));
+    }
+}
@@ -1,28 +1,79 @@
+use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
 use axdriver::prelude::*;
+use axerrno::{ax_err, ax_err_type, AxResult};
+use axsync::Mutex;
 
-const BLOCK_SIZE: usize = 512;
+#[cfg(feature = "devfs")]
+use alloc::sync::Weak;
+#[cfg(feature = "devfs")]
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
 
-/// A disk device with a cursor.
+use crate::cache::{BlockCache, BLOCK_SIZE, DEFAULT_CAPACITY};
+use crate::partition::{parse_partitions, select_partition};
+
+/// Disks registered by device names (e.g., `vda` and its partition `vda1`).
+static DISKS: Mutex<BTreeMap<String, Disk>> = Mutex::new(BTreeMap::new());
+
+/// A disk device with a cursor, on any block device that implements
+/// [`BlockDriverOps`].
+///
+/// All accesses go through a shared [`BlockCache`], so the written data may
+/// not reach the device until [`Disk::flush`] is called. Cloning a disk
+/// creates a new cursor on the same cache.
+///
+/// A disk can also be a partition of a block device, which accesses a range of
+/// blocks through the cache of the whole device.
+#[derive(Clone)]
 pub struct Disk {
     block_id: u64,
     offset: usize,
-    dev: AxBlockDevice,
+    view: Arc<DiskView>,
+}
+
+/// The blocks on a cached block device that a [`Disk`] accesses.
+struct DiskView {
+    cache: Arc<BlockCache>,
+    start_block: u64,
+    num_blocks: u64,
 }
 
 impl Disk {
     /// Create a new disk.
-    pub fn new(dev: AxBlockDevice) -> Self {
-        assert_eq!(BLOCK_SIZE, dev.block_size());
+    ///
+    /// Fails if the sector size of the device is not supported (see
+    /// [`BlockCache::new`]).
+    pub fn new(dev: impl BlockDriverOps + 'static) -> DevResult<Self> {
+        Self::with_cache_capacity(dev, DEFAULT_CAPACITY)
+    }
+
+    /// Create a new disk whose block cache holds at most `capacity` blocks.
+    pub fn with_cache_capacity(
+        dev: impl BlockDriverOps + 'static,
+        capacity: usize,
+    ) -> DevResult<Self> {
+        let cache = BlockCache::new(Box::new(dev), capacity)?;
+        let num_blocks = cache.num_blocks();
+        Ok(Self::on_cache(cache, 0, num_blocks))
+    }
+
+    /// Create a disk on the `num_blocks` blocks starting at `start_block` of
+    /// the cached device.
+    fn on_cache(cache: Arc<BlockCache>, start_block: u64, num_blocks: u64) -> Self {
         Self {
             block_id: 0,
             offset: 0,
-            dev,
+            view: Arc::new(DiskView {
+                cache,
+                start_block,
+                num_blocks,
+            }),
         }
     }
 
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.view.num_blocks * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -38,55 +89,212 @@ impl Disk {
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev
-                .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
-            self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            buf[..count].copy_from_slice(&data[start..start + count]);
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
-        };
-        Ok(read_size)
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        self.view
+            .cache
+            .read(self.device_block()?, self.offset, &mut buf[..count])?;
+        self.advance(count);
+        Ok(count)
     }
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
-            // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+        let count = buf.len().min(BLOCK_SIZE - self.offset);
+        self.view
+            .cache
+            .write(self.device_block()?, self.offset, &buf[..count])?;
+        self.advance(count);
+        Ok(count)
+    }
+
+    /// Write all cached dirty blocks back to the device.
+    pub fn flush(&self) -> DevResult {
+        self.view.cache.flush()
+    }
+
+    /// The block on the device that the cursor is in.
+    fn device_block(&self) -> DevResult<u64> {
+        if self.block_id >= self.view.num_blocks {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(self.view.start_block + self.block_id)
+    }
+
+    /// Whether the disk is in use by others than `DISKS`.
+    fn in_use(&self) -> bool {
+        Arc::strong_count(&self.view) > 1
+    }
+
+    /// Whether the two disks share any block on the same device, e.g., a
+    /// partition and the whole device.
+    fn overlaps(&self, other: &Disk) -> bool {
+        let (a, b) = (&self.view, &other.view);
+        Arc::ptr_eq(&a.cache, &b.cache)
+            && a.start_block < b.start_block + b.num_blocks
+            && b.start_block < a.start_block + a.num_blocks
+    }
+
+    fn advance(&mut self, count: usize) {
+        self.offset += count;
+        if self.offset >= BLOCK_SIZE {
             self.block_id += 1;
-            BLOCK_SIZE
-        } else {
-            // partial block
-            let mut data = [0u8; BLOCK_SIZE];
-            let start = self.offset;
-            let count = buf.len().min(BLOCK_SIZE - self.offset);
-
-            self.dev.read_block(self.block_id, &mut data)?;
-            data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
-
-            self.offset += count;
-            if self.offset >= BLOCK_SIZE {
-                self.block_id += 1;
-                self.offset -= BLOCK_SIZE;
-            }
-            count
+            self.offset -= BLOCK_SIZE;
+        }
+    }
+}
+
+/// Registers the block device `dev` and its partitions by names.
+///
+/// The partitions are named by appending the partition numbers to `name`, and
+/// share the block cache of the whole device. If
+/// `root_part` is given, returns the disk to hold the root filesystem, which is
+/// the partition selected by `root_part` (see [`select_partition`]) if the
+/// device is partitioned, or the whole device otherwise.
+pub(crate) fn register_block_device(
+    name: &str,
+    mut dev: impl BlockDriverOps + 'static,
+    root_part: Option<&str>,
+) -> AxResult<Option<Disk>> {
+    let mut disks = DISKS.lock();
+    if disks.contains_key(name) {
+        return ax_err!(AlreadyExists, "block device already exists");
+    }
+    let parts = if dev.block_size() == BLOCK_SIZE {
+        parse_partitions(&mut dev).unwrap_or_else(|e| {
+            warn!("failed to parse the partition table of {}: {:?}", name, e);
+            Vec::new()
+        })
+    } else {
+        warn!(
+            "{} has {}-byte sectors, its partition table is ignored",
+            name,
+            dev.block_size()
+        );
+        Vec::new()
+    };
+    let whole = Disk::new(dev).map_err(|_| ax_err_type!(Unsupported, "unsupported sector size"))?;
+    disks.insert(name.into(), whole.clone());
+    if parts.is_empty() {
+        return Ok(root_part.map(|_| whole));
+    }
+
+    let root = match root_part {
+        Some(spec) => match select_partition(&parts, spec) {
+            Some(part) => Some(part.index),
+            None => return ax_err!(NotFound, "root partition not found"),
+        },
+        None => None,
+    };
+    let mut root_disk = None;
+    for part in parts {
+        let part_name = format!("{}{}", name, part.index);
+        info!(
+            "  partition {}: start = {}, blocks = {}, label = {:?}",
+            part_name, part.start_block, part.num_blocks, part.label
+        );
+        let disk = Disk::on_cache(whole.view.cache.clone(), part.start_block, part.num_blocks);
+        if Some(part.index) == root {
+            root_disk = Some(disk.clone());
+        }
+        disks.insert(part_name, disk);
+    }
+    Ok(root_disk)
+}
+
+/// Finds the registered disk by the device name.
+///
+/// Returns [`ResourceBusy`](axerrno::AxError::ResourceBusy) if the disk, or
+/// any disk overlapping it (i.e., the whole device of a partition, or a
+/// partition of the whole device), is already in use by a filesystem.
+#[allow(dead_code)] // unused if no filesystem on block devices is enabled
+pub(crate) fn find_disk(name: &str) -> AxResult<Disk> {
+    let disks = DISKS.lock();
+    let disk = disks
+        .get(name)
+        .ok_or_else(|| ax_err_type!(NotFound, "block device not found"))?;
+    if disks.values().any(|d| d.overlaps(disk) && d.in_use()) {
+        return ax_err!(ResourceBusy, "block device is in use");
+    }
+    Ok(disk.clone())
+}
+
+/// The raw block device in `/dev` (e.g., `/dev/vda`), which reads and writes
+/// the registered disk at any byte offset.
+///
+/// It does not keep the disk in use, so the disk can still be mounted by
+/// [`find_disk`]. If a filesystem is mounted on the disk, they share the same
+/// block cache.
+#[cfg(feature = "devfs")]
+pub(crate) struct DiskNode(Weak<DiskView>);
+
+#[cfg(feature = "devfs")]
+impl DiskNode {
+    fn disk_at(&self, pos: u64) -> VfsResult<Disk> {
+        let view = self.0.upgrade().ok_or(VfsError::NotFound)?;
+        let mut disk = Disk {
+            block_id: 0,
+            offset: 0,
+            view,
         };
-        Ok(write_size)
+        disk.set_position(pos);
+        Ok(disk)
     }
 }
+
+#[cfg(feature = "devfs")]
+impl VfsNodeOps for DiskNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = self.disk_at(0)?.size();
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::BlockDevice,
+            size,
+            size / BLOCK_SIZE as u64,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut disk = self.disk_at(offset)?;
+        let len = disk.size().saturating_sub(offset).min(buf.len() as u64) as usize;
+        let mut pos = 0;
+        while pos < len {
+            pos += disk
+                .read_one(&mut buf[pos..len])
+                .map_err(|_| VfsError::Io)?;
+        }
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut disk = self.disk_at(offset)?;
+        let len = disk.size().saturating_sub(offset).min(buf.len() as u64) as usize;
+        if len == 0 && !buf.is_empty() {
+            return Err(VfsError::StorageFull);
+        }
+        let mut pos = 0;
+        while pos < len {
+            pos += disk.write_one(&buf[pos..len]).map_err(|_| VfsError::Io)?;
+        }
+        Ok(len)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.disk_at(0)?.flush().map_err(|_| VfsError::Io)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(()) // opened with `O_TRUNC`
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Returns the raw device nodes of all registered disks with their names.
+#[cfg(feature = "devfs")]
+pub(crate) fn disk_nodes() -> Vec<(String, DiskNode)> {
+    DISKS
+        .lock()
+        .iter()
+        .map(|(name, disk)| (name.clone(), DiskNode(Arc::downgrade(&disk.view))))
+        .collect()
+}
@@ -0,0 +1,24 @@
+//! The device filesystem mounted on `/dev`.
+//!
+//! The filesystem itself provides `/dev/null`, `/dev/zero` and the raw nodes
+//! of the registered block devices (e.g., `/dev/vda` and `/dev/vda1`), other
+//! modules add the devices they own (e.g., the console and the framebuffer)
+//! to the directory returned by [`root`] after the filesystems are
+//! initialized.
+
+use alloc::sync::Arc;
+use axsync::Mutex;
+
+pub use axfs_devfs::{ConsoleDev, DirNode, FramebufferDev, NullDev, RandomDev, ZeroDev};
+
+static DEV_ROOT: Mutex<Option<Arc<DirNode>>> = Mutex::new(None);
+
+pub(crate) fn init(root: Arc<DirNode>) {
+    *DEV_ROOT.lock() = Some(root);
+}
+
+/// Returns the root directory of the devfs, or `None` if the filesystems are
+/// not initialized.
+pub fn root() -> Option<Arc<DirNode>> {
+    DEV_ROOT.lock().clone()
+}
@@ -1,10 +1,21 @@
 //! Low-level filesystem operations.
 
+use alloc::{string::String, sync::Arc};
 use axerrno::{ax_err, ax_err_type, AxResult};
 use axfs_vfs::{VfsError, VfsNodeRef};
 use axio::SeekFrom;
 use capability::{Cap, WithCap};
 use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use crate::perm::{check_change, check_set_times, current_credentials, node_cap};
+use crate::root::{MountPoint, NodeLocation};
+
+pub use crate::cache::CacheStats;
+pub use crate::lock::{FileLocks, LockOwner, LockType, RangeLock};
+pub use crate::perm::{Credentials, CredentialsIf};
+pub use crate::time::ClockIf;
 
 #[cfg(feature = "myfs")]
 pub use crate::dev::Disk;
@@ -20,11 +31,27 @@ pub type FileAttr = axfs_vfs::VfsNodeAttr;
 /// Alias of [`axfs_vfs::VfsNodePerm`].
 pub type FilePerm = axfs_vfs::VfsNodePerm;
 
+/// The ID of the next opened file, used as the owner of its locks.
+static NEXT_FILE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Gets the attributes of `node`, with the inode number from
+/// [`VfsNodeOps::ino`], or the address of the node if the filesystem cannot
+/// identify its nodes.
+///
+/// [`VfsNodeOps::ino`]: axfs_vfs::VfsNodeOps::ino
+pub(crate) fn node_attr(node: &VfsNodeRef) -> AxResult<FileAttr> {
+    let mut attr = node.get_attr()?;
+    attr.set_ino(node.ino().unwrap_or(Arc::as_ptr(node) as *const () as u64));
+    Ok(attr)
+}
+
 /// An opened file object, with open permissions and a cursor.
 pub struct File {
     node: WithCap<VfsNodeRef>,
     is_append: bool,
     offset: u64,
+    locks: FileLocks,
+    _mount: Arc<MountPoint>,
 }
 
 /// An opened directory object, with open permissions and a cursor for
@@ -32,6 +59,8 @@ pub struct File {
 pub struct Directory {
     node: WithCap<VfsNodeRef>,
     entry_idx: usize,
+    path: String,
+    _mount: Arc<MountPoint>,
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -46,7 +75,7 @@ pub struct OpenOptions {
     create_new: bool,
     // system-specific
     _custom_flags: i32,
-    _mode: u32,
+    mode: u32,
 }
 
 impl OpenOptions {
@@ -62,7 +91,7 @@ impl OpenOptions {
             create_new: false,
             // system-specific
             _custom_flags: 0,
-            _mode: 0o666,
+            mode: 0o666,
         }
     }
     /// Sets the option for read access.
@@ -89,6 +118,11 @@ impl OpenOptions {
     pub fn create_new(&mut self, create_new: bool) {
         self.create_new = create_new;
     }
+    /// Sets the permission mode of the newly created file, which is masked by
+    /// the umask of the current task.
+    pub fn mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
 
     const fn is_valid(&self) -> bool {
         if !self.read && !self.write && !self.append {
@@ -112,29 +146,29 @@ impl OpenOptions {
 }
 
 impl File {
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_at(dir: Option<&str>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             return ax_err!(InvalidInput);
         }
 
-        let node_option = crate::root::lookup(dir, path);
-        let node = if opts.create || opts.create_new {
-            match node_option {
-                Ok(node) => {
+        let loc_option = crate::root::lookup(dir, path);
+        let (NodeLocation { node, path, mount }, created) = if opts.create || opts.create_new {
+            match loc_option {
+                Ok(loc) => {
                     // already exists
                     if opts.create_new {
                         return ax_err!(AlreadyExists);
                     }
-                    node
+                    (loc, false)
                 }
                 // not exists, create new
-                Err(VfsError::NotFound) => crate::root::create_file(dir, path)?,
+                Err(VfsError::NotFound) => (crate::root::create_file(dir, path, opts.mode)?, true),
                 Err(e) => return Err(e),
             }
         } else {
             // just open the existing
-            node_option?
+            (loc_option?, false)
         };
 
         let attr = node.get_attr()?;
@@ -143,8 +177,9 @@ impl File {
         {
             return ax_err!(IsADirectory);
         }
+        // the permission of a newly created file does not apply to this open
         let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        if !created && !node_cap(&current_credentials(), &attr).contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
 
@@ -152,10 +187,19 @@ impl File {
         if opts.truncate {
             node.truncate(0)?;
         }
+        let locks = FileLocks::new(
+            path,
+            &node,
+            mount.fs_id(),
+            NEXT_FILE_ID.fetch_add(1, Ordering::Relaxed),
+            access_cap,
+        );
         Ok(Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
+            locks,
+            _mount: mount,
         })
     }
 
@@ -222,6 +266,15 @@ impl File {
         Ok(())
     }
 
+    /// Synchronizes the data and metadata of the file to the underlying
+    /// device, like [`flush`](Self::flush) but does not require the file to
+    /// be opened for writing, as `fsync` in Unix.
+    pub fn sync_all(&self) -> AxResult {
+        // Safety: nothing of the file is modified by synchronizing it
+        unsafe { self.node.access_unchecked() }.fsync()?;
+        Ok(())
+    }
+
     /// Sets the cursor of the file to the specified offset. Returns the new
     /// position after the seek.
     pub fn seek(&mut self, pos: SeekFrom) -> AxResult<u64> {
@@ -238,12 +291,73 @@ impl File {
 
     /// Gets the file attributes.
     pub fn get_attr(&self) -> AxResult<FileAttr> {
-        self.node.access(Cap::empty())?.get_attr()
+        node_attr(self.node.access(Cap::empty())?)
+    }
+
+    /// Changes the permission of the file.
+    ///
+    /// Only the owner of the file and root can do this.
+    pub fn set_perm(&self, perm: FilePerm) -> AxResult {
+        let node = self.node.access(Cap::empty())?;
+        check_change(&node.get_attr()?, None, None)?;
+        node.set_perm(perm)
+    }
+
+    /// Changes the owner and the group of the file, `None` means unchanged.
+    ///
+    /// Only root can change the owner, but the owner of the file can change
+    /// the group to its own group.
+    pub fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+        let node = self.node.access(Cap::empty())?;
+        let attr = node.get_attr()?;
+        check_change(&attr, uid, gid)?;
+        node.set_owner(uid.unwrap_or(attr.uid()), gid.unwrap_or(attr.gid()))
+    }
+
+    /// Changes the access time and the modification time of the file, `None`
+    /// means unchanged. The change time is set to the current time.
+    ///
+    /// Only the owner of the file and root can do this.
+    pub fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> AxResult {
+        let node = self.node.access(Cap::empty())?;
+        check_set_times(&node.get_attr()?, false)?;
+        node.set_times(atime, mtime)
+    }
+
+    /// Sets all the timestamps of the file to the current time.
+    ///
+    /// Anyone who has the write permission of the file can do this.
+    pub fn touch(&self) -> AxResult {
+        let node = self.node.access(Cap::empty())?;
+        check_set_times(&node.get_attr()?, true)?;
+        let now = crate::time::current_time();
+        node.set_times(Some(now), Some(now))
+    }
+
+    /// Returns the absolute path of the file when it was opened.
+    pub fn path(&self) -> &str {
+        self.locks.path()
+    }
+
+    /// Returns whether the file is opened for reading.
+    pub const fn is_readable(&self) -> bool {
+        self.node.can_access(Cap::READ)
+    }
+
+    /// Returns whether the file is opened for writing.
+    pub const fn is_writable(&self) -> bool {
+        self.node.can_access(Cap::WRITE)
+    }
+
+    /// Returns the advisory locks of the file, which are released when the
+    /// file is dropped.
+    pub const fn locks(&self) -> &FileLocks {
+        &self.locks
     }
 }
 
 impl Directory {
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(dir: Option<&str>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -252,28 +366,34 @@ impl Directory {
             return ax_err!(InvalidInput);
         }
 
-        let node = crate::root::lookup(dir, path)?;
+        let NodeLocation { node, path, mount } = crate::root::lookup(dir, path)?;
         let attr = node.get_attr()?;
         if !attr.is_dir() {
             return ax_err!(NotADirectory);
         }
-        let access_cap = opts.into();
-        if !perm_to_cap(attr.perm()).contains(access_cap) {
+        let mut access_cap = opts.into();
+        let cap = node_cap(&current_credentials(), &attr);
+        if !cap.contains(access_cap) {
             return ax_err!(PermissionDenied);
         }
+        // searchable directories can be used to open the paths relative to it
+        access_cap |= cap & Cap::EXECUTE;
 
         node.open()?;
         Ok(Self {
             node: WithCap::new(node, access_cap),
             entry_idx: 0,
+            path,
+            _mount: mount,
         })
     }
 
-    fn access_at(&self, path: &str) -> AxResult<Option<&VfsNodeRef>> {
+    fn access_at(&self, path: &str) -> AxResult<Option<&str>> {
         if path.starts_with('/') {
             Ok(None)
         } else {
-            Ok(Some(self.node.access(Cap::EXECUTE)?))
+            self.node.access(Cap::EXECUTE)?;
+            Ok(Some(&self.path))
         }
     }
 
@@ -297,12 +417,18 @@ impl Directory {
 
     /// Creates an empty file at the path relative to this directory.
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
-        crate::root::create_file(self.access_at(path)?, path)
+        crate::root::create_file(self.access_at(path)?, path, 0o666).map(|loc| loc.node)
     }
 
     /// Creates an empty directory at the path relative to this directory.
     pub fn create_dir(&self, path: &str) -> AxResult {
-        crate::root::create_dir(self.access_at(path)?, path)
+        self.create_dir_with_mode(path, 0o777)
+    }
+
+    /// Creates an empty directory at the path relative to this directory, with
+    /// the permission bits of `mode` masked by the umask.
+    pub fn create_dir_with_mode(&self, path: &str, mode: u32) -> AxResult {
+        crate::root::create_dir(self.access_at(path)?, path, mode)
     }
 
     /// Removes a file at the path relative to this directory.
@@ -315,31 +441,105 @@ impl Directory {
         crate::root::remove_dir(self.access_at(path)?, path)
     }
 
+    /// Gets the attributes of the file at the path relative to this directory.
+    ///
+    /// If `follow_symlinks` is false and the path is a symbolic link, returns
+    /// the attributes of the link itself.
+    pub fn get_attr_at(&self, path: &str, follow_symlinks: bool) -> AxResult<FileAttr> {
+        node_attr(&self.lookup_at(path, follow_symlinks)?)
+    }
+
+    /// Changes the access time and the modification time of the file at the
+    /// path relative to this directory like [`File::set_times`].
+    ///
+    /// If `follow_symlinks` is false and the path is a symbolic link, changes
+    /// the times of the link itself.
+    pub fn set_times_at(
+        &self,
+        path: &str,
+        atime: Option<Duration>,
+        mtime: Option<Duration>,
+        follow_symlinks: bool,
+    ) -> AxResult {
+        let node = self.lookup_at(path, follow_symlinks)?;
+        check_set_times(&node.get_attr()?, false)?;
+        node.set_times(atime, mtime)
+    }
+
+    /// Sets all the timestamps of the file at the path relative to this
+    /// directory to the current time like [`File::touch`].
+    ///
+    /// If `follow_symlinks` is false and the path is a symbolic link, changes
+    /// the times of the link itself.
+    pub fn touch_at(&self, path: &str, follow_symlinks: bool) -> AxResult {
+        let node = self.lookup_at(path, follow_symlinks)?;
+        check_set_times(&node.get_attr()?, true)?;
+        let now = crate::time::current_time();
+        node.set_times(Some(now), Some(now))
+    }
+
+    fn lookup_at(&self, path: &str, follow_symlinks: bool) -> AxResult<VfsNodeRef> {
+        let dir = self.access_at(path)?;
+        let loc = if follow_symlinks {
+            crate::root::lookup(dir, path)?
+        } else {
+            crate::root::lookup_no_follow(dir, path)?
+        };
+        Ok(loc.node)
+    }
+
+    /// Gets the attributes of the directory.
+    pub fn get_attr(&self) -> AxResult<FileAttr> {
+        node_attr(self.node.access(Cap::empty())?)
+    }
+
     /// Reads directory entries starts from the current position into the
     /// given buffer. Returns the number of entries read.
     ///
     /// After the read, the cursor will be advanced by the number of entries
     /// read.
     pub fn read_dir(&mut self, dirents: &mut [DirEntry]) -> AxResult<usize> {
-        let n = self
-            .node
-            .access(Cap::READ)?
-            .read_dir(self.entry_idx, dirents)?;
+        let n = self.read_dir_at(self.entry_idx, dirents)?;
         self.entry_idx += n;
         Ok(n)
     }
 
-    /// Rename a file or directory to a new name.
+    /// Reads directory entries starts from the entry at `start_idx` into the
+    /// given buffer, without updating the cursor. Returns the number of entries
+    /// read.
+    pub fn read_dir_at(&self, start_idx: usize, dirents: &mut [DirEntry]) -> AxResult<usize> {
+        self.node.access(Cap::READ)?.read_dir(start_idx, dirents)
+    }
+
+    /// Rename a file or directory to a new name, both paths are relative to
+    /// this directory.
     /// Delete the original file if `old` already exists.
     ///
     /// This only works then the new path is in the same mounted fs.
     pub fn rename(&self, old: &str, new: &str) -> AxResult {
-        crate::root::rename(old, new)
+        Self::rename_at(Some(self), old, Some(self), new)
+    }
+
+    /// Rename `old` relative to `old_dir` to `new` relative to `new_dir`, the
+    /// paths are relative to the current directory if the directories are
+    /// `None`.
+    ///
+    /// This only works then the new path is in the same mounted fs.
+    pub fn rename_at(
+        old_dir: Option<&Self>,
+        old: &str,
+        new_dir: Option<&Self>,
+        new: &str,
+    ) -> AxResult {
+        let old_dir = old_dir.map(|dir| dir.access_at(old)).transpose()?;
+        let new_dir = new_dir.map(|dir| dir.access_at(new)).transpose()?;
+        crate::root::rename(old_dir.flatten(), old, new_dir.flatten(), new)
     }
 }
 
 impl Drop for File {
     fn drop(&mut self) {
+        self.locks.unlock_all(self.locks.owner());
         unsafe { self.node.access_unchecked().release().ok() };
     }
 }
@@ -387,17 +587,3 @@ impl From<&OpenOptions> for Cap {
         cap
     }
 }
-
-fn perm_to_cap(perm: FilePerm) -> Cap {
-    let mut cap = Cap::empty();
-    if perm.owner_readable() {
-        cap |= Cap::READ;
-    }
-    if perm.owner_writable() {
-        cap |= Cap::WRITE;
-    }
-    if perm.owner_executable() {
-        cap |= Cap::EXECUTE;
-    }
-    cap
-}
@@ -0,0 +1,50 @@
+use alloc::{boxed::Box, sync::Arc};
+use axfs_vfs::{VfsError, VfsResult};
+
+pub use axfs_ext2::Ext2FileSystem;
+
+use crate::dev::Disk;
+
+/// Checks whether the disk contains an ext2 filesystem.
+#[cfg(not(feature = "initramfs"))]
+pub fn probe(disk: &mut Disk) -> bool {
+    Ext2FileSystem::probe(disk)
+}
+
+/// Opens the ext2 filesystem on the disk.
+pub fn new(disk: Disk) -> VfsResult<Arc<Ext2FileSystem>> {
+    Ext2FileSystem::with_clock(Box::new(disk), crate::time::current_time).map(Arc::new)
+}
+
+impl axfs_ext2::BlockDevice for Disk {
+    fn read_at(&mut self, offset: u64, mut buf: &mut [u8]) -> VfsResult {
+        self.set_position(offset);
+        while !buf.is_empty() {
+            match self.read_one(buf) {
+                Ok(0) => return Err(VfsError::UnexpectedEof),
+                Ok(n) => {
+                    let tmp = buf;
+                    buf = &mut tmp[n..];
+                }
+                Err(_) => return Err(VfsError::Io),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, mut buf: &[u8]) -> VfsResult {
+        self.set_position(offset);
+        while !buf.is_empty() {
+            match self.write_one(buf) {
+                Ok(0) => return Err(VfsError::WriteZero),
+                Ok(n) => buf = &buf[n..],
+                Err(_) => return Err(VfsError::Io),
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> VfsResult {
+        Disk::flush(self).map_err(|_| VfsError::Io)
+    }
+}
@@ -1,64 +1,111 @@
 use alloc::sync::Arc;
-use core::cell::UnsafeCell;
+use core::time::Duration;
 
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Dir, File, LossyOemCpConverter, Read, Seek, SeekFrom, Time, Write};
 
 use crate::dev::Disk;
 
 const BLOCK_SIZE: usize = 512;
 
+type FsInner = fatfs::FileSystem<Disk, ClockTimeProvider, LossyOemCpConverter>;
+
+/// A FAT filesystem on a disk.
+///
+/// The `fatfs` files and directories borrow the `fatfs` filesystem, so every
+/// node holds a reference to it to keep it alive. The filesystem, along with
+/// the disk, is released after it's unmounted and all its nodes are dropped.
 pub struct FatFileSystem {
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
-    root_dir: UnsafeCell<Option<VfsNodeRef>>,
+    root_dir: VfsNodeRef,
+    disk: Disk,
 }
 
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+// The `fatfs` file or directory is dropped before the filesystem it borrows,
+// as the fields are dropped in order.
+pub struct FileWrapper<'a>(
+    Mutex<File<'a, Disk, ClockTimeProvider, LossyOemCpConverter>>,
+    Mutex<FatTimes>,
+    #[allow(dead_code)] // keeps the filesystem alive
+    Arc<FsInner>,
+);
+pub struct DirWrapper<'a>(
+    Dir<'a, Disk, ClockTimeProvider, LossyOemCpConverter>,
+    FatTimes,
+    Arc<FsInner>,
+);
+
+/// Provides the current time to `fatfs` for the timestamps it maintains.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClockTimeProvider;
+
+/// The timestamps of a file, which are read from its directory entry on
+/// lookup. FAT does not record the change time, we use the modification time
+/// instead.
+#[derive(Clone, Copy, Default)]
+struct FatTimes {
+    accessed: Duration,
+    modified: Duration,
+}
 
-unsafe impl Sync for FatFileSystem {}
-unsafe impl Send for FatFileSystem {}
 unsafe impl<'a> Send for FileWrapper<'a> {}
 unsafe impl<'a> Sync for FileWrapper<'a> {}
 unsafe impl<'a> Send for DirWrapper<'a> {}
 unsafe impl<'a> Sync for DirWrapper<'a> {}
 
 impl FatFileSystem {
+    /// Opens the FAT filesystem on the disk.
+    pub fn new(disk: Disk) -> VfsResult<Self> {
+        let inner = FsInner::new(disk.clone(), fs_options()).map_err(as_vfs_err)?;
+        #[allow(clippy::arc_with_non_send_sync)] // the nodes are `Send` and `Sync` instead
+        let inner = Arc::new(inner);
+        // SAFETY: the nodes hold `inner`, so it outlives the borrows
+        let root = unsafe { &*Arc::as_ptr(&inner) }.root_dir();
+        Ok(Self {
+            root_dir: Self::new_dir(root, FatTimes::default(), inner),
+            disk,
+        })
+    }
+
+    /// Formats the disk with a new FAT filesystem and opens it.
     #[cfg(feature = "use-ramdisk")]
-    pub fn new(mut disk: Disk) -> Self {
+    pub fn format(mut disk: Disk) -> VfsResult<Self> {
         let opts = fatfs::FormatVolumeOptions::new();
-        fatfs::format_volume(&mut disk, opts).expect("failed to format volume");
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
-        }
+        fatfs::format_volume(&mut disk, opts).map_err(as_vfs_err)?;
+        Self::new(disk)
     }
 
-    #[cfg(not(feature = "use-ramdisk"))]
-    pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
-            root_dir: UnsafeCell::new(None),
-        }
+    fn new_file(
+        file: File<'static, Disk, ClockTimeProvider, LossyOemCpConverter>,
+        times: FatTimes,
+        inner: Arc<FsInner>,
+    ) -> Arc<FileWrapper<'static>> {
+        Arc::new(FileWrapper(Mutex::new(file), Mutex::new(times), inner))
     }
 
-    pub fn init(&'static self) {
-        // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+    fn new_dir(
+        dir: Dir<'static, Disk, ClockTimeProvider, LossyOemCpConverter>,
+        times: FatTimes,
+        inner: Arc<FsInner>,
+    ) -> Arc<DirWrapper<'static>> {
+        Arc::new(DirWrapper(dir, times, inner))
     }
+}
 
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+fn fs_options() -> fatfs::FsOptions<ClockTimeProvider, LossyOemCpConverter> {
+    fatfs::FsOptions::new()
+        .time_provider(ClockTimeProvider)
+        .update_accessed_date(true)
+}
+
+impl fatfs::TimeProvider for ClockTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
     }
 
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
-        Arc::new(DirWrapper(dir))
+    fn get_current_date_time(&self) -> DateTime {
+        to_fat_time(crate::time::current_time())
     }
 }
 
@@ -70,25 +117,64 @@ impl VfsNodeOps for FileWrapper<'static> {
         let blocks = (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
         // FAT fs doesn't support permissions, we just set everything to 755
         let perm = VfsNodePerm::from_bits_truncate(0o755);
-        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks))
+        let mut attr = VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks);
+        let times = self.1.lock();
+        attr.set_times(times.accessed, times.modified, times.modified);
+        Ok(attr)
+    }
+
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        let mut file = self.0.lock();
+        let mut times = self.1.lock();
+        if let Some(atime) = atime {
+            let date = to_fat_time(atime).date;
+            file.set_accessed(date);
+            times.accessed = from_fat_date(date);
+        }
+        if let Some(mtime) = mtime {
+            let date_time = to_fat_time(mtime);
+            file.set_modified(date_time);
+            times.modified = from_fat_time(date_time);
+        }
+        Ok(())
     }
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
         file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.read(buf).map_err(as_vfs_err)
+        let n = file.read(buf).map_err(as_vfs_err)?;
+        // mirror the access date updated by `fatfs`
+        let today = to_fat_time(crate::time::current_time()).date;
+        self.1.lock().accessed = from_fat_date(today);
+        Ok(n)
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
         file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.write(buf).map_err(as_vfs_err)
+        let n = file.write(buf).map_err(as_vfs_err)?;
+        self.touch_modified();
+        Ok(n)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        // also writes the dirty blocks in the block cache back to the disk
+        self.0.lock().flush().map_err(as_vfs_err)
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
         let mut file = self.0.lock();
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.truncate().map_err(as_vfs_err)
+        file.truncate().map_err(as_vfs_err)?;
+        self.touch_modified();
+        Ok(())
+    }
+}
+
+impl FileWrapper<'_> {
+    /// Mirrors the modification time updated by `fatfs` on writing.
+    fn touch_modified(&self) {
+        self.1.lock().modified = from_fat_time(to_fat_time(crate::time::current_time()));
     }
 }
 
@@ -97,18 +183,24 @@ impl VfsNodeOps for DirWrapper<'static> {
 
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         // FAT fs doesn't support permissions, we just set everything to 755
-        Ok(VfsNodeAttr::new(
+        let mut attr = VfsNodeAttr::new(
             VfsNodePerm::from_bits_truncate(0o755),
             VfsNodeType::Dir,
             BLOCK_SIZE as u64,
             1,
-        ))
+        );
+        attr.set_times(self.1.accessed, self.1.modified, self.1.modified);
+        Ok(attr)
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
-        self.0
-            .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+        self.0.open_dir("..").map_or(None, |dir| {
+            Some(FatFileSystem::new_dir(
+                dir,
+                FatTimes::default(),
+                self.2.clone(),
+            ))
+        })
     }
 
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
@@ -123,9 +215,11 @@ impl VfsNodeOps for DirWrapper<'static> {
 
         // TODO: use `fatfs::Dir::find_entry`, but it's not public.
         if let Ok(file) = self.0.open_file(path) {
-            Ok(FatFileSystem::new_file(file))
+            let times = self.entry_times(path);
+            Ok(FatFileSystem::new_file(file, times, self.2.clone()))
         } else if let Ok(dir) = self.0.open_dir(path) {
-            Ok(FatFileSystem::new_dir(dir))
+            let times = self.entry_times(path);
+            Ok(FatFileSystem::new_dir(dir, times, self.2.clone()))
         } else {
             Err(VfsError::NotFound)
         }
@@ -198,10 +292,38 @@ impl VfsNodeOps for DirWrapper<'static> {
     }
 }
 
+impl DirWrapper<'_> {
+    /// Reads the timestamps from the directory entry of `path`, as they are
+    /// not available from the opened files and directories.
+    fn entry_times(&self, path: &str) -> FatTimes {
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => match self.0.open_dir(parent) {
+                Ok(dir) => (dir, name),
+                Err(_) => return FatTimes::default(),
+            },
+            None => (self.0.clone(), path),
+        };
+        dir.iter()
+            .flatten()
+            .find(|entry| entry.file_name().eq_ignore_ascii_case(name))
+            .map_or_else(FatTimes::default, |entry| FatTimes {
+                accessed: from_fat_date(entry.accessed()),
+                modified: from_fat_time(entry.modified()),
+            })
+    }
+}
+
 impl VfsOps for FatFileSystem {
+    fn umount(&self) -> VfsResult {
+        self.disk.flush().map_err(|_| VfsError::Io)
+    }
+
     fn root_dir(&self) -> VfsNodeRef {
-        let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
-        root_dir.clone()
+        self.root_dir.clone()
+    }
+
+    fn fs_type(&self) -> &str {
+        "vfat"
     }
 }
 
@@ -243,7 +365,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
@@ -264,6 +386,61 @@ impl Seek for Disk {
     }
 }
 
+/// Days from 1970-01-01 to the given date in the proleptic Gregorian calendar.
+const fn days_from_civil(year: u16, month: u16, day: u16) -> u64 {
+    let (y, m, d) = (year as u64 - (month <= 2) as u64, month as u64, day as u64);
+    let (era, yoe) = (y / 400, y % 400);
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Converts a FAT timestamp (treated as UTC) to the duration since the UNIX
+/// epoch.
+fn from_fat_time(date_time: DateTime) -> Duration {
+    let (date, time) = (date_time.date, date_time.time);
+    let days = days_from_civil(date.year, date.month, date.day);
+    let secs = days * 86400 + time.hour as u64 * 3600 + time.min as u64 * 60 + time.sec as u64;
+    Duration::from_secs(secs) + Duration::from_millis(time.millis as u64)
+}
+
+/// Converts a FAT date (treated as UTC) to the duration since the UNIX epoch.
+fn from_fat_date(date: Date) -> Duration {
+    from_fat_time(DateTime::new(date, Time::new(0, 0, 0, 0)))
+}
+
+/// Converts the duration since the UNIX epoch to a FAT timestamp (in UTC),
+/// which is clamped to the range from 1980 to 2107.
+fn to_fat_time(time: Duration) -> DateTime {
+    const MIN_SECS: u64 = days_from_civil(1980, 1, 1) * 86400;
+    const MAX_SECS: u64 = days_from_civil(2108, 1, 1) * 86400 - 1;
+    let secs = time.as_secs().clamp(MIN_SECS, MAX_SECS);
+    let millis = if secs == time.as_secs() {
+        time.subsec_millis() as u16
+    } else {
+        0
+    };
+
+    let (days, secs) = (secs / 86400 + 719468, secs % 86400);
+    let (era, doe) = (days / 146097, days % 146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+
+    DateTime::new(
+        Date::new(year as u16, month as u16, day as u16),
+        Time::new(
+            (secs / 3600) as u16,
+            (secs / 60 % 60) as u16,
+            (secs % 60) as u16,
+            millis,
+        ),
+    )
+}
+
 const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
     use fatfs::Error::*;
     match err {
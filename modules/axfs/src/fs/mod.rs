@@ -1,8 +1,11 @@
 cfg_if::cfg_if! {
     if #[cfg(feature = "myfs")] {
         pub mod myfs;
-    } else if #[cfg(feature = "fatfs")] {
+    } else {
+        #[cfg(feature = "fatfs")]
         pub mod fatfs;
+        #[cfg(feature = "ext2")]
+        pub mod ext2;
     }
 }
 
@@ -11,3 +14,9 @@ pub use axfs_devfs as devfs;
 
 #[cfg(feature = "ramfs")]
 pub use axfs_ramfs as ramfs;
+
+#[cfg(feature = "overlay")]
+pub mod overlay;
+
+#[cfg(any(feature = "procfs", feature = "sysfs"))]
+pub use axfs_pseudofs as pseudofs;
@@ -7,7 +7,9 @@ use axfs_vfs::VfsOps;
 pub trait MyFileSystemIf {
     /// Creates a new instance of the filesystem with initialization.
     ///
-    /// TODO: use generic disk type
+    /// The `disk` is the first block device (or its root partition), which
+    /// can be any device that implements
+    /// [`BlockDriverOps`](axdriver::prelude::BlockDriverOps).
     fn new_myfs(disk: Disk) -> Arc<dyn VfsOps>;
 }
 
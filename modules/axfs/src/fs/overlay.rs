@@ -0,0 +1,525 @@
+//! Overlay filesystem, which stacks a writable RAM filesystem over another
+//! filesystem.
+//!
+//! Like `overlayfs` in Linux, a node in the upper filesystem hides the node at
+//! the same path in the lower filesystem, except that the directories at the
+//! same path in both are merged. The lower filesystem is never modified:
+//!
+//! - A node in the lower filesystem is copied to the upper one, with its parent
+//!   directories, before it is modified (*copy-up*).
+//! - A removed node in the lower filesystem is hidden by a *whiteout*. A
+//!   directory created in its place is *opaque*, which hides the contents of
+//!   the lower directory.
+//!
+//! The whiteouts and opaque directories are kept in memory by their paths.
+//! Renaming directories of the lower filesystem is not supported and fails
+//! with [`CrossesDevices`](VfsError::CrossesDevices), so callers can fall back
+//! to copying.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::sync::{Arc, Weak};
+use alloc::{format, vec};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsError, VfsOps, VfsResult};
+use axsync::Mutex;
+use core::time::Duration;
+
+use super::ramfs::RamFileSystem;
+
+/// The whiteouts and opaque directories of an overlay filesystem.
+#[derive(Default)]
+struct Marks {
+    /// Paths of the removed nodes of the lower filesystem.
+    whiteouts: BTreeSet<String>,
+    /// Paths of the directories that hide the lower directories.
+    opaque: BTreeSet<String>,
+}
+
+impl Marks {
+    /// Removes the marks on `path` and all the paths under it.
+    fn clear(&mut self, path: &str) {
+        let under = |p: &String| {
+            p.strip_prefix(path)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        };
+        self.whiteouts.retain(|p| !under(p));
+        self.opaque.retain(|p| !under(p));
+    }
+}
+
+/// The state shared by all the nodes of an overlay filesystem.
+struct Shared {
+    marks: Mutex<Marks>,
+    /// Serializes copy-ups, so a node is copied only once.
+    copy_up_lock: Mutex<()>,
+    upper_root: VfsNodeRef,
+}
+
+/// An overlay filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct OverlayFileSystem {
+    lower: Arc<dyn VfsOps>,
+    upper: Arc<RamFileSystem>,
+    root: Arc<OverlayNode>,
+}
+
+impl OverlayFileSystem {
+    /// Creates an overlay filesystem that stacks `upper` over `lower`.
+    pub fn new(lower: Arc<dyn VfsOps>, upper: Arc<RamFileSystem>) -> Self {
+        let shared = Arc::new(Shared {
+            marks: Mutex::new(Marks::default()),
+            copy_up_lock: Mutex::new(()),
+            upper_root: upper.root_dir(),
+        });
+        let root = OverlayNode::new(
+            shared,
+            None,
+            String::new(),
+            Some(upper.root_dir()),
+            Some(lower.root_dir()),
+        );
+        Self { lower, upper, root }
+    }
+}
+
+impl VfsOps for OverlayFileSystem {
+    fn umount(&self) -> VfsResult {
+        self.upper.umount()?;
+        self.lower.umount()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+
+    fn fs_type(&self) -> &str {
+        "overlay"
+    }
+}
+
+/// A node in the overlay filesystem, which refers to the nodes at the same
+/// path in the upper and the lower filesystems.
+pub struct OverlayNode {
+    this: Weak<OverlayNode>,
+    shared: Arc<Shared>,
+    parent: Option<Arc<OverlayNode>>,
+    name: String,
+    /// The path relative to the root, without leading `/`.
+    path: String,
+    upper: Mutex<Option<VfsNodeRef>>,
+    lower: Option<VfsNodeRef>,
+}
+
+impl OverlayNode {
+    fn new(
+        shared: Arc<Shared>,
+        parent: Option<Arc<OverlayNode>>,
+        name: String,
+        upper: Option<VfsNodeRef>,
+        lower: Option<VfsNodeRef>,
+    ) -> Arc<Self> {
+        let path = match &parent {
+            Some(p) if !p.path.is_empty() => format!("{}/{}", p.path, name),
+            _ => name.clone(),
+        };
+        Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            shared,
+            parent,
+            name,
+            path,
+            upper: Mutex::new(upper),
+            lower,
+        })
+    }
+
+    fn this(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    fn child_path(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            name.into()
+        } else {
+            format!("{}/{}", self.path, name)
+        }
+    }
+
+    /// Returns the node in the upper filesystem, if it has been copied up or
+    /// created there.
+    fn upper(&self) -> Option<VfsNodeRef> {
+        let mut upper = self.upper.lock();
+        if upper.is_none() {
+            // it may have been copied up through another node of the same path
+            *upper = self
+                .parent
+                .as_ref()
+                .and_then(|p| p.upper())
+                .and_then(|dir| dir.lookup(&self.name).ok());
+        }
+        upper.clone()
+    }
+
+    /// Returns the node that is visible in the overlay filesystem.
+    fn current(&self) -> VfsNodeRef {
+        self.upper()
+            .or_else(|| self.lower.clone())
+            .expect("overlay node without backing nodes")
+    }
+
+    fn is_dir(&self) -> bool {
+        self.current().get_attr().is_ok_and(|attr| attr.is_dir())
+    }
+
+    /// Copies the node from the lower filesystem to the upper one if it is not
+    /// there yet, returns the node in the upper filesystem.
+    fn copy_up(&self) -> VfsResult<VfsNodeRef> {
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+        // the root is always in the upper filesystem
+        let upper_dir = self.parent.as_ref().unwrap().copy_up()?;
+        let lower = self.lower.as_ref().unwrap();
+
+        let _guard = self.shared.copy_up_lock.lock();
+        if let Some(upper) = self.upper() {
+            return Ok(upper);
+        }
+        debug!("overlay: copy up {}", self.path);
+        let attr = lower.get_attr()?;
+        match attr.file_type() {
+            ty @ (VfsNodeType::File | VfsNodeType::Dir) => upper_dir.create(&self.name, ty)?,
+            VfsNodeType::SymLink => {
+                let mut buf = vec![0; attr.size() as usize];
+                let len = lower.readlink(&mut buf)?;
+                let target =
+                    core::str::from_utf8(&buf[..len]).map_err(|_| VfsError::InvalidData)?;
+                upper_dir.symlink(&self.name, target)?;
+            }
+            _ => return Err(VfsError::Unsupported),
+        }
+        let upper = upper_dir.lookup(&self.name)?;
+        if attr.is_file() {
+            let mut buf = vec![0; 4096];
+            let mut offset = 0;
+            loop {
+                let len = lower.read_at(offset, &mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                upper.write_at(offset, &buf[..len])?;
+                offset += len as u64;
+            }
+        }
+        if !attr.is_symlink() {
+            upper.set_perm(attr.perm())?;
+        }
+        upper.set_owner(attr.uid(), attr.gid())?;
+        upper.set_times(Some(attr.atime()), Some(attr.mtime()))?;
+        *self.upper.lock() = Some(upper.clone());
+        Ok(upper)
+    }
+
+    /// Looks up the entry `name` in this directory.
+    fn lookup_child(&self, name: &str) -> VfsResult<Arc<Self>> {
+        let path = self.child_path(name);
+        let opaque = {
+            let marks = self.shared.marks.lock();
+            if marks.whiteouts.contains(&path) {
+                return Err(VfsError::NotFound);
+            }
+            marks.opaque.contains(&path)
+        };
+        let upper = self.upper().and_then(|dir| dir.lookup(name).ok());
+        let upper_is_dir = upper
+            .as_ref()
+            .map(|node| node.get_attr().is_ok_and(|attr| attr.is_dir()));
+        // the lower node is visible if there is no upper node, or both are
+        // merged directories
+        let lower = match upper_is_dir {
+            Some(false) => None,
+            _ if opaque => None,
+            _ => self
+                .lower
+                .clone()
+                .and_then(|dir| dir.lookup(name).ok())
+                .filter(|node| {
+                    upper_is_dir.is_none() || node.get_attr().is_ok_and(|attr| attr.is_dir())
+                }),
+        };
+        if upper.is_none() && lower.is_none() {
+            return Err(VfsError::NotFound);
+        }
+        Ok(Self::new(
+            self.shared.clone(),
+            Some(self.this()),
+            name.into(),
+            upper,
+            lower,
+        ))
+    }
+
+    /// Whether the lower filesystem has the entry `name` in this directory,
+    /// visible or not.
+    fn in_lower(&self, name: &str) -> bool {
+        self.lower
+            .clone()
+            .is_some_and(|dir| dir.lookup(name).is_ok())
+    }
+
+    /// Looks up the parent directory of `path`, returns it with the last
+    /// component of `path`.
+    fn lookup_parent<'a>(&self, path: &'a str) -> VfsResult<(Arc<Self>, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let parent = self.lookup_node(parent)?;
+        if !parent.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok((parent, name))
+    }
+
+    fn lookup_node(&self, path: &str) -> VfsResult<Arc<Self>> {
+        let mut node = self.this();
+        for name in path.split('/') {
+            node = match name {
+                "" | "." => continue,
+                ".." => node.parent.clone().ok_or(VfsError::NotFound)?,
+                _ if !node.is_dir() => return Err(VfsError::NotADirectory),
+                _ => node.lookup_child(name)?,
+            };
+        }
+        if path.ends_with('/') && !node.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok(node)
+    }
+
+    /// Returns the merged entries of this directory, except `.` and `..`.
+    fn entries(&self) -> VfsResult<BTreeMap<String, VfsNodeType>> {
+        let mut entries = BTreeMap::new();
+        if let Some(lower) = &self.lower {
+            let marks = self.shared.marks.lock();
+            for_each_entry(lower, |name, ty| {
+                if !marks.whiteouts.contains(&self.child_path(name)) {
+                    entries.insert(name.to_string(), ty);
+                }
+            })?;
+        }
+        if let Some(upper) = self.upper() {
+            for_each_entry(&upper, |name, ty| {
+                entries.insert(name.to_string(), ty);
+            })?;
+        }
+        Ok(entries)
+    }
+
+    /// Updates the marks after the entry `name` is created in the upper
+    /// directory.
+    fn created(&self, name: &str, is_dir: bool) {
+        let path = self.child_path(name);
+        let mut marks = self.shared.marks.lock();
+        if marks.whiteouts.remove(&path) && is_dir {
+            marks.opaque.insert(path);
+        }
+    }
+
+    /// Creates the entry `name` in the upper directory by `create`.
+    fn create_with(
+        &self,
+        path: &str,
+        is_dir: bool,
+        create: impl FnOnce(&VfsNodeRef, &str) -> VfsResult,
+    ) -> VfsResult {
+        let (dir, name) = self.lookup_parent(path)?;
+        if dir.lookup_child(name).is_ok() {
+            return Err(VfsError::AlreadyExists);
+        }
+        create(&dir.copy_up()?, name)?;
+        dir.created(name, is_dir);
+        Ok(())
+    }
+}
+
+/// Calls `f` on the name and type of each entry in the directory `dir`,
+/// except `.` and `..`.
+fn for_each_entry(dir: &VfsNodeRef, mut f: impl FnMut(&str, VfsNodeType)) -> VfsResult {
+    const EMPTY: VfsDirEntry = VfsDirEntry::default();
+    let mut dirents = [EMPTY; 16];
+    let mut start_idx = 0;
+    loop {
+        let n = dir.read_dir(start_idx, &mut dirents)?;
+        if n == 0 {
+            return Ok(());
+        }
+        for ent in &dirents[..n] {
+            let name =
+                core::str::from_utf8(ent.name_as_bytes()).map_err(|_| VfsError::InvalidData)?;
+            if name != "." && name != ".." {
+                f(name, ent.entry_type());
+            }
+        }
+        start_idx += n;
+    }
+}
+
+impl VfsNodeOps for OverlayNode {
+    fn open(&self) -> VfsResult {
+        self.current().open()
+    }
+
+    fn release(&self) -> VfsResult {
+        self.current().release()
+    }
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.current().get_attr()
+    }
+
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        self.copy_up()?.set_perm(perm)
+    }
+
+    fn set_owner(&self, uid: u32, gid: u32) -> VfsResult {
+        self.copy_up()?.set_owner(uid, gid)
+    }
+
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        self.copy_up()?.set_times(atime, mtime)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.current().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.copy_up()?.write_at(offset, buf)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.current().fsync()
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.copy_up()?.truncate(size)
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.clone().map(|p| p as VfsNodeRef)
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        self.lookup_node(path).map(|node| node as VfsNodeRef)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at overlay: {}", ty, path);
+        self.create_with(path, ty == VfsNodeType::Dir, |dir, name| {
+            dir.create(name, ty)
+        })
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at overlay: {}", path);
+        let (dir, name) = self.lookup_parent(path)?;
+        let node = dir.lookup_child(name)?;
+        if node.is_dir() && !node.entries()?.is_empty() {
+            return Err(VfsError::DirectoryNotEmpty);
+        }
+        if node.upper().is_some() {
+            dir.copy_up()?.remove(name)?;
+        }
+        let mut marks = self.shared.marks.lock();
+        marks.clear(&node.path);
+        if dir.in_lower(name) {
+            marks.whiteouts.insert(node.path.clone());
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let entries = self.entries()?;
+        let mut entries = entries.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some((name, &ty)) = entries.next() {
+                        *ent = VfsDirEntry::new(name, ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at overlay: {} -> {}", src_path, dst_path);
+        let (src_dir, src_name) = self.lookup_parent(src_path)?;
+        let (dst_dir, dst_name) = self.lookup_parent(dst_path)?;
+        let node = src_dir.lookup_child(src_name)?;
+        let is_dir = node.is_dir();
+        let dst = dst_dir.child_path(dst_name);
+        if node.path == dst {
+            return Ok(());
+        } else if is_dir && node.lower.is_some() {
+            return Err(VfsError::CrossesDevices);
+        }
+        if let Ok(old) = dst_dir.lookup_child(dst_name) {
+            match (is_dir, old.is_dir()) {
+                (true, true) if !old.entries()?.is_empty() => {
+                    return Err(VfsError::DirectoryNotEmpty)
+                }
+                (true, false) => return Err(VfsError::NotADirectory),
+                (false, true) => return Err(VfsError::IsADirectory),
+                _ => {}
+            }
+        }
+        node.copy_up()?;
+        dst_dir.copy_up()?;
+        self.shared.upper_root.rename(&node.path, &dst)?;
+
+        let mut marks = self.shared.marks.lock();
+        marks.clear(&node.path);
+        marks.clear(&dst);
+        if src_dir.in_lower(src_name) {
+            marks.whiteouts.insert(node.path.clone());
+        }
+        if is_dir && dst_dir.in_lower(dst_name) {
+            marks.opaque.insert(dst);
+        }
+        Ok(())
+    }
+
+    fn symlink(&self, path: &str, target: &str) -> VfsResult {
+        debug!("symlink at overlay: {} -> {}", path, target);
+        self.create_with(path, false, |dir, name| dir.symlink(name, target))
+    }
+
+    fn link(&self, path: &str, node: VfsNodeRef) -> VfsResult {
+        debug!("link at overlay: {}", path);
+        let node = node
+            .as_any()
+            .downcast_ref::<OverlayNode>()
+            .ok_or(VfsError::CrossesDevices)?;
+        if node.is_dir() {
+            return Err(VfsError::PermissionDenied);
+        }
+        let upper = node.copy_up()?;
+        self.create_with(path, false, |dir, name| dir.link(name, upper))
+    }
+
+    fn readlink(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        self.current().readlink(buf)
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
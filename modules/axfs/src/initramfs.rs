@@ -0,0 +1,198 @@
+//! Initial RAM filesystem unpacked from a cpio archive.
+//!
+//! The archive is in the portable format of cpio (`newc`), as the initramfs of
+//! Linux, which can be created by `find . | cpio -o -H newc`. Directories,
+//! regular files, symbolic links and hard links are unpacked with their
+//! permission bits, owners and modification times, other types of files are
+//! skipped. Missing parent directories are created, and later entries replace
+//! earlier files at the same path.
+//!
+//! The archive is linked into the kernel image from the file given by the
+//! `AX_INITRAMFS` environment variable at build time, or passed by the
+//! bootloader through [`set_initramfs`](crate::set_initramfs).
+
+#![cfg_attr(feature = "myfs", allow(dead_code))] // overridden by `myfs`
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+use axsync::spin::SpinNoIrq;
+use core::time::Duration;
+
+/// The archive linked into the kernel image, empty if not specified.
+static EMBEDDED: &[u8] = include_bytes!(env!("AX_INITRAMFS_FILE"));
+
+static ARCHIVE: SpinNoIrq<Option<&'static [u8]>> = SpinNoIrq::new(None);
+
+const HEADER_SIZE: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+pub(crate) fn set_archive(archive: &'static [u8]) {
+    *ARCHIVE.lock() = Some(archive);
+}
+
+/// Returns the archive passed by the bootloader, or the one linked into the
+/// kernel image.
+pub(crate) fn archive() -> &'static [u8] {
+    ARCHIVE.lock().unwrap_or(EMBEDDED)
+}
+
+/// An entry in the archive.
+struct Entry<'a> {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    name: &'a str,
+    data: &'a [u8],
+}
+
+struct Reader<'a> {
+    archive: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> VfsResult<&'a [u8]> {
+        let bytes = self
+            .archive
+            .get(self.pos..self.pos + len)
+            .ok_or(VfsError::InvalidData)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Skips the padding to the next 4-byte boundary.
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(4);
+    }
+
+    /// Reads the next entry, returns `None` at the trailer.
+    fn next_entry(&mut self) -> VfsResult<Option<Entry<'a>>> {
+        let header = self.take(HEADER_SIZE)?;
+        if &header[..6] != b"070701" && &header[..6] != b"070702" {
+            return Err(VfsError::InvalidData);
+        }
+        let field = |i: usize| {
+            core::str::from_utf8(&header[6 + i * 8..14 + i * 8])
+                .ok()
+                .and_then(|s| u32::from_str_radix(s, 16).ok())
+                .ok_or(VfsError::InvalidData)
+        };
+        let name = self.take(field(11)? as usize)?;
+        let name = core::str::from_utf8(name.strip_suffix(b"\0").unwrap_or(name))
+            .map_err(|_| VfsError::InvalidData)?;
+        self.align();
+        let data = self.take(field(6)? as usize)?;
+        self.align();
+        if name == TRAILER {
+            return Ok(None);
+        }
+        Ok(Some(Entry {
+            ino: field(0)?,
+            mode: field(1)?,
+            uid: field(2)?,
+            gid: field(3)?,
+            nlink: field(4)?,
+            mtime: field(5)?,
+            name,
+            data,
+        }))
+    }
+}
+
+/// Creates the missing parent directories of `path`.
+fn create_parents(root: &VfsNodeRef, path: &str) -> VfsResult {
+    for (i, _) in path.match_indices('/') {
+        let parent = &path[..i];
+        if root.clone().lookup(parent).is_err() {
+            root.create(parent, VfsNodeType::Dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpacks the archive into the directory `root`, returns the number of
+/// unpacked entries.
+pub(crate) fn unpack(archive: &[u8], root: &VfsNodeRef) -> VfsResult<usize> {
+    if archive.is_empty() {
+        return Ok(0);
+    }
+    let mut reader = Reader { archive, pos: 0 };
+    let mut count = 0;
+    // paths of the files with multiple links by their inode numbers
+    let mut links = BTreeMap::<u32, String>::new();
+    // the modification times of directories are set at last, after their
+    // contents are created
+    let mut dir_times = Vec::new();
+
+    while let Some(entry) = reader.next_entry()? {
+        let path = entry
+            .name
+            .split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+            .collect::<Vec<_>>()
+            .join("/");
+        let ty = entry.mode & S_IFMT;
+        if ![S_IFDIR, S_IFREG, S_IFLNK].contains(&ty) {
+            warn!("initramfs: skip special file {:?}", entry.name);
+            continue;
+        } else if path.is_empty() && ty != S_IFDIR {
+            return Err(VfsError::InvalidData);
+        }
+        create_parents(root, &path)?;
+
+        let exists = root.clone().lookup(&path).is_ok();
+        if exists && ty != S_IFDIR {
+            root.remove(&path)?;
+        }
+        match ty {
+            S_IFDIR if !exists => root.create(&path, VfsNodeType::Dir)?,
+            S_IFDIR => {}
+            S_IFREG => match links.get(&entry.ino) {
+                Some(first) if entry.nlink > 1 => {
+                    root.link(&path, root.clone().lookup(first)?)?;
+                }
+                _ => {
+                    root.create(&path, VfsNodeType::File)?;
+                    if entry.nlink > 1 {
+                        links.insert(entry.ino, path.clone());
+                    }
+                }
+            },
+            _ => {
+                let target = core::str::from_utf8(entry.data).map_err(|_| VfsError::InvalidData)?;
+                root.symlink(&path, target)?;
+            }
+        }
+
+        let node = root.clone().lookup(&path)?;
+        if ty == S_IFREG && !entry.data.is_empty() {
+            // the data of hard links is stored in the last one
+            node.truncate(0)?;
+            node.write_at(0, entry.data)?;
+        }
+        if ty != S_IFLNK {
+            node.set_perm(VfsNodePerm::from_bits_truncate(entry.mode as _))?;
+        }
+        node.set_owner(entry.uid, entry.gid)?;
+        let mtime = Some(Duration::from_secs(entry.mtime as u64));
+        if ty == S_IFDIR {
+            dir_times.push((node, mtime));
+        } else {
+            node.set_times(mtime, mtime)?;
+        }
+        count += 1;
+    }
+
+    for (dir, mtime) in dir_times {
+        dir.set_times(mtime, mtime)?;
+    }
+    Ok(count)
+}
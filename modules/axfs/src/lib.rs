@@ -1,15 +1,61 @@
 //! [ArceOS](https://github.com/rcore-os/arceos) filesystem module.
 //!
-//! It provides unified filesystem operations for various filesystems.
+//! It provides unified filesystem operations for various filesystems. Besides
+//! the filesystems mounted at initialization, other filesystems can be mounted
+//! on any directory at runtime by [`api::mount`], including the directories in
+//! other mounted filesystems.
+//!
+//! Filesystems on block devices access the device through an LRU block cache
+//! with write-back. The dirty blocks are written back when they are evicted,
+//! when a file is synchronized (see [`fops::File::sync_all`]), when the
+//! filesystem is unmounted, or when all filesystems are synchronized by
+//! [`api::sync`], which the runtime should call before the system shuts down.
+//!
+//! Block devices are registered by names `vda`, `vdb`, etc. If a device has an
+//! MBR or GPT partition table, each partition is also registered as a separate
+//! device (e.g., `vda1`, `vda2`). The main filesystem is on the first device,
+//! or on its partition selected by the `AX_ROOT_PART` environment variable at
+//! build time, which can be a partition number or a GPT partition label (the
+//! first partition by default). Other devices and partitions can be mounted at
+//! runtime by [`api::create_filesystem`] with the device name as the source
+//! (e.g., `/dev/vdb` or `/dev/vda2`).
+//!
+//! Files are accessed with the user ID and group ID of the current task, and
+//! the permission bits of the files are checked on opening, creating and
+//! removing, like in Unix. The credentials are provided by the runtime through
+//! [`CredentialsIf`], which is required to be implemented.
+//!
+//! The access, modification and change times of files are maintained with the
+//! current time provided by the runtime through [`ClockIf`], which is also
+//! required to be implemented. FAT only records the date of the last access.
 //!
 //! # Cargo Features
 //!
 //! - `fatfs`: Use [FAT] as the main filesystem and mount it on `/`. This feature
 //!    is **enabled** by default.
-//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`. This feature is
-//!    **enabled** by default.
+//! - `devfs`: Mount [`axfs_devfs::DeviceFileSystem`] on `/dev`, which contains
+//!    the raw block devices and the devices of other modules (see [`devfs`]).
+//!    This feature is **enabled** by default.
 //! - `ramfs`: Mount [`axfs_ramfs::RamFileSystem`] on `/tmp`. This feature is
 //!    **enabled** by default.
+//! - `procfs`: Mount a pseudo filesystem on `/proc`, whose contents are
+//!    generated from the kernel states (see [`procfs`]). This feature is
+//!    **enabled** by default.
+//! - `sysfs`: Mount a pseudo filesystem on `/sys`, which exposes the devices
+//!    and kernel parameters (see [`sysfs`]). This feature is **enabled** by
+//!    default.
+//! - `ext2`: Use [ext2] as the main filesystem if it is detected on the disk,
+//!    otherwise fall back to FAT (if `fatfs` is enabled). ext3 and ext4
+//!    filesystems are mounted read-only. This feature is **disabled** by
+//!    default.
+//! - `overlay`: Stack a writable [`axfs_ramfs::RamFileSystem`] over the main
+//!    filesystem, so files can be created and modified anywhere without
+//!    writing to the disk. The changes are lost on reboot. This feature is
+//!    **disabled** by default.
+//! - `initramfs`: Use [`axfs_ramfs::RamFileSystem`] as the main filesystem,
+//!    which is populated from a cpio archive at boot (see [`set_initramfs`]).
+//!    Block devices are not required, but they can still be mounted at
+//!    runtime. This feature is **disabled** by default.
 //! - `myfs`: Allow users to define their custom filesystems to override the
 //!    default. In this case, [`MyFileSystemIf`] is required to be implemented
 //!    to create and initialize other filesystems. This feature is **disabled** by
@@ -17,7 +63,10 @@
 //!    both are enabled.
 //!
 //! [FAT]: https://en.wikipedia.org/wiki/File_Allocation_Table
+//! [ext2]: https://en.wikipedia.org/wiki/Ext2
 //! [`MyFileSystemIf`]: fops::MyFileSystemIf
+//! [`CredentialsIf`]: fops::CredentialsIf
+//! [`ClockIf`]: fops::ClockIf
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
 #![feature(doc_auto_cfg)]
@@ -26,21 +75,60 @@
 extern crate log;
 extern crate alloc;
 
+mod cache;
 mod dev;
 mod fs;
+#[cfg(feature = "initramfs")]
+mod initramfs;
+mod lock;
 mod mounts;
+mod partition;
+mod perm;
 mod root;
+mod time;
 
 pub mod api;
+#[cfg(feature = "devfs")]
+pub mod devfs;
 pub mod fops;
+#[cfg(feature = "procfs")]
+pub mod procfs;
+#[cfg(feature = "sysfs")]
+pub mod sysfs;
 
+use alloc::format;
 use axdriver::{prelude::*, AxDeviceContainer};
 
 /// Initializes filesystems by block devices.
-pub fn init_filesystems(mut blk_devs: AxDeviceContainer<AxBlockDevice>) {
+///
+/// The block devices are registered by names `vda`, `vdb`, etc. in order, and
+/// the main filesystem is on the first device (unless `initramfs` is enabled).
+pub fn init_filesystems<D: BlockDriverOps + 'static>(mut blk_devs: AxDeviceContainer<D>) {
     info!("Initialize filesystems...");
 
-    let dev = blk_devs.take_one().expect("No block device found!");
-    info!("  use block device 0: {:?}", dev.device_name());
-    self::root::init_rootfs(self::dev::Disk::new(dev));
+    let root_part = option_env!("AX_ROOT_PART").unwrap_or("");
+    let mut root_disk = None;
+    let mut idx = 0;
+    while let Some(dev) = blk_devs.take_one() {
+        let name = format!("vd{}", (b'a' + idx as u8) as char);
+        info!("  use block device {}: {:?}", name, dev.device_name());
+        let root_part = (idx == 0).then_some(root_part);
+        match self::dev::register_block_device(&name, dev, root_part) {
+            Ok(disk) => root_disk = root_disk.or(disk),
+            Err(e) => panic!("failed to initialize block device {}: {:?}", name, e),
+        }
+        idx += 1;
+    }
+    self::root::init_rootfs(root_disk);
+}
+
+/// Sets the initramfs archive passed by the bootloader, which replaces the one
+/// linked into the kernel image.
+///
+/// The archive is in the `newc` format of cpio. The one linked into the kernel
+/// image is given by the `AX_INITRAMFS` environment variable at build time.
+/// It must be called before [`init_filesystems`].
+#[cfg(feature = "initramfs")]
+pub fn set_initramfs(archive: &'static [u8]) {
+    self::initramfs::set_archive(archive);
 }
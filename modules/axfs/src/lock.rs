@@ -0,0 +1,250 @@
+//! Advisory file locks.
+//!
+//! Like in Linux, there are two independent kinds of locks, which never
+//! conflict with each other: byte-range locks (`fcntl`) and whole-file locks
+//! (`flock`). Locks of the same owner never conflict, a shared lock conflicts
+//! with the exclusive locks of other owners, and an exclusive lock conflicts
+//! with all locks of other owners. The locks are advisory, they do not prevent
+//! reading or writing the files.
+//!
+//! Requests never block here, conflicting ones fail with
+//! [`WouldBlock`](axerrno::AxError::WouldBlock). Callers that want to wait
+//! should retry after the locks are released, e.g., on a wait queue notified
+//! after unlocking.
+//!
+//! Files are identified by their filesystems and inode numbers (see
+//! [`VfsNodeOps::ino`]), so the hard links of a file share the same locks,
+//! and the locks stay with the file when it's renamed or unlinked. On
+//! filesystems that cannot identify their nodes (e.g., FAT, which has no hard
+//! links), files are identified by their resolved absolute paths instead.
+//!
+//! [`VfsNodeOps::ino`]: axfs_vfs::VfsNodeOps::ino
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::VfsNodeRef;
+use axsync::spin::SpinNoIrq;
+use capability::Cap;
+
+/// The type of a file lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockType {
+    /// A shared (read) lock.
+    Shared,
+    /// An exclusive (write) lock.
+    Exclusive,
+}
+
+/// The owner of a file lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockOwner {
+    /// Owned by a process, like the POSIX record locks (`F_SETLK`). The value
+    /// is the process ID.
+    Process(u64),
+    /// Owned by an open file, like `flock` and the open file description locks
+    /// (`F_OFD_SETLK`). The value identifies the open file.
+    File(usize),
+}
+
+/// A byte-range lock on `start..end` of a file.
+///
+/// `end` is [`u64::MAX`] if the lock extends to the end of the file, however
+/// the file grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLock {
+    /// The type of the lock.
+    pub ty: LockType,
+    /// The first byte of the locked range.
+    pub start: u64,
+    /// The end of the locked range (exclusive).
+    pub end: u64,
+    /// The owner of the lock.
+    pub owner: LockOwner,
+}
+
+impl RangeLock {
+    const fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    fn conflicts(&self, other: &Self) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.ty == LockType::Exclusive || other.ty == LockType::Exclusive)
+    }
+}
+
+/// All the locks on a file.
+#[derive(Default)]
+struct LockSet {
+    ranges: Vec<RangeLock>,
+    whole: Vec<(LockOwner, LockType)>,
+}
+
+impl LockSet {
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty() && self.whole.is_empty()
+    }
+
+    /// Removes the range locks of `owner` on `start..end`, the locks partially
+    /// in the range are shrunk or split.
+    fn unlock_range(&mut self, owner: LockOwner, start: u64, end: u64) {
+        let mut tails = Vec::new();
+        self.ranges.retain_mut(|lock| {
+            if lock.owner != owner || !lock.overlaps(start, end) {
+                return true;
+            }
+            if lock.end > end {
+                tails.push(RangeLock {
+                    start: end,
+                    ..*lock
+                });
+            }
+            lock.end = start;
+            lock.start < start
+        });
+        self.ranges.extend(tails);
+    }
+}
+
+/// Identifies the file that the locks are on.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum LockKey {
+    /// The address of the filesystem object and the inode number.
+    Node(usize, u64),
+    /// The resolved absolute path.
+    Path(String),
+}
+
+static LOCKS: SpinNoIrq<BTreeMap<LockKey, LockSet>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Runs `f` on the locks of the file identified by `key`.
+fn with_locks<R>(key: &LockKey, f: impl FnOnce(&mut LockSet) -> R) -> R {
+    let mut all_locks = LOCKS.lock();
+    let locks = all_locks.entry(key.clone()).or_default();
+    let ret = f(locks);
+    if locks.is_empty() {
+        all_locks.remove(key);
+    }
+    ret
+}
+
+/// The advisory locks of an opened file.
+///
+/// It can be cloned from [`File::locks`](crate::fops::File::locks) to operate
+/// the locks without borrowing the file.
+#[derive(Debug, Clone)]
+pub struct FileLocks {
+    path: String,
+    key: LockKey,
+    owner: LockOwner,
+    cap: Cap,
+}
+
+impl FileLocks {
+    /// Creates the locks of the `node` opened at `path`, where `fs_id`
+    /// identifies the filesystem of the node.
+    pub(crate) fn new(path: String, node: &VfsNodeRef, fs_id: usize, id: usize, cap: Cap) -> Self {
+        let key = match node.ino() {
+            Some(ino) => LockKey::Node(fs_id, ino),
+            None => LockKey::Path(path.clone()),
+        };
+        Self {
+            path,
+            key,
+            owner: LockOwner::File(id),
+            cap,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the lock owner that represents the opened file.
+    pub const fn owner(&self) -> LockOwner {
+        self.owner
+    }
+
+    /// Places a byte-range lock, which replaces the locks of the same owner in
+    /// the range.
+    ///
+    /// A shared lock requires the file to be opened for reading, and an
+    /// exclusive lock requires it to be opened for writing. It fails with
+    /// [`WouldBlock`](axerrno::AxError::WouldBlock) if the lock conflicts with
+    /// the locks of other owners.
+    pub fn lock_range(&self, lock: RangeLock) -> AxResult {
+        let cap = match lock.ty {
+            LockType::Shared => Cap::READ,
+            LockType::Exclusive => Cap::WRITE,
+        };
+        if !self.cap.contains(cap) {
+            return ax_err!(PermissionDenied);
+        }
+        with_locks(&self.key, |locks| {
+            if locks.ranges.iter().any(|l| l.conflicts(&lock)) {
+                return ax_err!(WouldBlock);
+            }
+            locks.unlock_range(lock.owner, lock.start, lock.end);
+            // merge with the adjacent locks of the same owner and type
+            let mut merged = lock;
+            locks.ranges.retain(|l| {
+                let adjacent = l.owner == merged.owner
+                    && l.ty == merged.ty
+                    && l.start <= merged.end
+                    && merged.start <= l.end;
+                if adjacent {
+                    merged.start = merged.start.min(l.start);
+                    merged.end = merged.end.max(l.end);
+                }
+                !adjacent
+            });
+            locks.ranges.push(merged);
+            Ok(())
+        })
+    }
+
+    /// Removes the byte-range locks of `owner` on `start..end`.
+    pub fn unlock_range(&self, owner: LockOwner, start: u64, end: u64) {
+        with_locks(&self.key, |locks| locks.unlock_range(owner, start, end))
+    }
+
+    /// Returns a byte-range lock that conflicts with `lock`, if any.
+    pub fn test_range(&self, lock: &RangeLock) -> Option<RangeLock> {
+        with_locks(&self.key, |locks| {
+            locks.ranges.iter().find(|l| l.conflicts(lock)).copied()
+        })
+    }
+
+    /// Places a whole-file lock, which replaces the previous one of the same
+    /// owner.
+    ///
+    /// It fails with [`WouldBlock`](axerrno::AxError::WouldBlock) if the lock
+    /// conflicts with the locks of other owners.
+    pub fn lock_whole(&self, ty: LockType, owner: LockOwner) -> AxResult {
+        with_locks(&self.key, |locks| {
+            let conflicted = locks.whole.iter().any(|&(o, t)| {
+                o != owner && (t == LockType::Exclusive || ty == LockType::Exclusive)
+            });
+            if conflicted {
+                return ax_err!(WouldBlock);
+            }
+            locks.whole.retain(|&(o, _)| o != owner);
+            locks.whole.push((owner, ty));
+            Ok(())
+        })
+    }
+
+    /// Removes the whole-file lock of `owner`.
+    pub fn unlock_whole(&self, owner: LockOwner) {
+        with_locks(&self.key, |locks| locks.whole.retain(|&(o, _)| o != owner))
+    }
+
+    /// Removes all the locks of `owner`.
+    pub fn unlock_all(&self, owner: LockOwner) {
+        with_locks(&self.key, |locks| {
+            locks.ranges.retain(|l| l.owner != owner);
+            locks.whole.retain(|&(o, _)| o != owner);
+        })
+    }
+}
@@ -1,80 +1,148 @@
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeType, VfsOps, VfsResult};
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::VfsOps;
 
 use crate::fs;
 
+/// Creates the main filesystem on the disk, the type of which is detected from
+/// the contents of the disk.
+#[cfg(not(any(feature = "myfs", feature = "initramfs")))]
+pub(crate) fn main_fs(disk: crate::dev::Disk) -> Arc<dyn VfsOps> {
+    #[cfg(feature = "ext2")]
+    let disk = {
+        let mut disk = disk;
+        if fs::ext2::probe(&mut disk) {
+            info!("  detected ext2 filesystem on the disk");
+            return ext2(disk).expect("failed to initialize ext2 filesystem");
+        }
+        disk
+    };
+
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "fatfs")] {
+            // the RAM disk is empty until formatted
+            #[cfg(feature = "use-ramdisk")]
+            let fs = fs::fatfs::FatFileSystem::format(disk);
+            #[cfg(not(feature = "use-ramdisk"))]
+            let fs = fs::fatfs::FatFileSystem::new(disk);
+            Arc::new(fs.expect("failed to initialize FAT filesystem"))
+        } else {
+            let _ = disk;
+            panic!("no supported filesystem found on the disk")
+        }
+    }
+}
+
+/// Creates a filesystem of type `fstype`. For filesystems on block devices,
+/// `source` is the name of the device (e.g., `/dev/vda2` or `vda2`).
+pub(crate) fn create_filesystem(fstype: &str, source: &str) -> AxResult<Arc<dyn VfsOps>> {
+    match fstype {
+        #[cfg(feature = "ramfs")]
+        "ramfs" | "tmpfs" => Ok(ramfs()),
+        #[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+        "vfat" | "fat" => Ok(fatfs(find_disk(source)?)?),
+        #[cfg(all(feature = "ext2", not(feature = "myfs")))]
+        "ext2" | "ext3" | "ext4" => Ok(ext2(find_disk(source)?)?),
+        _ => {
+            let _ = source; // unused if no filesystem on block devices is enabled
+            ax_err!(Unsupported, "unknown filesystem type")
+        }
+    }
+}
+
+#[cfg(all(any(feature = "fatfs", feature = "ext2"), not(feature = "myfs")))]
+fn find_disk(source: &str) -> AxResult<crate::dev::Disk> {
+    crate::dev::find_disk(source.strip_prefix("/dev/").unwrap_or(source))
+}
+
+#[cfg(all(feature = "fatfs", not(feature = "myfs")))]
+fn fatfs(disk: crate::dev::Disk) -> axfs_vfs::VfsResult<Arc<dyn VfsOps>> {
+    Ok(Arc::new(fs::fatfs::FatFileSystem::new(disk)?))
+}
+
+#[cfg(all(feature = "ext2", not(feature = "myfs")))]
+fn ext2(disk: crate::dev::Disk) -> axfs_vfs::VfsResult<Arc<dyn VfsOps>> {
+    Ok(fs::ext2::new(disk)?)
+}
+
 #[cfg(feature = "devfs")]
 pub(crate) fn devfs() -> Arc<fs::devfs::DeviceFileSystem> {
-    let null = fs::devfs::NullDev;
-    let zero = fs::devfs::ZeroDev;
-    let bar = fs::devfs::ZeroDev;
     let devfs = fs::devfs::DeviceFileSystem::new();
-    let foo_dir = devfs.mkdir("foo");
-    devfs.add("null", Arc::new(null));
-    devfs.add("zero", Arc::new(zero));
-    foo_dir.add("bar", Arc::new(bar));
+    devfs.add("null", Arc::new(fs::devfs::NullDev));
+    devfs.add("zero", Arc::new(fs::devfs::ZeroDev));
+    for (name, node) in crate::dev::disk_nodes() {
+        devfs.add(&name, Arc::new(node));
+    }
+
+    crate::devfs::init(devfs.root().clone());
     Arc::new(devfs)
 }
 
 #[cfg(feature = "ramfs")]
 pub(crate) fn ramfs() -> Arc<fs::ramfs::RamFileSystem> {
-    Arc::new(fs::ramfs::RamFileSystem::new())
+    Arc::new(fs::ramfs::RamFileSystem::with_clock(
+        crate::time::current_time,
+    ))
+}
+
+/// Creates a RAM filesystem with the contents of the initramfs archive.
+#[cfg(all(feature = "initramfs", not(feature = "myfs")))]
+pub(crate) fn initramfs() -> Arc<dyn VfsOps> {
+    let fs = ramfs();
+    let archive = crate::initramfs::archive();
+    match crate::initramfs::unpack(archive, &fs.root_dir()) {
+        Ok(0) => warn!("  initramfs is empty"),
+        Ok(n) => info!("  unpacked {} entries from initramfs", n),
+        Err(e) => panic!("failed to unpack initramfs: {:?}", e),
+    }
+    fs
+}
+
+/// Stacks a writable RAM filesystem over `lower`.
+#[cfg(feature = "overlay")]
+pub(crate) fn overlay(lower: Arc<dyn VfsOps>) -> Arc<dyn VfsOps> {
+    Arc::new(fs::overlay::OverlayFileSystem::new(lower, ramfs()))
 }
 
 #[cfg(feature = "procfs")]
-pub(crate) fn procfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let procfs = fs::ramfs::RamFileSystem::new();
-    let proc_root = procfs.root_dir();
+pub(crate) fn procfs() -> Arc<fs::pseudofs::PseudoFileSystem> {
+    use fs::pseudofs::FileNode;
+
+    let procfs = fs::pseudofs::PseudoFileSystem::new("proc");
+    let proc_root = procfs.root();
+    proc_root.add("mounts", Arc::new(FileNode::new(crate::root::mounts_info)));
 
     // Create /proc/sys/net/core/somaxconn
-    proc_root.create("sys", VfsNodeType::Dir)?;
-    proc_root.create("sys/net", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core", VfsNodeType::Dir)?;
-    proc_root.create("sys/net/core/somaxconn", VfsNodeType::File)?;
-    let file_somaxconn = proc_root.clone().lookup("./sys/net/core/somaxconn")?;
-    file_somaxconn.write_at(0, b"4096\n")?;
+    let sys = proc_root.mkdir("sys");
+    let net_core = sys.mkdir("net").mkdir("core");
+    net_core.add("somaxconn", Arc::new(FileNode::new(|| "4096\n".into())));
 
     // Create /proc/sys/vm/overcommit_memory
-    proc_root.create("sys/vm", VfsNodeType::Dir)?;
-    proc_root.create("sys/vm/overcommit_memory", VfsNodeType::File)?;
-    let file_over = proc_root.clone().lookup("./sys/vm/overcommit_memory")?;
-    file_over.write_at(0, b"0\n")?;
-
-    // Create /proc/self/stat
-    proc_root.create("self", VfsNodeType::Dir)?;
-    proc_root.create("self/stat", VfsNodeType::File)?;
+    let vm = sys.mkdir("vm");
+    vm.add(
+        "overcommit_memory",
+        Arc::new(FileNode::new(|| "0\n".into())),
+    );
 
-    Ok(Arc::new(procfs))
+    crate::procfs::init(proc_root.clone());
+    Arc::new(procfs)
 }
 
 #[cfg(feature = "sysfs")]
-pub(crate) fn sysfs() -> VfsResult<Arc<fs::ramfs::RamFileSystem>> {
-    let sysfs = fs::ramfs::RamFileSystem::new();
-    let sys_root = sysfs.root_dir();
+pub(crate) fn sysfs() -> Arc<fs::pseudofs::PseudoFileSystem> {
+    use fs::pseudofs::FileNode;
+
+    let sysfs = fs::pseudofs::PseudoFileSystem::new("sysfs");
+    let sys_root = sysfs.root();
 
     // Create /sys/kernel/mm/transparent_hugepage/enabled
-    sys_root.create("kernel", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage", VfsNodeType::Dir)?;
-    sys_root.create("kernel/mm/transparent_hugepage/enabled", VfsNodeType::File)?;
-    let file_hp = sys_root
-        .clone()
-        .lookup("./kernel/mm/transparent_hugepage/enabled")?;
-    file_hp.write_at(0, b"always [madvise] never\n")?;
-
-    // Create /sys/devices/system/clocksource/clocksource0/current_clocksource
-    sys_root.create("devices", VfsNodeType::Dir)?;
-    sys_root.create("devices/system", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource", VfsNodeType::Dir)?;
-    sys_root.create("devices/system/clocksource/clocksource0", VfsNodeType::Dir)?;
-    sys_root.create(
-        "devices/system/clocksource/clocksource0/current_clocksource",
-        VfsNodeType::File,
-    )?;
-    let file_cc = sys_root
-        .clone()
-        .lookup("devices/system/clocksource/clocksource0/current_clocksource")?;
-    file_cc.write_at(0, b"tsc\n")?;
-
-    Ok(Arc::new(sysfs))
+    let kernel = sys_root.mkdir("kernel");
+    let thp = kernel.mkdir("mm").mkdir("transparent_hugepage");
+    thp.add(
+        "enabled",
+        Arc::new(FileNode::new(|| "always madvise [never]\n".into())),
+    );
+
+    crate::sysfs::init(sys_root.clone());
+    Arc::new(sysfs)
 }
@@ -0,0 +1,216 @@
+//! MBR and GPT partition tables.
+
+use alloc::{string::String, vec, vec::Vec};
+use axdriver::prelude::*;
+
+use crate::cache::BLOCK_SIZE;
+
+const MBR_SIGNATURE: u16 = 0xAA55;
+const MBR_ENTRY_OFFSET: usize = 446;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+const MBR_TYPE_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+/// The limit on the number of logical partitions, to avoid looping forever on
+/// a corrupted extended boot record chain.
+const MAX_LOGICAL_PARTITIONS: usize = 64;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MAX_ENTRIES: u32 = 256;
+
+/// A partition found in the partition table of a block device.
+#[derive(Debug, Clone)]
+pub struct PartitionInfo {
+    /// The partition number, counted from 1.
+    ///
+    /// For MBR, the primary partitions are numbered from 1 to 4 and the
+    /// logical partitions start from 5. For GPT, it is the index of the entry
+    /// in the partition array plus 1.
+    pub index: usize,
+    /// The first block of the partition.
+    pub start_block: u64,
+    /// The number of blocks in the partition.
+    pub num_blocks: u64,
+    /// The partition name in GPT, or an empty string for MBR.
+    pub label: String,
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// Parses the partition table of the device.
+///
+/// Returns an empty list if the device has no partition table.
+pub fn parse_partitions(dev: &mut dyn BlockDriverOps) -> DevResult<Vec<PartitionInfo>> {
+    let mut parts = parse_mbr(dev)?;
+    let total = dev.num_blocks();
+    parts.retain(|p| {
+        let valid = p.start_block > 0
+            && p.start_block
+                .checked_add(p.num_blocks)
+                .is_some_and(|end| end <= total);
+        if !valid {
+            warn!("partition {} is out of the device, ignored", p.index);
+        }
+        valid
+    });
+    Ok(parts)
+}
+
+fn parse_mbr(dev: &mut dyn BlockDriverOps) -> DevResult<Vec<PartitionInfo>> {
+    let mut mbr = [0; BLOCK_SIZE];
+    dev.read_block(0, &mut mbr)?;
+    if read_u16(&mbr, 510) != MBR_SIGNATURE {
+        return Ok(Vec::new());
+    }
+    let entries: Vec<_> = (0..4)
+        .map(|i| &mbr[MBR_ENTRY_OFFSET + i * 16..MBR_ENTRY_OFFSET + (i + 1) * 16])
+        .collect();
+    // a FAT boot sector also ends with the MBR signature, so it's only an MBR
+    // if the used entries look like partitions
+    let total = dev.num_blocks();
+    let mut used = entries.iter().filter(|e| e[4] != 0).peekable();
+    if used.peek().is_none() || !used.all(|e| is_sane_entry(e, total)) {
+        return Ok(Vec::new());
+    }
+    if entries.iter().any(|e| e[4] == MBR_TYPE_GPT_PROTECTIVE) {
+        return parse_gpt(dev);
+    }
+
+    let mut parts = Vec::new();
+    let mut extended = None;
+    for (i, entry) in entries.iter().enumerate() {
+        let (ty, start, count) = (entry[4], read_u32(entry, 8), read_u32(entry, 12));
+        if ty == 0 || count == 0 {
+            continue;
+        }
+        if MBR_TYPE_EXTENDED.contains(&ty) {
+            extended = Some(start as u64);
+        } else {
+            parts.push(PartitionInfo {
+                index: i + 1,
+                start_block: start as u64,
+                num_blocks: count as u64,
+                label: String::new(),
+            });
+        }
+    }
+    if let Some(ext_start) = extended {
+        parse_logical(dev, ext_start, &mut parts)?;
+    }
+    Ok(parts)
+}
+
+/// Returns whether the used MBR partition entry is well-formed and starts
+/// inside the device.
+fn is_sane_entry(entry: &[u8], total: u64) -> bool {
+    let (start, count) = (read_u32(entry, 8) as u64, read_u32(entry, 12));
+    if entry[0] != 0 && entry[0] != 0x80 {
+        return false; // invalid boot indicator
+    }
+    if entry[4] == MBR_TYPE_GPT_PROTECTIVE {
+        // it may cover more than the device, up to `u32::MAX` blocks
+        start == 1
+    } else {
+        start > 0 && start < total && count != 0
+    }
+}
+
+/// Follows the chain of extended boot records to find the logical partitions.
+fn parse_logical(
+    dev: &mut dyn BlockDriverOps,
+    ext_start: u64,
+    parts: &mut Vec<PartitionInfo>,
+) -> DevResult {
+    let mut ebr = [0; BLOCK_SIZE];
+    let mut ebr_block = ext_start;
+    for index in 5..5 + MAX_LOGICAL_PARTITIONS {
+        dev.read_block(ebr_block, &mut ebr)?;
+        if read_u16(&ebr, 510) != MBR_SIGNATURE {
+            break;
+        }
+        let entry = &ebr[MBR_ENTRY_OFFSET..MBR_ENTRY_OFFSET + 16];
+        let (start, count) = (read_u32(entry, 8), read_u32(entry, 12));
+        if entry[4] != 0 && count != 0 {
+            parts.push(PartitionInfo {
+                index,
+                // relative to the current extended boot record
+                start_block: ebr_block + start as u64,
+                num_blocks: count as u64,
+                label: String::new(),
+            });
+        }
+        let next = &ebr[MBR_ENTRY_OFFSET + 16..MBR_ENTRY_OFFSET + 32];
+        if next[4] == 0 {
+            break;
+        }
+        // relative to the start of the extended partition
+        ebr_block = ext_start + read_u32(next, 8) as u64;
+    }
+    Ok(())
+}
+
+fn parse_gpt(dev: &mut dyn BlockDriverOps) -> DevResult<Vec<PartitionInfo>> {
+    let mut header = [0; BLOCK_SIZE];
+    dev.read_block(1, &mut header)?;
+    if &header[..8] != GPT_SIGNATURE {
+        warn!("invalid GPT header");
+        return Err(DevError::BadState);
+    }
+    let entries_start = read_u64(&header, 72);
+    let num_entries = read_u32(&header, 80);
+    let entry_size = read_u32(&header, 84) as usize;
+    if num_entries > GPT_MAX_ENTRIES || entry_size < 128 || BLOCK_SIZE % entry_size != 0 {
+        warn!("unsupported GPT partition entry array");
+        return Err(DevError::Unsupported);
+    }
+
+    let mut parts = Vec::new();
+    let mut block = vec![0; BLOCK_SIZE];
+    let entries_per_block = BLOCK_SIZE / entry_size;
+    for i in 0..num_entries as usize {
+        if i % entries_per_block == 0 {
+            dev.read_block(entries_start + (i / entries_per_block) as u64, &mut block)?;
+        }
+        let entry = &block[i % entries_per_block * entry_size..][..entry_size];
+        if entry[..16].iter().all(|&b| b == 0) {
+            continue; // unused entry
+        }
+        let (first, last) = (read_u64(entry, 32), read_u64(entry, 40));
+        if last < first {
+            continue;
+        }
+        let name: Vec<u16> = (0..36)
+            .map(|j| read_u16(entry, 56 + j * 2))
+            .take_while(|&c| c != 0)
+            .collect();
+        parts.push(PartitionInfo {
+            index: i + 1,
+            start_block: first,
+            // checked against the device size later
+            num_blocks: (last - first).saturating_add(1),
+            label: String::from_utf16_lossy(&name),
+        });
+    }
+    Ok(parts)
+}
+
+/// Selects the partition by the partition number (e.g., `"2"`) or by the GPT
+/// label (e.g., `"rootfs"`). The first partition is selected if `spec` is
+/// empty.
+pub fn select_partition<'a>(parts: &'a [PartitionInfo], spec: &str) -> Option<&'a PartitionInfo> {
+    if spec.is_empty() {
+        parts.first()
+    } else if let Ok(index) = spec.parse::<usize>() {
+        parts.iter().find(|p| p.index == index)
+    } else {
+        parts.iter().find(|p| p.label == spec)
+    }
+}
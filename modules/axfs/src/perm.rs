@@ -0,0 +1,162 @@
+//! Credentials of the current task and the permission checks of files.
+//!
+//! Files are accessed with the user ID and group ID of the current task, which
+//! are provided by [`CredentialsIf`]. The root user (ID 0) can read and write
+//! any file, and search any directory, regardless of the permission bits.
+
+use axerrno::{ax_err, AxResult};
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodePerm, VfsNodeRef, VfsResult};
+use capability::Cap;
+
+/// The user ID and group ID that files are accessed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    /// The user ID.
+    pub uid: u32,
+    /// The group ID.
+    pub gid: u32,
+}
+
+impl Credentials {
+    /// The credentials of the root user.
+    pub const ROOT: Self = Self::new(0, 0);
+
+    /// Creates credentials with the given user ID and group ID.
+    pub const fn new(uid: u32, gid: u32) -> Self {
+        Self { uid, gid }
+    }
+
+    /// Whether these are the credentials of the root user.
+    pub const fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+}
+
+/// The interface to get the credentials of the current task, which must be
+/// implemented by the runtime (or the tests).
+#[crate_interface::def_interface]
+pub trait CredentialsIf {
+    /// Returns the credentials of the current task.
+    fn current_credentials() -> Credentials;
+
+    /// Returns the file mode creation mask of the current task.
+    fn current_umask() -> u32;
+
+    /// Sets the file mode creation mask of the current task, returns the
+    /// previous mask.
+    fn set_current_umask(umask: u32) -> u32;
+}
+
+pub(crate) fn current_credentials() -> Credentials {
+    crate_interface::call_interface!(CredentialsIf::current_credentials())
+}
+
+pub(crate) fn current_umask() -> u32 {
+    crate_interface::call_interface!(CredentialsIf::current_umask())
+}
+
+pub(crate) fn set_current_umask(umask: u32) -> u32 {
+    crate_interface::call_interface!(CredentialsIf::set_current_umask(umask))
+}
+
+/// Returns the capabilities that `creds` have on a node with attributes
+/// `attr`.
+pub(crate) fn node_cap(creds: &Credentials, attr: &VfsNodeAttr) -> Cap {
+    let mode = attr.perm().mode();
+    if creds.is_root() {
+        // root can execute a file only if anyone can
+        return if attr.is_dir() || mode & 0o111 != 0 {
+            Cap::READ | Cap::WRITE | Cap::EXECUTE
+        } else {
+            Cap::READ | Cap::WRITE
+        };
+    }
+
+    let bits = if creds.uid == attr.uid() {
+        mode >> 6
+    } else if creds.gid == attr.gid() {
+        mode >> 3
+    } else {
+        mode
+    };
+    let mut cap = Cap::empty();
+    if bits & 0o4 != 0 {
+        cap |= Cap::READ;
+    }
+    if bits & 0o2 != 0 {
+        cap |= Cap::WRITE;
+    }
+    if bits & 0o1 != 0 {
+        cap |= Cap::EXECUTE;
+    }
+    cap
+}
+
+/// Checks whether `creds` have all the capabilities `cap` on `node`.
+pub(crate) fn check_access(creds: &Credentials, node: &VfsNodeRef, cap: Cap) -> AxResult {
+    if node_cap(creds, &node.get_attr()?).contains(cap) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Checks whether the current task can change the permission (both `uid` and
+/// `gid` are `None`) or the owner of a node with attributes `attr`.
+///
+/// Only root can change the owner, but the owner can change the permission,
+/// or change the group to its own group.
+pub(crate) fn check_change(attr: &VfsNodeAttr, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+    let creds = current_credentials();
+    if creds.is_root() {
+        return Ok(());
+    }
+    let uid_ok = uid.map_or(true, |uid| uid == attr.uid());
+    let gid_ok = gid.map_or(true, |gid| gid == attr.gid() || gid == creds.gid);
+    if creds.uid == attr.uid() && uid_ok && gid_ok {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Checks whether the current task can change the timestamps of a node with
+/// attributes `attr`, `to_now` means setting both of them to the current time.
+///
+/// Only the owner and root can set arbitrary timestamps, but anyone who can
+/// write the node can set them to the current time.
+pub(crate) fn check_set_times(attr: &VfsNodeAttr, to_now: bool) -> AxResult {
+    let creds = current_credentials();
+    if creds.is_root()
+        || creds.uid == attr.uid()
+        || (to_now && node_cap(&creds, attr).contains(Cap::WRITE))
+    {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// Sets the permission and the owner of a newly created node, the permission
+/// is `mode` masked by the umask of the current task, or unchanged if `mode`
+/// is `None`.
+///
+/// Filesystems that do not support permissions or owners are ignored.
+pub(crate) fn init_new_node(node: &VfsNodeRef, mode: Option<u32>) -> AxResult {
+    fn ignore_unsupported(res: VfsResult) -> AxResult {
+        match res {
+            Err(VfsError::Unsupported) => Ok(()),
+            res => res,
+        }
+    }
+
+    if let Some(mode) = mode {
+        let perm = VfsNodePerm::from_bits_truncate((mode & !current_umask()) as u16);
+        ignore_unsupported(node.set_perm(perm))?;
+    }
+    let creds = current_credentials();
+    if !creds.is_root() {
+        ignore_unsupported(node.set_owner(creds.uid, creds.gid))?;
+    }
+    Ok(())
+}
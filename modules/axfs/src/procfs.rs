@@ -0,0 +1,24 @@
+//! The process information pseudo filesystem mounted on `/proc`.
+//!
+//! The contents of the files are generated from the live kernel states on
+//! each read. The filesystem itself provides `/proc/mounts` and a few entries
+//! in `/proc/sys`, other modules add their entries (e.g., `/proc/meminfo` and
+//! the per-task directories) to the directory returned by [`root`] after the
+//! filesystems are initialized.
+
+use alloc::sync::Arc;
+use axsync::Mutex;
+
+pub use axfs_pseudofs::{DirNode, FileNode, SymlinkNode};
+
+static PROC_ROOT: Mutex<Option<Arc<DirNode>>> = Mutex::new(None);
+
+pub(crate) fn init(root: Arc<DirNode>) {
+    *PROC_ROOT.lock() = Some(root);
+}
+
+/// Returns the root directory of the procfs, or `None` if the filesystems are
+/// not initialized.
+pub fn root() -> Option<Arc<DirNode>> {
+    PROC_ROOT.lock().clone()
+}
@@ -1,34 +1,94 @@
-//! Root directory of the filesystem
+//! Root directory of the filesystem and the mount table.
 //!
-//! TODO: it doesn't work very well if the mount points have containment relationships.
+//! Filesystems can be mounted at any directory at runtime, including the
+//! directories inside other mounted filesystems. Paths are resolved component
+//! by component from the root, crossing the mount points, handling `..` and
+//! following symbolic links on the way.
+//!
+//! Each directory on the way must be searchable by the current task, and
+//! entries can only be added to or removed from writable directories.
 
-use alloc::{string::String, sync::Arc, vec::Vec};
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{ax_err, AxError, AxResult};
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{VfsNodeRef, VfsNodeType, VfsOps};
 use axsync::Mutex;
-use lazy_init::LazyInit;
+use capability::Cap;
+
+use crate::mounts;
+use crate::perm::{self, Credentials};
 
-use crate::{api::FileType, fs, mounts};
+/// The maximum number of symbolic links that can be followed during a single
+/// path resolution.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
-static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
 
-struct MountPoint {
-    path: &'static str,
+/// The mount table, the root filesystem is always the first entry.
+///
+/// Paths are resolved on a snapshot of it, so that it's not locked while
+/// walking the filesystems.
+static MOUNT_TABLE: Mutex<Vec<Arc<MountPoint>>> = Mutex::new(Vec::new());
+
+/// A filesystem mounted at some directory.
+///
+/// Opened files and directories hold a reference to the mount point of the
+/// filesystem they belong to, to prevent it from being unmounted.
+pub(crate) struct MountPoint {
+    path: String,
     fs: Arc<dyn VfsOps>,
 }
 
-struct RootDirectory {
-    main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+/// A node found by path resolution.
+pub(crate) struct NodeLocation {
+    /// The node itself.
+    pub node: VfsNodeRef,
+    /// The absolute path of the node, in which all `.`, `..` and symbolic
+    /// links are resolved.
+    pub path: String,
+    /// The mount point of the filesystem that the node belongs to.
+    pub mount: Arc<MountPoint>,
 }
 
-static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
+/// Resolves paths component by component, keeping the nodes from the root
+/// to the current directory.
+struct PathWalker {
+    /// Snapshot of the mount table when the walker is created.
+    mounts: Vec<Arc<MountPoint>>,
+    /// Absolute path of the current node.
+    path: String,
+    /// Nodes from the root to the current node.
+    nodes: Vec<VfsNodeRef>,
+    /// Whether the current node is a directory.
+    is_dir: bool,
+    /// Number of symbolic links followed so far.
+    follows: usize,
+    /// Credentials of the current task.
+    creds: Credentials,
+}
 
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
+    fn new(path: String, fs: Arc<dyn VfsOps>) -> Self {
         Self { path, fs }
     }
+
+    /// Whether the given absolute `path` is in this mounted filesystem
+    /// (ignoring the nested mount points).
+    fn contains(&self, path: &str) -> bool {
+        self.path == "/"
+            || path == self.path
+            || (path.starts_with(&self.path) && path[self.path.len()..].starts_with('/'))
+    }
+
+    /// Converts the absolute `path` to the path relative to the root of this
+    /// mounted filesystem.
+    fn relative_path<'a>(&self, path: &'a str) -> &'a str {
+        path[self.path.len()..].trim_start_matches('/')
+    }
+
+    /// Identifies the mounted filesystem among the live ones.
+    pub(crate) fn fs_id(&self) -> usize {
+        Arc::as_ptr(&self.fs) as *const () as usize
+    }
 }
 
 impl Drop for MountPoint {
@@ -37,214 +97,336 @@ impl Drop for MountPoint {
     }
 }
 
-impl RootDirectory {
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+impl PathWalker {
+    fn new() -> Self {
+        let mounts = MOUNT_TABLE.lock().clone();
         Self {
-            main_fs,
-            mounts: Vec::new(),
+            path: "/".into(),
+            nodes: vec![mounts[0].fs.root_dir()],
+            mounts,
+            is_dir: true,
+            follows: 0,
+            creds: perm::current_credentials(),
         }
     }
 
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
-            return ax_err!(InvalidInput, "cannot mount root filesystem");
-        }
+    /// Creates a walker at the directory where the relative `path` starts
+    /// from, which is `dir` or the current working directory if not given.
+    fn start_at(dir: Option<&str>, path: &str) -> AxResult<Self> {
+        let mut walker = Self::new();
         if !path.starts_with('/') {
-            return ax_err!(InvalidInput, "mount path must start with '/'");
+            match dir {
+                Some(dir) => walker.walk(dir, true)?,
+                None => walker.walk(&CURRENT_DIR_PATH.lock().clone(), true)?,
+            }
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {
-            return ax_err!(InvalidInput, "mount point already exists");
+        Ok(walker)
+    }
+
+    fn current(&self) -> &VfsNodeRef {
+        self.nodes.last().unwrap()
+    }
+
+    fn child_path(&self, name: &str) -> String {
+        if self.path == "/" {
+            format!("/{}", name)
+        } else {
+            format!("{}/{}", self.path, name)
         }
-        // create the mount point in the main filesystem if it does not exist
-        self.main_fs.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
-        Ok(())
     }
 
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    fn mount_at(&self, path: &str) -> Option<&Arc<MountPoint>> {
+        self.mounts.iter().find(|mp| mp.path == path)
     }
 
-    pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+    /// Returns the mount point of the filesystem that the node at the given
+    /// absolute `path` belongs to.
+    fn mount_of(&self, path: &str) -> &Arc<MountPoint> {
+        self.mounts
+            .iter()
+            .filter(|mp| mp.contains(path))
+            .max_by_key(|mp| mp.path.len())
+            .unwrap()
     }
 
-    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
-    where
-        F: FnOnce(Arc<dyn VfsOps>, &str) -> AxResult<T>,
-    {
-        debug!("lookup at root: {}", path);
-        let path = path.trim_matches('/');
-        if let Some(rest) = path.strip_prefix("./") {
-            return self.lookup_mounted_fs(rest, f);
-        }
+    fn push(&mut self, name: &str, node: VfsNodeRef, is_dir: bool) {
+        self.path = self.child_path(name);
+        self.nodes.push(node);
+        self.is_dir = is_dir;
+    }
 
-        let mut idx = 0;
-        let mut max_len = 0;
+    fn pop(&mut self) {
+        if self.nodes.len() > 1 {
+            self.nodes.pop();
+            self.path.truncate(self.path.rfind('/').unwrap().max(1));
+            self.is_dir = true;
+        } // the parent of '/' is itself
+    }
 
-        // Find the filesystem that has the longest mounted path match
-        // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
-            // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len {
-                max_len = mp.path.len() - 1;
-                idx = i;
+    /// Walks along `path` from the current node.
+    ///
+    /// Symbolic links in the middle of the path are always followed, the last
+    /// component is followed only if `follow_last` is `true`.
+    fn walk(&mut self, path: &str, follow_last: bool) -> AxResult {
+        if path.starts_with('/') {
+            self.nodes.truncate(1);
+            self.path = "/".into();
+            self.is_dir = true;
+        }
+        let mut names = path
+            .split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+            .peekable();
+        while let Some(name) = names.next() {
+            if !self.is_dir {
+                return ax_err!(NotADirectory);
+            } else if !self.creds.is_root() {
+                perm::check_access(&self.creds, self.current(), Cap::EXECUTE)?;
+            }
+            if name == ".." {
+                self.pop();
+                continue;
+            }
+            let node = match self.mount_at(&self.child_path(name)) {
+                Some(mp) => mp.fs.root_dir(),
+                None => self.current().clone().lookup(name)?,
+            };
+            let attr = node.get_attr()?;
+            let is_last = names.peek().is_none();
+            if attr.is_symlink() && (!is_last || follow_last) {
+                self.follows += 1;
+                if self.follows > MAX_SYMLINK_FOLLOWS {
+                    return ax_err!(FilesystemLoop);
+                }
+                // relative targets are resolved from the directory of the link
+                self.walk(&read_link_target(&node)?, true)?;
+            } else {
+                self.push(name, node, attr.is_dir());
             }
         }
+        Ok(())
+    }
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`
+    /// Walks to the directory that contains the last component of `path`,
+    /// returns the name of the last component.
+    fn walk_parent<'p>(&mut self, path: &'p str) -> AxResult<&'p str> {
+        let trimmed = path.trim_end_matches('/');
+        let (parent_path, name) = match trimmed.rfind('/') {
+            Some(n) => (&trimmed[..n + 1], &trimmed[n + 1..]),
+            None => ("", trimmed),
+        };
+        self.walk(parent_path, true)?;
+        if !self.is_dir {
+            return ax_err!(NotADirectory);
+        }
+        Ok(name)
+    }
+
+    /// Checks whether entries can be added to or removed from the current
+    /// directory.
+    fn check_writable(&self) -> AxResult {
+        perm::check_access(&self.creds, self.current(), Cap::WRITE | Cap::EXECUTE)
+    }
+
+    fn into_location(self) -> NodeLocation {
+        let mount = self.mount_of(&self.path).clone();
+        NodeLocation {
+            node: self.current().clone(),
+            path: self.path,
+            mount,
         }
     }
 }
 
-impl VfsNodeOps for RootDirectory {
-    axfs_vfs::impl_vfs_dir_default! {}
+fn read_link_target(node: &VfsNodeRef) -> AxResult<String> {
+    let mut buf = vec![0; node.get_attr()?.size() as usize];
+    let len = node.readlink(&mut buf)?;
+    buf.truncate(len);
+    String::from_utf8(buf).map_err(|_| AxError::InvalidData)
+}
 
-    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        self.main_fs.root_dir().get_attr()
+fn lookup_impl(dir: Option<&str>, path: &str, follow_last: bool) -> AxResult<NodeLocation> {
+    if path.is_empty() {
+        return ax_err!(NotFound);
     }
+    let mut walker = PathWalker::start_at(dir, path)?;
+    walker.walk(path, follow_last)?;
+    if path.ends_with('/') && !walker.is_dir {
+        ax_err!(NotADirectory)
+    } else {
+        Ok(walker.into_location())
+    }
+}
 
-    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        self.lookup_mounted_fs(path, |fs, rest_path| fs.root_dir().lookup(rest_path))
+/// Mounts the filesystem `fs` at the directory `path`.
+///
+/// The directory is created if it does not exist.
+pub(crate) fn mount(path: &str, fs: Arc<dyn VfsOps>) -> AxResult {
+    let mut walker = PathWalker::start_at(None, path)?;
+    match walker.walk(path, true) {
+        Ok(()) => {}
+        Err(AxError::NotFound) => {
+            // create the mount point if it does not exist
+            let mut parent = PathWalker::start_at(None, path)?;
+            let name = parent.walk_parent(path)?;
+            parent.current().create(name, VfsNodeType::Dir)?;
+            walker = PathWalker::start_at(None, path)?;
+            walker.walk(path, true)?;
+        }
+        Err(e) => return Err(e),
+    }
+    if !walker.is_dir {
+        return ax_err!(NotADirectory);
     }
 
-    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                Ok(()) // already exists
-            } else {
-                fs.root_dir().create(rest_path, ty)
-            }
-        })
+    let path = walker.path;
+    let mount_point = walker.nodes.pop().unwrap();
+    let mut mounts = MOUNT_TABLE.lock();
+    if mounts.iter().any(|mp| mp.path == path) {
+        return ax_err!(ResourceBusy, "mount point already in use");
     }
+    fs.mount(&path, mount_point)?;
+    mounts.push(Arc::new(MountPoint::new(path, fs)));
+    Ok(())
+}
 
-    fn remove(&self, path: &str) -> VfsResult {
-        self.lookup_mounted_fs(path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot remove mount points
-            } else {
-                fs.root_dir().remove(rest_path)
-            }
-        })
+/// Unmounts the filesystem mounted at the directory `path`.
+///
+/// Returns [`AxError::ResourceBusy`] if the filesystem is still in use, i.e.
+/// there are opened files or directories in it, other filesystems are mounted
+/// inside it, or the current working directory is in it.
+pub(crate) fn umount(path: &str) -> AxResult {
+    // the snapshot of the walker is dropped before checking the users
+    let path = {
+        let mut walker = PathWalker::start_at(None, path)?;
+        walker.walk(path, true)?;
+        walker.path
+    };
+    let mut mounts = MOUNT_TABLE.lock();
+    let idx = match mounts.iter().position(|mp| mp.path == path) {
+        Some(0) => return ax_err!(ResourceBusy, "cannot unmount the root filesystem"),
+        Some(idx) => idx,
+        None => return ax_err!(InvalidInput, "not a mount point"),
+    };
+    let mp = &mounts[idx];
+    if Arc::strong_count(mp) > 1
+        || mounts
+            .iter()
+            .any(|other| other.path != path && mp.contains(&other.path))
+        || mp.contains(&CURRENT_DIR_PATH.lock())
+    {
+        return ax_err!(ResourceBusy);
     }
+    mounts.remove(idx);
+    Ok(())
+}
 
-    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
-        self.lookup_mounted_fs(src_path, |fs, rest_path| {
-            if rest_path.is_empty() {
-                ax_err!(PermissionDenied) // cannot rename mount points
-            } else {
-                fs.root_dir().rename(rest_path, dst_path)
-            }
+/// Returns the mounted filesystems in the format of `/proc/mounts`.
+#[cfg(feature = "procfs")]
+pub(crate) fn mounts_info() -> String {
+    MOUNT_TABLE
+        .lock()
+        .iter()
+        .map(|mp| {
+            let fs_type = mp.fs.fs_type();
+            format!("{} {} {} rw 0 0\n", fs_type, mp.path, fs_type)
         })
-    }
+        .collect()
 }
 
-pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+pub(crate) fn init_rootfs(disk: Option<crate::dev::Disk>) {
     cfg_if::cfg_if! {
         if #[cfg(feature = "myfs")] { // override the default filesystem
-            let main_fs = fs::myfs::new_myfs(disk);
-        } else if #[cfg(feature = "fatfs")] {
-            static FAT_FS: LazyInit<Arc<fs::fatfs::FatFileSystem>> = LazyInit::new();
-            FAT_FS.init_by(Arc::new(fs::fatfs::FatFileSystem::new(disk)));
-            FAT_FS.init();
-            let main_fs = FAT_FS.clone();
+            let main_fs = crate::fs::myfs::new_myfs(disk.expect("No block device found!"));
+        } else if #[cfg(feature = "initramfs")] {
+            let _ = disk;
+            let main_fs = mounts::initramfs();
+        } else {
+            let main_fs = mounts::main_fs(disk.expect("No block device found!"));
         }
     }
+    #[cfg(feature = "overlay")]
+    let main_fs = mounts::overlay(main_fs);
 
-    let mut root_dir = RootDirectory::new(main_fs);
+    MOUNT_TABLE
+        .lock()
+        .push(Arc::new(MountPoint::new("/".into(), main_fs)));
+    *CURRENT_DIR_PATH.lock() = "/".into();
 
     #[cfg(feature = "devfs")]
-    root_dir
-        .mount("/dev", mounts::devfs())
-        .expect("failed to mount devfs at /dev");
+    mount("/dev", mounts::devfs()).expect("failed to mount devfs at /dev");
 
     #[cfg(feature = "ramfs")]
-    root_dir
-        .mount("/tmp", mounts::ramfs())
-        .expect("failed to mount ramfs at /tmp");
+    mount("/tmp", mounts::ramfs()).expect("failed to mount ramfs at /tmp");
 
-    // Mount another ramfs as procfs
     #[cfg(feature = "procfs")]
-    root_dir // should not fail
-        .mount("/proc", mounts::procfs().unwrap())
-        .expect("fail to mount procfs at /proc");
+    mount("/proc", mounts::procfs()).expect("fail to mount procfs at /proc");
 
-    // Mount another ramfs as sysfs
     #[cfg(feature = "sysfs")]
-    root_dir // should not fail
-        .mount("/sys", mounts::sysfs().unwrap())
-        .expect("fail to mount sysfs at /sys");
-
-    ROOT_DIR.init_by(Arc::new(root_dir));
-    CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
-    *CURRENT_DIR_PATH.lock() = "/".into();
-}
-
-fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
-    if path.starts_with('/') {
-        ROOT_DIR.clone()
-    } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
-    }
+    mount("/sys", mounts::sysfs()).expect("fail to mount sysfs at /sys");
 }
 
 pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
     if path.starts_with('/') {
         Ok(axfs_vfs::path::canonicalize(path))
     } else {
-        let path = CURRENT_DIR_PATH.lock().clone() + path;
+        let path = CURRENT_DIR_PATH.lock().clone() + "/" + path;
         Ok(axfs_vfs::path::canonicalize(&path))
     }
 }
 
-pub(crate) fn lookup(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
-    if path.is_empty() {
-        return ax_err!(NotFound);
-    }
-    let node = parent_node_of(dir, path).lookup(path)?;
-    if path.ends_with('/') && !node.get_attr()?.is_dir() {
-        ax_err!(NotADirectory)
-    } else {
-        Ok(node)
-    }
+pub(crate) fn lookup(dir: Option<&str>, path: &str) -> AxResult<NodeLocation> {
+    lookup_impl(dir, path, true)
+}
+
+pub(crate) fn lookup_no_follow(dir: Option<&str>, path: &str) -> AxResult<NodeLocation> {
+    lookup_impl(dir, path, false)
 }
 
-pub(crate) fn create_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult<VfsNodeRef> {
+pub(crate) fn create_file(dir: Option<&str>, path: &str, mode: u32) -> AxResult<NodeLocation> {
     if path.is_empty() {
         return ax_err!(NotFound);
     } else if path.ends_with('/') {
         return ax_err!(NotADirectory);
     }
-    let parent = parent_node_of(dir, path);
-    parent.create(path, VfsNodeType::File)?;
-    parent.lookup(path)
+    let mut walker = PathWalker::start_at(dir, path)?;
+    let name = walker.walk_parent(path)?;
+    walker.check_writable()?;
+    walker.current().create(name, VfsNodeType::File)?;
+    let node = walker.current().clone().lookup(name)?;
+    perm::init_new_node(&node, Some(mode))?;
+    walker.push(name, node, false);
+    Ok(walker.into_location())
 }
 
-pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    match lookup(dir, path) {
+pub(crate) fn create_dir(dir: Option<&str>, path: &str, mode: u32) -> AxResult {
+    match lookup_no_follow(dir, path) {
         Ok(_) => ax_err!(AlreadyExists),
-        Err(AxError::NotFound) => parent_node_of(dir, path).create(path, VfsNodeType::Dir),
+        Err(AxError::NotFound) => {
+            let mut walker = PathWalker::start_at(dir, path)?;
+            let name = walker.walk_parent(path)?;
+            walker.check_writable()?;
+            walker.current().create(name, VfsNodeType::Dir)?;
+            let node = walker.current().clone().lookup(name)?;
+            perm::init_new_node(&node, Some(mode))
+        }
         Err(e) => Err(e),
     }
 }
 
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
-    let node = lookup(dir, path)?;
-    let attr = node.get_attr()?;
+pub(crate) fn remove_file(dir: Option<&str>, path: &str) -> AxResult {
+    let attr = lookup_no_follow(dir, path)?.node.get_attr()?;
     if attr.is_dir() {
         ax_err!(IsADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        let mut walker = PathWalker::start_at(dir, path)?;
+        let name = walker.walk_parent(path)?;
+        walker.check_writable()?;
+        walker.current().remove(name)
     }
 }
 
-pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+pub(crate) fn remove_dir(dir: Option<&str>, path: &str) -> AxResult {
     if path.is_empty() {
         return ax_err!(NotFound);
     }
@@ -258,18 +440,19 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     {
         return ax_err!(InvalidInput);
     }
-    if ROOT_DIR.contains(&absolute_path(path)?) {
-        return ax_err!(PermissionDenied);
-    }
 
-    let node = lookup(dir, path)?;
-    let attr = node.get_attr()?;
+    let loc = lookup_no_follow(dir, path)?;
+    let attr = loc.node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_writable() {
-        ax_err!(PermissionDenied)
+    } else if loc.mount.path == loc.path {
+        ax_err!(PermissionDenied) // mount points cannot be removed
     } else {
-        parent_node_of(dir, path).remove(path)
+        drop(loc);
+        let mut walker = PathWalker::start_at(dir, path)?;
+        let name = walker.walk_parent(path)?;
+        walker.check_writable()?;
+        walker.current().remove(name)
     }
 }
 
@@ -278,33 +461,85 @@ pub(crate) fn current_dir() -> AxResult<String> {
 }
 
 pub(crate) fn set_current_dir(path: &str) -> AxResult {
-    let mut abs_path = absolute_path(path)?;
-    if !abs_path.ends_with('/') {
-        abs_path += "/";
-    }
-    if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
-        *CURRENT_DIR_PATH.lock() = "/".into();
-        return Ok(());
-    }
-
-    let node = lookup(None, &abs_path)?;
-    let attr = node.get_attr()?;
+    let loc = lookup(None, path)?;
+    let attr = loc.node.get_attr()?;
     if !attr.is_dir() {
         ax_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
+    } else if !perm::node_cap(&perm::current_credentials(), &attr).contains(Cap::EXECUTE) {
         ax_err!(PermissionDenied)
     } else {
-        *CURRENT_DIR.lock() = node;
-        *CURRENT_DIR_PATH.lock() = abs_path;
+        *CURRENT_DIR_PATH.lock() = loc.path;
         Ok(())
     }
 }
 
-pub(crate) fn rename(old: &str, new: &str) -> AxResult {
-    if parent_node_of(None, new).lookup(new).is_ok() {
+pub(crate) fn rename(
+    old_dir: Option<&str>,
+    old: &str,
+    new_dir: Option<&str>,
+    new: &str,
+) -> AxResult {
+    if lookup(new_dir, new).is_ok() {
         warn!("dst file already exist, now remove it");
-        remove_file(None, new)?;
+        remove_file(new_dir, new)?;
+    }
+
+    let mut src = PathWalker::start_at(old_dir, old)?;
+    let src_name = src.walk_parent(old)?;
+    let mut dst = PathWalker::start_at(new_dir, new)?;
+    let dst_name = dst.walk_parent(new)?;
+    src.check_writable()?;
+    dst.check_writable()?;
+    let (src_path, dst_path) = (src.child_path(src_name), dst.child_path(dst_name));
+
+    let mount = src.mount_of(&src_path);
+    if mount.path == src_path {
+        return ax_err!(PermissionDenied); // cannot rename mount points
+    } else if !Arc::ptr_eq(mount, dst.mount_of(&dst_path)) {
+        return ax_err!(CrossesDevices);
+    }
+    mount.fs.root_dir().rename(
+        mount.relative_path(&src_path),
+        mount.relative_path(&dst_path),
+    )
+}
+
+pub(crate) fn symlink(dir: Option<&str>, target: &str, path: &str) -> AxResult {
+    if path.is_empty() || target.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let mut walker = PathWalker::start_at(dir, path)?;
+    let name = walker.walk_parent(path)?;
+    if name.is_empty() || name == "." || name == ".." {
+        return ax_err!(AlreadyExists);
+    }
+    walker.check_writable()?;
+    walker.current().symlink(name, target)?;
+    // the permission of a symbolic link is never used
+    perm::init_new_node(&walker.current().clone().lookup(name)?, None)
+}
+
+pub(crate) fn link(dir: Option<&str>, old: &str, new: &str) -> AxResult {
+    let src = lookup_no_follow(dir, old)?;
+    if src.node.get_attr()?.is_dir() {
+        return ax_err!(PermissionDenied); // hard links to directories are not allowed
+    }
+
+    let mut walker = PathWalker::start_at(dir, new)?;
+    let name = walker.walk_parent(new)?;
+    if name.is_empty() || name == "." || name == ".." {
+        return ax_err!(AlreadyExists);
+    } else if !Arc::ptr_eq(&src.mount, walker.mount_of(&walker.child_path(name))) {
+        return ax_err!(CrossesDevices);
+    }
+    walker.check_writable()?;
+    walker.current().link(name, src.node)
+}
+
+pub(crate) fn read_link(dir: Option<&str>, path: &str) -> AxResult<String> {
+    let node = lookup_no_follow(dir, path)?.node;
+    if !node.get_attr()?.is_symlink() {
+        return ax_err!(InvalidInput);
     }
-    parent_node_of(None, old).rename(old, new)
+    read_link_target(&node)
 }
@@ -0,0 +1,26 @@
+//! The pseudo filesystem mounted on `/sys`, which exposes the devices and
+//! kernel parameters.
+//!
+//! The filesystem itself only provides a few compatible entries in
+//! `/sys/kernel`, other modules add their entries (e.g., the attributes of
+//! the probed devices) to the directory returned by [`root`] after the
+//! filesystems are initialized. A writable attribute is created by
+//! [`FileNode::new_writable`], the written data is passed to the module that
+//! owns the attribute.
+
+use alloc::sync::Arc;
+use axsync::Mutex;
+
+pub use axfs_pseudofs::{DirNode, FileNode, SymlinkNode};
+
+static SYS_ROOT: Mutex<Option<Arc<DirNode>>> = Mutex::new(None);
+
+pub(crate) fn init(root: Arc<DirNode>) {
+    *SYS_ROOT.lock() = Some(root);
+}
+
+/// Returns the root directory of the sysfs, or `None` if the filesystems are
+/// not initialized.
+pub fn root() -> Option<Arc<DirNode>> {
+    SYS_ROOT.lock().clone()
+}
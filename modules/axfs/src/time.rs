@@ -0,0 +1,16 @@
+//! The clock for the timestamps of files.
+
+use core::time::Duration;
+
+/// The interface to get the current time for the timestamps of files, which
+/// must be implemented by the runtime (or the tests).
+#[crate_interface::def_interface]
+pub trait ClockIf {
+    /// Returns the current time, as the duration since the UNIX epoch (or
+    /// since boot if the wall-clock time is unknown).
+    fn current_time() -> Duration;
+}
+
+pub(crate) fn current_time() -> Duration {
+    crate_interface::call_interface!(ClockIf::current_time())
+}
@@ -1,7 +1,11 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
 use axfs::api as fs;
+use axfs::fops::{self, ClockIf, Credentials, CredentialsIf, LockOwner, LockType, RangeLock};
 use axio as io;
 
-use fs::{File, FileType, OpenOptions};
+use fs::{File, FileType, OpenOptions, Permissions};
 use io::{prelude::*, Error, Result};
 
 macro_rules! assert_err {
@@ -13,6 +17,48 @@ macro_rules! assert_err {
     };
 }
 
+static UID: AtomicU32 = AtomicU32::new(0);
+static GID: AtomicU32 = AtomicU32::new(0);
+static UMASK: AtomicU32 = AtomicU32::new(0o022);
+
+struct CredentialsIfImpl;
+
+#[crate_interface::impl_interface]
+impl CredentialsIf for CredentialsIfImpl {
+    fn current_credentials() -> Credentials {
+        Credentials::new(UID.load(Ordering::Relaxed), GID.load(Ordering::Relaxed))
+    }
+
+    fn current_umask() -> u32 {
+        UMASK.load(Ordering::Relaxed)
+    }
+
+    fn set_current_umask(umask: u32) -> u32 {
+        UMASK.swap(umask, Ordering::Relaxed)
+    }
+}
+
+fn set_credentials(uid: u32, gid: u32) {
+    UID.store(uid, Ordering::Relaxed);
+    GID.store(gid, Ordering::Relaxed);
+}
+
+/// The current time in seconds, which only changes by [`set_time`].
+static NOW: AtomicU64 = AtomicU64::new(0);
+
+struct ClockIfImpl;
+
+#[crate_interface::impl_interface]
+impl ClockIf for ClockIfImpl {
+    fn current_time() -> Duration {
+        Duration::from_secs(NOW.load(Ordering::Relaxed))
+    }
+}
+
+fn set_time(secs: u64) {
+    NOW.store(secs, Ordering::Relaxed);
+}
+
 fn test_read_write_file() -> Result<()> {
     let fname = "///very/long//.././long//./path/./test.txt";
     println!("read and write file {:?}:", fname);
@@ -208,14 +254,20 @@ fn test_devfs_ramfs() -> Result<()> {
     assert!(!md.is_file());
     assert!(md.is_dir());
 
-    // stat /dev/foo/bar
-    let fname = ".//.///././/./dev///.///./foo//././bar";
+    // stat /dev/zero
+    let fname = ".//.///././/./dev///.///./..//dev//././zero";
     let file = File::open(fname)?;
     let md = file.metadata()?;
     println!("metadata of {:?}: {:?}", fname, md);
     assert_eq!(md.file_type(), FileType::CharDevice);
     assert!(!md.is_dir());
 
+    // the raw block device holding the root filesystem
+    assert!(dirents.contains(&"vda".into()));
+    let md = fs::metadata("/dev/vda")?;
+    assert_eq!(md.file_type(), FileType::BlockDevice);
+    assert_eq!(md.len() % 512, 0);
+
     // error cases
     assert_err!(fs::metadata("/dev/null/"), NotADirectory);
     assert_err!(fs::create_dir("dev"), AlreadyExists);
@@ -231,9 +283,12 @@ fn test_devfs_ramfs() -> Result<()> {
     // parent of '/dev'
     assert_eq!(fs::create_dir("///dev//..//233//"), Ok(()));
     assert_eq!(fs::write(".///dev//..//233//.///test.txt", "test"), Ok(()));
-    assert_err!(fs::remove_file("./dev//../..//233//.///test.txt"), NotFound);
-    assert_eq!(fs::remove_file("./dev//..//233//../233/./test.txt"), Ok(()));
-    assert_eq!(fs::remove_dir("dev//foo/../foo/../.././/233"), Ok(()));
+    assert_eq!(fs::remove_file("./dev//../..//233//.///test.txt"), Ok(())); // '/..' is '/'
+    assert_err!(
+        fs::remove_file("./dev//..//233//../233/./test.txt"),
+        NotFound
+    );
+    assert_eq!(fs::remove_dir("dev//../dev/../.././/233"), Ok(()));
     assert_err!(fs::remove_dir("very/../dev//"), PermissionDenied);
 
     // tests in /tmp
@@ -242,7 +297,7 @@ fn test_devfs_ramfs() -> Result<()> {
     assert_eq!(fs::read_dir("tmp").unwrap().count(), 1);
     assert_eq!(fs::write(".///tmp///dir//.///test.txt", "test"), Ok(()));
     assert_eq!(fs::read("tmp//././/dir//.///test.txt"), Ok("test".into()));
-    // assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty); // TODO
+    assert_err!(fs::remove_dir("dev/../tmp//dir"), DirectoryNotEmpty);
     assert_err!(fs::remove_dir("/tmp/dir/../dir"), DirectoryNotEmpty);
     assert_eq!(fs::remove_file("./tmp//dir//test.txt"), Ok(()));
     assert_eq!(fs::remove_dir("tmp/dir/.././dir///"), Ok(()));
@@ -252,6 +307,444 @@ fn test_devfs_ramfs() -> Result<()> {
     Ok(())
 }
 
+fn test_links() -> Result<()> {
+    println!("test symbolic links and hard links:");
+    fs::create_dir("/tmp/links")?;
+    fs::write("/tmp/links/file.txt", "Rust is cool!\n")?;
+
+    // symbolic links to a file and a directory
+    fs::symlink("file.txt", "/tmp/links/sym")?;
+    fs::symlink("/tmp/links", "tmp/dir_sym")?;
+    assert_eq!(fs::read_link("/tmp/links/sym")?, "file.txt");
+    assert_eq!(fs::read_link("/tmp/dir_sym")?, "/tmp/links");
+    assert_eq!(fs::read_to_string("/tmp/links/sym")?, "Rust is cool!\n");
+    assert_eq!(fs::read_to_string("tmp/dir_sym/sym")?, "Rust is cool!\n");
+    assert!(fs::symlink_metadata("/tmp/links/sym")?.is_symlink());
+    assert!(fs::metadata("/tmp/links/sym")?.is_file());
+    assert!(fs::metadata("/tmp/dir_sym/")?.is_dir());
+    assert_err!(fs::read_link("/tmp/links/file.txt"), InvalidInput);
+    assert_err!(fs::symlink("file.txt", "/tmp/links/sym"), AlreadyExists);
+
+    // symbolic link loop
+    fs::symlink("loop2", "/tmp/links/loop1")?;
+    fs::symlink("loop1", "/tmp/links/loop2")?;
+    assert_err!(fs::read("/tmp/links/loop1"), FilesystemLoop);
+
+    // hard links
+    fs::hard_link("/tmp/dir_sym/file.txt", "/tmp/links/hard")?;
+    assert_err!(
+        fs::hard_link("/tmp/links", "/tmp/dir_hard"),
+        PermissionDenied
+    );
+    assert_eq!(fs::remove_file("/tmp/links/file.txt"), Ok(()));
+    assert_eq!(fs::read_to_string("/tmp/links/hard")?, "Rust is cool!\n");
+    assert_err!(fs::read("/tmp/links/sym"), NotFound); // dangling
+
+    for name in ["sym", "loop1", "loop2", "hard"] {
+        fs::remove_file(&format!("/tmp/links/{}", name))?;
+    }
+    assert_eq!(fs::remove_file("/tmp/dir_sym"), Ok(()));
+    assert_eq!(fs::remove_dir("/tmp/links"), Ok(()));
+    assert_eq!(fs::read_dir("tmp").unwrap().count(), 0);
+
+    println!("test_links() OK!");
+    Ok(())
+}
+
+fn test_mount() -> Result<()> {
+    println!("test mount and umount:");
+    fs::create_dir("/tmp/mnt")?;
+    fs::mount("/tmp/mnt", fs::create_filesystem("ramfs", "none")?)?;
+    assert_eq!(fs::read_dir("/tmp/mnt").unwrap().count(), 0);
+    fs::write("/tmp/mnt/file.txt", "mounted")?;
+    assert_err!(fs::create_filesystem("foofs", "none"), Unsupported);
+
+    // nested mount, the mount point is created if not exists
+    fs::mount("/tmp/mnt/nested", fs::create_filesystem("tmpfs", "none")?)?;
+    fs::write("/tmp/mnt/nested/file.txt", "nested")?;
+    assert_eq!(
+        fs::read_to_string("/tmp/mnt/nested/../file.txt")?,
+        "mounted"
+    );
+    assert_eq!(fs::read_to_string("/tmp/mnt/nested/file.txt")?, "nested");
+    assert_err!(
+        fs::rename("/tmp/mnt/file.txt", "/tmp/mnt/nested/moved.txt"),
+        CrossesDevices
+    );
+    assert_err!(fs::remove_dir("/tmp/mnt/nested"), PermissionDenied);
+    assert_err!(
+        fs::mount("/tmp/mnt/nested", fs::create_filesystem("ramfs", "none")?),
+        ResourceBusy
+    );
+
+    // busy filesystems cannot be unmounted
+    assert_err!(fs::umount("/tmp/mnt"), ResourceBusy); // has nested mount
+    let file = File::open("/tmp/mnt/nested/file.txt")?;
+    assert_err!(fs::umount("/tmp/mnt/nested"), ResourceBusy);
+    drop(file);
+    fs::set_current_dir("/tmp/mnt/nested")?;
+    assert_err!(fs::umount("/tmp/mnt/nested"), ResourceBusy);
+    fs::set_current_dir("/")?;
+
+    fs::umount("/tmp/mnt/nested")?;
+    assert_err!(fs::metadata("/tmp/mnt/nested/file.txt"), NotFound);
+    assert_err!(fs::umount("/tmp/mnt/nested"), InvalidInput);
+    fs::umount("/tmp/mnt")?;
+    assert_eq!(fs::read_dir("/tmp/mnt").unwrap().count(), 0);
+    assert_err!(fs::umount("/"), ResourceBusy);
+    assert_eq!(fs::remove_dir("/tmp/mnt"), Ok(()));
+    assert_eq!(fs::read_dir("tmp").unwrap().count(), 0);
+
+    println!("test_mount() OK!");
+    Ok(())
+}
+
+fn test_procfs() -> Result<()> {
+    println!("test procfs:");
+    let mounts = fs::read_to_string("/proc/mounts")?;
+    println!("/proc/mounts:\n{}", mounts);
+    assert!(mounts.contains(" / "));
+    assert!(mounts.contains("proc /proc proc rw 0 0\n"));
+    assert!(!mounts.contains("/tmp/mnt"));
+    fs::mount("/tmp/mnt", fs::create_filesystem("ramfs", "none")?)?;
+    assert!(fs::read_to_string("/proc/mounts")?.contains("ramfs /tmp/mnt ramfs"));
+    fs::umount("/tmp/mnt")?;
+    fs::remove_dir("/tmp/mnt")?;
+
+    assert_eq!(
+        fs::read_to_string("/proc/sys/net/core/somaxconn")?,
+        "4096\n"
+    );
+    assert_err!(fs::write("/proc/mounts", "test"), PermissionDenied);
+    assert_err!(fs::write("/proc/test", "test"), PermissionDenied);
+
+    // entries added by other modules
+    let root = axfs::procfs::root().unwrap();
+    root.add(
+        "version",
+        std::sync::Arc::new(axfs::procfs::FileNode::new(|| "ArceOS\n".into())),
+    );
+    let dirents = fs::read_dir("/proc")?
+        .map(|e| e.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert!(dirents.contains(&"version".into()));
+    assert_eq!(fs::read_to_string("/proc/version")?, "ArceOS\n");
+
+    println!("test_procfs() OK!");
+    Ok(())
+}
+
+fn test_sysfs() -> Result<()> {
+    println!("test sysfs:");
+    assert_eq!(
+        fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")?,
+        "always madvise [never]\n"
+    );
+    assert!(fs::read_to_string("/proc/mounts")?.contains("sysfs /sys sysfs rw 0 0\n"));
+
+    // writable attributes added by other modules
+    let value = std::sync::Arc::new(std::sync::Mutex::new(String::from("100\n")));
+    let (v1, v2) = (value.clone(), value.clone());
+    axfs::sysfs::root().unwrap().mkdir("kernel").add(
+        "test_value",
+        std::sync::Arc::new(axfs::sysfs::FileNode::new_writable(
+            move || v1.lock().unwrap().clone(),
+            move |data| {
+                data.trim()
+                    .parse::<u32>()
+                    .map_err(|_| Error::InvalidInput)?;
+                *v2.lock().unwrap() = data.into();
+                Ok(())
+            },
+        )),
+    );
+    assert_eq!(fs::read_to_string("/sys/kernel/test_value")?, "100\n");
+    fs::write("/sys/kernel/test_value", "250\n")?;
+    assert_eq!(*value.lock().unwrap(), "250\n");
+    assert_eq!(fs::read_to_string("/sys/kernel/test_value")?, "250\n");
+    assert_err!(fs::write("/sys/kernel/test_value", "abc"), InvalidInput);
+    assert_err!(
+        fs::write("/sys/kernel/mm/transparent_hugepage/enabled", "always"),
+        PermissionDenied
+    );
+
+    println!("test_sysfs() OK!");
+    Ok(())
+}
+
+fn test_owner_permission() -> Result<()> {
+    println!("test ownership and permissions:");
+    let mode = |path| fs::metadata(path).map(|md| md.permissions().mode());
+    let perm = Permissions::from_bits_truncate;
+
+    // the mode of new files is masked by umask
+    let old_umask = fs::umask(0o027);
+    fs::create_dir("/tmp/home")?;
+    fs::write("/tmp/home/root.txt", "root")?;
+    assert_eq!(mode("/tmp/home")?, 0o750);
+    assert_eq!(mode("/tmp/home/root.txt")?, 0o640);
+    assert_eq!(fs::umask(old_umask), 0o027);
+
+    fs::chown("/tmp/home", Some(1000), Some(100))?;
+    let md = fs::metadata("/tmp/home")?;
+    assert_eq!((md.uid(), md.gid()), (1000, 100));
+
+    set_credentials(1000, 100);
+    // files of other users
+    assert_err!(fs::read("/tmp/home/root.txt"), PermissionDenied);
+    assert_err!(
+        fs::set_permissions("/tmp/home/root.txt", perm(0o666)),
+        PermissionDenied
+    );
+    assert_err!(fs::write("/tmp/test.txt", "test"), PermissionDenied);
+    assert_err!(fs::create_dir("/tmp/dir"), PermissionDenied);
+    assert_err!(fs::remove_dir("/tmp/home"), PermissionDenied);
+    // but they can be removed from the directory of the user
+    fs::remove_file("/tmp/home/root.txt")?;
+
+    // new files are owned by the user
+    fs::write("/tmp/home/user.txt", "user")?;
+    let md = fs::metadata("/tmp/home/user.txt")?;
+    assert_eq!((md.uid(), md.gid()), (1000, 100));
+    assert_eq!(md.permissions().mode(), 0o644);
+    fs::set_permissions("/tmp/home/user.txt", perm(0o400))?;
+    assert_err!(File::create("/tmp/home/user.txt"), PermissionDenied);
+    assert_eq!(fs::read_to_string("/tmp/home/user.txt")?, "user");
+
+    // only root can give the files away
+    fs::chown("/tmp/home/user.txt", None, Some(100))?;
+    assert_err!(
+        fs::chown("/tmp/home/user.txt", Some(0), None),
+        PermissionDenied
+    );
+    assert_err!(
+        fs::chown("/tmp/home/user.txt", None, Some(0)),
+        PermissionDenied
+    );
+
+    // directories without the search permission
+    fs::set_permissions("/tmp/home", perm(0o600))?;
+    assert_err!(fs::metadata("/tmp/home/user.txt"), PermissionDenied);
+    assert_err!(fs::set_current_dir("/tmp/home"), PermissionDenied);
+    assert_eq!(fs::read_dir("/tmp/home")?.count(), 1);
+
+    // root can do anything
+    set_credentials(0, 0);
+    assert_eq!(fs::read_to_string("/tmp/home/user.txt")?, "user");
+    fs::remove_file("/tmp/home/user.txt")?;
+    fs::remove_dir("/tmp/home")?;
+    assert_eq!(fs::read_dir("tmp").unwrap().count(), 0);
+
+    println!("test_owner_permission() OK!");
+    Ok(())
+}
+
+fn test_timestamps() -> Result<()> {
+    println!("test timestamps:");
+    let secs = |t: Duration| t.as_secs();
+    let times =
+        |path| fs::metadata(path).map(|md| [md.accessed(), md.modified(), md.changed()].map(secs));
+
+    set_time(100);
+    fs::write("/tmp/time.txt", "time")?;
+    assert_eq!(times("/tmp/time.txt")?, [100; 3]);
+    assert_eq!(secs(fs::metadata("/tmp")?.modified()), 100);
+
+    set_time(200);
+    fs::read("/tmp/time.txt")?;
+    assert_eq!(times("/tmp/time.txt")?, [200, 100, 100]);
+    set_time(300);
+    let mut file = OpenOptions::new().append(true).open("/tmp/time.txt")?;
+    file.write_all(b"!")?;
+    assert_eq!(secs(file.metadata()?.modified()), 300);
+    file.set_permissions(Permissions::from_bits_truncate(0o666))?;
+    assert_eq!(times("/tmp/time.txt")?, [200, 300, 300]);
+
+    set_time(400);
+    let (atime, mtime) = (Duration::from_secs(10), Duration::from_secs(20));
+    fs::set_times("/tmp/time.txt", Some(atime), None)?;
+    assert_eq!(times("/tmp/time.txt")?, [10, 300, 400]);
+    file.set_times(None, Some(mtime))?;
+    assert_eq!(times("/tmp/time.txt")?, [10, 20, 400]);
+
+    // others can only set the timestamps to the current time if they can write
+    set_credentials(1000, 1000);
+    assert_err!(
+        fs::set_times("/tmp/time.txt", Some(atime), None),
+        PermissionDenied
+    );
+    set_time(500);
+    fs::touch("/tmp/time.txt")?;
+    assert_eq!(times("/tmp/time.txt")?, [500; 3]);
+    set_credentials(0, 0);
+    fs::set_permissions("/tmp/time.txt", Permissions::from_bits_truncate(0o644))?;
+    set_credentials(1000, 1000);
+    assert_err!(fs::touch("/tmp/time.txt"), PermissionDenied);
+
+    // the timestamps of a symbolic link itself
+    set_credentials(0, 0);
+    fs::symlink("time.txt", "/tmp/time_link")?;
+    fs::lset_times("/tmp/time_link", Some(atime), Some(mtime))?;
+    assert_eq!(secs(fs::symlink_metadata("/tmp/time_link")?.modified()), 20);
+    assert_eq!(times("/tmp/time.txt")?, [500; 3]);
+    fs::remove_file("/tmp/time_link")?;
+
+    drop(file);
+    fs::remove_file("/tmp/time.txt")?;
+    set_time(0);
+
+    println!("test_timestamps() OK!");
+    Ok(())
+}
+
+fn test_file_locks() -> Result<()> {
+    println!("test file locks:");
+    let f1 = File::create("/tmp/lock.txt")?;
+    let f2 = File::open("/tmp/lock.txt")?;
+
+    // whole-file locks of different files
+    f1.try_lock_shared()?;
+    f2.try_lock_shared()?;
+    assert_err!(f1.try_lock(), WouldBlock);
+    f2.unlock()?;
+    f1.try_lock()?;
+    assert_err!(f2.try_lock_shared(), WouldBlock);
+    drop(f1); // released on close
+    f2.try_lock()?;
+    f2.unlock()?;
+
+    // byte-range locks
+    let (a, b) = (LockOwner::Process(1), LockOwner::Process(2));
+    let lock = |ty, start, end, owner| RangeLock {
+        ty,
+        start,
+        end,
+        owner,
+    };
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    let ro = fops::File::open("/tmp/lock.txt", &opts)?;
+    opts.write(true);
+    let rw = fops::File::open("/tmp/lock.txt", &opts)?;
+    assert_err!(
+        ro.locks().lock_range(lock(LockType::Exclusive, 0, 10, a)),
+        PermissionDenied
+    );
+    rw.locks().lock_range(lock(LockType::Exclusive, 0, 10, a))?;
+    ro.locks().lock_range(lock(LockType::Shared, 10, 20, b))?;
+    assert_err!(
+        ro.locks().lock_range(lock(LockType::Shared, 5, 15, b)),
+        WouldBlock
+    );
+    assert_eq!(
+        ro.locks().test_range(&lock(LockType::Shared, 5, 15, b)),
+        Some(lock(LockType::Exclusive, 0, 10, a))
+    );
+    // splitting and merging the locks of the same owner
+    rw.locks().unlock_range(a, 3, 6);
+    ro.locks().lock_range(lock(LockType::Shared, 3, 6, b))?;
+    assert_err!(
+        rw.locks().lock_range(lock(LockType::Exclusive, 2, 4, a)),
+        WouldBlock
+    );
+    rw.locks().unlock_range(a, 6, 10);
+    rw.locks()
+        .lock_range(lock(LockType::Exclusive, 6, u64::MAX, b))?;
+    assert_eq!(
+        rw.locks().test_range(&lock(LockType::Shared, 0, 100, a)),
+        Some(lock(LockType::Exclusive, 6, u64::MAX, b))
+    );
+    ro.locks().unlock_all(b);
+    assert_eq!(
+        rw.locks().test_range(&lock(LockType::Exclusive, 3, 100, b)),
+        None
+    );
+    rw.locks().lock_range(lock(LockType::Exclusive, 3, 5, a))?;
+    assert_eq!(
+        rw.locks().test_range(&lock(LockType::Shared, 0, 100, b)),
+        Some(lock(LockType::Exclusive, 0, 5, a))
+    );
+    rw.locks().unlock_all(a);
+
+    // the locks are on the file, whichever link and name it's opened by
+    fs::hard_link("/tmp/lock.txt", "/tmp/lock_hard.txt")?;
+    let f3 = File::open("/tmp/lock_hard.txt")?;
+    f2.try_lock()?;
+    assert_err!(f3.try_lock_shared(), WouldBlock);
+    fs::rename("/tmp/lock.txt", "/tmp/lock_moved.txt")?;
+    assert_err!(
+        File::open("/tmp/lock_moved.txt")?.try_lock_shared(),
+        WouldBlock
+    );
+    f2.unlock()?;
+    f3.try_lock()?;
+    fs::remove_file("/tmp/lock_hard.txt")?;
+    File::create("/tmp/lock_hard.txt")?.try_lock()?; // a new file
+
+    drop((ro, rw, f2, f3));
+    fs::remove_file("/tmp/lock_moved.txt")?;
+    fs::remove_file("/tmp/lock_hard.txt")?;
+    println!("test_file_locks() OK!");
+    Ok(())
+}
+
+fn test_directory_handles() -> Result<()> {
+    println!("test directory handles:");
+    let mut opts = fops::OpenOptions::new();
+    opts.read(true);
+    let root = fops::Directory::open_dir("/", &opts)?;
+    root.create_dir_with_mode("at", 0o750)?;
+    let mut at = root.open_dir_at("at", &opts)?;
+    assert!(at.get_attr()?.is_dir());
+    assert_eq!(root.get_attr_at("at", true)?.perm().bits(), 0o750);
+
+    // paths relative to the directory handles
+    let mut wopts = fops::OpenOptions::new();
+    wopts.write(true);
+    wopts.create(true);
+    at.open_file_at("a.txt", &wopts)?.write(b"hello")?;
+    assert_eq!(fs::read_to_string("/at/a.txt")?, "hello");
+    assert_eq!(root.get_attr_at("at/a.txt", true)?.size(), 5);
+    at.create_file("b.txt")?;
+    fs::symlink("a.txt", "/at/link")?;
+    assert!(at.get_attr_at("link", false)?.is_symlink());
+    assert!(at.get_attr_at("link", true)?.is_file());
+    assert!(at.get_attr_at("/at", true)?.is_dir());
+    assert_err!(at.get_attr_at("c.txt", true), NotFound);
+    at.set_times_at("link", None, Some(Duration::from_secs(30)), false)?;
+    assert_eq!(at.get_attr_at("link", false)?.mtime().as_secs(), 30);
+    assert_ne!(at.get_attr_at("link", true)?.mtime().as_secs(), 30);
+
+    // renaming between directories
+    fops::Directory::rename_at(Some(&at), "a.txt", Some(&root), "moved.txt")?;
+    assert_eq!(fs::read_to_string("/moved.txt")?, "hello");
+    root.rename("moved.txt", "at/a.txt")?;
+    assert_eq!(fs::read_to_string("/at/a.txt")?, "hello");
+
+    // reading entries with and without the cursor
+    const EMPTY: fops::DirEntry = fops::DirEntry::default();
+    let mut entries = [EMPTY; 8];
+    let n = at.read_dir(&mut entries)?;
+    assert_eq!(at.read_dir(&mut entries)?, 0);
+    assert_eq!(at.read_dir_at(1, &mut entries)?, n - 1);
+    assert_eq!(at.read_dir_at(0, &mut entries)?, n);
+    let mut names: Vec<_> = entries[..n]
+        .iter()
+        .map(|e| String::from_utf8_lossy(e.name_as_bytes()).into_owned())
+        .filter(|name| name != "." && name != "..")
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.txt", "b.txt", "link"]);
+
+    for name in names {
+        at.remove_file(&name)?;
+    }
+    drop(at);
+    root.remove_dir("at")?;
+    println!("test_directory_handles() OK!");
+    Ok(())
+}
+
 pub fn test_all() {
     test_read_write_file().expect("test_read_write_file() failed");
     test_read_dir().expect("test_read_dir() failed");
@@ -259,4 +752,12 @@ pub fn test_all() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs_ramfs().expect("test_devfs_ramfs() failed");
+    test_links().expect("test_links() failed");
+    test_mount().expect("test_mount() failed");
+    test_procfs().expect("test_procfs() failed");
+    test_sysfs().expect("test_sysfs() failed");
+    test_owner_permission().expect("test_owner_permission() failed");
+    test_timestamps().expect("test_timestamps() failed");
+    test_file_locks().expect("test_file_locks() failed");
+    test_directory_handles().expect("test_directory_handles() failed");
 }
@@ -0,0 +1,31 @@
+#![cfg(all(feature = "ext2", not(any(feature = "myfs", feature = "initramfs"))))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use driver_block::ramdisk::RamDisk;
+
+const IMG_PATH: &str = "resources/ext2.img";
+
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let data = std::fs::read(path)?;
+    println!("size = {} bytes", data.len());
+    Ok(RamDisk::from(&data))
+}
+
+#[test]
+fn test_ext2() {
+    println!("Testing ext2 with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    test_common::test_all();
+
+    let stats = axfs::api::block_cache_stats();
+    println!("block cache: {:?}", stats);
+    assert!(stats.hits > 0 && stats.misses > 0);
+}
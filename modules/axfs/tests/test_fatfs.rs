@@ -24,4 +24,8 @@ fn test_fatfs() {
     axfs::init_filesystems(AxDeviceContainer::from_one(disk));
 
     test_common::test_all();
+
+    let stats = axfs::api::block_cache_stats();
+    println!("block cache: {:?}", stats);
+    assert!(stats.hits > 0 && stats.misses > 0);
 }
@@ -0,0 +1,147 @@
+#![cfg(all(feature = "initramfs", not(feature = "myfs")))]
+
+mod test_common;
+
+use std::time::Duration;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axio::{Error, Result};
+use driver_block::ramdisk::RamDisk;
+
+const DIR: u32 = 0o040000;
+const FILE: u32 = 0o100000;
+const SYMLINK: u32 = 0o120000;
+const CHAR_DEV: u32 = 0o020000;
+
+/// Builds an archive in the `newc` format of cpio.
+#[derive(Default)]
+struct Archive {
+    data: Vec<u8>,
+    next_ino: u32,
+}
+
+impl Archive {
+    fn add_entry(&mut self, name: &str, mode: u32, ino: u32, nlink: u32, contents: &[u8]) {
+        let (uid, gid) = if name.starts_with("etc") {
+            (1000, 100)
+        } else {
+            (0, 0)
+        };
+        let fields = [
+            ino,
+            mode,
+            uid,
+            gid,
+            nlink,
+            1000, // mtime
+            contents.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+        self.data.extend_from_slice(b"070701");
+        for field in fields {
+            self.data
+                .extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        self.pad(4);
+        self.data.extend_from_slice(contents);
+        self.pad(4);
+    }
+
+    fn add(&mut self, name: &str, mode: u32, contents: &str) {
+        self.next_ino += 1;
+        self.add_entry(name, mode, self.next_ino, 1, contents.as_bytes());
+    }
+
+    fn pad(&mut self, align: usize) {
+        self.data.resize(self.data.len().next_multiple_of(align), 0);
+    }
+
+    fn finish(mut self) -> &'static [u8] {
+        self.add_entry("TRAILER!!!", 0, 0, 1, b"");
+        self.pad(512);
+        self.data.leak()
+    }
+}
+
+fn make_archive() -> &'static [u8] {
+    // the files expected by `test_common`, and:
+    // .
+    // ├── bin
+    // │   └── app
+    // ├── data
+    // │   ├── a
+    // │   └── b (hard link to a)
+    // └── etc
+    //     ├── config
+    //     └── link -> config
+    let mut archive = Archive::default();
+    archive.add(".", DIR | 0o755, "");
+    archive.add("./short.txt", FILE | 0o644, "Rust is cool!\n");
+    archive.add("./long.txt", FILE | 0o644, &"Rust is cool!\n".repeat(100));
+    archive.add("./very-long-dir-name", DIR | 0o755, "");
+    archive.add(
+        "./very-long-dir-name/very-long-file-name.txt",
+        FILE | 0o644,
+        "Rust is cool!\n",
+    );
+    // the parent directories are not in the archive
+    archive.add("./very/long/path/test.txt", FILE | 0o644, "Rust is cool!\n");
+
+    archive.add("bin/app", FILE | 0o755, "#!/bin/sh\n");
+    archive.add("etc", DIR | 0o750, "");
+    archive.add("etc/config", FILE | 0o600, "key=value\n");
+    archive.add("etc/link", SYMLINK | 0o777, "config");
+    archive.add("console", CHAR_DEV | 0o600, "");
+    // the data of hard links is stored in the last one
+    archive.add_entry("data/a", FILE | 0o644, 100, 2, b"");
+    archive.add_entry("data/b", FILE | 0o644, 100, 2, b"shared\n");
+    archive.finish()
+}
+
+fn test_initramfs_contents() -> Result<()> {
+    println!("test initramfs contents:");
+    assert!(fs::read_to_string("/proc/mounts")?.contains("ramfs / ramfs"));
+
+    let etc = fs::metadata("/etc")?;
+    assert!(etc.is_dir());
+    assert_eq!(etc.permissions().mode(), 0o750);
+    assert_eq!((etc.uid(), etc.gid()), (1000, 100));
+    assert_eq!(etc.modified(), Duration::from_secs(1000));
+
+    let config = fs::metadata("/etc/config")?;
+    assert_eq!(config.permissions().mode(), 0o600);
+    assert_eq!(config.modified(), Duration::from_secs(1000));
+    assert_eq!(fs::read_to_string("/etc/config")?, "key=value\n");
+    assert_eq!(fs::read_link("/etc/link")?, "config");
+    assert_eq!(fs::read_to_string("/etc/link")?, "key=value\n");
+    assert_eq!(fs::metadata("/bin/app")?.permissions().mode(), 0o755);
+    assert!(fs::metadata("/very/long")?.is_dir());
+    assert_eq!(fs::metadata("/console").err(), Some(Error::NotFound));
+
+    assert_eq!(fs::read_to_string("/data/a")?, "shared\n");
+    fs::write("/data/b", "changed\n")?;
+    assert_eq!(fs::read_to_string("/data/a")?, "changed\n");
+
+    println!("test_initramfs_contents() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_initramfs() {
+    println!("Testing initramfs ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::set_initramfs(make_archive());
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // dummy disk, actually not used.
+
+    test_initramfs_contents().expect("test_initramfs_contents() failed");
+    test_common::test_all();
+}
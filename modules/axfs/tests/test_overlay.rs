@@ -0,0 +1,154 @@
+#![cfg(all(feature = "myfs", feature = "overlay"))]
+
+mod test_common;
+
+use std::sync::{Arc, OnceLock};
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axfs::fops::{Disk, MyFileSystemIf};
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{VfsNodeType, VfsOps};
+use axio::{Error, Result};
+use driver_block::ramdisk::RamDisk;
+
+/// The lower filesystem, which must not be modified through the overlay.
+static LOWER: OnceLock<Arc<RamFileSystem>> = OnceLock::new();
+
+struct MyFileSystemIfImpl;
+
+#[crate_interface::impl_interface]
+impl MyFileSystemIf for MyFileSystemIfImpl {
+    fn new_myfs(_disk: Disk) -> Arc<dyn VfsOps> {
+        // the files expected by `test_common`, and:
+        // .
+        // ├── lower.txt
+        // ├── lower_link -> lower.txt
+        // └── lower_dir
+        //     ├── a.txt
+        //     └── b.txt
+        let lower = Arc::new(RamFileSystem::new());
+        let root = lower.root_dir();
+        let write = |path: &str, contents: &str| {
+            root.create(path, VfsNodeType::File).unwrap();
+            let file = root.clone().lookup(path).unwrap();
+            file.write_at(0, contents.as_bytes()).unwrap();
+        };
+        write("short.txt", "Rust is cool!\n");
+        write("long.txt", &"Rust is cool!\n".repeat(100));
+        root.create("very-long-dir-name", VfsNodeType::Dir).unwrap();
+        write(
+            "very-long-dir-name/very-long-file-name.txt",
+            "Rust is cool!\n",
+        );
+        for dir in ["very", "very/long", "very/long/path"] {
+            root.create(dir, VfsNodeType::Dir).unwrap();
+        }
+        write("very/long/path/test.txt", "Rust is cool!\n");
+
+        write("lower.txt", "lower\n");
+        root.symlink("lower_link", "lower.txt").unwrap();
+        root.create("lower_dir", VfsNodeType::Dir).unwrap();
+        write("lower_dir/a.txt", "");
+        write("lower_dir/b.txt", "");
+        LOWER.set(lower.clone()).ok();
+        lower
+    }
+}
+
+fn read_lower(path: &str) -> Option<String> {
+    let node = LOWER.get()?.root_dir().lookup(path).ok()?;
+    let mut buf = [0; 2048];
+    let len = node.read_at(0, &mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+fn dir_names(path: &str) -> Result<Vec<String>> {
+    let mut names = fs::read_dir(path)?
+        .map(|e| e.map(|e| e.file_name()))
+        .collect::<Result<Vec<_>>>()?;
+    names.sort();
+    Ok(names)
+}
+
+fn test_overlay_layers() -> Result<()> {
+    println!("test overlay layers:");
+    assert!(fs::read_to_string("/proc/mounts")?.contains("overlay / overlay"));
+
+    // copy-up on write
+    assert_eq!(fs::read_to_string("/lower.txt")?, "lower\n");
+    fs::write("/lower.txt", "upper\n")?;
+    assert_eq!(fs::read_to_string("/lower.txt")?, "upper\n");
+    assert_eq!(read_lower("lower.txt").unwrap(), "lower\n");
+    assert_eq!(fs::read_link("/lower_link")?, "lower.txt");
+    assert_eq!(fs::read_to_string("/lower_link")?, "upper\n");
+
+    // merged directories
+    fs::write("/lower_dir/c.txt", "new\n")?;
+    assert_eq!(dir_names("/lower_dir")?, ["a.txt", "b.txt", "c.txt"]);
+    assert!(read_lower("lower_dir/c.txt").is_none());
+
+    // whiteouts
+    fs::remove_file("/lower_dir/a.txt")?;
+    assert_eq!(
+        fs::metadata("/lower_dir/a.txt").err(),
+        Some(Error::NotFound)
+    );
+    assert_eq!(dir_names("/lower_dir")?, ["b.txt", "c.txt"]);
+    assert!(read_lower("lower_dir/a.txt").is_some());
+    assert_eq!(
+        fs::remove_dir("/lower_dir").err(),
+        Some(Error::DirectoryNotEmpty)
+    );
+    fs::write("/lower_dir/a.txt", "again\n")?;
+    assert_eq!(fs::read_to_string("/lower_dir/a.txt")?, "again\n");
+
+    // renaming
+    assert_eq!(
+        fs::rename("/lower_dir", "/moved_dir").err(),
+        Some(Error::CrossesDevices)
+    );
+    fs::rename("/lower.txt", "/moved.txt")?;
+    assert_eq!(fs::read_to_string("/moved.txt")?, "upper\n");
+    assert_eq!(fs::metadata("/lower.txt").err(), Some(Error::NotFound));
+    assert_eq!(read_lower("lower.txt").unwrap(), "lower\n");
+    fs::rename("/moved.txt", "/lower.txt")?;
+    assert_eq!(fs::read_to_string("/lower.txt")?, "upper\n");
+
+    // opaque directories
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::remove_file(&format!("/lower_dir/{}", name))?;
+    }
+    fs::remove_dir("/lower_dir")?;
+    assert_eq!(fs::metadata("/lower_dir").err(), Some(Error::NotFound));
+    assert!(!dir_names("/")?.contains(&"lower_dir".into()));
+    fs::create_dir("/lower_dir")?;
+    assert!(dir_names("/lower_dir")?.is_empty());
+    assert_eq!(
+        fs::metadata("/lower_dir/b.txt").err(),
+        Some(Error::NotFound)
+    );
+    assert!(read_lower("lower_dir/b.txt").is_some());
+    fs::remove_dir("/lower_dir")?;
+
+    println!("test_overlay_layers() OK!");
+    Ok(())
+}
+
+#[test]
+fn test_overlay() {
+    println!("Testing overlay ...");
+
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(RamDisk::default())); // dummy disk, actually not used.
+
+    test_overlay_layers().expect("test_overlay_layers() failed");
+    test_common::test_all();
+
+    // all the changes are in the upper filesystem
+    let lower = LOWER.get().unwrap().root_dir_node().get_entries();
+    assert_eq!(lower.len(), 7);
+    assert_eq!(read_lower("short.txt").unwrap(), "Rust is cool!\n");
+    assert_eq!(read_lower("long.txt").unwrap().len(), 1400);
+    assert_eq!(read_lower("lower.txt").unwrap(), "lower\n");
+}
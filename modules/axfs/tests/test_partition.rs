@@ -0,0 +1,135 @@
+#![cfg(all(feature = "ext2", not(any(feature = "myfs", feature = "initramfs"))))]
+
+mod test_common;
+
+use axdriver::AxDeviceContainer;
+use axfs::api as fs;
+use axio::{prelude::*, Error, SeekFrom};
+use driver_block::ramdisk::RamDisk;
+
+const IMG_PATH: &str = "resources/ext2.img";
+const BLOCK_SIZE: usize = 512;
+const FIRST_LBA: usize = 34;
+
+fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+    buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+fn put_u64(buf: &mut [u8], offset: usize, val: u64) {
+    buf[offset..offset + 8].copy_from_slice(&val.to_le_bytes());
+}
+
+/// Creates a GPT partitioned disk with two copies of the ext2 image, labeled
+/// "root" and "data".
+fn make_disk() -> std::io::Result<RamDisk> {
+    let path = std::env::current_dir()?.join(IMG_PATH);
+    println!("Loading disk image from {:?} ...", path);
+    let image = std::fs::read(path)?;
+    let part_blocks = image.len() / BLOCK_SIZE;
+    let mut data = vec![0; (FIRST_LBA + part_blocks * 2 + 33) * BLOCK_SIZE];
+
+    // protective MBR
+    let mbr = &mut data[..BLOCK_SIZE];
+    mbr[446 + 4] = 0xEE;
+    put_u32(mbr, 446 + 8, 1);
+    put_u32(mbr, 446 + 12, u32::MAX);
+    mbr[510..].copy_from_slice(&[0x55, 0xAA]);
+
+    // GPT header, partition entries start at LBA 2
+    let header = &mut data[BLOCK_SIZE..BLOCK_SIZE * 2];
+    header[..8].copy_from_slice(b"EFI PART");
+    put_u64(header, 72, 2);
+    put_u32(header, 80, 128);
+    put_u32(header, 84, 128);
+
+    for (i, label) in ["root", "data"].iter().enumerate() {
+        let first = FIRST_LBA + i * part_blocks;
+        let entry = &mut data[BLOCK_SIZE * 2 + i * 128..][..128];
+        entry[..16].fill(0xAF); // any non-zero type GUID
+        put_u64(entry, 32, first as u64);
+        put_u64(entry, 40, (first + part_blocks - 1) as u64);
+        for (j, c) in label.encode_utf16().enumerate() {
+            entry[56 + j * 2..58 + j * 2].copy_from_slice(&c.to_le_bytes());
+        }
+        data[first * BLOCK_SIZE..][..image.len()].copy_from_slice(&image);
+    }
+    Ok(RamDisk::from(&data))
+}
+
+#[test]
+fn test_partition() {
+    println!("Testing ext2 on GPT partitions with ramdisk ...");
+
+    let disk = make_disk().expect("failed to load disk image");
+    axtask::init_scheduler(); // call this to use `axsync::Mutex`.
+    axfs::init_filesystems(AxDeviceContainer::from_one(disk));
+
+    // the root filesystem is on the first partition
+    test_common::test_all();
+
+    // mount the second partition
+    fs::mount("/data", fs::create_filesystem("ext2", "/dev/vda2").unwrap()).unwrap();
+    assert_eq!(
+        fs::read_to_string("/data/short.txt").unwrap(),
+        "Rust is cool!\n"
+    );
+    fs::write("/data/new.txt", "on partition 2").unwrap();
+    assert!(fs::metadata("/new.txt").is_err());
+
+    let err = |source| fs::create_filesystem("ext2", source).err();
+    assert_eq!(err("vda1"), Some(Error::ResourceBusy));
+    assert_eq!(err("/dev/vda2"), Some(Error::ResourceBusy));
+    assert_eq!(err("vda"), Some(Error::ResourceBusy)); // the partitions are in use
+    assert_eq!(err("/dev/vda3"), Some(Error::NotFound));
+
+    // remount
+    fs::umount("/data").unwrap();
+    fs::mount("/data", fs::create_filesystem("ext2", "vda2").unwrap()).unwrap();
+    assert_eq!(
+        fs::read_to_string("/data/new.txt").unwrap(),
+        "on partition 2"
+    );
+
+    // raw access to the whole disk and the partitions
+    let mut buf = [0; 1024];
+    let mut vda = fs::File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/vda")
+        .unwrap();
+    let size = vda.metadata().unwrap().len();
+    vda.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[510..512], &[0x55, 0xAA]);
+    assert_eq!(&buf[512..520], b"EFI PART");
+
+    let mut vda2 = fs::File::open("/dev/vda2").unwrap();
+    vda2.seek(SeekFrom::Start(1024 + 56)).unwrap();
+    vda2.read_exact(&mut buf[..2]).unwrap();
+    assert_eq!(u16::from_le_bytes([buf[0], buf[1]]), 0xEF53); // ext2 magic
+
+    // write across blocks in the unused area at the end of the disk
+    vda.seek(SeekFrom::Start(size - 1000)).unwrap();
+    assert_eq!(vda.write(&[0x5A; 1024]).unwrap(), 1000);
+    assert_eq!(vda.write(&[0x5A; 1]).err(), Some(Error::StorageFull));
+    vda.flush().unwrap();
+    vda.seek(SeekFrom::Start(size - 1024)).unwrap();
+    assert_eq!(vda.read(&mut buf).unwrap(), 1024);
+    assert!(buf[..24].iter().all(|&b| b == 0));
+    assert!(buf[24..].iter().all(|&b| b == 0x5A));
+
+    // the whole disk and the partitions share the same cache
+    fs::umount("/data").unwrap();
+    let part_blocks = (size / BLOCK_SIZE as u64 - FIRST_LBA as u64 - 33) / 2;
+    let mut vda2 = fs::File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/vda2")
+        .unwrap();
+    vda2.write_all(&[0xA5; 100]).unwrap(); // in the unused boot block of ext2
+    vda.seek(SeekFrom::Start(
+        (FIRST_LBA as u64 + part_blocks) * BLOCK_SIZE as u64,
+    ))
+    .unwrap();
+    vda.read_exact(&mut buf[..100]).unwrap();
+    assert!(buf[..100].iter().all(|&b| b == 0xA5));
+}
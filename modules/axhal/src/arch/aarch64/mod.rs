@@ -1,6 +1,9 @@
 mod context;
 pub(crate) mod trap;
 
+#[cfg(feature = "uspace")]
+mod uspace;
+
 use core::arch::asm;
 
 use aarch64_cpu::registers::{DAIF, TPIDR_EL0, TTBR0_EL1, TTBR1_EL1, VBAR_EL1};
@@ -9,6 +12,9 @@ use tock_registers::interfaces::{Readable, Writeable};
 
 pub use self::context::{FpState, TaskContext, TrapFrame};
 
+#[cfg(feature = "uspace")]
+pub use self::uspace::UspaceContext;
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -135,3 +141,22 @@ pub fn read_thread_pointer() -> usize {
 pub unsafe fn write_thread_pointer(tpidr_el0: usize) {
     TPIDR_EL0.set(tpidr_el0 as _)
 }
+
+/// Reads a random number from the hardware random number generator of the
+/// CPU (`RNDR`, with `FEAT_RNG`).
+///
+/// Returns [`None`] if it's not supported, or it fails to generate a number
+/// in a reasonable time.
+pub fn hw_random() -> Option<u64> {
+    let isar0: u64;
+    unsafe { asm!("mrs {}, id_aa64isar0_el1", out(reg) isar0, options(nomem, nostack)) };
+    if isar0 >> 60 == 0 {
+        return None;
+    }
+    let (value, ok): (u64, u64);
+    // `RNDR` clears `NZCV.Z` on success
+    unsafe {
+        asm!("mrs {0}, s3_3_c2_c4_0", "cset {1}, ne", out(reg) value, out(reg) ok, options(nomem, nostack))
+    };
+    (ok != 0).then_some(value)
+}
@@ -1,12 +1,17 @@
 use core::arch::global_asm;
 
 use aarch64_cpu::registers::{ESR_EL1, FAR_EL1};
+use page_table_entry::MappingFlags;
 use tock_registers::interfaces::Readable;
 
 use super::TrapFrame;
+use crate::mem::VirtAddr;
 
 global_asm!(include_str!("trap.S"));
 
+/// The "write not read" bit in the ISS of data aborts.
+const ISS_DA_WNR: u64 = 1 << 6;
+
 #[repr(u8)]
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -35,6 +40,35 @@ fn invalid_exception(tf: &TrapFrame, kind: TrapKind, source: TrapSource) {
     );
 }
 
+fn handle_page_fault(tf: &TrapFrame, is_user: bool) {
+    let esr = ESR_EL1.extract();
+    let iss = esr.read(ESR_EL1::ISS);
+    let vaddr = VirtAddr::from(FAR_EL1.get() as usize);
+    let mut access_flags = match esr.read_as_enum(ESR_EL1::EC) {
+        Some(ESR_EL1::EC::Value::InstrAbortLowerEL)
+        | Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => MappingFlags::EXECUTE,
+        _ if iss & ISS_DA_WNR != 0 => MappingFlags::WRITE,
+        _ => MappingFlags::READ,
+    };
+    if is_user {
+        access_flags |= MappingFlags::USER;
+    }
+    if crate::trap::handle_page_fault_extern(vaddr, access_flags, is_user) {
+        return;
+    }
+    if is_user {
+        warn!(
+            "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
+            tf.elr, vaddr, iss
+        );
+    } else {
+        panic!(
+            "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}:\n{:#x?}",
+            tf.elr, vaddr, iss, tf,
+        );
+    }
+}
+
 #[no_mangle]
 fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
@@ -44,30 +78,20 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
             debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
             tf.elr += 4;
         }
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::SVC64) => {
+            super::enable_irqs();
+            tf.r[0] = crate::trap::handle_syscall_extern(tf, tf.r[8] as usize) as u64;
+            super::disable_irqs();
+        }
+        #[cfg(not(feature = "uspace"))]
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No supervisor call is supported currently!");
         }
         Some(ESR_EL1::EC::Value::DataAbortLowerEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            warn!(
-                "EL0 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss
-            );
-        }
+        | Some(ESR_EL1::EC::Value::InstrAbortLowerEL) => handle_page_fault(tf, true),
         Some(ESR_EL1::EC::Value::DataAbortCurrentEL)
-        | Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => {
-            let iss = esr.read(ESR_EL1::ISS);
-            panic!(
-                "EL1 Page Fault @ {:#x}, FAR={:#x}, ISS={:#x}:\n{:#x?}",
-                tf.elr,
-                FAR_EL1.get(),
-                iss,
-                tf,
-            );
-        }
+        | Some(ESR_EL1::EC::Value::InstrAbortCurrentEL) => handle_page_fault(tf, false),
         _ => {
             panic!(
                 "Unhandled synchronous exception @ {:#x}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
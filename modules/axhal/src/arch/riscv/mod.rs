@@ -4,12 +4,18 @@ mod macros;
 mod context;
 mod trap;
 
+#[cfg(feature = "uspace")]
+mod uspace;
+
 use memory_addr::{PhysAddr, VirtAddr};
 use riscv::asm;
 use riscv::register::{satp, sstatus, stvec};
 
 pub use self::context::{GeneralRegisters, TaskContext, TrapFrame};
 
+#[cfg(feature = "uspace")]
+pub use self::uspace::UspaceContext;
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -107,3 +113,13 @@ pub fn read_thread_pointer() -> usize {
 pub unsafe fn write_thread_pointer(tp: usize) {
     core::arch::asm!("mv tp, {}", in(reg) tp)
 }
+
+/// Reads a random number from the hardware random number generator of the
+/// CPU.
+///
+/// Always returns [`None`], as the entropy source of the `Zkr` extension is
+/// not accessible in supervisor mode by default.
+#[inline]
+pub fn hw_random() -> Option<u64> {
+    None
+}
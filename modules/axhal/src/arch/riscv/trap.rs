@@ -1,6 +1,9 @@
+use page_table_entry::MappingFlags;
 use riscv::register::scause::{self, Exception as E, Trap};
+use riscv::register::stval;
 
 use super::TrapFrame;
+use crate::mem::VirtAddr;
 
 include_asm_marcos!();
 
@@ -14,11 +17,44 @@ fn handle_breakpoint(sepc: &mut usize) {
     *sepc += 2
 }
 
+fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bool) {
+    let vaddr = VirtAddr::from(stval::read());
+    if is_user {
+        access_flags |= MappingFlags::USER;
+    }
+    if crate::trap::handle_page_fault_extern(vaddr, access_flags, is_user) {
+        return;
+    }
+    if is_user {
+        warn!(
+            "User page fault @ {:#x}, fault_vaddr={:#x} ({:?})",
+            tf.sepc, vaddr, access_flags
+        );
+    } else {
+        panic!(
+            "Kernel page fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
+            tf.sepc, vaddr, access_flags, tf,
+        );
+    }
+}
+
 #[no_mangle]
-fn riscv_trap_handler(tf: &mut TrapFrame, _from_user: bool) {
+fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
     let scause = scause::read();
     match scause.cause() {
+        Trap::Exception(E::LoadPageFault) => handle_page_fault(tf, MappingFlags::READ, from_user),
+        Trap::Exception(E::StorePageFault) => handle_page_fault(tf, MappingFlags::WRITE, from_user),
+        Trap::Exception(E::InstructionPageFault) => {
+            handle_page_fault(tf, MappingFlags::EXECUTE, from_user)
+        }
         Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::UserEnvCall) => {
+            tf.sepc += 4;
+            super::enable_irqs();
+            tf.regs.a0 = crate::trap::handle_syscall_extern(tf, tf.regs.a7) as usize;
+            super::disable_irqs();
+        }
         Trap::Interrupt(_) => crate::trap::handle_irq_extern(scause.bits()),
         _ => {
             panic!(
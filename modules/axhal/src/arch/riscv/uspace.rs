@@ -0,0 +1,135 @@
+//! Support for user space: context for entering user mode.
+
+use core::arch::asm;
+
+use memory_addr::VirtAddr;
+use riscv::register::{sepc, sscratch};
+
+use super::{GeneralRegisters, TrapFrame};
+
+include_asm_marcos!();
+
+impl TrapFrame {
+    /// Gets the 0th syscall argument.
+    pub const fn arg0(&self) -> usize {
+        self.regs.a0
+    }
+
+    /// Gets the 1st syscall argument.
+    pub const fn arg1(&self) -> usize {
+        self.regs.a1
+    }
+
+    /// Gets the 2nd syscall argument.
+    pub const fn arg2(&self) -> usize {
+        self.regs.a2
+    }
+
+    /// Gets the 3rd syscall argument.
+    pub const fn arg3(&self) -> usize {
+        self.regs.a3
+    }
+
+    /// Gets the 4th syscall argument.
+    pub const fn arg4(&self) -> usize {
+        self.regs.a4
+    }
+
+    /// Gets the 5th syscall argument.
+    pub const fn arg5(&self) -> usize {
+        self.regs.a5
+    }
+}
+
+/// Context to enter user space.
+#[derive(Debug, Clone)]
+pub struct UspaceContext(TrapFrame);
+
+impl UspaceContext {
+    /// Creates a new context with the given entry point, user stack pointer,
+    /// and the argument.
+    pub fn new(entry: usize, ustack_top: VirtAddr, arg0: usize) -> Self {
+        const SPIE: usize = 1 << 5; // enable interrupts after `sret`
+        const SUM: usize = 1 << 18; // permit supervisor user memory access
+        Self(TrapFrame {
+            regs: GeneralRegisters {
+                a0: arg0,
+                sp: ustack_top.as_usize(),
+                ..Default::default()
+            },
+            sepc: entry,
+            sstatus: SPIE | SUM, // SPP = 0 (user mode)
+        })
+    }
+
+    /// Creates a new context from the given [`TrapFrame`].
+    ///
+    /// It copies all registers, so the user program continues from where the
+    /// trap (e.g., a system call) happened.
+    pub fn from(tf: &TrapFrame) -> Self {
+        Self(tf.clone())
+    }
+
+    /// Gets the instruction pointer.
+    pub const fn get_ip(&self) -> usize {
+        self.0.sepc
+    }
+
+    /// Gets the stack pointer.
+    pub const fn get_sp(&self) -> usize {
+        self.0.regs.sp
+    }
+
+    /// Sets the instruction pointer.
+    pub fn set_ip(&mut self, pc: usize) {
+        self.0.sepc = pc;
+    }
+
+    /// Sets the stack pointer.
+    pub fn set_sp(&mut self, sp: usize) {
+        self.0.regs.sp = sp;
+    }
+
+    /// Sets the return value register.
+    pub fn set_retval(&mut self, a0: usize) {
+        self.0.regs.a0 = a0;
+    }
+
+    /// Enters user space.
+    ///
+    /// It restores the user registers and jumps to the user entry point
+    /// (saved in `sepc`). When an exception, interrupt or system call occurs
+    /// in user space, the CPU switches back to the kernel stack `kstack_top`.
+    ///
+    /// This function never returns.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it changes processor mode and the stack.
+    pub unsafe fn enter_uspace(&self, kstack_top: VirtAddr) -> ! {
+        super::disable_irqs();
+        sscratch::write(kstack_top.as_usize());
+        sepc::write(self.0.sepc);
+        // The trap frame will be saved at the top of the kernel stack on the
+        // next trap, where the supervisor `gp` and `tp` are restored from.
+        let kernel_trap_addr = kstack_top.as_usize() - core::mem::size_of::<TrapFrame>();
+        asm!("
+            mv      sp, a0
+            LDR     t1, sp, 2
+            LDR     t0, sp, 3
+            STR     gp, a1, 2
+            STR     tp, a1, 3
+            mv      gp, t1
+            mv      tp, t0
+
+            LDR     t0, sp, 32
+            csrw    sstatus, t0
+            POP_GENERAL_REGS
+            LDR     sp, sp, 1
+            sret",
+            in("a0") &self.0,
+            in("a1") kernel_trap_addr,
+            options(noreturn),
+        )
+    }
+}
@@ -187,11 +187,13 @@ impl TaskContext {
             self.ext_state.save();
             next_ctx.ext_state.restore();
         }
-        #[cfg(feature = "tls")]
+        #[cfg(any(feature = "tls", feature = "uspace"))]
         {
             self.fs_base = super::read_thread_pointer();
             unsafe { super::write_thread_pointer(next_ctx.fs_base) };
         }
+        #[cfg(all(target_os = "none", feature = "uspace"))]
+        super::set_kernel_stack_top(next_ctx.kstack_top);
         unsafe { context_switch(&mut self.rsp, &next_ctx.rsp) }
     }
 }
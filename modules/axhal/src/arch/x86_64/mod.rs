@@ -5,6 +5,9 @@ mod idt;
 #[cfg(target_os = "none")]
 mod trap;
 
+#[cfg(all(target_os = "none", feature = "uspace"))]
+mod uspace;
+
 use core::arch::asm;
 
 use memory_addr::{PhysAddr, VirtAddr};
@@ -13,9 +16,14 @@ use x86_64::instructions::interrupts;
 
 pub use self::context::{ExtendedState, FxsaveArea, TaskContext, TrapFrame};
 pub use self::gdt::GdtStruct;
-pub use self::idt::IdtStruct;
+pub use self::idt::{IdtStruct, DOUBLE_FAULT_IST_INDEX};
 pub use x86_64::structures::tss::TaskStateSegment;
 
+#[cfg(all(target_os = "none", feature = "uspace"))]
+pub use self::uspace::{UspaceContext, SYSCALL_VECTOR};
+#[cfg(all(target_os = "none", feature = "uspace"))]
+pub(crate) use self::uspace::{init_syscall, set_kernel_stack_top};
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -108,3 +116,35 @@ pub fn read_thread_pointer() -> usize {
 pub unsafe fn write_thread_pointer(fs_base: usize) {
     unsafe { msr::wrmsr(msr::IA32_FS_BASE, fs_base as u64) }
 }
+
+/// Reads a random number from the hardware random number generator of the
+/// CPU (`RDRAND`).
+///
+/// Returns [`None`] if it's not supported, or it fails to generate a number
+/// after several retries.
+pub fn hw_random() -> Option<u64> {
+    use core::sync::atomic::{AtomicU8, Ordering};
+    // 0: not checked yet, 1: supported, 2: not supported
+    static SUPPORTED: AtomicU8 = AtomicU8::new(0);
+    if SUPPORTED.load(Ordering::Relaxed) == 0 {
+        let supported = raw_cpuid::CpuId::new()
+            .get_feature_info()
+            .is_some_and(|f| f.has_rdrand());
+        SUPPORTED.store(if supported { 1 } else { 2 }, Ordering::Relaxed);
+    }
+    if SUPPORTED.load(Ordering::Relaxed) != 1 {
+        return None;
+    }
+    // Retry as recommended by Intel, it only fails if the entropy is
+    // temporarily exhausted.
+    for _ in 0..10 {
+        let (value, ok): (u64, u8);
+        unsafe {
+            asm!("rdrand {0}", "setc {1}", out(reg) value, out(reg_byte) ok, options(nomem, nostack))
+        };
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
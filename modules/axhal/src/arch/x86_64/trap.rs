@@ -1,34 +1,56 @@
+use page_table_entry::MappingFlags;
 use x86::{controlregs::cr2, irq::*};
+use x86_64::structures::idt::PageFaultErrorCode;
 
 use super::context::TrapFrame;
+use crate::mem::VirtAddr;
 
 core::arch::global_asm!(include_str!("trap.S"));
 
 const IRQ_VECTOR_START: u8 = 0x20;
 const IRQ_VECTOR_END: u8 = 0xff;
 
+fn handle_page_fault(tf: &TrapFrame) {
+    let vaddr = VirtAddr::from(unsafe { cr2() });
+    let err = PageFaultErrorCode::from_bits_truncate(tf.error_code);
+    let mut access_flags = if err.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+        MappingFlags::WRITE
+    } else if err.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+        MappingFlags::EXECUTE
+    } else {
+        MappingFlags::READ
+    };
+    if tf.is_user() {
+        access_flags |= MappingFlags::USER;
+    }
+    if crate::trap::handle_page_fault_extern(vaddr, access_flags, tf.is_user()) {
+        return;
+    }
+    if tf.is_user() {
+        warn!(
+            "User #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}",
+            tf.rip, vaddr, tf.error_code,
+        );
+    } else {
+        panic!(
+            "Kernel #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}:\n{:#x?}",
+            tf.rip, vaddr, tf.error_code, tf,
+        );
+    }
+}
+
 #[no_mangle]
 fn x86_trap_handler(tf: &TrapFrame) {
     match tf.vector as u8 {
-        PAGE_FAULT_VECTOR => {
-            if tf.is_user() {
-                warn!(
-                    "User #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}",
-                    tf.rip,
-                    unsafe { cr2() },
-                    tf.error_code,
-                );
-            } else {
-                panic!(
-                    "Kernel #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x}:\n{:#x?}",
-                    tf.rip,
-                    unsafe { cr2() },
-                    tf.error_code,
-                    tf,
-                );
-            }
-        }
+        PAGE_FAULT_VECTOR => handle_page_fault(tf),
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
+        DOUBLE_FAULT_VECTOR => {
+            // page faults cannot be delivered if the stack overflows, let the
+            // handler report the fault on the guard page
+            let vaddr = VirtAddr::from(unsafe { cr2() });
+            crate::trap::handle_page_fault_extern(vaddr, MappingFlags::WRITE, false);
+            panic!("#DF @ {:#x}, fault_vaddr={:#x}:\n{:#x?}", tf.rip, vaddr, tf);
+        }
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
                 "#GP @ {:#x}, error_code={:#x}:\n{:#x?}",
@@ -0,0 +1,191 @@
+//! Support for user space: context for entering user mode, and the entry of
+//! system calls via the `syscall` instruction.
+
+use core::arch::asm;
+
+use memory_addr::VirtAddr;
+use x86_64::registers::model_specific::{Efer, EferFlags, KernelGsBase, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+
+use super::{GdtStruct, TrapFrame};
+
+/// The pseudo vector number saved in [`TrapFrame::vector`] for system calls,
+/// which is not a valid interrupt vector.
+pub const SYSCALL_VECTOR: u64 = 0x100;
+
+/// The user stack pointer saved on the entry of system calls.
+#[percpu::def_percpu]
+static USER_RSP: usize = 0;
+
+/// The top of the kernel stack to switch to on the entry of system calls.
+#[percpu::def_percpu]
+static KERNEL_RSP: usize = 0;
+
+core::arch::global_asm!(
+    include_str!("syscall.S"),
+    user_rsp = sym __PERCPU_USER_RSP,
+    kernel_rsp = sym __PERCPU_KERNEL_RSP,
+    udata_sel = const GdtStruct::UDATA_SELECTOR.0,
+    ucode_sel = const GdtStruct::UCODE64_SELECTOR.0,
+    syscall_vector = const SYSCALL_VECTOR,
+);
+
+impl TrapFrame {
+    /// Gets the 0th syscall argument.
+    pub const fn arg0(&self) -> usize {
+        self.rdi as _
+    }
+
+    /// Gets the 1st syscall argument.
+    pub const fn arg1(&self) -> usize {
+        self.rsi as _
+    }
+
+    /// Gets the 2nd syscall argument.
+    pub const fn arg2(&self) -> usize {
+        self.rdx as _
+    }
+
+    /// Gets the 3rd syscall argument.
+    pub const fn arg3(&self) -> usize {
+        self.r10 as _
+    }
+
+    /// Gets the 4th syscall argument.
+    pub const fn arg4(&self) -> usize {
+        self.r8 as _
+    }
+
+    /// Gets the 5th syscall argument.
+    pub const fn arg5(&self) -> usize {
+        self.r9 as _
+    }
+}
+
+/// Context to enter user space.
+#[derive(Debug, Clone)]
+pub struct UspaceContext(TrapFrame);
+
+impl UspaceContext {
+    /// Creates a new context with the given entry point, user stack pointer,
+    /// and the argument.
+    pub fn new(entry: usize, ustack_top: VirtAddr, arg0: usize) -> Self {
+        Self(TrapFrame {
+            rdi: arg0 as _,
+            rip: entry as _,
+            cs: GdtStruct::UCODE64_SELECTOR.0 as _,
+            rflags: RFlags::INTERRUPT_FLAG.bits(), // IOPL = 0, IF = 1
+            rsp: ustack_top.as_usize() as _,
+            ss: GdtStruct::UDATA_SELECTOR.0 as _,
+            ..Default::default()
+        })
+    }
+
+    /// Creates a new context from the given [`TrapFrame`].
+    ///
+    /// It copies all registers, so the user program continues from where the
+    /// trap (e.g., a system call) happened.
+    pub fn from(tf: &TrapFrame) -> Self {
+        Self(tf.clone())
+    }
+
+    /// Gets the instruction pointer.
+    pub const fn get_ip(&self) -> usize {
+        self.0.rip as _
+    }
+
+    /// Gets the stack pointer.
+    pub const fn get_sp(&self) -> usize {
+        self.0.rsp as _
+    }
+
+    /// Sets the instruction pointer.
+    pub fn set_ip(&mut self, rip: usize) {
+        self.0.rip = rip as _;
+    }
+
+    /// Sets the stack pointer.
+    pub fn set_sp(&mut self, rsp: usize) {
+        self.0.rsp = rsp as _;
+    }
+
+    /// Sets the return value register.
+    pub fn set_retval(&mut self, rax: usize) {
+        self.0.rax = rax as _;
+    }
+
+    /// Enters user space.
+    ///
+    /// It restores the user registers and jumps to the user entry point
+    /// (saved in `rip`). When an exception, interrupt or system call occurs in
+    /// user space, the CPU switches back to the kernel stack `kstack_top`.
+    ///
+    /// This function never returns.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it changes processor mode and the stack.
+    pub unsafe fn enter_uspace(&self, kstack_top: VirtAddr) -> ! {
+        super::disable_irqs();
+        set_kernel_stack_top(kstack_top);
+        asm!("
+            mov     rsp, {tf}
+            pop     rax
+            pop     rcx
+            pop     rdx
+            pop     rbx
+            pop     rbp
+            pop     rsi
+            pop     rdi
+            pop     r8
+            pop     r9
+            pop     r10
+            pop     r11
+            pop     r12
+            pop     r13
+            pop     r14
+            pop     r15
+            add     rsp, 16     // skip vector, error_code
+            swapgs
+            iretq",
+            tf = in(reg) &self.0,
+            options(noreturn),
+        )
+    }
+}
+
+/// Sets the kernel stack to switch to when the CPU traps from user space.
+///
+/// It must be updated for each task that runs in user space, before the task
+/// returns to user space.
+pub(crate) fn set_kernel_stack_top(kstack_top: VirtAddr) {
+    unsafe { KERNEL_RSP.write_current_raw(kstack_top.as_usize()) };
+    crate::platform::set_tss_stack_top(kstack_top);
+}
+
+/// Initializes the `syscall` instruction on the current CPU.
+pub(crate) fn init_syscall() {
+    extern "C" {
+        fn syscall_entry();
+    }
+    LStar::write(x86_64::VirtAddr::new(syscall_entry as usize as _));
+    Star::write(
+        GdtStruct::UCODE64_SELECTOR,
+        GdtStruct::UDATA_SELECTOR,
+        GdtStruct::KCODE64_SELECTOR,
+        GdtStruct::KDATA_SELECTOR,
+    )
+    .unwrap();
+    // disable interrupts, single-stepping and string operations going
+    // backwards on entry
+    SFMask::write(RFlags::INTERRUPT_FLAG | RFlags::TRAP_FLAG | RFlags::DIRECTION_FLAG);
+    unsafe { Efer::update(|efer| efer.insert(EferFlags::SYSTEM_CALL_EXTENSIONS)) };
+    KernelGsBase::write(x86_64::VirtAddr::new(0));
+}
+
+#[no_mangle]
+fn x86_syscall_handler(tf: &mut TrapFrame) {
+    super::enable_irqs();
+    tf.rax = crate::trap::handle_syscall_extern(tf, tf.rax as usize) as _;
+    super::disable_irqs();
+}
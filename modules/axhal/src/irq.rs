@@ -1,5 +1,6 @@
 //! Interrupt management.
 
+use core::sync::atomic::{AtomicUsize, Ordering};
 use handler_table::HandlerTable;
 
 use crate::platform::irq::MAX_IRQ_COUNT;
@@ -11,10 +12,24 @@ pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The number of times each IRQ has been dispatched.
+static IRQ_COUNTS: [AtomicUsize; MAX_IRQ_COUNT] = [const { AtomicUsize::new(0) }; MAX_IRQ_COUNT];
+
+/// Returns the IRQs that have handlers registered in the IRQ handler table,
+/// along with the number of times each of them has been dispatched.
+pub fn irq_stats() -> impl Iterator<Item = (usize, usize)> {
+    (0..MAX_IRQ_COUNT)
+        .filter(|&irq_num| IRQ_HANDLER_TABLE.is_registered(irq_num))
+        .map(|irq_num| (irq_num, IRQ_COUNTS[irq_num].load(Ordering::Relaxed)))
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     trace!("IRQ {}", irq_num);
+    // count before handling, the handler may not return soon (e.g., timer
+    // interrupts that cause preemption)
+    IRQ_COUNTS[irq_num].fetch_add(1, Ordering::Relaxed);
     if !IRQ_HANDLER_TABLE.handle(irq_num) {
         warn!("Unhandled IRQ {}", irq_num);
     }
@@ -19,6 +19,8 @@
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
+//! - `uspace`: Enable user space support, i.e., entering user mode and
+//!    handling system calls from it.
 //! - `irq`: Enable interrupt handling support.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
@@ -40,6 +42,7 @@ mod platform;
 pub mod arch;
 pub mod cpu;
 pub mod mem;
+pub mod misc;
 pub mod time;
 pub mod trap;
 
@@ -64,11 +67,6 @@ pub mod console {
     }
 }
 
-/// Miscellaneous operation, e.g. terminate the system.
-pub mod misc {
-    pub use super::platform::misc::*;
-}
-
 /// Multi-core operations.
 #[cfg(feature = "smp")]
 pub mod mp {
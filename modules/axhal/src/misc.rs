@@ -0,0 +1,62 @@
+//! Miscellaneous operation, e.g. terminate the system.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spinlock::SpinNoIrq;
+
+#[allow(unused_imports)] // some platforms have nothing other than `terminate`
+pub use crate::platform::misc::*;
+
+/// A function called before the system is terminated, e.g., to write the
+/// cached data back to the devices.
+pub type TerminateHook = fn();
+
+const MAX_TERMINATE_HOOKS: usize = 8;
+
+static TERMINATE_HOOKS: SpinNoIrq<[Option<TerminateHook>; MAX_TERMINATE_HOOKS]> =
+    SpinNoIrq::new([None; MAX_TERMINATE_HOOKS]);
+
+static TERMINATING: AtomicBool = AtomicBool::new(false);
+
+/// Registers a function to be called by [`terminate`] before the system is
+/// shut down.
+///
+/// Returns `false` if there are too many hooks.
+pub fn register_terminate_hook(hook: TerminateHook) -> bool {
+    let mut hooks = TERMINATE_HOOKS.lock();
+    match hooks.iter_mut().find(|h| h.is_none()) {
+        Some(slot) => {
+            *slot = Some(hook);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Calls the registered hooks in order, only for the first time it is called.
+///
+/// The hooks may block, so it should be called with preemption enabled if the
+/// system is going to be terminated by [`terminate`] in a non-preemptible
+/// context.
+pub fn run_terminate_hooks() {
+    if !TERMINATING.swap(true, Ordering::AcqRel) {
+        // do not call the hooks with the lock held
+        let hooks = *TERMINATE_HOOKS.lock();
+        for hook in hooks.iter().flatten() {
+            hook();
+        }
+    }
+}
+
+/// Shutdown the whole system, including all CPUs, after calling the
+/// registered hooks (see [`run_terminate_hooks`]).
+pub fn terminate() -> ! {
+    run_terminate_hooks();
+    crate::platform::misc::terminate()
+}
+
+/// Shutdown the whole system without calling the registered hooks, e.g., on
+/// panic, when the hooks may not work.
+pub fn terminate_immediately() -> ! {
+    crate::platform::misc::terminate()
+}
@@ -1,7 +1,14 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
-use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
+use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment, DOUBLE_FAULT_IST_INDEX};
 use lazy_init::LazyInit;
+use x86_64::VirtAddr;
+
+const DOUBLE_FAULT_STACK_SIZE: usize = 0x4000;
+
+/// The stacks for double faults of all CPUs.
+static mut DOUBLE_FAULT_STACKS: [[u8; DOUBLE_FAULT_STACK_SIZE]; axconfig::SMP] =
+    [[0; DOUBLE_FAULT_STACK_SIZE]; axconfig::SMP];
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
 
@@ -11,16 +18,33 @@ static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
 #[percpu::def_percpu]
 static GDT: LazyInit<GdtStruct> = LazyInit::new();
 
+fn new_tss() -> TaskStateSegment {
+    let mut tss = TaskStateSegment::new();
+    let stack = unsafe { core::ptr::addr_of!(DOUBLE_FAULT_STACKS[crate::cpu::this_cpu_id()]) };
+    let stack_top = stack as usize + DOUBLE_FAULT_STACK_SIZE;
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = VirtAddr::new(stack_top as u64);
+    tss
+}
+
 fn init_percpu() {
     unsafe {
         IDT.load();
         let tss = TSS.current_ref_mut_raw();
         let gdt = GDT.current_ref_mut_raw();
-        tss.init_by(TaskStateSegment::new());
+        tss.init_by(new_tss());
         gdt.init_by(GdtStruct::new(tss));
         gdt.load();
         gdt.load_tss();
     }
+    #[cfg(feature = "uspace")]
+    crate::arch::init_syscall();
+}
+
+/// Sets the stack to switch to when the CPU traps from user mode (the `RSP0`
+/// field of the TSS).
+#[cfg(feature = "uspace")]
+pub(crate) fn set_tss_stack_top(kstack_top: memory_addr::VirtAddr) {
+    unsafe { TSS.current_ref_mut_raw().privilege_stack_table[0] = VirtAddr::new(kstack_top.as_usize() as u64) };
 }
 
 /// Initializes IDT, GDT on the primary CPU.
@@ -19,6 +19,9 @@ pub mod console {
     pub use super::uart16550::*;
 }
 
+#[cfg(feature = "uspace")]
+pub(crate) use self::dtables::set_tss_stack_top;
+
 extern "C" {
     fn rust_main(cpu_id: usize, dtb: usize) -> !;
     #[cfg(feature = "smp")]
@@ -2,6 +2,14 @@
 
 use crate_interface::{call_interface, def_interface};
 
+use crate::mem::VirtAddr;
+
+#[cfg(feature = "uspace")]
+use crate::arch::TrapFrame;
+
+#[doc(no_inline)]
+pub use page_table_entry::MappingFlags;
+
 /// Trap handler interface.
 ///
 /// This trait is defined with the [`#[def_interface]`][1] attribute. Users
@@ -13,7 +21,52 @@ use crate_interface::{call_interface, def_interface};
 pub trait TrapHandler {
     /// Handles interrupt requests for the given IRQ number.
     fn handle_irq(irq_num: usize);
-    // more e.g.: handle_page_fault();
+
+    /// Handles the page fault at `vaddr`, returns whether it is resolved.
+    ///
+    /// `access_flags` is the kind of access that caused the fault, which
+    /// contains one of [`READ`], [`WRITE`] and [`EXECUTE`], and [`USER`] if
+    /// `is_user` is true, i.e., the fault is from user mode.
+    ///
+    /// [`READ`]: MappingFlags::READ
+    /// [`WRITE`]: MappingFlags::WRITE
+    /// [`EXECUTE`]: MappingFlags::EXECUTE
+    /// [`USER`]: MappingFlags::USER
+    fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool;
+}
+
+/// System call handler interface.
+///
+/// This trait is defined with the [`#[def_interface]`][1] attribute. Users
+/// should implement it with [`#[impl_interface]`][2] in any other crate.
+///
+/// [1]: crate_interface::def_interface
+/// [2]: crate_interface::impl_interface
+#[cfg(feature = "uspace")]
+#[def_interface]
+pub trait SyscallHandler {
+    /// Handles the system call `syscall_num` from user space, the arguments
+    /// can be got from the trap frame `tf`.
+    ///
+    /// Returns the value to be written to the return value register, which is
+    /// usually a negative error number on failure.
+    fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize;
+}
+
+/// Process management interface, for the traps from user space that
+/// terminate the current process.
+///
+/// This trait is defined with the [`#[def_interface]`][1] attribute. Users
+/// should implement it with [`#[impl_interface]`][2] in any other crate.
+///
+/// [1]: crate_interface::def_interface
+/// [2]: crate_interface::impl_interface
+#[cfg(feature = "uspace")]
+#[def_interface]
+pub trait ProcessHandler {
+    /// Terminates the current process as if it's killed by the signal
+    /// `signo`, releasing its resources.
+    fn kill_current(signo: i32) -> !;
 }
 
 /// Call the external IRQ handler.
@@ -21,3 +74,26 @@ pub trait TrapHandler {
 pub(crate) fn handle_irq_extern(irq_num: usize) {
     call_interface!(TrapHandler::handle_irq, irq_num);
 }
+
+/// Call the external page fault handler.
+#[allow(dead_code)]
+pub(crate) fn handle_page_fault_extern(
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+) -> bool {
+    call_interface!(TrapHandler::handle_page_fault, vaddr, access_flags, is_user)
+}
+
+/// Call the external system call handler.
+#[cfg(feature = "uspace")]
+pub(crate) fn handle_syscall_extern(tf: &TrapFrame, syscall_num: usize) -> isize {
+    call_interface!(SyscallHandler::handle_syscall, tf, syscall_num)
+}
+
+/// Terminates the current process as if it's killed by the signal `signo`,
+/// e.g. `SIGSEGV` for an unresolved page fault from user space.
+#[cfg(feature = "uspace")]
+pub fn kill_current_process(signo: i32) -> ! {
+    call_interface!(ProcessHandler::kill_current, signo)
+}
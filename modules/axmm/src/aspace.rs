@@ -0,0 +1,511 @@
+//! User address spaces.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use axerrno::{ax_err, AxError, AxResult};
+use axhal::mem::{phys_to_virt, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{PageSize, PageTable};
+use memory_addr::{align_down_4k, align_up_4k};
+use spinlock::SpinNoIrq;
+
+use crate::{paging_err_to_ax_err, MappingFlags};
+
+/// The start of the kernel half of the virtual address space, whose root-level
+/// page table entries are shared by all user page tables.
+///
+/// On AArch64, the kernel uses a separate page table (`TTBR1_EL1`), so nothing
+/// is shared.
+#[cfg(target_arch = "x86_64")]
+const KERNEL_ASPACE_BASE: usize = 0xffff_8000_0000_0000;
+#[cfg(target_arch = "riscv64")]
+const KERNEL_ASPACE_BASE: usize = 0xffff_ffc0_0000_0000;
+
+/// Reference counts of the frames shared copy-on-write by more than one
+/// address space (see [`AddrSpace::clone_cow`]), by their physical addresses.
+///
+/// Frames not in the map are owned by a single address space.
+static SHARED_FRAMES: SpinNoIrq<BTreeMap<PhysAddr, usize>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Adds a reference to the frame at `paddr`.
+fn share_frame(paddr: PhysAddr) {
+    *SHARED_FRAMES.lock().entry(paddr).or_insert(1) += 1;
+}
+
+/// Removes a reference to the frame at `paddr`, and deallocates it if it was
+/// the last one.
+fn release_frame(paddr: PhysAddr) {
+    let mut shared = SHARED_FRAMES.lock();
+    match shared.get_mut(&paddr) {
+        Some(count) if *count > 2 => *count -= 1,
+        Some(_) => {
+            shared.remove(&paddr);
+        }
+        None => crate::dealloc_frame(paddr),
+    }
+}
+
+/// A contiguous region of user memory with the same mapping flags.
+///
+/// Its pages are mapped to zero-filled frames on the first access.
+struct MemoryArea {
+    start: usize,
+    /// Size of the area in bytes, aligned to pages.
+    size: usize,
+    flags: MappingFlags,
+}
+
+impl MemoryArea {
+    const fn end(&self) -> usize {
+        self.start + self.size
+    }
+
+    /// Splits the area at `addr`, returns the part after it.
+    fn split_off(&mut self, addr: usize) -> Self {
+        let tail = Self {
+            start: addr,
+            size: self.end() - addr,
+            flags: self.flags,
+        };
+        self.size = addr - self.start;
+        tail
+    }
+}
+
+/// The virtual address space of a user process.
+///
+/// The user part starts at [`axconfig::USPACE_BASE`] and has the size
+/// [`axconfig::USPACE_SIZE`]. The address space owns the page table and all
+/// the frames mapped in the user part, while the kernel mappings are shared
+/// with the kernel page table. Frames are only shared with other address
+/// spaces copy-on-write, after [`AddrSpace::clone_cow`].
+pub struct AddrSpace {
+    pt: PageTable,
+    /// All the memory areas, by their start addresses.
+    areas: BTreeMap<usize, MemoryArea>,
+}
+
+impl AddrSpace {
+    /// Creates a new address space with no user mappings.
+    pub fn new_empty() -> AxResult<Self> {
+        let mut pt = PageTable::try_new().map_err(paging_err_to_ax_err)?;
+        #[cfg(not(target_arch = "aarch64"))]
+        pt.copy_from(
+            &crate::kernel_page_table().lock(),
+            KERNEL_ASPACE_BASE.into(),
+            0usize.wrapping_sub(KERNEL_ASPACE_BASE),
+        );
+        Ok(Self {
+            pt,
+            areas: BTreeMap::new(),
+        })
+    }
+
+    /// Creates a copy of the address space, e.g., for `fork`.
+    ///
+    /// The mapped frames are shared by both address spaces, and are mapped
+    /// read-only until they are written, when the writer gets its own copy of
+    /// the frame (copy-on-write).
+    pub fn clone_cow(&mut self) -> AxResult<Self> {
+        let mut new = Self::new_empty()?;
+        for area in self.areas.values() {
+            new.areas.insert(
+                area.start,
+                MemoryArea {
+                    start: area.start,
+                    size: area.size,
+                    flags: area.flags,
+                },
+            );
+            for vaddr in (area.start..area.end()).step_by(PAGE_SIZE_4K) {
+                let Ok((paddr, flags, _)) = self.pt.query(vaddr.into()) else {
+                    continue;
+                };
+                let flags = flags - MappingFlags::WRITE;
+                new.pt
+                    .map(vaddr.into(), paddr, PageSize::Size4K, flags)
+                    .map_err(paging_err_to_ax_err)?;
+                share_frame(paddr);
+                self.pt
+                    .update(vaddr.into(), None, Some(flags))
+                    .map_err(paging_err_to_ax_err)?;
+            }
+        }
+        axhal::arch::flush_tlb(None);
+        Ok(new)
+    }
+
+    /// Returns the start address of the user address space.
+    pub const fn base(&self) -> VirtAddr {
+        VirtAddr::from(axconfig::USPACE_BASE)
+    }
+
+    /// Returns the end address of the user address space.
+    pub const fn end(&self) -> VirtAddr {
+        VirtAddr::from(axconfig::USPACE_BASE + axconfig::USPACE_SIZE)
+    }
+
+    /// Returns whether `[start, start + size)` is in the user address space.
+    pub fn contains_range(&self, start: VirtAddr, size: usize) -> bool {
+        start >= self.base()
+            && start
+                .as_usize()
+                .checked_add(size)
+                .is_some_and(|end| end <= self.end().as_usize())
+    }
+
+    /// Returns the physical address of the root page table.
+    pub const fn page_table_root(&self) -> PhysAddr {
+        self.pt.root_paddr()
+    }
+
+    /// Switches to the page table of this address space on the current CPU.
+    ///
+    /// # Safety
+    ///
+    /// The user mappings of the previous address space (if any) are no longer
+    /// accessible.
+    pub unsafe fn activate(&self) {
+        #[cfg(target_arch = "aarch64")]
+        axhal::arch::write_page_table_root0(self.page_table_root());
+        #[cfg(not(target_arch = "aarch64"))]
+        axhal::arch::write_page_table_root(self.page_table_root());
+    }
+
+    /// Finds a free range of `size` bytes (aligned to pages), which starts at
+    /// or after `hint`.
+    pub fn find_free_area(&self, hint: VirtAddr, size: usize) -> Option<VirtAddr> {
+        let size = align_up_4k(size);
+        let mut start = align_up_4k(hint.as_usize().max(axconfig::USPACE_BASE));
+        for area in self.areas.range(..).map(|(_, a)| a) {
+            if area.end() <= start {
+                continue;
+            }
+            if start.checked_add(size)? <= area.start {
+                break;
+            }
+            start = area.end();
+        }
+        if self.contains_range(start.into(), size) {
+            Some(start.into())
+        } else {
+            None
+        }
+    }
+
+    /// Adds a memory area of `[start, start + size)` with the given mapping
+    /// flags, where the pages are mapped to zero-filled frames on the first
+    /// access, or immediately if `populate` is true.
+    ///
+    /// `start` and `size` must be aligned to pages, and the range must not
+    /// overlap with existing areas.
+    pub fn map_alloc(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        populate: bool,
+    ) -> AxResult {
+        if !start.is_aligned_4k() || !memory_addr::is_aligned_4k(size) || size == 0 {
+            return ax_err!(InvalidInput, "unaligned memory area");
+        }
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "memory area out of the user address space");
+        }
+        let (start, end) = (start.as_usize(), start.as_usize() + size);
+        if !self.overlapping(start, end).is_empty() {
+            return ax_err!(AlreadyExists, "memory area overlaps");
+        }
+        self.areas.insert(start, MemoryArea { start, size, flags });
+        if populate {
+            self.populate_range(start, end, MappingFlags::empty(), false)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the memory areas in `[start, start + size)`, and deallocates
+    /// the frames mapped in the range.
+    ///
+    /// Areas that cross the boundaries are split. `start` and `size` must be
+    /// aligned to pages.
+    pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
+        if !start.is_aligned_4k() || !memory_addr::is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "unaligned memory area");
+        }
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "memory area out of the user address space");
+        }
+        let (start, end) = (start.as_usize(), start.as_usize() + size);
+        for key in self.overlapping(start, end) {
+            let mut area = self.areas.remove(&key).unwrap();
+            if area.start < start {
+                let tail = area.split_off(start);
+                self.areas.insert(area.start, area);
+                area = tail;
+            }
+            if area.end() > end {
+                let tail = area.split_off(end);
+                self.areas.insert(tail.start, tail);
+            }
+            self.unpopulate(area.start, area.end());
+        }
+        Ok(())
+    }
+
+    /// Removes all the memory areas, and deallocates all the mapped frames.
+    pub fn clear(&mut self) {
+        for (_, area) in core::mem::take(&mut self.areas) {
+            self.unpopulate(area.start, area.end());
+        }
+    }
+
+    /// Checks that `[start, start + size)` is fully covered by memory areas
+    /// that permit the access of `access_flags`, and maps all the pages in it
+    /// to frames.
+    ///
+    /// It is usually used to check the user buffers passed to system calls,
+    /// which are then accessed by the kernel directly, without page faults.
+    pub fn populate(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        access_flags: MappingFlags,
+    ) -> AxResult {
+        if size == 0 {
+            return Ok(());
+        }
+        if !self.contains_range(start, size) {
+            return Err(AxError::BadAddress);
+        }
+        let start = start.as_usize();
+        self.populate_range(
+            align_down_4k(start),
+            align_up_4k(start + size),
+            access_flags,
+            true,
+        )
+    }
+
+    /// Reads the user memory at `start` into `buf`, regardless of the mapping
+    /// flags.
+    ///
+    /// The address space does not need to be the active one.
+    pub fn read(&mut self, start: VirtAddr, buf: &mut [u8]) -> AxResult {
+        self.process_bytes(start, buf.len(), false, |src, pos| {
+            buf[pos..pos + src.len()].copy_from_slice(src)
+        })
+    }
+
+    /// Writes `data` to the user memory at `start`, regardless of the mapping
+    /// flags.
+    ///
+    /// The address space does not need to be the active one.
+    pub fn write(&mut self, start: VirtAddr, data: &[u8]) -> AxResult {
+        self.process_bytes(start, data.len(), true, |dst, pos| {
+            dst.copy_from_slice(&data[pos..pos + dst.len()])
+        })
+    }
+
+    /// Handles the page fault at `vaddr` in this address space, returns
+    /// whether it is resolved.
+    ///
+    /// The fault is resolved by mapping the page to a new zero-filled frame,
+    /// or by copying the shared frame on writes (copy-on-write), if `vaddr` is
+    /// in a memory area that permits the access.
+    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+        let vaddr = vaddr.as_usize();
+        let Some(area) = self.area_of(vaddr) else {
+            return false;
+        };
+        if !area.flags.contains(access_flags) {
+            return false;
+        }
+        let (page, flags) = (align_down_4k(vaddr), area.flags);
+        match self.pt.query(page.into()) {
+            Ok((_, mapped_flags, _)) => {
+                // mapped, but the access is not permitted, unless it's a write
+                // to a copy-on-write page
+                access_flags.contains(MappingFlags::WRITE)
+                    && !mapped_flags.contains(MappingFlags::WRITE)
+                    && self.unshare_page(page, flags).is_ok()
+            }
+            Err(_) => self.populate_page(page, flags).is_ok(),
+        }
+    }
+}
+
+// private methods
+impl AddrSpace {
+    /// Returns the start addresses of the areas that overlap `[start, end)`.
+    fn overlapping(&self, start: usize, end: usize) -> Vec<usize> {
+        let mut keys = self
+            .areas
+            .range(..end)
+            .rev()
+            .take_while(|(_, a)| a.end() > start)
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        keys.reverse();
+        keys
+    }
+
+    /// Returns the area that contains `vaddr`.
+    fn area_of(&self, vaddr: usize) -> Option<&MemoryArea> {
+        self.areas
+            .range(..=vaddr)
+            .next_back()
+            .map(|(_, a)| a)
+            .filter(|a| vaddr < a.end())
+    }
+
+    /// Maps the page at `vaddr` to a new zero-filled frame.
+    fn populate_page(&mut self, vaddr: usize, flags: MappingFlags) -> AxResult<PhysAddr> {
+        let paddr = crate::alloc_frame(true)?;
+        if let Err(e) = self.pt.map(vaddr.into(), paddr, PageSize::Size4K, flags) {
+            crate::dealloc_frame(paddr);
+            return Err(paging_err_to_ax_err(e));
+        }
+        axhal::arch::flush_tlb(Some(vaddr.into()));
+        Ok(paddr)
+    }
+
+    /// Makes the copy-on-write page at `vaddr` writable, by copying the frame
+    /// if it is still shared.
+    fn unshare_page(&mut self, vaddr: usize, flags: MappingFlags) -> AxResult {
+        let (paddr, _, _) = self.pt.query(vaddr.into()).map_err(paging_err_to_ax_err)?;
+        let new_paddr = if SHARED_FRAMES.lock().contains_key(&paddr) {
+            let new_paddr = crate::alloc_frame(false)?;
+            unsafe {
+                crate::frame_slice_mut(new_paddr).copy_from_slice(crate::frame_slice_mut(paddr))
+            };
+            release_frame(paddr);
+            Some(new_paddr)
+        } else {
+            None // the other sharers have gone
+        };
+        self.pt
+            .update(vaddr.into(), new_paddr, Some(flags))
+            .map_err(paging_err_to_ax_err)?;
+        axhal::arch::flush_tlb(Some(vaddr.into()));
+        Ok(())
+    }
+
+    /// Maps all the unmapped pages in `[start, end)` to frames, and makes the
+    /// copy-on-write pages writable for the write `access_flags`.
+    ///
+    /// If `check` is true, the range must be covered by areas that permit the
+    /// access.
+    fn populate_range(
+        &mut self,
+        start: usize,
+        end: usize,
+        access_flags: MappingFlags,
+        check: bool,
+    ) -> AxResult {
+        let mut pos = start;
+        for key in self.overlapping(start, end) {
+            let area = &self.areas[&key];
+            if area.start > pos {
+                return Err(AxError::BadAddress);
+            }
+            if check && !area.flags.contains(access_flags) {
+                return Err(AxError::BadAddress);
+            }
+            let (flags, area_end) = (area.flags, area.end().min(end));
+            let write = access_flags.contains(MappingFlags::WRITE);
+            for vaddr in (pos..area_end).step_by(PAGE_SIZE_4K) {
+                match self.pt.query(vaddr.into()) {
+                    Ok((_, mapped_flags, _)) => {
+                        if write && !mapped_flags.contains(MappingFlags::WRITE) {
+                            self.unshare_page(vaddr, flags)?;
+                        }
+                    }
+                    Err(_) => {
+                        self.populate_page(vaddr, flags)?;
+                    }
+                }
+            }
+            pos = area_end;
+        }
+        if pos < end {
+            return Err(AxError::BadAddress);
+        }
+        Ok(())
+    }
+
+    /// Unmaps the pages in `[start, end)`, and deallocates the frames that
+    /// are not shared.
+    fn unpopulate(&mut self, start: usize, end: usize) {
+        for vaddr in (start..end).step_by(PAGE_SIZE_4K) {
+            if let Ok((paddr, _)) = self.pt.unmap(vaddr.into()) {
+                release_frame(paddr);
+                axhal::arch::flush_tlb(Some(vaddr.into()));
+            }
+        }
+    }
+
+    /// Calls `f` with the bytes in each page of `[start, start + size)` and
+    /// their offsets from `start`, through the linear mapping of the frames.
+    ///
+    /// The copy-on-write pages are made writable first if `write` is true.
+    fn process_bytes<F>(&mut self, start: VirtAddr, size: usize, write: bool, mut f: F) -> AxResult
+    where
+        F: FnMut(&mut [u8], usize),
+    {
+        if size == 0 {
+            return Ok(());
+        }
+        if !self.contains_range(start, size) {
+            return Err(AxError::BadAddress);
+        }
+        let (start, end) = (start.as_usize(), start.as_usize() + size);
+        let access = if write {
+            MappingFlags::WRITE
+        } else {
+            MappingFlags::empty()
+        };
+        self.populate_range(align_down_4k(start), align_up_4k(end), access, false)?;
+        let mut vaddr = start;
+        while vaddr < end {
+            let (paddr, _, _) = self.pt.query(vaddr.into()).map_err(paging_err_to_ax_err)?;
+            let len = (align_down_4k(vaddr) + PAGE_SIZE_4K).min(end) - vaddr;
+            let bytes =
+                unsafe { core::slice::from_raw_parts_mut(phys_to_virt(paddr).as_mut_ptr(), len) };
+            f(bytes, vaddr - start);
+            vaddr += len;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// Creates the root-level entries of the dynamic mapping region in the kernel
+/// page table in advance.
+///
+/// Root-level entries of the kernel page table are copied to user page tables
+/// when they are created, so entries created afterwards are not visible in
+/// user address spaces.
+#[cfg(not(target_arch = "aarch64"))]
+pub(crate) fn prepare_kernel_page_table(pt: &mut PageTable) {
+    const GRANULE: usize = PageSize::Size1G as usize; // the smallest root-level granule
+    let vmap_end = axconfig::VMAP_BASE + axconfig::VMAP_SIZE;
+    for vaddr in (axconfig::VMAP_BASE..vmap_end).step_by(GRANULE) {
+        // mapping a page creates all the intermediate tables
+        let vaddr = VirtAddr::from(vaddr);
+        if pt
+            .map(
+                vaddr,
+                PhysAddr::from(0),
+                PageSize::Size4K,
+                MappingFlags::READ,
+            )
+            .is_ok()
+        {
+            pt.unmap(vaddr).ok();
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! [ArceOS](https://github.com/rcore-os/arceos) memory management module.
+//!
+//! It owns the kernel page table, which maps all the physical memory regions
+//! linearly (see [`axhal::mem::phys_to_virt`]), and manages a dedicated region
+//! of the kernel address space for dynamic mappings, which starts at
+//! [`axconfig::VMAP_BASE`] and has the size [`axconfig::VMAP_SIZE`].
+//!
+//! Pages in the dynamic mapping region are mapped to separately allocated
+//! physical frames, e.g., by [`map_page`] and [`alloc_frame`]. They can also be
+//! mapped on demand by the handlers registered with
+//! [`register_page_fault_handler`].
+//!
+//! Unmapping only flushes the TLB of the current CPU. On multi-core systems,
+//! the freed frames and pages are not reused until all CPUs have flushed their
+//! TLBs on their next timer ticks (see [`on_timer_tick`]), or on their next
+//! unmaps without the `irq` feature.
+//!
+//! # Cargo Features
+//!
+//! - `irq`: Timer ticks are available to flush the TLBs (see
+//!   [`on_timer_tick`]).
+//! - `uspace`: Enable user address spaces ([`AddrSpace`]), which have separate
+//!   page tables sharing the kernel mappings.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+
+extern crate alloc;
+
+#[cfg(feature = "uspace")]
+mod aspace;
+mod tlb;
+
+use allocator::{BaseAllocator, BitmapPageAllocator, PageAllocator};
+use axerrno::{AxError, AxResult};
+use axhal::mem::{memory_regions, phys_to_virt, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::{PageSize, PageTable, PagingError, PagingIfImpl};
+use lazy_init::LazyInit;
+use page_table::PagingIf;
+use spinlock::SpinNoIrq;
+
+#[doc(no_inline)]
+pub use axhal::paging::MappingFlags;
+
+#[cfg(feature = "uspace")]
+pub use self::aspace::AddrSpace;
+pub use self::tlb::on_timer_tick;
+
+static KERNEL_PAGE_TABLE: LazyInit<SpinNoIrq<PageTable>> = LazyInit::new();
+
+/// Allocator of the pages in the dynamic mapping region.
+static VMAP_PAGES: SpinNoIrq<BitmapPageAllocator<PAGE_SIZE_4K>> =
+    SpinNoIrq::new(BitmapPageAllocator::new());
+
+/// A handler of page faults in the dynamic mapping region, which returns
+/// whether the fault is resolved, e.g., by mapping the page.
+///
+/// The arguments are the faulting address and the kind of the access.
+pub type PageFaultHandler = fn(VirtAddr, MappingFlags) -> bool;
+
+const MAX_PAGE_FAULT_HANDLERS: usize = 8;
+
+static PAGE_FAULT_HANDLERS: SpinNoIrq<[Option<PageFaultHandler>; MAX_PAGE_FAULT_HANDLERS]> =
+    SpinNoIrq::new([None; MAX_PAGE_FAULT_HANDLERS]);
+
+const fn paging_err_to_ax_err(err: PagingError) -> AxError {
+    match err {
+        PagingError::NoMemory => AxError::NoMemory,
+        PagingError::NotAligned | PagingError::MappedToHugePage => AxError::InvalidInput,
+        PagingError::NotMapped => AxError::NotFound,
+        PagingError::AlreadyMapped => AxError::AlreadyExists,
+    }
+}
+
+/// Returns the kernel page table.
+pub fn kernel_page_table() -> &'static SpinNoIrq<PageTable> {
+    &KERNEL_PAGE_TABLE
+}
+
+/// Returns whether the given address is in the dynamic mapping region.
+pub const fn is_vmap_addr(vaddr: VirtAddr) -> bool {
+    let vaddr = vaddr.as_usize();
+    vaddr >= axconfig::VMAP_BASE && vaddr - axconfig::VMAP_BASE < axconfig::VMAP_SIZE
+}
+
+/// Allocates contiguous pages in the dynamic mapping region, returns the
+/// start address.
+///
+/// No physical frames are mapped to the allocated pages.
+pub fn alloc_vmap_pages(num_pages: usize) -> AxResult<VirtAddr> {
+    VMAP_PAGES
+        .lock()
+        .alloc_pages(num_pages, PAGE_SIZE_4K)
+        .map(VirtAddr::from)
+        .map_err(|_| AxError::NoMemory)
+}
+
+/// Deallocates contiguous pages in the dynamic mapping region, which are
+/// allocated by [`alloc_vmap_pages`].
+///
+/// The pages must have been unmapped. They are not reused until all CPUs
+/// have flushed their TLBs.
+pub fn dealloc_vmap_pages(start: VirtAddr, num_pages: usize) {
+    tlb::free_vmap_pages(start, num_pages)
+}
+
+/// Allocates a physical frame, and fills it with zeros if `zeroed` is true.
+pub fn alloc_frame(zeroed: bool) -> AxResult<PhysAddr> {
+    let paddr = PagingIfImpl::alloc_frame().ok_or(AxError::NoMemory)?;
+    if zeroed {
+        unsafe { core::ptr::write_bytes(phys_to_virt(paddr).as_mut_ptr(), 0, PAGE_SIZE_4K) };
+    }
+    Ok(paddr)
+}
+
+/// Deallocates a physical frame allocated by [`alloc_frame`].
+///
+/// The frame is not reused until all CPUs have flushed their TLBs, as it may
+/// still be accessed through the stale TLB entries if it was mapped.
+pub fn dealloc_frame(paddr: PhysAddr) {
+    tlb::free_frame(paddr)
+}
+
+/// Returns the contents of a physical frame, which is accessed through the
+/// linear mapping.
+///
+/// # Safety
+///
+/// The caller must ensure that the frame is not deallocated while the slice
+/// is alive.
+pub unsafe fn frame_slice_mut<'a>(paddr: PhysAddr) -> &'a mut [u8] {
+    core::slice::from_raw_parts_mut(phys_to_virt(paddr).as_mut_ptr(), PAGE_SIZE_4K)
+}
+
+/// Maps the 4K page at `vaddr` to the frame at `paddr` in the kernel page
+/// table.
+pub fn map_page(vaddr: VirtAddr, paddr: PhysAddr, flags: MappingFlags) -> AxResult {
+    KERNEL_PAGE_TABLE
+        .lock()
+        .map(vaddr, paddr, PageSize::Size4K, flags)
+        .map_err(paging_err_to_ax_err)?;
+    axhal::arch::flush_tlb(Some(vaddr));
+    Ok(())
+}
+
+/// Unmaps the 4K page at `vaddr` in the kernel page table, returns the frame
+/// it was mapped to.
+///
+/// Only the TLB of the current CPU is flushed, the frame must be freed by
+/// [`dealloc_frame`], which waits for the other CPUs to flush.
+pub fn unmap_page(vaddr: VirtAddr) -> AxResult<PhysAddr> {
+    let (paddr, _) = KERNEL_PAGE_TABLE
+        .lock()
+        .unmap(vaddr)
+        .map_err(paging_err_to_ax_err)?;
+    axhal::arch::flush_tlb(Some(vaddr));
+    Ok(paddr)
+}
+
+/// Changes the mapping flags of the 4K page at `vaddr` in the kernel page
+/// table.
+///
+/// Only the TLB of the current CPU is flushed.
+pub fn protect_page(vaddr: VirtAddr, flags: MappingFlags) -> AxResult {
+    let mut pt = KERNEL_PAGE_TABLE.lock();
+    pt.query(vaddr).map_err(paging_err_to_ax_err)?;
+    pt.update(vaddr, None, Some(flags))
+        .map_err(paging_err_to_ax_err)?;
+    axhal::arch::flush_tlb(Some(vaddr));
+    Ok(())
+}
+
+/// Queries the frame and the mapping flags of the 4K page at `vaddr` in the
+/// kernel page table.
+pub fn query_page(vaddr: VirtAddr) -> AxResult<(PhysAddr, MappingFlags)> {
+    let (paddr, flags, _) = KERNEL_PAGE_TABLE
+        .lock()
+        .query(vaddr)
+        .map_err(paging_err_to_ax_err)?;
+    Ok((paddr, flags))
+}
+
+/// Registers a handler of page faults in the dynamic mapping region.
+///
+/// Returns `false` if there are too many handlers.
+pub fn register_page_fault_handler(handler: PageFaultHandler) -> bool {
+    let mut handlers = PAGE_FAULT_HANDLERS.lock();
+    match handlers.iter_mut().find(|h| h.is_none()) {
+        Some(slot) => {
+            *slot = Some(handler);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Handles the page fault at `vaddr` in kernel mode, returns whether it is
+/// resolved.
+///
+/// Faults in the dynamic mapping region are passed to the registered handlers
+/// in order, until one of them resolves it.
+pub fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+    if !is_vmap_addr(vaddr) {
+        return false;
+    }
+    // the handlers may block, do not call them with the lock held
+    let handlers = *PAGE_FAULT_HANDLERS.lock();
+    handlers
+        .iter()
+        .flatten()
+        .any(|handler| handler(vaddr, access_flags))
+}
+
+/// Initializes the kernel page table and the dynamic mapping region, and
+/// switches to the kernel page table on the primary CPU.
+pub fn init_memory_management() {
+    let mut kernel_page_table = PageTable::try_new().expect("failed to create page table");
+    for r in memory_regions() {
+        kernel_page_table
+            .map_region(phys_to_virt(r.paddr), r.paddr, r.size, r.flags.into(), true)
+            .expect("failed to map memory region");
+    }
+    #[cfg(all(feature = "uspace", not(target_arch = "aarch64")))]
+    aspace::prepare_kernel_page_table(&mut kernel_page_table);
+    KERNEL_PAGE_TABLE.init_by(SpinNoIrq::new(kernel_page_table));
+
+    if axconfig::VMAP_SIZE > 0 {
+        debug!(
+            "dynamic mapping region: [{:#x}, {:#x})",
+            axconfig::VMAP_BASE,
+            axconfig::VMAP_BASE + axconfig::VMAP_SIZE
+        );
+        VMAP_PAGES
+            .lock()
+            .init(axconfig::VMAP_BASE, axconfig::VMAP_SIZE);
+    }
+    init_memory_management_secondary();
+}
+
+/// Switches back to the kernel page table on the current CPU, so that no user
+/// address space is active.
+///
+/// On AArch64, the kernel page table is always active (in `TTBR1_EL1`), the
+/// user page table (in `TTBR0_EL1`) is left unchanged.
+#[cfg(feature = "uspace")]
+pub fn activate_kernel_page_table() {
+    #[cfg(not(target_arch = "aarch64"))]
+    unsafe {
+        axhal::arch::write_page_table_root(KERNEL_PAGE_TABLE.lock().root_paddr())
+    };
+}
+
+/// Switches to the kernel page table on the secondary CPUs.
+pub fn init_memory_management_secondary() {
+    unsafe { axhal::arch::write_page_table_root(KERNEL_PAGE_TABLE.lock().root_paddr()) };
+}
@@ -0,0 +1,108 @@
+//! Deferred frees of the unmapped kernel pages.
+//!
+//! Unmapping a page only flushes the TLB of the current CPU, the other CPUs
+//! may still access the page through their stale TLB entries. So on multi-core
+//! systems, the frames and the pages in the dynamic mapping region are not
+//! freed until every CPU has flushed its entire TLB after they are unmapped,
+//! which is done on the next timer tick of each CPU (see [`on_timer_tick`]).
+//!
+//! Without the `irq` feature there are no timer ticks, so each CPU flushes its
+//! TLB and reclaims the frees instead when it unmaps pages itself.
+
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use allocator::PageAllocator;
+use axconfig::SMP;
+use axhal::mem::{PhysAddr, VirtAddr};
+use axhal::paging::PagingIfImpl;
+use page_table::PagingIf;
+use spinlock::SpinNoIrq;
+
+/// Something to be freed after all CPUs have flushed their TLBs.
+enum Deferred {
+    Frame(PhysAddr),
+    VmapPages(VirtAddr, usize),
+}
+
+impl Deferred {
+    fn free(self) {
+        match self {
+            Self::Frame(paddr) => PagingIfImpl::dealloc_frame(paddr),
+            Self::VmapPages(start, num_pages) => super::VMAP_PAGES
+                .lock()
+                .dealloc_pages(start.as_usize(), num_pages),
+        }
+    }
+}
+
+/// Incremented each time something is deferred, so that the CPUs know their
+/// TLBs need to be flushed.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The generation each CPU has loaded before its last flush.
+static FLUSHED: [AtomicUsize; SMP] = [const { AtomicUsize::new(0) }; SMP];
+
+/// Deferred frees with the generations that must be flushed on all CPUs
+/// before they can be freed, in increasing order.
+static PENDING: SpinNoIrq<VecDeque<(usize, Deferred)>> = SpinNoIrq::new(VecDeque::new());
+
+fn free(item: Deferred) {
+    if SMP == 1 {
+        // the TLB has been flushed on the only CPU
+        item.free();
+        return;
+    }
+    let mut pending = PENDING.lock();
+    // the page table is updated before the new generation is published
+    let generation = GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    pending.push_back((generation, item));
+    drop(pending);
+    // no preemption without IRQs, so it stays on the current CPU
+    #[cfg(not(feature = "irq"))]
+    flush_and_reclaim();
+}
+
+/// Frees the frame after all CPUs have flushed their TLBs.
+pub(crate) fn free_frame(paddr: PhysAddr) {
+    free(Deferred::Frame(paddr));
+}
+
+/// Frees the pages in the dynamic mapping region after all CPUs have flushed
+/// their TLBs.
+pub(crate) fn free_vmap_pages(start: VirtAddr, num_pages: usize) {
+    free(Deferred::VmapPages(start, num_pages));
+}
+
+/// Flushes the TLB of the current CPU if any page has been unmapped since its
+/// last flush, and frees the unmapped pages once all CPUs have flushed.
+///
+/// With the `irq` feature, it must be called on every timer tick on every CPU,
+/// otherwise nothing is freed on multi-core systems.
+pub fn on_timer_tick() {
+    if SMP > 1 {
+        flush_and_reclaim();
+    }
+}
+
+/// Flushes the TLB of the current CPU if it's behind the latest generation,
+/// then frees the pending items that all CPUs have flushed.
+fn flush_and_reclaim() {
+    let flushed = &FLUSHED[axhal::cpu::this_cpu_id()];
+    let generation = GENERATION.load(Ordering::Acquire);
+    if flushed.load(Ordering::Relaxed) == generation {
+        return;
+    }
+    axhal::arch::flush_tlb(None);
+    flushed.store(generation, Ordering::Release);
+
+    let min_flushed = FLUSHED
+        .iter()
+        .map(|f| f.load(Ordering::Acquire))
+        .min()
+        .unwrap_or(generation);
+    let mut pending = PENDING.lock();
+    while pending.front().is_some_and(|(g, _)| *g <= min_flushed) {
+        pending.pop_front().unwrap().1.free();
+    }
+}
@@ -9,6 +9,7 @@
 //! - [`TcpSocket`]: A TCP socket that provides POSIX-like APIs.
 //! - [`UdpSocket`]: A UDP socket that provides POSIX-like APIs.
 //! - [`dns_query`]: Function for DNS query.
+//! - [`tcp_sockets`]: Function to list all TCP sockets.
 //!
 //! # Cargo Features
 //!
@@ -36,6 +37,7 @@ pub use self::net_impl::TcpSocket;
 pub use self::net_impl::UdpSocket;
 pub use self::net_impl::{bench_receive, bench_transmit};
 pub use self::net_impl::{dns_query, poll_interfaces};
+pub use self::net_impl::{tcp_sockets, TcpSocketInfo};
 
 use axdriver::{prelude::*, AxDeviceContainer};
 
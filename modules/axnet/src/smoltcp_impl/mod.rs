@@ -23,7 +23,7 @@ use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 use self::listen_table::ListenTable;
 
 pub use self::dns::dns_query;
-pub use self::tcp::TcpSocket;
+pub use self::tcp::{tcp_sockets, TcpSocket, TcpSocketInfo};
 pub use self::udp::UdpSocket;
 
 macro_rules! env_or_default {
@@ -119,6 +119,18 @@ impl<'a> SocketSetWrapper<'a> {
         f(socket)
     }
 
+    pub fn for_each_tcp_socket<F>(&self, mut f: F)
+    where
+        F: FnMut(&socket::tcp::Socket),
+    {
+        let set = self.0.lock();
+        for (_, socket) in set.iter() {
+            if let Some(socket) = socket::tcp::Socket::downcast(socket) {
+                f(socket);
+            }
+        }
+    }
+
     pub fn poll_interfaces(&self) {
         ETH0.poll(&self.0);
     }
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 use core::net::SocketAddr;
 use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
@@ -525,3 +526,66 @@ fn get_ephemeral_port() -> AxResult<u16> {
     }
     ax_err!(AddrInUse, "no avaliable ports!")
 }
+
+/// Information of a TCP socket, as shown in `/proc/net/tcp`.
+#[derive(Debug, Clone, Copy)]
+pub struct TcpSocketInfo {
+    /// The local address.
+    pub local_addr: SocketAddr,
+    /// The remote address, which is unspecified for listening sockets.
+    pub peer_addr: SocketAddr,
+    /// The connection state, numbered as in Linux (e.g., 1 for `ESTABLISHED`
+    /// and 10 for `LISTEN`).
+    pub state: u8,
+    /// The number of bytes in the send queue.
+    pub send_queue: usize,
+    /// The number of bytes in the receive queue.
+    pub recv_queue: usize,
+}
+
+/// Returns the information of all TCP sockets, including the listening ones.
+pub fn tcp_sockets() -> Vec<TcpSocketInfo> {
+    let mut sockets: Vec<_> = LISTEN_TABLE
+        .listen_endpoints()
+        .into_iter()
+        .map(|endpoint| TcpSocketInfo {
+            local_addr: into_core_sockaddr(IpEndpoint::new(
+                endpoint.addr.unwrap_or(UNSPECIFIED_ENDPOINT.addr),
+                endpoint.port,
+            )),
+            peer_addr: into_core_sockaddr(UNSPECIFIED_ENDPOINT),
+            state: 10,
+            send_queue: 0,
+            recv_queue: 0,
+        })
+        .collect();
+    SOCKET_SET.for_each_tcp_socket(|socket| {
+        // sockets in the SYN queue of listeners have no local endpoints
+        if let (Some(local), Some(remote)) = (socket.local_endpoint(), socket.remote_endpoint()) {
+            sockets.push(TcpSocketInfo {
+                local_addr: into_core_sockaddr(local),
+                peer_addr: into_core_sockaddr(remote),
+                state: linux_tcp_state(socket.state()),
+                send_queue: socket.send_queue(),
+                recv_queue: socket.recv_queue(),
+            });
+        }
+    });
+    sockets
+}
+
+const fn linux_tcp_state(state: State) -> u8 {
+    match state {
+        State::Established => 1,
+        State::SynSent => 2,
+        State::SynReceived => 3,
+        State::FinWait1 => 4,
+        State::FinWait2 => 5,
+        State::TimeWait => 6,
+        State::Closed => 7,
+        State::CloseWait => 8,
+        State::LastAck => 9,
+        State::Listen => 10,
+        State::Closing => 11,
+    }
+}
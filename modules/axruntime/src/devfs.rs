@@ -0,0 +1,70 @@
+//! Entries of `/dev` for the devices owned by other modules.
+
+use alloc::sync::Arc;
+use axerrno::AxResult;
+use axfs::devfs::{ConsoleDev, RandomDev};
+
+/// Adds the entries to `/dev`, it should be called after all other modules
+/// have been initialized.
+pub(crate) fn init() {
+    let Some(root) = axfs::devfs::root() else {
+        return;
+    };
+
+    let console = Arc::new(ConsoleDev::new(console_read, console_write));
+    root.add("console", console.clone());
+    root.add("tty", console);
+
+    if axhal::arch::hw_random().is_some() {
+        let random = Arc::new(RandomDev::new(axhal::arch::hw_random));
+        root.add("random", random.clone());
+        root.add("urandom", random);
+    } else {
+        warn!("No hardware random number generator, /dev/random is not available");
+    }
+
+    #[cfg(feature = "display")]
+    {
+        let info = axdisplay::framebuffer_info();
+        // SAFETY: the framebuffer is mapped as long as the display device
+        // exists, which is never dropped.
+        let fb = unsafe {
+            axfs::devfs::FramebufferDev::new(
+                info.fb_base_vaddr,
+                info.fb_size,
+                axdisplay::framebuffer_flush,
+            )
+        };
+        root.add("fb0", Arc::new(fb));
+    }
+}
+
+/// Blocks until at least one byte is read.
+fn console_read(buf: &mut [u8]) -> AxResult<usize> {
+    let getchar = || axhal::console::getchar().map(|c| if c == b'\r' { b'\n' } else { c });
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    loop {
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            match getchar() {
+                Some(c) => buf[read_len] = c,
+                None => break,
+            }
+            read_len += 1;
+        }
+        if read_len > 0 {
+            return Ok(read_len);
+        }
+        #[cfg(feature = "multitask")]
+        axtask::yield_now();
+        #[cfg(not(feature = "multitask"))]
+        core::hint::spin_loop();
+    }
+}
+
+fn console_write(buf: &[u8]) -> AxResult<usize> {
+    axhal::console::write_bytes(buf);
+    Ok(buf.len())
+}
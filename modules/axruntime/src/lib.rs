@@ -7,10 +7,13 @@
 //!
 //! - `alloc`: Enable global memory allocator.
 //! - `paging`: Enable page table manipulation support.
+//! - `uspace`: Enable user space support, where tasks can have their own
+//!   address spaces.
 //! - `irq`: Enable interrupt handling support.
 //! - `multitask`: Enable multi-threading support.
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
-//! - `fs`: Enable filesystem support.
+//! - `fs`: Enable filesystem support, including the `/dev`, `/proc` and `/sys`
+//!   entries of the enabled modules.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
 //!
@@ -22,10 +25,20 @@
 #[macro_use]
 extern crate axlog;
 
+#[cfg(feature = "fs")]
+extern crate alloc;
+
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
 mod trap;
 
+#[cfg(feature = "fs")]
+mod devfs;
+#[cfg(feature = "fs")]
+mod procfs;
+#[cfg(feature = "fs")]
+mod sysfs;
+
 #[cfg(feature = "smp")]
 mod mp;
 
@@ -92,6 +105,53 @@ fn is_init_ok() -> bool {
     INITED_CPUS.load(Ordering::Acquire) == axconfig::SMP
 }
 
+#[cfg(feature = "fs")]
+struct CredentialsIfImpl;
+
+#[cfg(feature = "fs")]
+#[crate_interface::impl_interface]
+impl axfs::fops::CredentialsIf for CredentialsIfImpl {
+    fn current_credentials() -> axfs::fops::Credentials {
+        #[cfg(feature = "multitask")]
+        if let Some(curr) = axtask::current_may_uninit() {
+            return axfs::fops::Credentials::new(curr.uid(), curr.gid());
+        }
+        axfs::fops::Credentials::ROOT
+    }
+
+    fn current_umask() -> u32 {
+        #[cfg(feature = "multitask")]
+        if let Some(curr) = axtask::current_may_uninit() {
+            return curr.umask();
+        }
+        UMASK.load(Ordering::Relaxed)
+    }
+
+    fn set_current_umask(umask: u32) -> u32 {
+        #[cfg(feature = "multitask")]
+        if let Some(curr) = axtask::current_may_uninit() {
+            return curr.set_umask(umask);
+        }
+        UMASK.swap(umask & 0o777, Ordering::Relaxed)
+    }
+}
+
+/// The file mode creation mask if there are no tasks.
+#[cfg(feature = "fs")]
+static UMASK: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0o022);
+
+#[cfg(feature = "fs")]
+struct ClockIfImpl;
+
+#[cfg(feature = "fs")]
+#[crate_interface::impl_interface]
+impl axfs::fops::ClockIf for ClockIfImpl {
+    fn current_time() -> core::time::Duration {
+        // no RTC yet, use the time since boot
+        axhal::time::current_time()
+    }
+}
+
 /// The main entry point of the ArceOS runtime.
 ///
 /// It is called from the bootstrapping code in [axhal]. `cpu_id` is the ID of
@@ -143,7 +203,7 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     #[cfg(feature = "paging")]
     {
         info!("Initialize kernel page table...");
-        remap_kernel_memory().expect("remap kernel memoy failed");
+        axmm::init_memory_management();
     }
 
     info!("Initialize platform devices...");
@@ -165,6 +225,16 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
 
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
+
+        #[cfg(feature = "fs")]
+        {
+            axhal::misc::register_terminate_hook(|| {
+                let _ = axfs::api::sync();
+            });
+            self::devfs::init();
+            self::procfs::init();
+            self::sysfs::init(&all_devices.info);
+        }
     }
 
     #[cfg(feature = "smp")]
@@ -229,56 +299,49 @@ fn init_allocator() {
     }
 }
 
-#[cfg(feature = "paging")]
-fn remap_kernel_memory() -> Result<(), axhal::paging::PagingError> {
-    use axhal::mem::{memory_regions, phys_to_virt};
-    use axhal::paging::PageTable;
-    use lazy_init::LazyInit;
-
-    static KERNEL_PAGE_TABLE: LazyInit<PageTable> = LazyInit::new();
-
-    if axhal::cpu::this_cpu_is_bsp() {
-        let mut kernel_page_table = PageTable::try_new()?;
-        for r in memory_regions() {
-            kernel_page_table.map_region(
-                phys_to_virt(r.paddr),
-                r.paddr,
-                r.size,
-                r.flags.into(),
-                true,
-            )?;
-        }
-        KERNEL_PAGE_TABLE.init_by(kernel_page_table);
-    }
+/// Number of timer ticks per second, can be changed at runtime by
+/// [`set_ticks_per_sec`].
+#[cfg(feature = "irq")]
+static TICKS_PER_SEC: AtomicUsize = AtomicUsize::new(axconfig::TICKS_PER_SEC);
 
-    unsafe { axhal::arch::write_page_table_root(KERNEL_PAGE_TABLE.root_paddr()) };
-    Ok(())
+/// Returns the current number of timer ticks per second.
+#[cfg(feature = "irq")]
+fn ticks_per_sec() -> usize {
+    TICKS_PER_SEC.load(Ordering::Relaxed)
+}
+
+/// Sets the number of timer ticks per second, which takes effect from the
+/// next timer tick.
+#[cfg(feature = "irq")]
+fn set_ticks_per_sec(ticks: usize) {
+    assert!(ticks > 0);
+    TICKS_PER_SEC.store(ticks, Ordering::Relaxed);
 }
 
 #[cfg(feature = "irq")]
 fn init_interrupt() {
     use axhal::time::TIMER_IRQ_NUM;
 
-    // Setup timer interrupt handler
-    const PERIODIC_INTERVAL_NANOS: u64 =
-        axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
-
     #[percpu::def_percpu]
     static NEXT_DEADLINE: u64 = 0;
 
+    // Setup timer interrupt handler
     fn update_timer() {
+        let periodic_interval_nanos = axhal::time::NANOS_PER_SEC / ticks_per_sec() as u64;
         let now_ns = axhal::time::current_time_nanos();
         // Safety: we have disabled preemption in IRQ handler.
         let mut deadline = unsafe { NEXT_DEADLINE.read_current_raw() };
         if now_ns >= deadline {
-            deadline = now_ns + PERIODIC_INTERVAL_NANOS;
+            deadline = now_ns + periodic_interval_nanos;
         }
-        unsafe { NEXT_DEADLINE.write_current_raw(deadline + PERIODIC_INTERVAL_NANOS) };
+        unsafe { NEXT_DEADLINE.write_current_raw(deadline + periodic_interval_nanos) };
         axhal::time::set_oneshot_timer(deadline);
     }
 
     axhal::irq::register_handler(TIMER_IRQ_NUM, || {
         update_timer();
+        #[cfg(feature = "paging")]
+        axmm::on_timer_tick();
         #[cfg(feature = "multitask")]
         axtask::on_timer_tick();
     });
@@ -0,0 +1,175 @@
+//! Entries of `/proc` generated from the live states of other modules.
+
+#![allow(unused_imports)] // most entries depend on other features
+
+use alloc::{format, string::String, string::ToString, sync::Arc, vec::Vec};
+use core::fmt::Write;
+
+use axfs::procfs::{DirNode, FileNode, SymlinkNode};
+
+/// Adds the entries to `/proc`, it should be called after all other modules
+/// have been initialized.
+#[allow(unused_variables)] // `root` is unused if no entries are enabled
+pub(crate) fn init() {
+    let Some(root) = axfs::procfs::root() else {
+        return;
+    };
+
+    #[cfg(feature = "alloc")]
+    root.add("meminfo", Arc::new(FileNode::new(meminfo)));
+    #[cfg(feature = "irq")]
+    root.add("interrupts", Arc::new(FileNode::new(interrupts)));
+    #[cfg(feature = "net")]
+    root.mkdir("net")
+        .add("tcp", Arc::new(FileNode::new(net_tcp)));
+    #[cfg(feature = "multitask")]
+    {
+        root.set_dynamic_dirs(task_ids, populate_task_dir);
+        root.add(
+            "self",
+            Arc::new(SymlinkNode::new(|| {
+                axtask::current().id().as_u64().to_string()
+            })),
+        );
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn meminfo() -> String {
+    const PAGE_SIZE: usize = 0x1000;
+    let allocator = axalloc::global_allocator();
+    let (used_pages, free_pages) = (allocator.used_pages(), allocator.available_pages());
+    let (used_bytes, free_bytes) = (allocator.used_bytes(), allocator.available_bytes());
+    // the byte allocator takes memory from the page allocator, so the pages
+    // it holds are counted as used
+    let total = (used_pages + free_pages) * PAGE_SIZE;
+    let free = free_pages * PAGE_SIZE + free_bytes;
+    format!(
+        "MemTotal: {:>12} kB\nMemFree:  {:>12} kB\nHeapUsed: {:>12} kB\nHeapFree: {:>12} kB\n",
+        total / 1024,
+        free / 1024,
+        used_bytes / 1024,
+        free_bytes / 1024,
+    )
+}
+
+#[cfg(feature = "irq")]
+fn interrupts() -> String {
+    let mut s = String::new();
+    for (irq_num, count) in axhal::irq::irq_stats() {
+        writeln!(s, "{:>4}: {:>10}", irq_num, count).unwrap();
+    }
+    s
+}
+
+#[cfg(feature = "net")]
+fn net_tcp() -> String {
+    use core::net::{IpAddr, SocketAddr};
+
+    // IPv4 addresses are printed in the native byte order like Linux.
+    fn addr_hex(addr: &SocketAddr) -> String {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => u32::from_ne_bytes(ip.octets()),
+            IpAddr::V6(_) => 0,
+        };
+        format!("{:08X}:{:04X}", ip, addr.port())
+    }
+
+    let mut s = String::from("  sl  local_address rem_address   st tx_queue rx_queue\n");
+    for (i, sock) in axnet::tcp_sockets().iter().enumerate() {
+        writeln!(
+            s,
+            "{:4}: {} {} {:02X} {:08X}:{:08X}",
+            i,
+            addr_hex(&sock.local_addr),
+            addr_hex(&sock.peer_addr),
+            sock.state,
+            sock.send_queue,
+            sock.recv_queue,
+        )
+        .unwrap();
+    }
+    s
+}
+
+#[cfg(feature = "multitask")]
+fn task_ids() -> Vec<String> {
+    axtask::all_tasks()
+        .iter()
+        .map(|task| task.id().as_u64().to_string())
+        .collect()
+}
+
+#[cfg(feature = "multitask")]
+fn populate_task_dir(name: &str, dir: &Arc<DirNode>) {
+    use axtask::{AxTaskRef, TaskState};
+
+    let Some(task) = axtask::all_tasks()
+        .into_iter()
+        .find(|task| task.id().as_u64().to_string() == name)
+    else {
+        return;
+    };
+
+    // Do not keep the task alive by its `/proc` entries.
+    let weak = Arc::downgrade(&task);
+    let file = move |f: fn(&AxTaskRef) -> String| {
+        let weak = weak.clone();
+        Arc::new(FileNode::new(move || {
+            weak.upgrade().map(|task| f(&task)).unwrap_or_default()
+        }))
+    };
+
+    fn state_char(task: &AxTaskRef) -> char {
+        match task.state() {
+            TaskState::Running | TaskState::Ready => 'R',
+            TaskState::Blocked => 'S',
+            TaskState::Exited => 'Z',
+        }
+    }
+
+    dir.add("comm", file(|task| format!("{}\n", task.name())));
+    dir.add(
+        "stat",
+        file(|task| {
+            // pid (comm) state, followed by the fields we do not track (zero)
+            // up to the 39th, which is the last CPU the task ran on.
+            let mut s = format!(
+                "{} ({}) {}",
+                task.id().as_u64(),
+                task.name(),
+                state_char(task)
+            );
+            for _ in 4..39 {
+                s.push_str(" 0");
+            }
+            writeln!(s, " {}", task.cpu_id()).unwrap();
+            s
+        }),
+    );
+    dir.add(
+        "status",
+        file(|task| {
+            let state = match state_char(task) {
+                'R' => "R (running)",
+                'S' => "S (sleeping)",
+                _ => "Z (zombie)",
+            };
+            format!(
+                "Name:\t{}\nState:\t{}\nPid:\t{}\nCpu:\t{}\nWchan:\t{:#x}\n",
+                task.name(),
+                state,
+                task.id().as_u64(),
+                task.cpu_id(),
+                task.wait_channel(),
+            )
+        }),
+    );
+    dir.add(
+        "wchan",
+        file(|task| match task.wait_channel() {
+            0 => "0".into(),
+            wchan => format!("{:#x}", wchan),
+        }),
+    );
+}
@@ -0,0 +1,179 @@
+//! Entries of `/sys` for the probed devices and kernel parameters.
+
+use alloc::{format, string::String, sync::Arc};
+use axdriver::{prelude::DeviceType, DeviceBus, DeviceInfo};
+use axerrno::{ax_err, AxResult};
+use axfs::sysfs::{DirNode, FileNode};
+
+/// Adds the entries to `/sys`, it should be called after all other modules
+/// have been initialized.
+pub(crate) fn init(devices: &[DeviceInfo]) {
+    let Some(root) = axfs::sysfs::root() else {
+        return;
+    };
+
+    init_devices(&root.mkdir("class"), devices);
+
+    let kernel = root.mkdir("kernel");
+    init_config(&kernel.mkdir("config"));
+    kernel.add(
+        "log_level",
+        Arc::new(FileNode::new_writable(
+            || format!("{}\n", axlog::max_level()),
+            set_log_level,
+        )),
+    );
+    #[cfg(feature = "irq")]
+    kernel.add(
+        "ticks_per_sec",
+        Arc::new(FileNode::new_writable(
+            || format!("{}\n", crate::ticks_per_sec()),
+            set_ticks_per_sec,
+        )),
+    );
+
+    let clocksource = if cfg!(target_arch = "x86_64") {
+        "tsc"
+    } else if cfg!(target_arch = "riscv64") {
+        "riscv_clocksource"
+    } else if cfg!(target_arch = "aarch64") {
+        "arch_sys_counter"
+    } else {
+        "unknown"
+    };
+    add_value(
+        &root
+            .mkdir("devices")
+            .mkdir("system")
+            .mkdir("clocksource")
+            .mkdir("clocksource0"),
+        "current_clocksource",
+        format!("{}\n", clocksource),
+    );
+}
+
+/// Adds a read-only file whose content never changes.
+fn add_value(dir: &DirNode, name: &str, value: String) {
+    dir.add(name, Arc::new(FileNode::new(move || value.clone())));
+}
+
+/// Creates `/sys/class/<class>/<name>` for each device, the devices are named
+/// in the same way as the modules using them (e.g., `eth0` and `vda`).
+fn init_devices(class: &Arc<DirNode>, devices: &[DeviceInfo]) {
+    for (i, dev) in devices.iter().enumerate() {
+        // index among the devices of the same type
+        let n = devices[..i]
+            .iter()
+            .filter(|d| d.device_type == dev.device_type)
+            .count();
+        let (class_name, type_name, name) = match dev.device_type {
+            DeviceType::Block => ("block", "block", format!("vd{}", (b'a' + n as u8) as char)),
+            DeviceType::Char => ("tty", "char", format!("ttyS{}", n)),
+            DeviceType::Net => ("net", "net", format!("eth{}", n)),
+            DeviceType::Display => ("graphics", "display", format!("fb{}", n)),
+        };
+
+        let dir = class.mkdir(class_name).mkdir(&name);
+        add_value(&dir, "type", format!("{}\n", type_name));
+        add_value(&dir, "name", format!("{}\n", dev.name));
+        match dev.bus {
+            DeviceBus::Platform => add_value(&dir, "bus", "platform\n".into()),
+            DeviceBus::Mmio { base, size } => {
+                add_value(&dir, "bus", "mmio\n".into());
+                add_value(&dir, "mmio_base", format!("{:#x}\n", base));
+                add_value(&dir, "mmio_size", format!("{:#x}\n", size));
+            }
+            DeviceBus::Pci {
+                bus,
+                device,
+                function,
+            } => {
+                add_value(&dir, "bus", "pci\n".into());
+                add_value(
+                    &dir,
+                    "pci_bdf",
+                    format!("0000:{:02x}:{:02x}.{}\n", bus, device, function),
+                );
+            }
+        }
+    }
+}
+
+/// Creates `/sys/kernel/config/<name>` for each platform parameter in
+/// [`axconfig`].
+fn init_config(config: &DirNode) {
+    fn regions(regions: &[(usize, usize)]) -> String {
+        regions
+            .iter()
+            .map(|(base, size)| format!("{:#x} {:#x}\n", base, size))
+            .collect()
+    }
+
+    for (name, value) in [
+        ("arch", format!("{}\n", axconfig::ARCH)),
+        ("platform", format!("{}\n", axconfig::PLATFORM)),
+        ("family", format!("{}\n", axconfig::FAMILY)),
+        ("smp", format!("{}\n", axconfig::SMP)),
+        (
+            "phys_memory_base",
+            format!("{:#x}\n", axconfig::PHYS_MEMORY_BASE),
+        ),
+        (
+            "phys_memory_size",
+            format!("{:#x}\n", axconfig::PHYS_MEMORY_SIZE),
+        ),
+        (
+            "kernel_base_paddr",
+            format!("{:#x}\n", axconfig::KERNEL_BASE_PADDR),
+        ),
+        (
+            "kernel_base_vaddr",
+            format!("{:#x}\n", axconfig::KERNEL_BASE_VADDR),
+        ),
+        (
+            "phys_virt_offset",
+            format!("{:#x}\n", axconfig::PHYS_VIRT_OFFSET),
+        ),
+        ("mmio_regions", regions(axconfig::MMIO_REGIONS)),
+        (
+            "virtio_mmio_regions",
+            regions(axconfig::VIRTIO_MMIO_REGIONS),
+        ),
+        ("pci_ecam_base", format!("{:#x}\n", axconfig::PCI_ECAM_BASE)),
+        ("pci_bus_end", format!("{}\n", axconfig::PCI_BUS_END)),
+        ("pci_ranges", regions(axconfig::PCI_RANGES)),
+        (
+            "timer_frequency",
+            format!("{}\n", axconfig::TIMER_FREQUENCY),
+        ),
+        (
+            "task_stack_size",
+            format!("{:#x}\n", axconfig::TASK_STACK_SIZE),
+        ),
+        ("ticks_per_sec", format!("{}\n", axconfig::TICKS_PER_SEC)),
+    ] {
+        add_value(config, name, value);
+    }
+}
+
+fn set_log_level(data: &str) -> AxResult {
+    const LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+    let level = data.trim();
+    if !LEVELS.iter().any(|l| l.eq_ignore_ascii_case(level)) {
+        return ax_err!(InvalidInput, "unknown log level");
+    }
+    axlog::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(feature = "irq")]
+fn set_ticks_per_sec(data: &str) -> AxResult {
+    // at least 100 us per tick
+    match data.trim().parse::<usize>() {
+        Ok(ticks) if (1..=10_000).contains(&ticks) => {
+            crate::set_ticks_per_sec(ticks);
+            Ok(())
+        }
+        _ => ax_err!(InvalidInput, "invalid number of ticks per second"),
+    }
+}
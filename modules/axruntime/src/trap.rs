@@ -1,3 +1,6 @@
+use axhal::mem::VirtAddr;
+use axhal::trap::MappingFlags;
+
 struct TrapHandlerImpl;
 
 #[crate_interface::impl_interface]
@@ -10,4 +13,28 @@ impl axhal::trap::TrapHandler for TrapHandlerImpl {
             drop(guard); // rescheduling may occur when preemption is re-enabled.
         }
     }
+
+    fn handle_page_fault(_vaddr: VirtAddr, _access_flags: MappingFlags, _is_user: bool) -> bool {
+        #[cfg(feature = "uspace")]
+        if let Some(aspace) = axtask::current_may_uninit().and_then(|t| t.aspace().cloned()) {
+            let mut aspace = aspace.lock();
+            if aspace.contains_range(_vaddr, 1) && aspace.handle_page_fault(_vaddr, _access_flags) {
+                return true;
+            }
+            if _is_user {
+                drop(aspace);
+                warn!(
+                    "unresolved user page fault @ {:#x} ({:?}), exiting",
+                    _vaddr, _access_flags
+                );
+                const SIGSEGV: i32 = 11;
+                axhal::trap::kill_current_process(SIGSEGV);
+            }
+        }
+        #[cfg(feature = "paging")]
+        if !_is_user {
+            return axmm::handle_page_fault(_vaddr, _access_flags);
+        }
+        false
+    }
 }
@@ -1,21 +1,33 @@
-//! A naïve sleeping mutex.
+//! A sleeping mutex with priority inheritance.
 
 use core::cell::UnsafeCell;
 use core::fmt;
 use core::ops::{Deref, DerefMut};
 use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "irq")]
+use core::time::Duration;
 
-use axtask::{current, WaitQueue};
+use axtask::{current, AxTaskRef, WaitQueue};
+use spinlock::SpinNoIrq;
 
 /// A mutual exclusion primitive useful for protecting shared data, similar to
 /// [`std::sync::Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
 ///
 /// When the mutex is locked, the current task will block and be put into the
-/// wait queue. When the mutex is unlocked, all tasks waiting on the queue
+/// wait queue. When the mutex is unlocked, one task waiting on the queue
 /// will be woken up.
+///
+/// To avoid priority inversion, the task holding the mutex inherits the
+/// priority of the waiting task if it's higher, and passes it on to the owner
+/// of the mutex that it's waiting for in turn (see [`axtask::lock_blocked`]).
+/// The priority inherited through the mutex is dropped when it's unlocked,
+/// while the ones inherited through the other mutexes still held are kept.
 pub struct Mutex<T: ?Sized> {
     wq: WaitQueue,
     owner_id: AtomicU64,
+    /// The task holding the mutex, which is updated along with `owner_id`
+    /// with the lock held.
+    owner: SpinNoIrq<Option<AxTaskRef>>,
     data: UnsafeCell<T>,
 }
 
@@ -38,6 +50,7 @@ impl<T> Mutex<T> {
         Self {
             wq: WaitQueue::new(),
             owner_id: AtomicU64::new(0),
+            owner: SpinNoIrq::new(None),
             data: UnsafeCell::new(data),
         }
     }
@@ -64,51 +77,100 @@ impl<T: ?Sized> Mutex<T> {
         self.owner_id.load(Ordering::Relaxed) != 0
     }
 
+    /// The key of the mutex for priority inheritance.
+    fn key(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    /// Returns `true` if the lock is held by the current task.
+    #[inline(always)]
+    pub fn is_owned_by_current(&self) -> bool {
+        self.owner_id.load(Ordering::Relaxed) == current().id().as_u64()
+    }
+
     /// Locks the [`Mutex`] and returns a guard that permits access to the inner data.
     ///
     /// The returned value may be dereferenced for data access
     /// and the lock will be dropped when the guard falls out of scope.
     pub fn lock(&self) -> MutexGuard<T> {
-        let current_id = current().id().as_u64();
         loop {
-            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
-            // when called in a loop.
-            match self.owner_id.compare_exchange_weak(
-                0,
-                current_id,
-                Ordering::Acquire,
-                Ordering::Relaxed,
-            ) {
-                Ok(_) => break,
-                Err(owner_id) => {
-                    assert_ne!(
-                        owner_id,
-                        current_id,
-                        "{} tried to acquire mutex it already owns.",
-                        current().id_name()
-                    );
-                    // Wait until the lock looks unlocked before retrying
-                    self.wq.wait_until(|| !self.is_locked());
-                }
+            if let Some(guard) = self.try_lock_or_inherit() {
+                return guard;
             }
+            // Wait until the lock looks unlocked before retrying
+            self.wq.wait_until(|| !self.is_locked());
         }
-        MutexGuard {
-            lock: self,
-            data: unsafe { &mut *self.data.get() },
+    }
+
+    /// Try to lock this [`Mutex`], waiting at most `dur` if it's held by
+    /// another task. Returns `None` if it times out.
+    #[cfg(feature = "irq")]
+    pub fn try_lock_for(&self, dur: Duration) -> Option<MutexGuard<T>> {
+        let deadline = axhal::time::current_time() + dur;
+        loop {
+            if let Some(guard) = self.try_lock_or_inherit() {
+                return Some(guard);
+            }
+            let now = axhal::time::current_time();
+            if now >= deadline {
+                axtask::lock_unblocked();
+                return None;
+            }
+            self.wq
+                .wait_timeout_until(deadline - now, || !self.is_locked());
+        }
+    }
+
+    /// Try to lock this [`Mutex`], and if it's held by another task, let the
+    /// owner inherit the priority of the current task.
+    fn try_lock_or_inherit(&self) -> Option<MutexGuard<T>> {
+        let curr = current();
+        let current_id = curr.id().as_u64();
+        let mut owner = self.owner.lock();
+        match self
+            .owner_id
+            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => {
+                *owner = Some(curr.as_task_ref().clone());
+                axtask::lock_acquired(self.key());
+                Some(MutexGuard {
+                    lock: self,
+                    data: unsafe { &mut *self.data.get() },
+                })
+            }
+            Err(owner_id) => {
+                assert_ne!(
+                    owner_id,
+                    current_id,
+                    "{} tried to acquire mutex it already owns.",
+                    curr.id_name()
+                );
+                // Let the owner run at our priority until it unlocks
+                let task = owner.clone();
+                drop(owner);
+                if let Some(task) = task {
+                    axtask::lock_blocked(&task, self.key());
+                }
+                None
+            }
         }
     }
 
     /// Try to lock this [`Mutex`], returning a lock guard if successful.
     #[inline(always)]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-        let current_id = current().id().as_u64();
+        let curr = current();
+        let mut owner = self.owner.lock();
         // The reason for using a strong compare_exchange is explained here:
         // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
         if self
             .owner_id
-            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+            .compare_exchange(0, curr.id().as_u64(), Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            *owner = Some(curr.as_task_ref().clone());
+            axtask::lock_acquired(self.key());
             Some(MutexGuard {
                 lock: self,
                 data: unsafe { &mut *self.data.get() },
@@ -118,6 +180,21 @@ impl<T: ?Sized> Mutex<T> {
         }
     }
 
+    /// Creates a guard for the [`Mutex`] that is locked by the current task
+    /// without a guard, e.g. the guard is forgotten.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held by the current task, and there must be no other
+    /// guards. This can be useful together with [`Mutex::force_unlock`] for
+    /// exposing the lock to FFI.
+    pub unsafe fn make_guard_unchecked(&self) -> MutexGuard<T> {
+        MutexGuard {
+            lock: self,
+            data: unsafe { &mut *self.data.get() },
+        }
+    }
+
     /// Force unlock the [`Mutex`].
     ///
     /// # Safety
@@ -126,6 +203,7 @@ impl<T: ?Sized> Mutex<T> {
     /// thread. However, this can be useful in some instances for exposing
     /// the lock to FFI that doesn’t know how to deal with RAII.
     pub unsafe fn force_unlock(&self) {
+        let mut owner = self.owner.lock();
         let owner_id = self.owner_id.swap(0, Ordering::Release);
         assert_eq!(
             owner_id,
@@ -133,6 +211,9 @@ impl<T: ?Sized> Mutex<T> {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
+        *owner = None;
+        axtask::lock_released(self.key());
+        drop(owner);
         self.wq.notify_one(true);
     }
 
@@ -167,6 +248,14 @@ impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
     }
 }
 
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Returns the mutex that the guard locks, to lock it again after the
+    /// guard is dropped.
+    pub(crate) fn mutex(guard: &Self) -> &'a Mutex<T> {
+        guard.lock
+    }
+}
+
 impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
     type Target = T;
     #[inline(always)]
@@ -199,11 +288,9 @@ impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
 
 #[cfg(test)]
 mod tests {
+    use crate::tests::{INIT, SERIAL};
     use crate::Mutex;
     use axtask as thread;
-    use std::sync::Once;
-
-    static INIT: Once = Once::new();
 
     fn may_interrupt() {
         // simulate interrupts
@@ -214,6 +301,7 @@ mod tests {
 
     #[test]
     fn lots_and_lots() {
+        let _lock = SERIAL.lock();
         INIT.call_once(thread::init_scheduler);
 
         const NUM_TASKS: u32 = 10;
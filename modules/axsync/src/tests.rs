@@ -0,0 +1,276 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex as StdMutex;
+
+use axtask as thread;
+
+use crate::{Barrier, Condvar, Mutex, Once, OnceLock, RwLock, Semaphore};
+
+pub(crate) static INIT: std::sync::Once = std::sync::Once::new();
+pub(crate) static SERIAL: StdMutex<()> = StdMutex::new(());
+
+fn wait_for(counter: &AtomicUsize, n: usize) {
+    while counter.load(Ordering::Acquire) < n {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn test_condvar() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    static READY: Mutex<usize> = Mutex::new(0);
+    static CVAR: Condvar = Condvar::new();
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            let guard = CVAR.wait_while(READY.lock(), |ready| *ready == 0);
+            assert_eq!(*guard, 1);
+            drop(guard);
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+    thread::yield_now();
+    assert_eq!(FINISHED.load(Ordering::Acquire), 0);
+
+    *READY.lock() = 1;
+    CVAR.notify_all();
+    wait_for(&FINISHED, NUM_TASKS);
+    println!("Condvar test OK");
+}
+
+#[test]
+fn test_rwlock() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_READERS: usize = 5;
+    const NUM_WRITERS: usize = 5;
+    const NUM_ITERS: usize = 1000;
+    static LOCK: RwLock<(usize, usize)> = RwLock::new((0, 0));
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_WRITERS {
+        thread::spawn(|| {
+            for _ in 0..NUM_ITERS {
+                let mut val = LOCK.write();
+                val.0 += 1;
+                thread::yield_now();
+                val.1 += 1;
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+    for _ in 0..NUM_READERS {
+        thread::spawn(|| {
+            for _ in 0..NUM_ITERS {
+                let val = LOCK.read();
+                assert!(LOCK.try_write().is_none());
+                thread::yield_now();
+                assert_eq!(val.0, val.1);
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+    wait_for(&FINISHED, NUM_READERS + NUM_WRITERS);
+
+    assert_eq!(
+        *LOCK.read(),
+        (NUM_WRITERS * NUM_ITERS, NUM_WRITERS * NUM_ITERS)
+    );
+    println!("RwLock test OK");
+}
+
+#[test]
+fn test_semaphore() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    const NUM_PERMITS: usize = 3;
+    static SEM: Semaphore = Semaphore::new(NUM_PERMITS);
+    static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            for _ in 0..100 {
+                let _permit = SEM.access();
+                let active = ACTIVE.fetch_add(1, Ordering::AcqRel) + 1;
+                assert!(active <= NUM_PERMITS);
+                thread::yield_now();
+                ACTIVE.fetch_sub(1, Ordering::AcqRel);
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+    wait_for(&FINISHED, NUM_TASKS);
+
+    assert_eq!(SEM.available(), NUM_PERMITS);
+    println!("Semaphore test OK");
+}
+
+#[test]
+fn test_barrier() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    const NUM_ROUNDS: usize = 5;
+    static BARRIER: Barrier = Barrier::new(NUM_TASKS);
+    static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+    static LEADERS: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..NUM_TASKS {
+        thread::spawn(|| {
+            for round in 1..=NUM_ROUNDS {
+                ARRIVED.fetch_add(1, Ordering::AcqRel);
+                if BARRIER.wait().is_leader() {
+                    LEADERS.fetch_add(1, Ordering::AcqRel);
+                }
+                assert!(ARRIVED.load(Ordering::Acquire) >= round * NUM_TASKS);
+                BARRIER.wait();
+            }
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+    wait_for(&FINISHED, NUM_TASKS);
+
+    assert_eq!(LEADERS.load(Ordering::Acquire), NUM_ROUNDS);
+    println!("Barrier test OK");
+}
+
+#[test]
+fn test_once() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    static ONCE: Once = Once::new();
+    static CELL: OnceLock<usize> = OnceLock::new();
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    for i in 0..NUM_TASKS {
+        thread::spawn(move || {
+            ONCE.call_once(|| {
+                CALLS.fetch_add(1, Ordering::AcqRel);
+                thread::yield_now();
+            });
+            assert!(ONCE.is_completed());
+            assert_eq!(CALLS.load(Ordering::Acquire), 1);
+            let val = *CELL.get_or_init(|| {
+                thread::yield_now();
+                i
+            });
+            assert_eq!(CELL.get(), Some(&val));
+            FINISHED.fetch_add(1, Ordering::Release);
+        });
+    }
+    wait_for(&FINISHED, NUM_TASKS);
+
+    assert!(CELL.set(NUM_TASKS).is_err());
+    println!("Once test OK");
+}
+
+#[test]
+fn test_priority_inheritance() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    if !thread::set_priority(-5) {
+        println!("Priority inheritance test skipped: no priorities in the scheduler");
+        return;
+    }
+
+    static M: Mutex<()> = Mutex::new(());
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    thread::spawn(|| {
+        assert!(thread::set_priority(10));
+        let guard = M.lock();
+        LOCKED.store(true, Ordering::Release);
+        // the main task blocks on the mutex and lends its priority
+        while thread::current().priority() != -5 {
+            thread::yield_now();
+        }
+        drop(guard);
+        assert_eq!(thread::current().priority(), 10);
+        FINISHED.fetch_add(1, Ordering::Release);
+    });
+
+    while !LOCKED.load(Ordering::Acquire) {
+        thread::yield_now();
+    }
+    drop(M.lock());
+    wait_for(&FINISHED, 1);
+
+    assert_eq!(thread::current().priority(), -5);
+    assert!(thread::set_priority(0));
+    println!("Priority inheritance test OK");
+}
+
+#[test]
+fn test_nested_priority_inheritance() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(thread::init_scheduler);
+
+    if !thread::set_priority(-5) {
+        println!("Nested priority inheritance test skipped: no priorities in the scheduler");
+        return;
+    }
+
+    static A: Mutex<()> = Mutex::new(());
+    static B: Mutex<()> = Mutex::new(());
+    static C: Mutex<()> = Mutex::new(());
+    static LOCKED: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    let owner = thread::spawn(|| {
+        assert!(thread::set_priority(10));
+        let a = A.lock();
+        let b = B.lock();
+        LOCKED.fetch_add(1, Ordering::Release);
+        // the main task blocks on `A`
+        while thread::current().priority() != -5 {
+            thread::yield_now();
+        }
+        drop(a);
+        // still inherited from the waiter through `B`
+        assert_eq!(thread::current().priority(), 0);
+        // the main task blocks on `C` of the waiter, which passes it on
+        while thread::current().priority() != -5 {
+            thread::yield_now();
+        }
+        drop(b);
+        assert_eq!(thread::current().priority(), 10);
+        FINISHED.fetch_add(1, Ordering::Release);
+    });
+    wait_for(&LOCKED, 1);
+
+    let waiter = thread::spawn(|| {
+        assert!(thread::set_priority(0));
+        let c = C.lock();
+        LOCKED.fetch_add(1, Ordering::Release);
+        drop(B.lock());
+        drop(c);
+        FINISHED.fetch_add(1, Ordering::Release);
+    });
+    while owner.priority() != 0 {
+        thread::yield_now();
+    }
+    assert_eq!(LOCKED.load(Ordering::Acquire), 2);
+
+    drop(A.lock());
+    drop(C.lock());
+    wait_for(&FINISHED, 2);
+    assert_eq!(waiter.priority(), 0);
+
+    assert_eq!(thread::current().priority(), -5);
+    assert!(thread::set_priority(0));
+    println!("Nested priority inheritance test OK");
+}
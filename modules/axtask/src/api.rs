@@ -1,11 +1,14 @@
 //! Task APIs for multi-task configuration.
 
-use alloc::{string::String, sync::Arc};
+use alloc::{string::String, sync::Arc, vec::Vec};
 
-pub(crate) use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+#[cfg(feature = "uspace")]
+use {axmm::AddrSpace, spinlock::SpinNoIrq};
+
+pub(crate) use crate::run_queue::{current_run_queue, AxRunQueue};
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::task::{CurrentTask, TaskId, TaskInner, TaskState};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::wait_queue::WaitQueue;
 
@@ -68,6 +71,10 @@ pub fn init_scheduler() {
     crate::run_queue::init();
     #[cfg(feature = "irq")]
     crate::timers::init();
+    #[cfg(feature = "paging")]
+    assert!(axmm::register_page_fault_handler(
+        crate::task::handle_stack_overflow
+    ));
 
     info!("  use {} scheduler.", Scheduler::scheduler_name());
 }
@@ -84,18 +91,21 @@ pub fn init_scheduler_secondary() {
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
     crate::timers::check_events();
-    RUN_QUEUE.lock().scheduler_timer_tick();
+    current_run_queue().scheduler_timer_tick();
 }
 
 /// Spawns a new task with the given parameters.
 ///
+/// The task is put into the run queue of the least loaded CPU, and may be
+/// migrated to other CPUs later for load balancing.
+///
 /// Returns the task reference.
 pub fn spawn_raw<F>(f: F, name: String, stack_size: usize) -> AxTaskRef
 where
     F: FnOnce() + Send + 'static,
 {
     let task = TaskInner::new(f, name, stack_size);
-    RUN_QUEUE.lock().add_task(task.clone());
+    current_run_queue().spawn_task(task.clone());
     task
 }
 
@@ -112,6 +122,32 @@ where
     spawn_raw(f, "".into(), axconfig::TASK_STACK_SIZE)
 }
 
+/// Spawns a new task with the user address space `aspace`.
+///
+/// The task starts in kernel mode by running `f`, which usually prepares the
+/// user context and enters user space (see [`axhal::arch::UspaceContext`])
+/// eventually. The address space is activated whenever the task is running.
+///
+/// The default task stack size is [`axconfig::TASK_STACK_SIZE`].
+///
+/// Returns the task reference.
+#[cfg(feature = "uspace")]
+pub fn spawn_user<F>(f: F, name: String, aspace: Arc<SpinNoIrq<AddrSpace>>) -> AxTaskRef
+where
+    F: FnOnce() + Send + 'static,
+{
+    let task = TaskInner::new_user(f, name, axconfig::TASK_STACK_SIZE, aspace);
+    current_run_queue().spawn_task(task.clone());
+    task
+}
+
+/// Returns all tasks that are not dropped, sorted by the task IDs.
+///
+/// The exited tasks are included until they are reclaimed.
+pub fn all_tasks() -> Vec<AxTaskRef> {
+    crate::task::all_tasks()
+}
+
 /// Set the priority for current task.
 ///
 /// The range of the priority is dependent on the underlying scheduler. For
@@ -122,13 +158,84 @@ where
 ///
 /// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
 pub fn set_priority(prio: isize) -> bool {
-    RUN_QUEUE.lock().set_current_priority(prio)
+    current_run_queue().set_current_priority(prio)
+}
+
+/// Records that the current task is blocked on the lock `key` (e.g., the
+/// address of the lock) held by `owner`, and lets the owner inherit the
+/// priority of the current task if it's higher. A smaller value means a
+/// higher priority, as the nice value of [CFS].
+///
+/// It's used for priority inheritance. If the owner is also blocked on another
+/// lock, the priority is passed on to the owner of that lock, and so on. Each
+/// owner keeps the priority until it calls [`lock_released`] for the lock it's
+/// inherited through, and the priority set by [`set_priority`] in between
+/// takes effect after that. It has no effect if the underlying scheduler
+/// doesn't support priorities.
+///
+/// The owner must have called [`lock_acquired`] for the lock. The current task
+/// is no longer blocked after it calls [`lock_acquired`] or [`lock_unblocked`].
+///
+/// [CFS]: https://en.wikipedia.org/wiki/Completely_Fair_Scheduler
+pub fn lock_blocked(owner: &AxTaskRef, key: usize) {
+    current_run_queue().block_current_on_lock(owner, key);
+}
+
+/// Records that the current task has acquired the lock `key`, through which it
+/// may inherit priorities (see [`lock_blocked`]).
+pub fn lock_acquired(key: usize) {
+    let curr = current();
+    let mut state = curr.lock_state().lock();
+    state.blocked_on = None;
+    state.held.push((key, isize::MAX));
+}
+
+/// Records that the current task has given up waiting for the lock that it's
+/// blocked on.
+pub fn lock_unblocked() {
+    current().lock_state().lock().blocked_on = None;
+}
+
+/// Records that the current task has released the lock `key`, and drops the
+/// priority inherited through it. The highest one inherited through the other
+/// locks it still holds is kept.
+pub fn lock_released(key: usize) {
+    current_run_queue().release_current_lock(key);
+}
+
+/// Sets the CPU affinity of the given task, i.e. the CPUs that it is allowed
+/// to run on, bit `i` of `cpu_mask` for CPU `i`.
+///
+/// If the task is on a CPU not in the mask, it's migrated when it's scheduled
+/// next time, or immediately if it's the current task.
+///
+/// Returns `false` if the mask contains no valid CPUs.
+pub fn set_affinity(task: &AxTaskRef, cpu_mask: usize) -> bool {
+    let cpu_mask = cpu_mask & crate::task::CPU_MASK_FULL;
+    if cpu_mask == 0 {
+        return false;
+    }
+    task.set_cpu_mask(cpu_mask);
+    if current().ptr_eq(task) {
+        current_run_queue().migrate_current();
+    } else {
+        #[cfg(feature = "preempt")]
+        if task.is_running() && !task.can_run_on(task.cpu_id()) {
+            task.set_preempt_pending(true);
+        }
+    }
+    true
+}
+
+/// Gets the CPU affinity of the given task, see [`set_affinity`].
+pub fn get_affinity(task: &AxTaskRef) -> usize {
+    task.cpu_mask()
 }
 
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    current_run_queue().yield_current();
 }
 
 /// Current task is going to sleep for the given duration.
@@ -143,14 +250,39 @@ pub fn sleep(dur: core::time::Duration) {
 /// If the feature `irq` is not enabled, it uses busy-wait instead.
 pub fn sleep_until(deadline: axhal::time::TimeValue) {
     #[cfg(feature = "irq")]
-    RUN_QUEUE.lock().sleep_until(deadline);
+    current_run_queue().sleep_until(deadline);
     #[cfg(not(feature = "irq"))]
     axhal::time::busy_wait_until(deadline);
 }
 
+/// Blocks the current task until `condition` becomes true, which is checked
+/// again whenever a task exits.
+///
+/// It is used to wait for any of several tasks to exit, while
+/// [`TaskInner::join`] waits for a specific one.
+pub fn wait_any_exit_until<F>(condition: F)
+where
+    F: Fn() -> bool,
+{
+    crate::run_queue::ANY_TASK_EXITED.wait_until(condition)
+}
+
 /// Exits the current task.
+///
+/// The memory in the user address space of the task (if any) is deallocated
+/// immediately, unless the address space is shared with other tasks.
 pub fn exit(exit_code: i32) -> ! {
-    RUN_QUEUE.lock().exit_current(exit_code)
+    #[cfg(feature = "uspace")]
+    if let Some(aspace) = current().aspace() {
+        if Arc::strong_count(aspace) == 1 {
+            aspace.lock().clear();
+        }
+    }
+    if current().is_init() {
+        // the system will be terminated with preemption disabled
+        axhal::misc::run_terminate_hooks();
+    }
+    current_run_queue().exit_current(exit_code)
 }
 
 /// The idle task routine.
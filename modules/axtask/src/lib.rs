@@ -4,6 +4,10 @@
 //! creation, scheduling, sleeping, termination, etc. The scheduler algorithm
 //! is configurable by cargo features.
 //!
+//! Each CPU has its own run queue. New tasks are put into the least loaded
+//! one, and ready tasks are migrated between CPUs periodically (with the
+//! `irq` feature) or when a CPU becomes idle.
+//!
 //! # Cargo Features
 //!
 //! - `multitask`: Enable multi-task support. If it's enabled, complex task
@@ -13,6 +17,11 @@
 //!    APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!    [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `paging`: Allocate task stacks in the dynamic mapping region of the
+//!   kernel address space, with unmapped guard pages below them to detect
+//!   stack overflows.
+//! - `uspace`: Enable tasks with user address spaces (see [`spawn_user`]),
+//!   which are switched along with the tasks.
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -1,72 +1,311 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use kernel_guard::NoPreemptIrqSave;
 use lazy_init::LazyInit;
 use scheduler::BaseScheduler;
-use spinlock::SpinNoIrq;
+use spinlock::{SpinNoIrq, SpinRaw};
 
 use crate::task::{CurrentTask, TaskState};
-use crate::{AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::{AxTask, AxTaskRef, Scheduler, TaskInner, WaitQueue};
 
-// TODO: per-CPU
-pub(crate) static RUN_QUEUE: LazyInit<SpinNoIrq<AxRunQueue>> = LazyInit::new();
+/// The limit on the length of the chain of the lock owners that a priority is
+/// passed on to, which also stops the cycles of deadlocked tasks.
+const MAX_INHERIT_DEPTH: usize = 16;
 
-// TODO: per-CPU
-static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
+/// Number of timer ticks between two periodic load balancing on each CPU.
+#[cfg(feature = "irq")]
+const BALANCE_INTERVAL_TICKS: usize = 10;
 
-static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
+#[percpu::def_percpu]
+static RUN_QUEUE: LazyInit<AxRunQueue> = LazyInit::new();
 
 #[percpu::def_percpu]
 static IDLE_TASK: LazyInit<AxTaskRef> = LazyInit::new();
 
+/// The task switched out by the last context switch on this CPU. Its
+/// `on_cpu` flag is cleared by the next task, once the switch is complete.
+#[percpu::def_percpu]
+static PREV_TASK: usize = 0;
+
+/// Notified whenever a task exits, see [`crate::wait_any_exit_until`].
+pub(crate) static ANY_TASK_EXITED: WaitQueue = WaitQueue::new();
+
+/// The run queue of a CPU.
+///
+/// It is operated by its own CPU with IRQs and preemption disabled (see
+/// [`current_run_queue`]). Other CPUs only add new tasks to it, or steal
+/// ready tasks from it for load balancing.
+///
+/// A woken up task is put into the run queue of the CPU that wakes it up, so
+/// only one scheduler lock is held at a time.
 pub(crate) struct AxRunQueue {
-    scheduler: Scheduler,
+    cpu_id: usize,
+    scheduler: SpinRaw<Scheduler>,
+    /// Number of tasks in the scheduler, which is the load of the CPU.
+    num_tasks: AtomicUsize,
+    #[cfg(feature = "irq")]
+    ticks_to_balance: AtomicUsize,
+    /// The exited tasks to be dropped by the `gc` task of the CPU.
+    exited_tasks: SpinNoIrq<VecDeque<AxTaskRef>>,
+    wait_for_exit: WaitQueue,
+}
+
+/// A reference to the run queue of the current CPU, which disables IRQs and
+/// preemption while it's alive, so that the current task will not migrate to
+/// other CPUs.
+pub(crate) struct CurrentRunQueueRef {
+    inner: &'static AxRunQueue,
+    _guard: NoPreemptIrqSave,
+}
+
+impl Deref for CurrentRunQueueRef {
+    type Target = AxRunQueue;
+    fn deref(&self) -> &Self::Target {
+        self.inner
+    }
+}
+
+/// Gets the run queue of the current CPU.
+pub(crate) fn current_run_queue() -> CurrentRunQueueRef {
+    let _guard = NoPreemptIrqSave::new();
+    // Safety: preemption is disabled, and the run queue is initialized along
+    // with the scheduler.
+    let inner = unsafe { RUN_QUEUE.current_ref_raw().get_unchecked() };
+    CurrentRunQueueRef { inner, _guard }
+}
+
+/// Returns the run queues of other CPUs that are initialized.
+fn other_run_queues(cpu_id: usize) -> impl Iterator<Item = &'static AxRunQueue> {
+    (0..axconfig::SMP)
+        .filter(move |&i| i != cpu_id)
+        .filter_map(|i| unsafe { RUN_QUEUE.remote_ref_raw(i) }.try_get())
 }
 
 impl AxRunQueue {
-    pub fn new() -> SpinNoIrq<Self> {
-        let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
+    fn new(cpu_id: usize) -> Self {
+        let gc_task = TaskInner::new(
+            move || gc_entry(cpu_id),
+            "gc".into(),
+            axconfig::TASK_STACK_SIZE,
+        );
         let mut scheduler = Scheduler::new();
         scheduler.add_task(gc_task);
-        SpinNoIrq::new(Self { scheduler })
+        Self {
+            cpu_id,
+            scheduler: SpinRaw::new(scheduler),
+            num_tasks: AtomicUsize::new(1),
+            #[cfg(feature = "irq")]
+            ticks_to_balance: AtomicUsize::new(BALANCE_INTERVAL_TICKS),
+            exited_tasks: SpinNoIrq::new(VecDeque::new()),
+            wait_for_exit: WaitQueue::new(),
+        }
+    }
+
+    /// Returns the number of ready tasks in the run queue.
+    fn num_tasks(&self) -> usize {
+        self.num_tasks.load(Ordering::Acquire)
+    }
+
+    /// Returns the run queue of the least loaded CPU that the task is allowed
+    /// to run on, which is this one if there is a tie.
+    ///
+    /// Falls back to this one if none of the allowed CPUs is initialized.
+    fn select_run_queue(&self, task: &AxTaskRef) -> &AxRunQueue {
+        let least_loaded = other_run_queues(self.cpu_id)
+            .filter(|rq| task.can_run_on(rq.cpu_id))
+            .min_by_key(|rq| rq.num_tasks());
+        match least_loaded {
+            Some(rq) if !task.can_run_on(self.cpu_id) || rq.num_tasks() < self.num_tasks() => rq,
+            _ => self,
+        }
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
+    /// Adds a new task to the run queue of the least loaded CPU that it is
+    /// allowed to run on.
+    pub fn spawn_task(&self, task: AxTaskRef) {
         debug!("task spawn: {}", task.id_name());
         assert!(task.is_ready());
-        self.scheduler.add_task(task);
+        self.select_run_queue(&task).add_task(task);
+    }
+
+    fn add_task(&self, task: AxTaskRef) {
+        let mut scheduler = self.scheduler.lock();
+        scheduler.add_task(task);
+        self.num_tasks.fetch_add(1, Ordering::Release);
+    }
+
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        let task = self.scheduler.lock().pick_next_task()?;
+        self.num_tasks.fetch_sub(1, Ordering::Release);
+        Some(task)
+    }
+
+    /// Takes a ready task from the busiest run queue of other CPUs, if it has
+    /// more than `min_tasks` tasks.
+    ///
+    /// Only the next task of that run queue is considered. If it's not allowed
+    /// to run on this CPU, it's put back and nothing is stolen.
+    fn steal_task(&self, min_tasks: usize) -> Option<AxTaskRef> {
+        let busiest = other_run_queues(self.cpu_id).max_by_key(|rq| rq.num_tasks())?;
+        if busiest.num_tasks() <= min_tasks {
+            return None;
+        }
+        // do not wait for the lock, as the other CPU may be stealing from us
+        let mut scheduler = busiest.scheduler.try_lock()?;
+        let task = scheduler.pick_next_task()?;
+        if !task.can_run_on(self.cpu_id) {
+            scheduler.put_prev_task(task, true);
+            return None;
+        }
+        busiest.num_tasks.fetch_sub(1, Ordering::Release);
+        drop(scheduler);
+        debug!(
+            "task migrate: {}, CPU {} -> {}",
+            task.id_name(),
+            busiest.cpu_id,
+            self.cpu_id
+        );
+        Some(task)
     }
 
     #[cfg(feature = "irq")]
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn scheduler_timer_tick(&self) {
         let curr = crate::current();
-        if !curr.is_idle() && self.scheduler.task_tick(curr.as_task_ref()) {
+        if !curr.is_idle() && self.scheduler.lock().task_tick(curr.as_task_ref()) {
             #[cfg(feature = "preempt")]
             curr.set_preempt_pending(true);
         }
+        if self.ticks_to_balance.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.ticks_to_balance
+                .store(BALANCE_INTERVAL_TICKS, Ordering::Relaxed);
+            self.balance_load();
+        }
     }
 
-    pub fn yield_current(&mut self) {
+    /// Pulls a task from the busiest CPU, if it has at least two more tasks
+    /// than this one.
+    #[cfg(feature = "irq")]
+    fn balance_load(&self) {
+        if let Some(task) = self.steal_task(self.num_tasks() + 1) {
+            self.add_task(task);
+        }
+    }
+
+    pub fn yield_current(&self) {
         let curr = crate::current();
         trace!("task yield: {}", curr.id_name());
         assert!(curr.is_running());
         self.resched(false);
     }
 
-    pub fn set_current_priority(&mut self, prio: isize) -> bool {
-        self.scheduler
-            .set_priority(crate::current().as_task_ref(), prio)
+    /// Moves the current task to another CPU, if it's not allowed to run on
+    /// this CPU anymore.
+    pub fn migrate_current(&self) {
+        let curr = crate::current();
+        if !curr.can_run_on(self.cpu_id) {
+            debug!("task migrate: {}, CPU {}", curr.id_name(), self.cpu_id);
+            self.resched(false);
+        }
+    }
+
+    pub fn set_current_priority(&self, prio: isize) -> bool {
+        let curr = crate::current();
+        let mut scheduler = self.scheduler.lock();
+        if !scheduler.set_priority(curr.as_task_ref(), prio) {
+            return false;
+        }
+        curr.set_base_priority(prio);
+        if curr.priority() != prio {
+            // keep the inherited priority until it's restored
+            scheduler.set_priority(curr.as_task_ref(), curr.priority());
+        }
+        true
+    }
+
+    /// Records that the current task is blocked on the lock `key` held by
+    /// `owner`, and passes its priority on to the owner, and then to the
+    /// owners of the locks that they are blocked on in turn.
+    ///
+    /// A lock passes on a priority only once, and only while it's held.
+    pub fn block_current_on_lock(&self, owner: &AxTaskRef, key: usize) {
+        let curr = crate::current();
+        let prio = curr.priority();
+        curr.lock_state().lock().blocked_on = Some((key, owner.clone()));
+
+        let (mut task, mut key) = (owner.clone(), key);
+        for _ in 0..MAX_INHERIT_DEPTH {
+            let mut state = task.lock_state().lock();
+            match state.held.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, inherited)) if prio < *inherited => *inherited = prio,
+                _ => break,
+            }
+            if prio < task.priority() {
+                self.update_inherited_priority(&task, &state.held);
+                debug!("task inherit priority: {}, {}", task.id_name(), prio);
+            }
+            let Some(next) = state.blocked_on.clone() else {
+                break;
+            };
+            drop(state);
+            (key, task) = next;
+        }
+    }
+
+    /// Records that the current task has released the lock `key`, and drops
+    /// the priority inherited through it.
+    pub fn release_current_lock(&self, key: usize) {
+        let curr = crate::current();
+        let mut state = curr.lock_state().lock();
+        let Some(idx) = state.held.iter().position(|&(k, _)| k == key) else {
+            return;
+        };
+        if state.held.swap_remove(idx).1 != isize::MAX {
+            self.update_inherited_priority(curr.as_task_ref(), &state.held);
+            debug!(
+                "task restore priority: {}, {}",
+                curr.id_name(),
+                curr.priority()
+            );
+        }
+    }
+
+    /// Sets the inherited priority of `task` to the highest one inherited
+    /// through the `held` locks, and updates the scheduler of the CPU that the
+    /// task is on, which is not necessarily this one.
+    fn update_inherited_priority(&self, task: &AxTaskRef, held: &[(usize, isize)]) {
+        let inherited = held.iter().map(|&(_, prio)| prio).min();
+        let mut scheduler = loop {
+            let cpu_id = task.cpu_id();
+            let rq = if cpu_id == self.cpu_id {
+                self
+            } else {
+                // Safety: the task has been on that CPU, so its run queue is
+                // initialized.
+                unsafe { RUN_QUEUE.remote_ref_raw(cpu_id).get_unchecked() }
+            };
+            let scheduler = rq.scheduler.lock();
+            // it's set with the scheduler locked, see `switch_to`
+            if task.cpu_id() == cpu_id {
+                break scheduler;
+            }
+        };
+        task.set_inherited_priority(inherited.unwrap_or(isize::MAX));
+        if !scheduler.set_priority(task, task.priority()) {
+            // not supported by the scheduler
+            task.set_inherited_priority(isize::MAX);
+        }
     }
 
     #[cfg(feature = "preempt")]
-    pub fn preempt_resched(&mut self) {
+    pub fn preempt_resched(&self) {
         let curr = crate::current();
         assert!(curr.is_running());
 
-        // When we get the mutable reference of the run queue, we must
-        // have held the `SpinNoIrq` lock with both IRQs and preemption
-        // disabled. So we need to set `current_disable_count` to 1 in
-        // `can_preempt()` to obtain the preemption permission before
-        //  locking the run queue.
+        // When we get the reference of the run queue, we must have disabled
+        // both IRQs and preemption. So we need to set `current_disable_count`
+        // to 1 in `can_preempt()` to obtain the preemption permission.
         let can_preempt = curr.can_preempt(1);
 
         debug!(
@@ -81,25 +320,31 @@ impl AxRunQueue {
         }
     }
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+    pub fn exit_current(&self, exit_code: i32) -> ! {
         let curr = crate::current();
         debug!("task exit: {}, exit_code={}", curr.id_name(), exit_code);
         assert!(curr.is_running());
         assert!(!curr.is_idle());
         if curr.is_init() {
-            EXITED_TASKS.lock().clear();
+            self.exited_tasks.lock().clear();
             axhal::misc::terminate();
         } else {
             curr.set_state(TaskState::Exited);
             curr.notify_exit(exit_code, self);
-            EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
+            self.exited_tasks.lock().push_back(curr.clone());
+            self.wait_for_exit.notify_one_locked(false, self);
+            ANY_TASK_EXITED.notify_all_locked(false, self);
             self.resched(false);
         }
         unreachable!("task exited!");
     }
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
+    /// Blocks the current task, and switches to another task.
+    ///
+    /// `wait_queue_push` is called after the task is marked as blocked, to
+    /// put it into a wait queue (or the timer list), from where it can be
+    /// woken up by other CPUs immediately.
+    pub fn block_current<F>(&self, wait_queue_push: F)
     where
         F: FnOnce(AxTaskRef),
     {
@@ -117,11 +362,16 @@ impl AxRunQueue {
         self.resched(false);
     }
 
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
+    pub fn unblock_task(&self, task: AxTaskRef, resched: bool) {
         debug!("task unblock: {}", task.id_name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.scheduler.add_task(task); // TODO: priority
+        // the task may be woken up by the timer and a wait queue at the same
+        // time, only one of them succeeds
+        if task.transition_state(TaskState::Blocked, TaskState::Ready) {
+            if task.can_run_on(self.cpu_id) {
+                self.add_task(task); // TODO: priority
+            } else {
+                self.select_run_queue(&task).add_task(task);
+            }
             if resched {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
@@ -130,7 +380,7 @@ impl AxRunQueue {
     }
 
     #[cfg(feature = "irq")]
-    pub fn sleep_until(&mut self, deadline: axhal::time::TimeValue) {
+    pub fn sleep_until(&self, deadline: axhal::time::TimeValue) {
         let curr = crate::current();
         debug!("task sleep: {}, deadline={:?}", curr.id_name(), deadline);
         assert!(curr.is_running());
@@ -138,9 +388,7 @@ impl AxRunQueue {
 
         let now = axhal::time::current_time();
         if now < deadline {
-            crate::timers::set_alarm_wakeup(deadline, curr.clone());
-            curr.set_state(TaskState::Blocked);
-            self.resched(false);
+            self.block_current(|task| crate::timers::set_alarm_wakeup(deadline, task));
         }
     }
 }
@@ -148,22 +396,43 @@ impl AxRunQueue {
 impl AxRunQueue {
     /// Common reschedule subroutine. If `preempt`, keep current task's time
     /// slice, otherwise reset it.
-    fn resched(&mut self, preempt: bool) {
+    ///
+    /// If there is no ready task on this CPU, it tries to steal one from
+    /// other CPUs before running the idle task.
+    fn resched(&self, preempt: bool) {
         let prev = crate::current();
         if prev.is_running() {
             prev.set_state(TaskState::Ready);
             if !prev.is_idle() {
-                self.scheduler.put_prev_task(prev.clone(), preempt);
+                if prev.can_run_on(self.cpu_id) {
+                    let mut scheduler = self.scheduler.lock();
+                    scheduler.put_prev_task(prev.clone(), preempt);
+                    self.num_tasks.fetch_add(1, Ordering::Release);
+                } else {
+                    self.select_run_queue(prev.as_task_ref())
+                        .add_task(prev.clone());
+                }
             }
         }
-        let next = self.scheduler.pick_next_task().unwrap_or_else(|| unsafe {
-            // Safety: IRQs must be disabled at this time.
-            IDLE_TASK.current_ref_raw().get_unchecked().clone()
-        });
+        let next = loop {
+            match self.pick_next_task().or_else(|| self.steal_task(0)) {
+                // the affinity of the task has been changed since it's added
+                Some(task) if !task.can_run_on(self.cpu_id) => {
+                    let rq = self.select_run_queue(&task);
+                    if core::ptr::eq(rq, self) {
+                        break task;
+                    }
+                    rq.add_task(task);
+                }
+                Some(task) => break task,
+                // Safety: IRQs must be disabled at this time.
+                None => break unsafe { IDLE_TASK.current_ref_raw().get_unchecked().clone() },
+            }
+        };
         self.switch_to(prev, next);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    fn switch_to(&self, prev_task: CurrentTask, next_task: AxTaskRef) {
         trace!(
             "context switch: {} -> {}",
             prev_task.id_name(),
@@ -171,11 +440,25 @@ impl AxRunQueue {
         );
         #[cfg(feature = "preempt")]
         next_task.set_preempt_pending(false);
-        next_task.set_state(TaskState::Running);
         if prev_task.ptr_eq(&next_task) {
+            next_task.set_state(TaskState::Running);
             return;
         }
 
+        // The next task may be still switching out on another CPU, if it has
+        // been stolen or woken up just now. Wait until its context is saved,
+        // before marking it as running.
+        while next_task.on_cpu() {
+            core::hint::spin_loop();
+        }
+        next_task.set_state(TaskState::Running);
+        next_task.set_on_cpu(true);
+        // with the scheduler locked, so that the priority of the task is not
+        // updated in the scheduler of the CPU it's leaving
+        let scheduler = self.scheduler.lock();
+        next_task.set_cpu_id(self.cpu_id);
+        drop(scheduler);
+
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
             let next_ctx_ptr = next_task.ctx_mut_ptr();
@@ -185,49 +468,76 @@ impl AxRunQueue {
             assert!(Arc::strong_count(prev_task.as_task_ref()) > 1);
             assert!(Arc::strong_count(&next_task) >= 1);
 
+            #[cfg(feature = "uspace")]
+            next_task.activate_aspace();
+
+            PREV_TASK.write_current_raw(Arc::as_ptr(prev_task.as_task_ref()) as usize);
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
+
+            // We may be on another CPU now, do not touch `self` anymore.
+            clear_prev_task_on_cpu();
         }
     }
 }
 
-fn gc_entry() {
+/// Marks the task switched out by the last context switch on the current CPU
+/// as not running on the CPU, after its context is saved.
+///
+/// # Safety
+///
+/// It must be called right after a context switch, with IRQs disabled.
+pub(crate) unsafe fn clear_prev_task_on_cpu() {
+    let prev = PREV_TASK.read_current_raw() as *const AxTask;
+    (*prev).set_on_cpu(false);
+}
+
+fn gc_entry(cpu_id: usize) {
+    // Safety: the run queue of the CPU is initialized before the `gc` task
+    // runs, and it's never dropped.
+    let rq = unsafe { RUN_QUEUE.remote_ref_raw(cpu_id).get_unchecked() };
     loop {
         // Drop all exited tasks and recycle resources.
-        let n = EXITED_TASKS.lock().len();
+        let n = rq.exited_tasks.lock().len();
         for _ in 0..n {
             // Do not do the slow drops in the critical section.
-            let task = EXITED_TASKS.lock().pop_front();
+            let task = rq.exited_tasks.lock().pop_front();
             if let Some(task) = task {
-                if Arc::strong_count(&task) == 1 {
+                if Arc::strong_count(&task) == 1 && !task.on_cpu() {
                     // If I'm the last holder of the task, drop it immediately.
                     drop(task);
                 } else {
                     // Otherwise (e.g, `switch_to` is not compeleted, held by the
                     // joiner, etc), push it back and wait for them to drop first.
-                    EXITED_TASKS.lock().push_back(task);
+                    rq.exited_tasks.lock().push_back(task);
                 }
             }
         }
-        WAIT_FOR_EXIT.wait();
+        rq.wait_for_exit.wait();
     }
 }
 
 pub(crate) fn init() {
     const IDLE_TASK_STACK_SIZE: usize = 4096;
+    let cpu_id = axhal::cpu::this_cpu_id();
     let idle_task = TaskInner::new(|| crate::run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
     IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
 
     let main_task = TaskInner::new_init("main".into());
     main_task.set_state(TaskState::Running);
+    main_task.set_on_cpu(true);
 
-    RUN_QUEUE.init_by(AxRunQueue::new());
+    RUN_QUEUE.with_current(|rq| rq.init_by(AxRunQueue::new(cpu_id)));
     unsafe { CurrentTask::init_current(main_task) }
 }
 
 pub(crate) fn init_secondary() {
+    let cpu_id = axhal::cpu::this_cpu_id();
     let idle_task = TaskInner::new_init("idle".into());
     idle_task.set_state(TaskState::Running);
+    idle_task.set_on_cpu(true);
     IDLE_TASK.with_current(|i| i.init_by(idle_task.clone()));
+
+    RUN_QUEUE.with_current(|rq| rq.init_by(AxRunQueue::new(cpu_id)));
     unsafe { CurrentTask::init_current(idle_task) }
 }
@@ -1,19 +1,49 @@
-use alloc::{boxed::Box, string::String, sync::Arc};
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
+use core::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicIsize, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
 use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
 
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
-
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
 
+#[cfg(feature = "uspace")]
+use axmm::AddrSpace;
+
 use axhal::arch::TaskContext;
 use memory_addr::{align_up_4k, VirtAddr};
+use spinlock::SpinNoIrq;
 
 use crate::{AxRunQueue, AxTask, AxTaskRef, WaitQueue};
 
+/// The CPU mask with all CPUs set.
+pub(crate) const CPU_MASK_FULL: usize = if axconfig::SMP >= usize::BITS as usize {
+    usize::MAX
+} else {
+    (1 << axconfig::SMP) - 1
+};
+
+const _: () = assert!(
+    axconfig::SMP <= usize::BITS as usize,
+    "too many CPUs for the CPU mask"
+);
+
+/// All tasks that are not dropped, indexed by the task IDs.
+static TASK_LIST: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// The locks that a task holds or is blocked on, for priority inheritance.
+#[derive(Default)]
+pub(crate) struct LockState {
+    /// The keys of the locks held by the task, with the priorities inherited
+    /// through them, or [`isize::MAX`] if none.
+    pub held: Vec<(usize, isize)>,
+    /// The key of the lock that the task is blocked on, and its owner.
+    pub blocked_on: Option<(usize, AxTaskRef)>,
+}
+
 /// A unique identifier for a thread.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct TaskId(u64);
@@ -21,10 +51,14 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
+    /// The task is running on a CPU.
     Running = 1,
+    /// The task is in the run queue, waiting to be scheduled.
     Ready = 2,
+    /// The task is blocked in a wait queue or sleeping.
     Blocked = 3,
+    /// The task has exited, but is not dropped yet.
     Exited = 4,
 }
 
@@ -37,8 +71,23 @@ pub struct TaskInner {
 
     entry: Option<*mut dyn FnOnce()>,
     state: AtomicU8,
-
-    in_wait_queue: AtomicBool,
+    /// The CPU that the task is running on or ran on last time.
+    cpu_id: AtomicUsize,
+    /// Whether the context of the task is in use by a CPU, i.e. it's running
+    /// or being switched out.
+    on_cpu: AtomicBool,
+    /// The CPUs that the task is allowed to run on, bit `i` for CPU `i`.
+    cpu_mask: AtomicUsize,
+
+    /// The priority set by [`crate::set_priority`].
+    base_prio: AtomicIsize,
+    /// The highest priority inherited through the locks held by the task
+    /// (see [`crate::lock_blocked`]), or [`isize::MAX`] if none.
+    inherited_prio: AtomicIsize,
+    lock_state: SpinNoIrq<LockState>,
+
+    /// The address of the wait queue that the task is in, or 0 if not.
+    wait_queue: AtomicUsize,
     #[cfg(feature = "irq")]
     in_timer_list: AtomicBool,
 
@@ -50,9 +99,19 @@ pub struct TaskInner {
     exit_code: AtomicI32,
     wait_for_exit: WaitQueue,
 
+    /// The user ID, group ID and file mode creation mask, which are inherited
+    /// by the tasks spawned by this task.
+    uid: AtomicU32,
+    gid: AtomicU32,
+    umask: AtomicU32,
+
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
 
+    /// The user address space, which is activated when the task is running.
+    #[cfg(feature = "uspace")]
+    aspace: Option<Arc<SpinNoIrq<AddrSpace>>>,
+
     #[cfg(feature = "tls")]
     tls: TlsArea,
 }
@@ -101,6 +160,96 @@ impl TaskInner {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Gets the state of the task.
+    #[inline]
+    pub fn state(&self) -> TaskState {
+        self.state.load(Ordering::Acquire).into()
+    }
+
+    /// Gets the ID of the CPU that the task is running on, or ran on last
+    /// time if it is not running.
+    #[inline]
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    /// Gets the CPU affinity of the task, i.e. the CPUs that it is allowed to
+    /// run on, bit `i` for CPU `i`.
+    ///
+    /// It's inherited by the tasks spawned by this task.
+    #[inline]
+    pub fn cpu_mask(&self) -> usize {
+        self.cpu_mask.load(Ordering::Acquire)
+    }
+
+    /// Gets the priority of the task, which is the higher one of the priority
+    /// set by [`crate::set_priority`] and the ones inherited through the locks
+    /// it holds (see [`crate::lock_blocked`]).
+    ///
+    /// A smaller value means a higher priority.
+    #[inline]
+    pub fn priority(&self) -> isize {
+        self.base_prio
+            .load(Ordering::Acquire)
+            .min(self.inherited_prio.load(Ordering::Acquire))
+    }
+
+    /// Gets the wait channel of the task, i.e. the address of the
+    /// [`WaitQueue`] that the task is waiting in, or 0 if it is not in any
+    /// wait queue.
+    #[inline]
+    pub fn wait_channel(&self) -> usize {
+        self.wait_queue.load(Ordering::Acquire)
+    }
+
+    /// Gets the user ID of the task.
+    #[inline]
+    pub fn uid(&self) -> u32 {
+        self.uid.load(Ordering::Acquire)
+    }
+
+    /// Gets the group ID of the task.
+    #[inline]
+    pub fn gid(&self) -> u32 {
+        self.gid.load(Ordering::Acquire)
+    }
+
+    /// Sets the user ID and group ID of the task.
+    ///
+    /// The tasks spawned by this task afterwards have the same IDs. It does
+    /// not check whether the change is allowed, which is up to the caller.
+    pub fn set_ids(&self, uid: u32, gid: u32) {
+        self.uid.store(uid, Ordering::Release);
+        self.gid.store(gid, Ordering::Release);
+    }
+
+    /// Gets the file mode creation mask of the task.
+    #[inline]
+    pub fn umask(&self) -> u32 {
+        self.umask.load(Ordering::Acquire)
+    }
+
+    /// Sets the file mode creation mask of the task, returns the previous
+    /// mask.
+    pub fn set_umask(&self, umask: u32) -> u32 {
+        self.umask.swap(umask & 0o777, Ordering::AcqRel)
+    }
+
+    /// Gets the top address of the kernel stack of the task.
+    ///
+    /// Returns `None` for the "init tasks", which run on the boot stacks.
+    #[cfg(feature = "uspace")]
+    pub fn kernel_stack_top(&self) -> Option<VirtAddr> {
+        self.kstack.as_ref().map(|s| s.top())
+    }
+
+    /// Gets the user address space of the task, if it is spawned by
+    /// [`spawn_user`](crate::spawn_user).
+    #[cfg(feature = "uspace")]
+    pub fn aspace(&self) -> Option<&Arc<SpinNoIrq<AddrSpace>>> {
+        self.aspace.as_ref()
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
@@ -121,7 +270,13 @@ impl TaskInner {
             is_init: false,
             entry: None,
             state: AtomicU8::new(TaskState::Ready as u8),
-            in_wait_queue: AtomicBool::new(false),
+            cpu_id: AtomicUsize::new(axhal::cpu::this_cpu_id()),
+            on_cpu: AtomicBool::new(false),
+            cpu_mask: AtomicUsize::new(CPU_MASK_FULL),
+            base_prio: AtomicIsize::new(0),
+            inherited_prio: AtomicIsize::new(isize::MAX),
+            lock_state: SpinNoIrq::new(LockState::default()),
+            wait_queue: AtomicUsize::new(0),
             #[cfg(feature = "irq")]
             in_timer_list: AtomicBool::new(false),
             #[cfg(feature = "preempt")]
@@ -130,8 +285,13 @@ impl TaskInner {
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
+            uid: AtomicU32::new(0),
+            gid: AtomicU32::new(0),
+            umask: AtomicU32::new(0o022),
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
+            #[cfg(feature = "uspace")]
+            aspace: None,
             #[cfg(feature = "tls")]
             tls: TlsArea::alloc(),
         }
@@ -139,11 +299,42 @@ impl TaskInner {
 
     /// Create a new task with the given entry function and stack size.
     pub(crate) fn new<F>(entry: F, name: String, stack_size: usize) -> AxTaskRef
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Self::register(Arc::new(AxTask::new(Self::new_inner(
+            entry, name, stack_size,
+        ))))
+    }
+
+    /// Create a new task with the given entry function and the user address
+    /// space.
+    #[cfg(feature = "uspace")]
+    pub(crate) fn new_user<F>(
+        entry: F,
+        name: String,
+        stack_size: usize,
+        aspace: Arc<SpinNoIrq<AddrSpace>>,
+    ) -> AxTaskRef
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut t = Self::new_inner(entry, name, stack_size);
+        t.aspace = Some(aspace);
+        Self::register(Arc::new(AxTask::new(t)))
+    }
+
+    fn new_inner<F>(entry: F, name: String, stack_size: usize) -> Self
     where
         F: FnOnce() + Send + 'static,
     {
         let mut t = Self::new_common(TaskId::new(), name);
         debug!("new task: {}", t.id_name());
+        if let Some(curr) = crate::current_may_uninit() {
+            t.set_ids(curr.uid(), curr.gid());
+            t.set_umask(curr.umask());
+            t.set_cpu_mask(curr.cpu_mask());
+        }
         let kstack = TaskStack::alloc(align_up_4k(stack_size));
 
         #[cfg(feature = "tls")]
@@ -157,7 +348,7 @@ impl TaskInner {
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(AxTask::new(t))
+        t
     }
 
     /// Creates an "init task" using the current CPU states, to use as the
@@ -174,12 +365,14 @@ impl TaskInner {
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(AxTask::new(t))
+        Self::register(Arc::new(AxTask::new(t)))
     }
 
-    #[inline]
-    pub(crate) fn state(&self) -> TaskState {
-        self.state.load(Ordering::Acquire).into()
+    fn register(task: AxTaskRef) -> AxTaskRef {
+        TASK_LIST
+            .lock()
+            .insert(task.id.as_u64(), Arc::downgrade(&task));
+        task
     }
 
     #[inline]
@@ -187,6 +380,15 @@ impl TaskInner {
         self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Changes the state of the task from `from` to `to`, fails if the
+    /// current state is not `from`.
+    #[inline]
+    pub(crate) fn transition_state(&self, from: TaskState, to: TaskState) -> bool {
+        self.state
+            .compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
     #[inline]
     pub(crate) fn is_running(&self) -> bool {
         matches!(self.state(), TaskState::Running)
@@ -197,11 +399,6 @@ impl TaskInner {
         matches!(self.state(), TaskState::Ready)
     }
 
-    #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
-        matches!(self.state(), TaskState::Blocked)
-    }
-
     #[inline]
     pub(crate) const fn is_init(&self) -> bool {
         self.is_init
@@ -214,12 +411,54 @@ impl TaskInner {
 
     #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
-        self.in_wait_queue.load(Ordering::Acquire)
+        self.wait_queue.load(Ordering::Acquire) != 0
+    }
+
+    #[inline]
+    pub(crate) fn set_wait_queue(&self, wait_queue: Option<&WaitQueue>) {
+        let addr = wait_queue.map_or(0, |wq| wq as *const _ as usize);
+        self.wait_queue.store(addr, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn set_cpu_mask(&self, cpu_mask: usize) {
+        self.cpu_mask.store(cpu_mask, Ordering::Release);
     }
 
     #[inline]
-    pub(crate) fn set_in_wait_queue(&self, in_wait_queue: bool) {
-        self.in_wait_queue.store(in_wait_queue, Ordering::Release);
+    pub(crate) fn set_base_priority(&self, prio: isize) {
+        self.base_prio.store(prio, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn set_inherited_priority(&self, prio: isize) {
+        self.inherited_prio.store(prio, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn lock_state(&self) -> &SpinNoIrq<LockState> {
+        &self.lock_state
+    }
+
+    /// Whether the task is allowed to run on the given CPU.
+    #[inline]
+    pub(crate) fn can_run_on(&self, cpu_id: usize) -> bool {
+        self.cpu_mask() & (1 << cpu_id) != 0
+    }
+
+    #[inline]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
     }
 
     #[inline]
@@ -265,18 +504,28 @@ impl TaskInner {
     fn current_check_preempt_pending() {
         let curr = crate::current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = crate::RUN_QUEUE.lock();
+            let rq = crate::run_queue::current_run_queue();
             if curr.need_resched.load(Ordering::Acquire) {
                 rq.preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &AxRunQueue) {
         self.exit_code.store(exit_code, Ordering::Release);
         self.wait_for_exit.notify_all_locked(false, rq);
     }
 
+    /// Activates the user address space of the task on the current CPU, or
+    /// the kernel page table if it has none.
+    #[cfg(feature = "uspace")]
+    pub(crate) fn activate_aspace(&self) {
+        match &self.aspace {
+            Some(aspace) => unsafe { aspace.lock().activate() },
+            None => axmm::activate_kernel_page_table(),
+        }
+    }
+
     #[inline]
     pub(crate) const unsafe fn ctx_mut_ptr(&self) -> *mut TaskContext {
         self.ctx.get()
@@ -296,15 +545,29 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
+        TASK_LIST.lock().remove(&self.id.as_u64());
     }
 }
 
+/// Returns all tasks that are not dropped, sorted by the task IDs.
+pub(crate) fn all_tasks() -> Vec<AxTaskRef> {
+    // upgrade outside the lock, as dropping the last reference of a task also
+    // locks the list
+    let tasks: Vec<_> = TASK_LIST.lock().values().cloned().collect();
+    tasks.iter().filter_map(Weak::upgrade).collect()
+}
+
 struct TaskStack {
     ptr: NonNull<u8>,
     layout: Layout,
 }
 
+/// Number of unmapped guard pages below each task stack.
+#[cfg(feature = "paging")]
+const STACK_GUARD_PAGES: usize = 1;
+
 impl TaskStack {
+    #[cfg(not(feature = "paging"))]
     pub fn alloc(size: usize) -> Self {
         let layout = Layout::from_size_align(size, 16).unwrap();
         Self {
@@ -313,15 +576,86 @@ impl TaskStack {
         }
     }
 
+    #[cfg(feature = "paging")]
+    pub fn alloc(size: usize) -> Self {
+        use axhal::mem::PAGE_SIZE_4K;
+        use axmm::MappingFlags;
+
+        // Only whole pages are mapped, so `top()` must be on a page boundary
+        let size = align_up_4k(size);
+        let layout = Layout::from_size_align(size, PAGE_SIZE_4K).unwrap();
+        let base = axmm::alloc_vmap_pages(STACK_GUARD_PAGES + size / PAGE_SIZE_4K)
+            .expect("failed to allocate task stack");
+        let start = base.as_usize() + STACK_GUARD_PAGES * PAGE_SIZE_4K;
+        for vaddr in (start..start + size).step_by(PAGE_SIZE_4K) {
+            let paddr = axmm::alloc_frame(false).expect("failed to allocate task stack");
+            axmm::map_page(
+                vaddr.into(),
+                paddr,
+                MappingFlags::READ | MappingFlags::WRITE,
+            )
+            .expect("failed to map task stack");
+        }
+        Self {
+            ptr: NonNull::new(start as *mut u8).unwrap(),
+            layout,
+        }
+    }
+
     pub const fn top(&self) -> VirtAddr {
         unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
     }
+
+    /// Returns whether `vaddr` is in the guard pages of the stack.
+    #[cfg(feature = "paging")]
+    fn is_guard_addr(&self, vaddr: VirtAddr) -> bool {
+        let start = self.ptr.as_ptr() as usize;
+        let guard_size = STACK_GUARD_PAGES * axhal::mem::PAGE_SIZE_4K;
+        (start - guard_size..start).contains(&vaddr.as_usize())
+    }
 }
 
 impl Drop for TaskStack {
+    #[cfg(not(feature = "paging"))]
     fn drop(&mut self) {
         unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
     }
+
+    #[cfg(feature = "paging")]
+    fn drop(&mut self) {
+        use axhal::mem::PAGE_SIZE_4K;
+
+        let start = self.ptr.as_ptr() as usize;
+        for vaddr in (start..start + self.layout.size()).step_by(PAGE_SIZE_4K) {
+            if let Ok(paddr) = axmm::unmap_page(vaddr.into()) {
+                axmm::dealloc_frame(paddr);
+            }
+        }
+        axmm::dealloc_vmap_pages(
+            (start - STACK_GUARD_PAGES * PAGE_SIZE_4K).into(),
+            STACK_GUARD_PAGES + self.layout.size() / PAGE_SIZE_4K,
+        );
+    }
+}
+
+/// Reports the stack overflow of the current task, if the page fault at
+/// `vaddr` hits the guard pages of its stack.
+#[cfg(feature = "paging")]
+pub(crate) fn handle_stack_overflow(vaddr: VirtAddr, _access_flags: axmm::MappingFlags) -> bool {
+    if let Some(curr) = crate::current_may_uninit() {
+        if let Some(kstack) = &curr.kstack {
+            if kstack.is_guard_addr(vaddr) {
+                panic!(
+                    "stack overflow in {} @ {:#x}, stack: [{:#x}, {:#x})",
+                    curr.id_name(),
+                    vaddr,
+                    kstack.ptr.as_ptr() as usize,
+                    kstack.top(),
+                );
+            }
+        }
+    }
+    false
 }
 
 use core::mem::ManuallyDrop;
@@ -379,8 +713,8 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
-    // release the lock that was implicitly held across the reschedule
-    unsafe { crate::RUN_QUEUE.force_unlock() };
+    // finish the context switch that was started by the previous task
+    unsafe { crate::run_queue::clear_prev_task_on_cpu() };
     #[cfg(feature = "irq")]
     axhal::arch::enable_irqs();
     let task = crate::current();
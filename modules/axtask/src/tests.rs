@@ -1,7 +1,7 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, Once};
 
-use crate::{self as axtask, current, WaitQueue};
+use crate::{self as axtask, current, TaskState, WaitQueue};
 
 static INIT: Once = Once::new();
 static SERIAL: Mutex<()> = Mutex::new(());
@@ -128,3 +128,86 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_task_list() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    let task = axtask::spawn_raw(|| WQ.wait(), "waiter".into(), 0x10000);
+    while task.state() != TaskState::Blocked {
+        axtask::yield_now();
+    }
+    assert_eq!(task.wait_channel(), &WQ as *const _ as usize);
+    assert_eq!(current().state(), TaskState::Running);
+    assert_eq!(current().wait_channel(), 0);
+
+    let tasks = axtask::all_tasks();
+    assert!(tasks.iter().any(|t| t.id() == current().id()));
+    assert!(tasks
+        .iter()
+        .any(|t| t.id() == task.id() && t.name() == "waiter"));
+    assert!(tasks
+        .windows(2)
+        .all(|w| w[0].id().as_u64() < w[1].id().as_u64()));
+    drop(tasks);
+
+    WQ.notify_one(true);
+    assert_eq!(task.join(), Some(0));
+    assert_eq!(task.state(), TaskState::Exited);
+    assert_eq!(task.wait_channel(), 0);
+}
+
+#[test]
+fn test_task_credentials() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let (uid, gid, umask) = (current().uid(), current().gid(), current().umask());
+    current().set_ids(1000, 100);
+    assert_eq!(current().set_umask(0o077), umask);
+
+    // inherited by the spawned tasks, but changed separately
+    let task = axtask::spawn_raw(
+        || {
+            assert_eq!((current().uid(), current().gid()), (1000, 100));
+            assert_eq!(current().umask(), 0o077);
+            current().set_ids(0, 0);
+        },
+        "child".into(),
+        0x1000,
+    );
+    assert_eq!(task.join(), Some(0));
+    assert_eq!((task.uid(), task.gid()), (0, 0));
+    assert_eq!((current().uid(), current().gid()), (1000, 100));
+
+    current().set_ids(uid, gid);
+    current().set_umask(umask);
+}
+
+#[test]
+fn test_task_affinity() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let curr = current().as_task_ref().clone();
+    let cpu_mask = axtask::get_affinity(&curr);
+    assert_eq!(cpu_mask, (1 << axconfig::SMP) - 1);
+    assert!(!axtask::set_affinity(&curr, 0));
+    assert!(!axtask::set_affinity(&curr, 1 << axconfig::SMP));
+    assert!(axtask::set_affinity(&curr, 1));
+    assert_eq!(axtask::get_affinity(&curr), 1);
+    assert_eq!(current().cpu_id(), 0);
+
+    // inherited by the spawned tasks
+    let task = axtask::spawn_raw(
+        || assert_eq!(current().cpu_mask(), 1),
+        "child".into(),
+        0x1000,
+    );
+    assert_eq!(task.join(), Some(0));
+
+    assert!(axtask::set_affinity(&curr, cpu_mask));
+}
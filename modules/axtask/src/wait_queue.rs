@@ -2,7 +2,8 @@ use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use spinlock::SpinRaw;
 
-use crate::{AxRunQueue, AxTaskRef, CurrentTask, RUN_QUEUE};
+use crate::run_queue::{current_run_queue, AxRunQueue};
+use crate::{AxTaskRef, CurrentTask};
 
 /// A queue to store sleeping tasks.
 ///
@@ -27,7 +28,7 @@ use crate::{AxRunQueue, AxTaskRef, CurrentTask, RUN_QUEUE};
 /// assert_eq!(VALUE.load(Ordering::Relaxed), 1);
 /// ```
 pub struct WaitQueue {
-    queue: SpinRaw<VecDeque<AxTaskRef>>, // we already disabled IRQs when lock the `RUN_QUEUE`
+    queue: SpinRaw<VecDeque<AxTaskRef>>, // we already disabled IRQs when get the run queue
 }
 
 impl WaitQueue {
@@ -50,10 +51,10 @@ impl WaitQueue {
         // the event from another queue.
         if curr.in_wait_queue() {
             // wake up by timer (timeout).
-            // `RUN_QUEUE` is not locked here, so disable IRQs.
+            // the run queue is not held here, so disable IRQs.
             let _guard = kernel_guard::IrqSave::new();
             self.queue.lock().retain(|t| !curr.ptr_eq(t));
-            curr.set_in_wait_queue(false);
+            curr.set_wait_queue(None);
         }
         #[cfg(feature = "irq")]
         if curr.in_timer_list() {
@@ -65,10 +66,7 @@ impl WaitQueue {
     /// Blocks the current task and put it into the wait queue, until other task
     /// notifies it.
     pub fn wait(&self) {
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task)
-        });
+        current_run_queue().block_current(|task| self.push_locked(task));
         self.cancel_events(crate::current());
     }
 
@@ -77,18 +75,24 @@ impl WaitQueue {
     ///
     /// Note that even other tasks notify this task, it will not wake up until
     /// the condition becomes true.
+    ///
+    /// The `condition` is checked with the wait queue locked and IRQs disabled,
+    /// so it must not block or access this wait queue.
     pub fn wait_until<F>(&self, condition: F)
     where
         F: Fn() -> bool,
     {
         loop {
-            let mut rq = RUN_QUEUE.lock();
+            let rq = current_run_queue();
+            // check the condition with the wait queue locked, so that the
+            // notification after the condition is changed will not be missed
+            let mut wq = self.queue.lock();
             if condition() {
                 break;
             }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
+            rq.block_current(move |task| {
+                task.set_wait_queue(Some(self));
+                wq.push_back(task);
             });
         }
         self.cancel_events(crate::current());
@@ -105,11 +109,12 @@ impl WaitQueue {
             curr.id_name(),
             deadline
         );
-        crate::timers::set_alarm_wakeup(deadline, curr.clone());
 
-        RUN_QUEUE.lock().block_current(|task| {
-            task.set_in_wait_queue(true);
-            self.queue.lock().push_back(task)
+        current_run_queue().block_current(|task| {
+            // set the alarm after the task is blocked, or the wakeup may be
+            // missed if the timer fires on another CPU in the meantime.
+            crate::timers::set_alarm_wakeup(deadline, task.clone());
+            self.push_locked(task);
         });
         let timeout = curr.in_wait_queue(); // still in the wait queue, must have timed out
         self.cancel_events(curr);
@@ -133,18 +138,21 @@ impl WaitQueue {
             curr.id_name(),
             deadline
         );
-        crate::timers::set_alarm_wakeup(deadline, curr.clone());
 
         let mut timeout = true;
         while axhal::time::current_time() < deadline {
-            let mut rq = RUN_QUEUE.lock();
+            let rq = current_run_queue();
+            let mut wq = self.queue.lock();
             if condition() {
                 timeout = false;
                 break;
             }
-            rq.block_current(|task| {
-                task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
+            rq.block_current(move |task| {
+                if !task.in_timer_list() {
+                    crate::timers::set_alarm_wakeup(deadline, task.clone());
+                }
+                task.set_wait_queue(Some(self));
+                wq.push_back(task);
             });
         }
         self.cancel_events(curr);
@@ -156,12 +164,8 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_one(&self, resched: bool) -> bool {
-        let mut rq = RUN_QUEUE.lock();
-        if !self.queue.lock().is_empty() {
-            self.notify_one_locked(resched, &mut rq)
-        } else {
-            false
-        }
+        let rq = current_run_queue();
+        self.notify_one_locked(resched, &rq)
     }
 
     /// Wakes all tasks in the wait queue.
@@ -169,16 +173,7 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_all(&self, resched: bool) {
-        loop {
-            let mut rq = RUN_QUEUE.lock();
-            if let Some(task) = self.queue.lock().pop_front() {
-                task.set_in_wait_queue(false);
-                rq.unblock_task(task, resched);
-            } else {
-                break;
-            }
-            drop(rq); // we must unlock `RUN_QUEUE` after unlocking `self.queue`.
-        }
+        while self.notify_one_locked(resched, &current_run_queue()) {}
     }
 
     /// Wake up the given task in the wait queue.
@@ -186,20 +181,34 @@ impl WaitQueue {
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_task(&mut self, resched: bool, task: &AxTaskRef) -> bool {
-        let mut rq = RUN_QUEUE.lock();
+        let rq = current_run_queue();
         let mut wq = self.queue.lock();
         if let Some(index) = wq.iter().position(|t| Arc::ptr_eq(t, task)) {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(wq.remove(index).unwrap(), resched);
+            let task = wq.remove(index).unwrap();
+            task.set_wait_queue(None);
+            drop(wq);
+            rq.unblock_task(task, resched);
             true
         } else {
             false
         }
     }
 
-    pub(crate) fn notify_one_locked(&self, resched: bool, rq: &mut AxRunQueue) -> bool {
-        if let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
+    fn push_locked(&self, task: AxTaskRef) {
+        let mut wq = self.queue.lock();
+        task.set_wait_queue(Some(self));
+        wq.push_back(task);
+    }
+
+    /// Wakes up the first task in the wait queue, and puts it into the given
+    /// run queue of the current CPU.
+    ///
+    /// The wait queue is unlocked before the task is added to the run queue,
+    /// so that the scheduler lock is never held with a wait queue lock.
+    pub(crate) fn notify_one_locked(&self, resched: bool, rq: &AxRunQueue) -> bool {
+        let task = self.queue.lock().pop_front();
+        if let Some(task) = task {
+            task.set_wait_queue(None);
             rq.unblock_task(task, resched);
             true
         } else {
@@ -207,10 +216,7 @@ impl WaitQueue {
         }
     }
 
-    pub(crate) fn notify_all_locked(&self, resched: bool, rq: &mut AxRunQueue) {
-        while let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
-        }
+    pub(crate) fn notify_all_locked(&self, resched: bool, rq: &AxRunQueue) {
+        while self.notify_one_locked(resched, rq) {}
     }
 }
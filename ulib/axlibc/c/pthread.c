@@ -6,82 +6,83 @@
 #include <stdio.h>
 #include <unistd.h>
 
-int pthread_setcancelstate(int new, int *old)
+int pthread_equal(pthread_t a, pthread_t b)
+{
+    return a == b;
+}
+
+// TODO
+int pthread_setname_np(pthread_t thread, const char *name)
 {
     unimplemented();
     return 0;
 }
 
-int pthread_setcanceltype(int new, int *old)
+int pthread_mutexattr_init(pthread_mutexattr_t *a)
 {
-    unimplemented();
+    *a = (pthread_mutexattr_t){0};
     return 0;
 }
 
-// TODO
-void pthread_testcancel(void)
+int pthread_mutexattr_destroy(pthread_mutexattr_t *a)
 {
-    unimplemented();
-    return;
+    return 0;
 }
 
-// TODO
-int pthread_cancel(pthread_t t)
+int pthread_mutexattr_settype(pthread_mutexattr_t *a, int type)
 {
-    unimplemented();
+    if ((unsigned)type > 2)
+        return EINVAL;
+    a->__attr = (a->__attr & ~3) | type;
     return 0;
 }
 
-// TODO
-int pthread_mutex_trylock(pthread_mutex_t *m)
+int pthread_mutexattr_gettype(const pthread_mutexattr_t *restrict a, int *restrict type)
 {
-    unimplemented();
+    *type = a->__attr & 3;
     return 0;
 }
 
-// TODO
-int pthread_setname_np(pthread_t thread, const char *name)
+int pthread_condattr_init(pthread_condattr_t *a)
 {
-    unimplemented();
+    *a = (pthread_condattr_t){0};
     return 0;
 }
 
-int pthread_cond_init(pthread_cond_t *restrict c, const pthread_condattr_t *restrict a)
+int pthread_condattr_destroy(pthread_condattr_t *a)
 {
-    *c = (pthread_cond_t){0};
-    if (a) {
-        c->_c_clock = a->__attr & 0x7fffffff;
-        if (a->__attr >> 31)
-            c->_c_shared = (void *)-1;
-    }
     return 0;
 }
 
-// TODO
-int pthread_cond_signal(pthread_cond_t *__cond)
+int pthread_condattr_setclock(pthread_condattr_t *a, clockid_t clk)
 {
-    unimplemented();
+    if (clk < 0 || clk > CLOCK_MONOTONIC)
+        return EINVAL;
+    a->__attr &= 0x80000000;
+    a->__attr |= clk;
     return 0;
 }
 
-// TODO
-int pthread_cond_wait(pthread_cond_t *__restrict__ __cond, pthread_mutex_t *__restrict__ __mutex)
+int pthread_condattr_getclock(const pthread_condattr_t *restrict a, clockid_t *restrict clk)
 {
-    unimplemented();
+    *clk = a->__attr & 0x7fffffff;
     return 0;
 }
 
-// TODO
-int pthread_cond_broadcast(pthread_cond_t *c)
+int pthread_rwlockattr_init(pthread_rwlockattr_t *a)
+{
+    *a = (pthread_rwlockattr_t){0};
+    return 0;
+}
+
+int pthread_rwlockattr_destroy(pthread_rwlockattr_t *a)
 {
-    unimplemented();
     return 0;
 }
 
 #define DEFAULT_STACK_SIZE 131072
 #define DEFAULT_GUARD_SIZE 8192
 
-// TODO
 int pthread_attr_init(pthread_attr_t *a)
 {
     *a = (pthread_attr_t){0};
@@ -92,6 +93,11 @@ int pthread_attr_init(pthread_attr_t *a)
     return 0;
 }
 
+int pthread_attr_destroy(pthread_attr_t *a)
+{
+    return 0;
+}
+
 int pthread_attr_getstacksize(const pthread_attr_t *restrict a, size_t *restrict size)
 {
     *size = a->_a_stacksize;
@@ -107,4 +113,18 @@ int pthread_attr_setstacksize(pthread_attr_t *a, size_t size)
     return 0;
 }
 
+int pthread_attr_getdetachstate(const pthread_attr_t *a, int *state)
+{
+    *state = a->_a_detach;
+    return 0;
+}
+
+int pthread_attr_setdetachstate(pthread_attr_t *a, int state)
+{
+    if (state > 1U)
+        return EINVAL;
+    a->_a_detach = state;
+    return 0;
+}
+
 #endif // AX_CONFIG_MULTITASK
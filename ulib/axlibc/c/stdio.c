@@ -353,11 +353,12 @@ int getc(FILE *f)
     return 0;
 }
 
-// TODO
 int remove(const char *path)
 {
-    unimplemented();
-    return 0;
+    int r = unlink(path);
+    if (r && errno == EISDIR)
+        r = rmdir(path);
+    return r;
 }
 
 // TODO
@@ -1,7 +1,9 @@
 #include <errno.h>
+#include <fcntl.h>
 #include <limits.h>
 #include <stddef.h>
 #include <stdio.h>
+#include <sys/stat.h>
 #include <sys/time.h>
 #include <time.h>
 
@@ -160,13 +162,26 @@ int gettimeofday(struct timeval *tv, struct timezone *tz)
     return 0;
 }
 
-// TODO:
+#ifdef AX_CONFIG_FS
+
 int utimes(const char *filename, const struct timeval times[2])
 {
-    unimplemented();
-    return 0;
+    struct timespec ts[2];
+    if (times) {
+        for (int i = 0; i < 2; i++) {
+            if (times[i].tv_usec < 0 || times[i].tv_usec >= 1000000) {
+                errno = EINVAL;
+                return -1;
+            }
+            ts[i].tv_sec = times[i].tv_sec;
+            ts[i].tv_nsec = times[i].tv_usec * 1000;
+        }
+    }
+    return utimensat(AT_FDCWD, filename, times ? ts : NULL, 0);
 }
 
+#endif // AX_CONFIG_FS
+
 // TODO
 void tzset()
 {
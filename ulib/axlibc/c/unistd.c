@@ -6,20 +6,6 @@
 #include <time.h>
 #include <unistd.h>
 
-// TODO:
-uid_t geteuid(void)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-uid_t getuid(void)
-{
-    unimplemented();
-    return 0;
-}
-
 // TODO
 pid_t setsid(void)
 {
@@ -61,46 +47,14 @@ int access(const char *pathname, int mode)
     return 0;
 }
 
-// TODO:
-ssize_t readlink(const char *path, char *buf, size_t bufsiz)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
 int unlink(const char *pathname)
 {
-    unimplemented();
-    return 0;
+    return unlinkat(AT_FDCWD, pathname, 0);
 }
 
-// TODO:
 int rmdir(const char *pathname)
 {
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int fsync(int fd)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO
-int fdatasync(int __fildes)
-{
-    unimplemented();
-    return 0;
-}
-
-// TODO:
-int fchown(int fd, uid_t owner, gid_t group)
-{
-    unimplemented("owner: %x group: %x", owner, group);
-    return 0;
+    return unlinkat(AT_FDCWD, pathname, AT_REMOVEDIR);
 }
 
 // TODO:
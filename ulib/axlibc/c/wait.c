@@ -2,12 +2,14 @@
 #include <sys/resource.h>
 #include <sys/wait.h>
 
+#ifndef AX_CONFIG_PROCESS
 // TODO
 pid_t waitpid(pid_t pid, int *status, int options)
 {
     unimplemented();
     return 0;
 }
+#endif
 
 // TODO
 pid_t wait3(int *status, int _options, struct rusage *usage)
@@ -0,0 +1,14 @@
+#ifndef _SPAWN_H
+#define _SPAWN_H
+
+#include <sys/types.h>
+
+// File actions and attributes are not supported, always pass null pointers.
+typedef struct posix_spawnattr posix_spawnattr_t;
+typedef struct posix_spawn_file_actions posix_spawn_file_actions_t;
+
+int posix_spawn(pid_t *__restrict, const char *__restrict, const posix_spawn_file_actions_t *,
+                const posix_spawnattr_t *__restrict, char *const *__restrict,
+                char *const *__restrict);
+
+#endif
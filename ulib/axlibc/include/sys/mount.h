@@ -0,0 +1,20 @@
+#ifndef _SYS_MOUNT_H
+#define _SYS_MOUNT_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MS_RDONLY 1
+#define MS_NOSUID 2
+#define MS_NODEV  4
+#define MS_NOEXEC 8
+
+int mount(const char *, const char *, const char *, unsigned long, const void *);
+int umount(const char *);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // _SYS_MOUNT_H
@@ -6,6 +6,11 @@
 
 #define WNOHANG 1
 
+#define WEXITSTATUS(s) (((s) & 0xff00) >> 8)
+#define WTERMSIG(s)    ((s) & 0x7f)
+#define WIFEXITED(s)   (!WTERMSIG(s))
+
+pid_t wait(int *status);
 pid_t waitpid(pid_t pid, int *status, int options);
 pid_t wait3(int *, int, struct rusage *);
 
@@ -15,15 +15,20 @@
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `net`: Enable networking support.
+//!     - `process`: Enable user processes ([posix_spawn], [waitpid]) support.
 //! - Lib C functions
 //!     - `fd`: Enable file descriptor table.
 //!     - `pipe`: Enable pipe support.
 //!     - `select`: Enable synchronous I/O multiplexing ([select]) support.
 //!     - `epoll`: Enable event polling ([epoll]) support.
+//!     - `mmap`: Enable memory-mapped files ([mmap]) support.
 //!
 //! [ArceOS]: https://github.com/rcore-os/arceos
 //! [select]: https://man7.org/linux/man-pages/man2/select.2.html
 //! [epoll]: https://man7.org/linux/man-pages/man7/epoll.7.html
+//! [mmap]: https://man7.org/linux/man-pages/man2/mmap.2.html
+//! [posix_spawn]: https://man7.org/linux/man-pages/man3/posix_spawn.3.html
+//! [waitpid]: https://man7.org/linux/man-pages/man2/waitpid.2.html
 
 #![cfg_attr(all(not(test), not(doc)), no_std)]
 #![feature(doc_cfg)]
@@ -57,10 +62,14 @@ mod fs;
 mod io_mpx;
 #[cfg(feature = "alloc")]
 mod malloc;
+#[cfg(feature = "mmap")]
+mod mmap;
 #[cfg(feature = "net")]
 mod net;
 #[cfg(feature = "pipe")]
 mod pipe;
+#[cfg(feature = "process")]
+mod process;
 #[cfg(feature = "multitask")]
 mod pthread;
 #[cfg(feature = "alloc")]
@@ -89,7 +98,10 @@ pub use self::resource::{getrlimit, setrlimit};
 pub use self::setjmp::{longjmp, setjmp};
 pub use self::sys::sysconf;
 pub use self::time::{clock_gettime, nanosleep};
-pub use self::unistd::{abort, exit, getpid};
+pub use self::unistd::{
+    abort, exit, getegid, geteuid, getgid, getpid, getuid, sched_getaffinity, sched_setaffinity,
+    setegid, seteuid, setgid, setuid,
+};
 
 #[cfg(feature = "alloc")]
 pub use self::malloc::{free, malloc};
@@ -100,7 +112,14 @@ pub use self::strftime::strftime;
 pub use self::fd_ops::{ax_fcntl, close, dup, dup2, dup3};
 
 #[cfg(feature = "fs")]
-pub use self::fs::{ax_open, fstat, getcwd, lseek, lstat, rename, stat};
+pub use self::fs::{
+    ax_open, ax_openat, chmod, chown, fchmod, fchown, flock, fstat, fstatat, futimens, getcwd,
+    getdents64, lchown, link, lseek, lstat, mkdirat, mount, readlink, rename, renameat, stat,
+    symlink, umask, umount, unlinkat, utimensat,
+};
+
+#[cfg(feature = "mmap")]
+pub use self::mmap::{mmap, msync, munmap};
 
 #[cfg(feature = "net")]
 pub use self::net::{
@@ -109,9 +128,32 @@ pub use self::net::{
 };
 
 #[cfg(feature = "multitask")]
-pub use self::pthread::{pthread_create, pthread_exit, pthread_join, pthread_self};
+pub use self::pthread::{
+    pthread_cancel, pthread_create, pthread_detach, pthread_exit, pthread_getaffinity_np,
+    pthread_join, pthread_once, pthread_self, pthread_setaffinity_np, pthread_setcancelstate,
+    pthread_setcanceltype, pthread_testcancel,
+};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_cond_broadcast, pthread_cond_destroy, pthread_cond_init, pthread_cond_signal,
+    pthread_cond_wait,
+};
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub use self::pthread::{pthread_cond_timedwait, pthread_mutex_timedlock};
 #[cfg(feature = "multitask")]
-pub use self::pthread::{pthread_mutex_init, pthread_mutex_lock, pthread_mutex_unlock};
+pub use self::pthread::{
+    pthread_getspecific, pthread_key_create, pthread_key_delete, pthread_setspecific,
+};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_mutex_destroy, pthread_mutex_init, pthread_mutex_lock, pthread_mutex_trylock,
+    pthread_mutex_unlock,
+};
+#[cfg(feature = "multitask")]
+pub use self::pthread::{
+    pthread_rwlock_destroy, pthread_rwlock_init, pthread_rwlock_rdlock, pthread_rwlock_tryrdlock,
+    pthread_rwlock_trywrlock, pthread_rwlock_unlock, pthread_rwlock_wrlock,
+};
 
 #[cfg(feature = "pipe")]
 pub use self::pipe::pipe;
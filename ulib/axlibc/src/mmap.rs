@@ -0,0 +1,44 @@
+use core::ffi::{c_int, c_void};
+
+use arceos_posix_api::{sys_mmap, sys_msync, sys_munmap};
+
+use crate::{ctypes, utils::e};
+
+/// Map files or devices into memory
+///
+/// Return the start address of the mapping if succeed, or `MAP_FAILED` if
+/// failed.
+#[no_mangle]
+pub unsafe extern "C" fn mmap(
+    addr: *mut c_void,
+    len: usize,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    let ret = sys_mmap(addr, len, prot, flags, fd, off);
+    // errors are returned as negative error codes
+    if (-4095..0).contains(&(ret as isize)) {
+        crate::errno::set_errno(-(ret as isize) as _);
+        usize::MAX as _ // MAP_FAILED
+    } else {
+        ret
+    }
+}
+
+/// Unmap files or devices from memory
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn munmap(addr: *mut c_void, len: usize) -> c_int {
+    e(sys_munmap(addr, len))
+}
+
+/// Synchronize a file with its memory mapping
+///
+/// Return 0 if succeed
+#[no_mangle]
+pub unsafe extern "C" fn msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int {
+    e(sys_msync(addr, len, flags))
+}
@@ -0,0 +1,45 @@
+use core::ffi::{c_char, c_int, c_void};
+
+use arceos_posix_api::{sys_getppid, sys_posix_spawn, sys_waitpid};
+
+use crate::utils::e;
+
+/// Spawn a process running the executable at `path`, and store its ID in `pid`.
+///
+/// The file actions and attributes are not supported, and must be null.
+///
+/// Return 0 if success, or the error number.
+#[no_mangle]
+pub unsafe extern "C" fn posix_spawn(
+    pid: *mut c_int,
+    path: *const c_char,
+    file_actions: *const c_void,
+    attrp: *const c_void,
+    argv: *const *mut c_char,
+    envp: *const *mut c_char,
+) -> c_int {
+    // returns the error number instead of setting `errno`
+    -sys_posix_spawn(pid, path, file_actions, attrp, argv, envp)
+}
+
+/// Wait for a child process to exit, and store its exit status in `status`.
+///
+/// Return the ID of the exited child.
+#[no_mangle]
+pub unsafe extern "C" fn waitpid(pid: c_int, status: *mut c_int, options: c_int) -> c_int {
+    e(sys_waitpid(pid, status, options))
+}
+
+/// Wait for any child process to exit, and store its exit status in `status`.
+///
+/// Return the ID of the exited child.
+#[no_mangle]
+pub unsafe extern "C" fn wait(status: *mut c_int) -> c_int {
+    e(sys_waitpid(-1, status, 0))
+}
+
+/// Get the parent process ID.
+#[no_mangle]
+pub unsafe extern "C" fn getppid() -> c_int {
+    e(sys_getppid())
+}
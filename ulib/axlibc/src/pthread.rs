@@ -1,4 +1,4 @@
-use crate::{ctypes, utils::e};
+use crate::{ctypes, utils::pe};
 use arceos_posix_api as api;
 use core::ffi::{c_int, c_void};
 
@@ -19,7 +19,7 @@ pub unsafe extern "C" fn pthread_create(
     start_routine: extern "C" fn(arg: *mut c_void) -> *mut c_void,
     arg: *mut c_void,
 ) -> c_int {
-    e(api::sys_pthread_create(res, attr, start_routine, arg))
+    pe(api::sys_pthread_create(res, attr, start_routine, arg))
 }
 
 /// Exits the current thread. The value `retval` will be returned to the joiner.
@@ -34,7 +34,66 @@ pub unsafe extern "C" fn pthread_join(
     thread: ctypes::pthread_t,
     retval: *mut *mut c_void,
 ) -> c_int {
-    e(api::sys_pthread_join(thread, retval))
+    pe(api::sys_pthread_join(thread, retval))
+}
+
+/// Detach the given thread, so that its resources are freed when it exits.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_detach(thread: ctypes::pthread_t) -> c_int {
+    pe(api::sys_pthread_detach(thread))
+}
+
+/// Request the given thread to be canceled.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cancel(thread: ctypes::pthread_t) -> c_int {
+    pe(api::sys_pthread_cancel(thread))
+}
+
+/// Set the cancelability state of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setcancelstate(state: c_int, oldstate: *mut c_int) -> c_int {
+    pe(api::sys_pthread_setcancelstate(state, oldstate))
+}
+
+/// Set the cancelability type of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setcanceltype(ty: c_int, oldtype: *mut c_int) -> c_int {
+    pe(api::sys_pthread_setcanceltype(ty, oldtype))
+}
+
+/// Exits the current thread if a cancellation is requested.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_testcancel() {
+    api::sys_pthread_testcancel()
+}
+
+/// Call `init_routine` only once for the given `once_control`.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_once(
+    once_control: *mut ctypes::pthread_once_t,
+    init_routine: extern "C" fn(),
+) -> c_int {
+    pe(api::sys_pthread_once(once_control, init_routine))
+}
+
+/// Set the CPU affinity of the given thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setaffinity_np(
+    thread: ctypes::pthread_t,
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> c_int {
+    pe(api::sys_pthread_setaffinity_np(thread, cpusetsize, cpuset))
+}
+
+/// Get the CPU affinity of the given thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_getaffinity_np(
+    thread: ctypes::pthread_t,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> c_int {
+    pe(api::sys_pthread_getaffinity_np(thread, cpusetsize, cpuset))
 }
 
 /// Initialize a mutex.
@@ -43,17 +102,162 @@ pub unsafe extern "C" fn pthread_mutex_init(
     mutex: *mut ctypes::pthread_mutex_t,
     attr: *const ctypes::pthread_mutexattr_t,
 ) -> c_int {
-    e(api::sys_pthread_mutex_init(mutex, attr))
+    pe(api::sys_pthread_mutex_init(mutex, attr))
+}
+
+/// Destroy the given mutex.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_mutex_destroy(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
+    pe(api::sys_pthread_mutex_destroy(mutex))
 }
 
 /// Lock the given mutex.
 #[no_mangle]
 pub unsafe extern "C" fn pthread_mutex_lock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
-    e(api::sys_pthread_mutex_lock(mutex))
+    pe(api::sys_pthread_mutex_lock(mutex))
 }
 
 /// Unlock the given mutex.
 #[no_mangle]
 pub unsafe extern "C" fn pthread_mutex_unlock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
-    e(api::sys_pthread_mutex_unlock(mutex))
+    pe(api::sys_pthread_mutex_unlock(mutex))
+}
+
+/// Try to lock the given mutex.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_mutex_trylock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
+    pe(api::sys_pthread_mutex_trylock(mutex))
+}
+
+/// Lock the given mutex before the absolute time `abstime`.
+#[cfg(feature = "irq")]
+#[no_mangle]
+pub unsafe extern "C" fn pthread_mutex_timedlock(
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    pe(api::sys_pthread_mutex_timedlock(mutex, abstime))
+}
+
+/// Initialize a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    pe(api::sys_pthread_cond_init(cond, attr))
+}
+
+/// Destroy a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pe(api::sys_pthread_cond_destroy(cond))
+}
+
+/// Unlock the mutex and wait on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    pe(api::sys_pthread_cond_wait(cond, mutex))
+}
+
+/// Unlock the mutex and wait on the condition variable until the absolute
+/// time `abstime`.
+#[cfg(feature = "irq")]
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    pe(api::sys_pthread_cond_timedwait(cond, mutex, abstime))
+}
+
+/// Wake up one thread waiting on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pe(api::sys_pthread_cond_signal(cond))
+}
+
+/// Wake up all threads waiting on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    pe(api::sys_pthread_cond_broadcast(cond))
+}
+
+/// Initialize a read-write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    pe(api::sys_pthread_rwlock_init(rwlock, attr))
+}
+
+/// Destroy a read-write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pe(api::sys_pthread_rwlock_destroy(rwlock))
+}
+
+/// Lock the read-write lock for reading.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pe(api::sys_pthread_rwlock_rdlock(rwlock))
+}
+
+/// Try to lock the read-write lock for reading.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pe(api::sys_pthread_rwlock_tryrdlock(rwlock))
+}
+
+/// Lock the read-write lock for writing.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pe(api::sys_pthread_rwlock_wrlock(rwlock))
+}
+
+/// Try to lock the read-write lock for writing.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pe(api::sys_pthread_rwlock_trywrlock(rwlock))
+}
+
+/// Unlock the read-write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    pe(api::sys_pthread_rwlock_unlock(rwlock))
+}
+
+/// Create a thread-specific data key.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_key_create(
+    key: *mut ctypes::pthread_key_t,
+    destructor: Option<unsafe extern "C" fn(*mut c_void)>,
+) -> c_int {
+    pe(api::sys_pthread_key_create(key, destructor))
+}
+
+/// Delete a thread-specific data key.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_key_delete(key: ctypes::pthread_key_t) -> c_int {
+    pe(api::sys_pthread_key_delete(key))
+}
+
+/// Returns the value of the key for the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_getspecific(key: ctypes::pthread_key_t) -> *mut c_void {
+    api::sys_pthread_getspecific(key)
+}
+
+/// Set the value of the key for the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setspecific(
+    key: ctypes::pthread_key_t,
+    value: *const c_void,
+) -> c_int {
+    pe(api::sys_pthread_setspecific(key, value))
 }
@@ -1,12 +1,103 @@
-use arceos_posix_api::{sys_exit, sys_getpid};
+use arceos_posix_api::{
+    sys_exit, sys_getgid, sys_getpid, sys_getuid, sys_sched_getaffinity, sys_sched_setaffinity,
+    sys_setgid, sys_setuid,
+};
 use core::ffi::c_int;
 
+use crate::{ctypes, utils::e};
+
 /// Get current thread ID.
 #[no_mangle]
 pub unsafe extern "C" fn getpid() -> c_int {
     sys_getpid()
 }
 
+/// Get the user ID of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn getuid() -> ctypes::uid_t {
+    sys_getuid()
+}
+
+/// Get the effective user ID of the current thread, which is the same as the
+/// real one.
+#[no_mangle]
+pub unsafe extern "C" fn geteuid() -> ctypes::uid_t {
+    sys_getuid()
+}
+
+/// Get the group ID of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn getgid() -> ctypes::gid_t {
+    sys_getgid()
+}
+
+/// Get the effective group ID of the current thread, which is the same as
+/// the real one.
+#[no_mangle]
+pub unsafe extern "C" fn getegid() -> ctypes::gid_t {
+    sys_getgid()
+}
+
+/// Set the CPU affinity of the thread `pid`, or the current thread if `pid`
+/// is 0.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn sched_setaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    cpuset: *const ctypes::cpu_set_t,
+) -> c_int {
+    e(sys_sched_setaffinity(pid, cpusetsize, cpuset))
+}
+
+/// Get the CPU affinity of the thread `pid`, or the current thread if `pid`
+/// is 0.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn sched_getaffinity(
+    pid: ctypes::pid_t,
+    cpusetsize: usize,
+    cpuset: *mut ctypes::cpu_set_t,
+) -> c_int {
+    e(sys_sched_getaffinity(pid, cpusetsize, cpuset))
+}
+
+/// Set the user ID of the current thread.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn setuid(uid: ctypes::uid_t) -> c_int {
+    e(sys_setuid(uid))
+}
+
+/// Set the group ID of the current thread.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn setgid(gid: ctypes::gid_t) -> c_int {
+    e(sys_setgid(gid))
+}
+
+/// Set the effective user ID of the current thread, which is the same as
+/// the real one.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn seteuid(euid: ctypes::uid_t) -> c_int {
+    e(sys_setuid(euid))
+}
+
+/// Set the effective group ID of the current thread, which is the same as
+/// the real one.
+///
+/// Return 0 if the operation succeeds, otherwise return -1.
+#[no_mangle]
+pub unsafe extern "C" fn setegid(egid: ctypes::gid_t) -> c_int {
+    e(sys_setgid(egid))
+}
+
 /// Abort the current process.
 #[no_mangle]
 pub unsafe extern "C" fn abort() -> ! {
@@ -8,3 +8,9 @@ pub fn e(ret: c_int) -> c_int {
         ret as _
     }
 }
+
+/// Converts the return value of a `sys_pthread_*` function, as the pthread
+/// functions return the error number instead of setting `errno`.
+pub fn pe(ret: c_int) -> c_int {
+    ret.abs()
+}
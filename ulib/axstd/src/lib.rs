@@ -30,6 +30,9 @@
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
+//!     - `ext2`: Use ext2 as the main filesystem if it is detected on the disk.
+//!     - `overlay`: Stack a writable RAM filesystem over the main filesystem.
+//!     - `initramfs`: Use a RAM filesystem unpacked from a cpio archive as the main filesystem.
 //!     - `net`: Enable networking support.
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.